@@ -0,0 +1,102 @@
+//! # Foreign benchmarks
+//! Measures a round trip to an actor on another system through an in-memory delegate, which serializes each message and
+//! response with bincode but has no network, so the result is the overhead Fluxion adds to a foreign send.
+//! See `benches/README.md` for how to run these and compare the results between changes.
+
+use std::{hint::black_box, marker::PhantomData, sync::{Arc, OnceLock}};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use fluxion::{message, Actor, ActorContext, Delegate, Fluxion, FluxionBuilder, Handler, Identifier, IndeterminateMessage, LocalRef, MessageSendError, MessageSender};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Runtime;
+
+/// Xors every message with the value it was created with, which is cheap enough that the send dominates.
+struct Xor(u64);
+
+impl Actor for Xor {
+    type Error = ();
+}
+
+/// A value for [`Xor`] to xor.
+#[message(u64)]
+#[derive(Serialize, Deserialize)]
+struct Value(u64);
+
+impl Handler<Value> for Xor {
+    async fn handle_message<D: Delegate>(&self, message: Value, _context: &ActorContext<D>) -> u64 {
+        message.0 ^ self.0
+    }
+}
+
+/// Delivers foreign messages to another system in the same process, serializing them on the way.
+#[derive(Default)]
+struct Loopback {
+    /// The other system, set once both exist
+    remote: OnceLock<Fluxion<Loopback>>,
+}
+
+impl Delegate for Loopback {
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: Serialize + for<'a> Deserialize<'a> {
+        let Identifier::Foreign(id, _) = id else {
+            return None;
+        };
+
+        let actor = self.remote.get()?.get_local::<A>(id).await?;
+        Some(Arc::new(LoopbackSender { actor, _message: PhantomData }))
+    }
+}
+
+/// Sends serialized messages to an actor on the other system.
+struct LoopbackSender<A: Actor, M> {
+    /// The actor on the other system
+    actor: LocalRef<A, Loopback>,
+    /// The type of message sent
+    _message: PhantomData<fn(M)>,
+}
+
+/// Serializes a value and deserializes it again, as if it had crossed the network.
+fn round_trip<T: Serialize + for<'a> Deserialize<'a>>(value: &T) -> T {
+    bincode::deserialize(&bincode::serialize(value).expect("the value should serialize")).expect("the value should deserialize")
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: IndeterminateMessage> MessageSender<M> for LoopbackSender<A, M>
+    where M::Result: Serialize + for<'a> Deserialize<'a> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        let response = self.actor.send(round_trip(&message)).await?;
+        Ok(round_trip(&response))
+    }
+}
+
+/// Sends to an actor on another system through the [`Loopback`] delegate.
+fn foreign(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().expect("the runtime should build");
+
+    let local = FluxionBuilder::new("local", Loopback::default()).build();
+    let remote = FluxionBuilder::new("remote", Loopback::default()).build();
+    local.get_delegate().remote.set(remote.clone()).ok();
+    remote.get_delegate().remote.set(local.clone()).ok();
+
+    let sender = setup(&runtime, &local, &remote);
+
+    let mut group = c.benchmark_group("foreign");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("round_trip", |b| b.to_async(&runtime).iter(|| async {
+        black_box(sender.send(Value(black_box(7))).await)
+    }));
+
+    group.finish();
+}
+
+/// Adds the actor to the remote system, and retrieves it from the local one.
+fn setup(runtime: &Runtime, local: &Fluxion<Loopback>, remote: &Fluxion<Loopback>) -> Arc<dyn MessageSender<Value>> {
+    runtime.block_on(async {
+        let id = remote.add(Xor(0x5555)).await.expect("the actor should initialize");
+        local.get::<Xor, Value>(Identifier::Foreign(id, "remote")).await.expect("the delegate should find the actor")
+    })
+}
+
+criterion_group!(benches, foreign);
+criterion_main!(benches);
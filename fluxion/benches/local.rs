@@ -0,0 +1,131 @@
+//! # Local benchmarks
+//! Measures messages sent between actors on the same system, routing through pools, and adding and killing actors.
+//! See `benches/README.md` for how to run these and compare the results between changes.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use fluxion::{message, Actor, ActorContext, ActorPool, Delegate, Fluxion, Handler, MessageSender, Router, RoutingStrategy};
+use std::{hint::black_box, sync::Arc};
+use tokio::runtime::Runtime;
+
+/// Xors every message with the value it was created with, which is cheap enough that the send dominates.
+struct Xor(u64);
+
+impl Actor for Xor {
+    type Error = ();
+}
+
+/// A value for [`Xor`] to xor.
+#[message(u64)]
+struct Value(u64);
+
+impl Handler<Value> for Xor {
+    async fn handle_message<D: Delegate>(&self, message: Value, _context: &ActorContext<D>) -> u64 {
+        message.0 ^ self.0
+    }
+}
+
+/// Creates a runtime for the async benchmarks to run on.
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread().enable_all().build().expect("the runtime should build")
+}
+
+/// Sends to a single local actor, both through a concrete [`fluxion::LocalRef`] and through `dyn MessageSender`.
+fn local(c: &mut Criterion) {
+    let runtime = runtime();
+    let system = Fluxion::new("bench", ());
+    let actor = runtime.block_on(async {
+        let id = system.add(Xor(0x5555)).await.expect("the actor should initialize");
+        system.get_local::<Xor>(id).await.expect("the actor was just added")
+    });
+    let sender: Arc<dyn MessageSender<Value>> = Arc::new(actor.clone());
+
+    let mut group = c.benchmark_group("local");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("send", |b| b.to_async(&runtime).iter(|| async {
+        black_box(actor.send(Value(black_box(7))).await)
+    }));
+
+    group.bench_function("send_dyn", |b| b.to_async(&runtime).iter(|| async {
+        black_box(sender.send(Value(black_box(7))).await)
+    }));
+
+    group.bench_function("tell", |b| b.to_async(&runtime).iter(|| async {
+        black_box(actor.tell(Value(black_box(7))).await)
+    }));
+
+    group.finish();
+}
+
+/// Sends through each kind of pool, with as many concurrent senders as workers.
+fn pools(c: &mut Criterion) {
+    const WORKERS: usize = 8;
+
+    let runtime = runtime();
+    let system = Fluxion::new("bench", ());
+    let (round_robin, least_loaded, pool) = runtime.block_on(async {
+        (
+            Arc::new(Router::new(&system, WORKERS, RoutingStrategy::RoundRobin, || Xor(0x5555)).await.expect("the actors should initialize")),
+            Arc::new(Router::new(&system, WORKERS, RoutingStrategy::LeastLoaded, || Xor(0x5555)).await.expect("the actors should initialize")),
+            Arc::new(ActorPool::new(&system, WORKERS, || Xor(0x5555)).await.expect("the actors should initialize")),
+        )
+    });
+
+    let mut group = c.benchmark_group("pools");
+    group.throughput(Throughput::Elements(WORKERS as u64));
+
+    let senders: [(&str, Arc<dyn MessageSender<Value>>); 3] = [
+        ("round_robin", round_robin),
+        ("least_loaded", least_loaded),
+        ("actor_pool", pool),
+    ];
+
+    for (name, sender) in senders {
+        group.bench_function(name, |b| b.to_async(&runtime).iter(|| {
+            let sender = sender.clone();
+            async move {
+                let sends = (0..WORKERS as u64).map(|i| {
+                    let sender = sender.clone();
+                    tokio::spawn(async move { sender.send(Value(i)).await })
+                });
+
+                for send in sends.collect::<Vec<_>>() {
+                    black_box(send.await.expect("the send should not panic")).ok();
+                }
+            }
+        }));
+    }
+
+    group.finish();
+}
+
+/// Adds actors and kills them again, which measures the registry and the slacktor instance.
+fn churn(c: &mut Criterion) {
+    const ACTORS: u64 = 100;
+
+    let runtime = runtime();
+    let system = Fluxion::new("bench", ());
+
+    let mut group = c.benchmark_group("churn");
+    group.throughput(Throughput::Elements(ACTORS));
+
+    group.bench_function("spawn_kill", |b| b.to_async(&runtime).iter_batched(
+        || system.clone(),
+        |system| async move {
+            let mut ids = Vec::with_capacity(ACTORS as usize);
+            for i in 0..ACTORS {
+                ids.push(system.add(Xor(i)).await.expect("the actor should initialize"));
+            }
+
+            for id in ids {
+                system.kill::<Xor>(id).await;
+            }
+        },
+        BatchSize::SmallInput,
+    ));
+
+    group.finish();
+}
+
+criterion_group!(benches, local, pools, churn);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! # Interop server
+//! Serves the actors shared by the websocket interop tests, so that the browser tests in `tests/websocket_browser.rs`
+//! have a native server to reach. To run this example, enable the `websocket` feature.
+
+#[path = "../tests/interop/mod.rs"]
+mod interop;
+
+#[tokio::main]
+async fn main() {
+    let listener = tokio::net::TcpListener::bind(interop::address().trim_start_matches("ws://")).await.unwrap();
+    println!("Serving the interop actors on {}", interop::address());
+
+    interop::serve(listener).await.unwrap();
+}
@@ -0,0 +1,42 @@
+//! # TCP transport
+//! Two systems in the same process, talking to each other over a local TCP socket using the bundled transport.
+//! To run this example, enable the `tcp` feature.
+
+use fluxion::{actor, message, transport::{tcp::{TcpDelegate, TcpServer}, Exports}, ActorContext, Delegate, Fluxion, Handler, Identifier};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+#[actor]
+struct Greeter;
+
+#[message(String)]
+#[derive(Serialize, Deserialize)]
+struct Greet(String);
+
+impl Handler<Greet> for Greeter {
+    async fn handle_message<D: Delegate>(&self, message: Greet, context: &ActorContext<D>) -> String {
+        format!("Hello {}, from {}", message.0, context.system().get_id())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // The server system exports its greeter to foreign systems.
+    let server = Fluxion::new("server", TcpDelegate::new());
+    server.add_named("greeter", Greeter).await.unwrap();
+
+    // Only exported actor/message pairs can be reached by other systems.
+    let exports = Exports::new(server.clone()).export::<Greeter, Greet>();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { TcpServer::new(exports).serve(listener).await });
+
+    // The client system knows where to find the server system.
+    let delegate = TcpDelegate::new();
+    delegate.add_peer("server", &address);
+    let client = Fluxion::new("client", delegate);
+
+    // Foreign actors are retrieved just like local ones.
+    let greeter = client.get::<Greeter, Greet>(Identifier::ForeignNamed("greeter", "server")).await.unwrap();
+    println!("{}", greeter.send(Greet("client".to_string())).await.unwrap());
+}
@@ -0,0 +1,42 @@
+//! # WebSocket transport
+//! Two systems in the same process, talking to each other over a local WebSocket using the bundled transport.
+//! To run this example, enable the `websocket` feature.
+
+use fluxion::{actor, message, transport::{websocket::{WebSocketDelegate, WebSocketServer}, Exports}, ActorContext, Delegate, Fluxion, Handler, Identifier};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+#[actor]
+struct Greeter;
+
+#[message(String)]
+#[derive(Serialize, Deserialize)]
+struct Greet(String);
+
+impl Handler<Greet> for Greeter {
+    async fn handle_message<D: Delegate>(&self, message: Greet, context: &ActorContext<D>) -> String {
+        format!("Hello {}, from {}", message.0, context.system().get_id())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // The server system exports its greeter to foreign systems.
+    let server = Fluxion::new("server", WebSocketDelegate::new());
+    server.add_named("greeter", Greeter).await.unwrap();
+
+    // Only exported actor/message pairs can be reached by other systems.
+    let exports = Exports::new(server.clone()).export::<Greeter, Greet>();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { WebSocketServer::new(exports).serve(listener).await });
+
+    // The client system knows where to find the server system.
+    let delegate = WebSocketDelegate::new();
+    delegate.add_peer("server", &address);
+    let client = Fluxion::new("client", delegate);
+
+    // Foreign actors are retrieved just like local ones.
+    let greeter = client.get::<Greeter, Greet>(Identifier::ForeignNamed("greeter", "server")).await.unwrap();
+    println!("{}", greeter.send(Greet("client".to_string())).await.unwrap());
+}
@@ -0,0 +1,106 @@
+//! # Access Control
+//! An [`AccessPolicy`], set with [`crate::FluxionBuilder::access_policy`], decides which local actors foreign systems
+//! may reach. It is consulted whenever a foreign system resolves a local actor or sends it a message, and is given the
+//! foreign system's identity, if its transport authenticated it, along with the actor and the message's type identifier.
+//!
+//! Actors that a foreign system is denied access to are reported to it as not existing, so that it can't discover them.
+//! Local sends are never checked.
+
+use alloc::{collections::BTreeSet, string::String};
+
+use crate::{Delegate, Fluxion};
+
+/// # [`AccessRequest`]
+/// A foreign system's attempt to reach a local actor, which an [`AccessPolicy`] allows or denies.
+#[derive(Debug, Clone, Copy)]
+pub struct AccessRequest<'a> {
+    /// The identity of the foreign system, if it was authenticated
+    peer: Option<&'a str>,
+    /// The id of the local actor
+    actor: u64,
+    /// The name of the local actor, if it has one
+    name: Option<&'a str>,
+    /// The type identifier of the message, if one is being sent or looked up
+    message: Option<&'a str>,
+}
+
+impl AccessRequest<'_> {
+    /// # [`AccessRequest::peer`]
+    /// Returns the identity of the foreign system, if its transport authenticated it, as described in [`crate::Metadata::peer`].
+    #[must_use]
+    pub fn peer(&self) -> Option<&str> {
+        self.peer
+    }
+
+    /// # [`AccessRequest::actor`]
+    /// Returns the id of the local actor.
+    #[must_use]
+    pub fn actor(&self) -> u64 {
+        self.actor
+    }
+
+    /// # [`AccessRequest::name`]
+    /// Returns the name of the local actor, if it has one.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.name
+    }
+
+    /// # [`AccessRequest::message`]
+    /// Returns the [`crate::MessageID`] of the message, or [`None`] if the foreign system is only resolving the actor's name.
+    #[must_use]
+    pub fn message(&self) -> Option<&str> {
+        self.message
+    }
+}
+
+/// # [`AccessPolicy`]
+/// Decides whether foreign systems may reach local actors. Implemented for functions that take an [`AccessRequest`].
+pub trait AccessPolicy: Send + Sync + 'static {
+    /// # [`AccessPolicy::allows`]
+    /// Returns whether the foreign system may reach the actor.
+    fn allows(&self, request: &AccessRequest<'_>) -> bool;
+}
+
+impl<F: Fn(&AccessRequest<'_>) -> bool + Send + Sync + 'static> AccessPolicy for F {
+    fn allows(&self, request: &AccessRequest<'_>) -> bool {
+        self(request)
+    }
+}
+
+/// # [`ExposedNames`]
+/// An [`AccessPolicy`] that only lets foreign systems reach the actors with the given names.
+#[derive(Debug, Clone, Default)]
+pub struct ExposedNames(BTreeSet<String>);
+
+impl ExposedNames {
+    /// # [`ExposedNames::new`]
+    /// Exposes the actors with the given names.
+    #[must_use]
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        Self(names.into_iter().map(String::from).collect())
+    }
+}
+
+impl AccessPolicy for ExposedNames {
+    fn allows(&self, request: &AccessRequest<'_>) -> bool {
+        request.name.is_some_and(|name| self.0.contains(name))
+    }
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// Returns whether the system's [`AccessPolicy`] lets a foreign system reach the local actor.
+    /// Every actor may be reached by systems without a policy.
+    pub(crate) async fn allows_foreign(&self, peer: Option<&str>, actor: u64, message: Option<&str>) -> bool {
+        let Some(policy) = &self.access_policy else {
+            return true;
+        };
+
+        // Names are only looked up for systems with a policy, as it means searching every name
+        let name = self.actor_ids.read().await.iter()
+            .find(|(_, id)| **id == actor)
+            .map(|(name, _)| name.clone());
+
+        policy.allows(&AccessRequest { peer, actor, name: name.as_deref(), message })
+    }
+}
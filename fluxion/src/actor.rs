@@ -1,9 +1,15 @@
 //! # Actors
 //! This module contains traits and other types and implementations surrounding actors and how they interface with the system.
 
-use alloc::sync::Arc;
+use core::{future::Future, sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, time::Duration};
 
-use crate::{Delegate, Fluxion, Message};
+use alloc::{boxed::Box, collections::{BTreeMap, VecDeque}, string::String, sync::Arc};
+use maitake_sync::spin::Mutex;
+
+use crate::{inspect::ActorStats, metrics::MetricsSink, trace::instrument, scheduler::Delivery, DeadLetterReason, Delegate, Fluxion, Identifier, IndeterminateMessage, LifecycleEvent, Message, MessageSendError, MessageSender, ScheduleError, ScheduleHandle};
+
+/// A stashed message, which delivers itself to the actor with the given id when called.
+type Stashed<D> = Box<dyn FnOnce(Fluxion<D>, u64) -> Delivery + Send>;
 
 /// # [`Actor`]
 /// This trait defines the interface between the system and the actor.
@@ -16,6 +22,13 @@ pub trait Actor: Send + Sync + 'static {
     /// can be returned by methods defined by this trait.
     type Error;
 
+    /// # [`MAX_CONCURRENCY`]
+    /// Messages are handled by the tasks that send them, so an actor handles any number of messages concurrently
+    /// through `&self` by default, which requires it to be internally synchronized. Setting this limits how many
+    /// messages the actor handles at once, and further messages wait until one finishes.
+    /// `Some(1)` handles one message at a time, and a limit of zero is treated as one.
+    const MAX_CONCURRENCY: Option<usize> = None;
+
     /// # [`initialize`]
     /// Called immediately before the actor is added to the system.
     fn initialize(&mut self) -> impl core::future::Future<Output = Result<(), Self::Error>> + Send {
@@ -28,6 +41,50 @@ pub trait Actor: Send + Sync + 'static {
     fn deinitialize(&self) -> impl core::future::Future<Output = ()> + Send {
         async {}
     }
+
+    /// # [`passivate`]
+    /// Called before an actor added with [`Fluxion::add_named_with_passivation`] is killed for being idle,
+    /// giving it a chance to persist its state. [`Actor::deinitialize`] is still called afterwards.
+    fn passivate(&self) -> impl core::future::Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// # [`Handoff`]
+/// Allows an actor to hand its state over to the instance that replaces it when it is restarted,
+/// for example with [`crate::Router::restart_with_state`], so that restarts don't always start from scratch.
+pub trait Handoff: Actor {
+    /// # [`Handoff::State`]
+    /// The state handed from an instance to its replacement.
+    type State: Send + 'static;
+
+    /// # [`Handoff::export_state`]
+    /// Called on the instance being replaced, while it is still running, to export its state.
+    /// Actors are shared between the handlers of concurrent messages, so this only has shared access to the actor.
+    fn export_state(&self) -> impl core::future::Future<Output = Option<Self::State>> + Send;
+
+    /// # [`Handoff::import_state`]
+    /// Called on the replacement with the exported state, if there was any, immediately before [`Actor::initialize`].
+    fn import_state(&mut self, state: Self::State);
+}
+
+/// Asks an actor to export its state with [`Handoff::export_state`].
+pub(crate) struct ExportState<S>(core::marker::PhantomData<fn() -> S>);
+
+impl<S> ExportState<S> {
+    pub(crate) fn new() -> Self {
+        Self(core::marker::PhantomData)
+    }
+}
+
+impl<S: Send + 'static> Message for ExportState<S> {
+    type Result = Option<S>;
+}
+
+impl<A: Handoff> Handler<ExportState<A::State>> for A {
+    async fn handle_message<D: Delegate>(&self, _message: ExportState<A::State>, _context: &ActorContext<D>) -> Option<A::State> {
+        self.export_state().await
+    }
 }
 
 /// # [`ActorContext`]
@@ -37,6 +94,22 @@ pub struct ActorContext<D> {
     pub(crate) system: Fluxion<D>,
     /// The actor's id
     pub(crate) id: usize,
+    /// Messages deferred with [`ActorContext::stash`], in the order they were stashed
+    pub(crate) stash: Mutex<VecDeque<Stashed<D>>>,
+    /// Timers started with [`ActorContext::start_timer`], keyed by name
+    pub(crate) timers: Arc<Mutex<BTreeMap<String, ScheduleHandle>>>,
+    /// Whether the actor has received a message since the last passivation sweep
+    pub(crate) active: AtomicBool,
+    /// Whether the actor is being killed for being idle
+    pub(crate) passivating: AtomicBool,
+    /// The passivation sweep, if the actor was added with [`Fluxion::add_named_with_passivation`]
+    pub(crate) passivation: Mutex<Option<ScheduleHandle>>,
+    /// The actor's counters, which are shared with the system's registry
+    pub(crate) stats: Arc<ActorStats>,
+    /// The id of the next request made with [`ActorContext::request_reentrant`]
+    pub(crate) requests: AtomicU64,
+    /// Admits messages to the actor, and signals its handlers once it is stopped
+    pub(crate) gate: Arc<crate::drain::Gate>,
 }
 
 impl<D: Delegate> ActorContext<D> {
@@ -53,8 +126,405 @@ impl<D: Delegate> ActorContext<D> {
     pub fn system(&self) -> &Fluxion<D> {
         &self.system
     }
+
+    /// # [`ActorContext::spawn`]
+    /// Starts configuring an actor to add to the same system as this actor, like [`Fluxion::spawn`].
+    pub fn spawn<A: Actor>(&self, actor: A) -> crate::Spawn<'_, A, D> {
+        self.system.spawn(actor)
+    }
+
+    /// # [`ActorContext::get`]
+    /// Retrieves a reference to another actor through this actor's system, like [`Fluxion::get`].
+    #[cfg(feature = "serde")]
+    pub async fn get<'a, A: Handler<M>, M: IndeterminateMessage>(&self,
+            #[cfg(feature="foreign")] id: impl Into<Identifier<'a>>,
+            #[cfg(not(feature="foreign"))] id: impl Into<Identifier>
+        ) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: serde::Serialize + for<'d> serde::Deserialize<'d> {
+        self.system.get::<A, M>(id).await
+    }
+
+    /// # [`ActorContext::get`]
+    /// Retrieves a reference to another actor through this actor's system, like [`Fluxion::get`].
+    #[cfg(not(feature = "serde"))]
+    pub async fn get<'a, A: Handler<M>, M: IndeterminateMessage>(&self,
+            id: impl Into<Identifier<'a>>,
+        ) -> Option<Arc<dyn MessageSender<M>>> {
+        self.system.get::<A, M>(id).await
+    }
+
+    /// # [`ActorContext::my_name`]
+    /// Returns the name this actor was assigned, or [`None`] if it has none.
+    /// If the actor has been assigned more than one name, the first in sorted order is returned.
+    pub async fn my_name(&self) -> Option<String> {
+        let id = self.id as u64;
+        self.system.actor_ids.read().await.iter()
+            .find(|(_, actor)| **actor == id)
+            .map(|(name, _)| name.clone())
+    }
+
+    /// # [`ActorContext::kill_self`]
+    /// Kills this actor, like [`Fluxion::kill_any`], returning false if it had already been killed.
+    /// Returns once [`Actor::deinitialize`] has completed, after which the handler carries on until it returns, but
+    /// messages sent to the actor fail. Any names the actor has are removed, as with [`Fluxion::kill`].
+    pub async fn kill_self(&self) -> bool {
+        self.system.kill_any(self.id as u64).await
+    }
+
+    /// # [`ActorContext::stash`]
+    /// Defers a message until [`ActorContext::unstash_all`] is called, for example while the actor is
+    /// waiting on a handshake to complete. The actor's type must be provided, usually as `context.stash::<Self, _>(message)`.
+    ///
+    /// Returns a [`crate::Deferred`] that resolves to the result of handling the message once it is unstashed, or to the
+    /// error its delivery failed with. The handler awaits it and returns the result, so that the original sender waits
+    /// until the message has actually been handled.
+    ///
+    /// <div class = "warning">
+    /// Awaiting the result holds on to the actor like any other send, so the message that unstashes it needs one of the
+    /// actor's other slots. An actor that handles one message at a time would wait forever, and should return without
+    /// awaiting it.
+    /// </div>
+    pub fn stash<A: Handler<M>, M: Message>(&self, message: M) -> crate::Deferred<M::Result> {
+        let (reply_to, reply) = crate::Deferred::channel();
+
+        self.stash.lock().push_back(Box::new(move |system: Fluxion<D>, id: u64| -> Delivery {
+            Box::pin(async move {
+                let actor = match system.try_get_local::<A>(id).await {
+                    Ok(actor) => actor,
+                    Err(e) => {
+                        system.dead_letters.record::<M>(id, DeadLetterReason::lookup(&e)).await;
+                        reply_to.fail(MessageSendError::UnknownError(Box::new(e)));
+                        return;
+                    },
+                };
+
+                match actor.send(message).await {
+                    Ok(result) => reply_to.reply(result),
+                    Err(e) => reply_to.fail(e),
+                }
+            })
+        }));
+
+        reply
+    }
+
+    /// # [`ActorContext::unstash_all`]
+    /// Sends every stashed message back to the actor on the system's [`crate::Executor`], in the order they were
+    /// stashed, waiting for each to be handled before sending the next. They queue for the actor like any other message,
+    /// so they are handled once the actor is free to, which for an actor that handles one message at a time is after
+    /// the current handler returns. Messages stashed after this is called are kept for the next call.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoExecutor`] if the system has no [`crate::Executor`] to send the messages on, in which
+    /// case they stay stashed.
+    pub fn unstash_all(&self) -> Result<(), ScheduleError> {
+        let executor = self.system.executor.as_ref().ok_or(ScheduleError::NoExecutor)?;
+
+        let stashed = core::mem::take(&mut *self.stash.lock());
+        if stashed.is_empty() {
+            return Ok(());
+        }
+
+        let system = self.system.clone();
+        let id = self.id as u64;
+
+        executor.spawn(Box::pin(async move {
+            for message in stashed {
+                message(system.clone(), id).await;
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// # [`ActorContext::start_timer`]
+    /// Delivers the message to this actor once the given delay has elapsed, replacing any timer with the same key.
+    /// The timer is removed once it fires, after which [`ActorContext::cancel_timer`] returns false for its key.
+    /// The actor's type must be provided, usually as `context.start_timer::<Self, _>(key, delay, message)`.
+    /// Timers are cancelled automatically when the actor is removed from the system.
+    ///
+    /// # Errors
+    /// Returns an error if the system was built without a [`crate::Timer`] or an [`crate::Executor`].
+    pub fn start_timer<A: Handler<M>, M: Message>(&self, key: &str, delay: Duration, message: M) -> Result<(), ScheduleError> {
+        let system = self.system.clone();
+        let id = self.id as u64;
+        let timers = Arc::downgrade(&self.timers);
+        let key = String::from(key);
+
+        let timer = crate::scheduler::schedule_once(self.system.timer.as_ref(), self.system.executor.as_ref(), delay, {
+            let key = key.clone();
+            move |timer| {
+                // The timer is done once it fires, so its key is removed unless another timer has replaced it since.
+                // Marking it as cancelled under the lock stops it from being stored if it fires before it is stored.
+                if let Some(timers) = timers.upgrade() {
+                    let mut timers = timers.lock();
+                    timer.cancel();
+                    if timers.get(&key).is_some_and(|existing| existing.is(timer)) {
+                        timers.remove(&key);
+                    }
+                }
+
+                Some(deliver::<A, M, D>(system, id, message))
+            }
+        })?;
+
+        self.store_timer(key, timer);
+        Ok(())
+    }
+
+    /// # [`ActorContext::start_periodic_timer`]
+    /// Delivers a copy of the message to this actor every `period`, replacing any timer with the same key.
+    /// The first message is delivered after one period has elapsed.
+    /// Timers are cancelled automatically when the actor is removed from the system.
+    ///
+    /// # Errors
+    /// Returns an error if the system was built without a [`crate::Timer`] or an [`crate::Executor`].
+    pub fn start_periodic_timer<A: Handler<M>, M: Message + Clone>(&self, key: &str, period: Duration, message: M) -> Result<(), ScheduleError> {
+        self.insert_timer(key, period, move |system, id| Some(deliver::<A, M, D>(system, id, message.clone())))
+    }
+
+    /// # [`ActorContext::send_to_self`]
+    /// Sends the message to this actor on the system's [`crate::Executor`], without waiting for it to be handled.
+    /// Sending through a reference would handle the message on the current task, waiting on the permit the current
+    /// handler holds if the actor limits its [`Actor::MAX_CONCURRENCY`]. The actor's type must be provided, usually as
+    /// `context.send_to_self::<Self, _>(message)`. The message's result is discarded.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoExecutor`] if the system has no [`crate::Executor`].
+    pub fn send_to_self<A: Handler<M>, M: Message>(&self, message: M) -> Result<(), ScheduleError> {
+        let executor = self.system.executor.as_ref().ok_or(ScheduleError::NoExecutor)?;
+        executor.spawn(deliver::<A, M, D>(self.system.clone(), self.id as u64, message));
+        Ok(())
+    }
+
+    /// # [`ActorContext::continue_with`]
+    /// Continues a long computation in another invocation of this actor's handlers, by sending it the message that
+    /// carries the rest of the work with [`ActorContext::send_to_self`]. A handler that does a chunk of the work and then
+    /// continues with the remainder, rather than doing all of it at once, lets the actor handle other messages in between:
+    /// messages already waiting for a [`Actor::MAX_CONCURRENCY`] permit are handled before the continuation.
+    ///
+    /// Returns false without sending the message if the actor is being drained or stopped, so that the computation
+    /// ends rather than holding up the shutdown. The handler can then save its progress instead.
+    ///
+    /// ```ignore
+    /// impl Handler<Crunch> for Cruncher {
+    ///     async fn handle_message<D: Delegate>(&self, message: Crunch, context: &ActorContext<D>) {
+    ///         let (chunk, rest) = message.0.split_at(message.0.len().min(1024));
+    ///         self.crunch(chunk);
+    ///
+    ///         if !rest.is_empty() && context.continue_with::<Self, _>(Crunch(rest.to_vec())) != Ok(true) {
+    ///             self.save_progress(rest);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoExecutor`] if the system has no [`crate::Executor`].
+    pub fn continue_with<A: Handler<M>, M: Message>(&self, message: M) -> Result<bool, ScheduleError> {
+        if !self.gate.is_open() {
+            return Ok(false);
+        }
+
+        self.send_to_self::<A, M>(message)?;
+        Ok(true)
+    }
+
+    /// # [`ActorContext::cancel_timer`]
+    /// Cancels the timer with the given key, returning false if there was no such timer.
+    pub fn cancel_timer(&self, key: &str) -> bool {
+        let Some(timer) = self.timers.lock().remove(key) else {
+            return false;
+        };
+
+        timer.cancel();
+        true
+    }
+
+    /// Cancels every timer started by this actor.
+    pub(crate) fn cancel_timers(&self) {
+        for (_, timer) in core::mem::take(&mut *self.timers.lock()) {
+            timer.cancel();
+        }
+    }
+
+    /// Kills the actor once it has gone a whole period without receiving a message, after calling [`Actor::passivate`].
+    /// Killing the actor removes its names as well.
+    pub(crate) fn passivate_after<A: Actor>(self: &Arc<Self>, idle: Duration) -> Result<(), ScheduleError> {
+        let context = Arc::downgrade(self);
+
+        let sweep = crate::scheduler::schedule(self.system.timer.as_ref(), self.system.executor.as_ref(), idle, move || {
+            let context = context.upgrade()?;
+
+            if context.active.swap(false, Ordering::Relaxed) {
+                return Some(Box::pin(async {}));
+            }
+
+            context.passivating.store(true, Ordering::Relaxed);
+            let system = context.system.clone();
+            let id = context.id as u64;
+
+            Some(Box::pin(async move {
+                system.kill::<A>(id).await;
+            }))
+        })?;
+
+        *self.passivation.lock() = Some(sweep);
+        Ok(())
+    }
+
+    /// Schedules the deliveries produced by `next`, and stores the timer under the given key.
+    fn insert_timer(&self, key: &str, period: Duration, mut next: impl FnMut(Fluxion<D>, u64) -> Option<Delivery> + Send + 'static) -> Result<(), ScheduleError> {
+        let system = self.system.clone();
+        let id = self.id as u64;
+
+        let timer = crate::scheduler::schedule(self.system.timer.as_ref(), self.system.executor.as_ref(), period, move || next(system.clone(), id))?;
+
+        self.store_timer(String::from(key), timer);
+        Ok(())
+    }
+
+    /// Stores a timer under the given key, replacing and cancelling any existing timer with the same key.
+    /// A one-shot timer that has already fired is not stored.
+    fn store_timer(&self, key: String, timer: ScheduleHandle) {
+        let mut timers = self.timers.lock();
+        let replaced = if timer.is_cancelled() {
+            timers.remove(&key)
+        } else {
+            timers.insert(key, timer)
+        };
+
+        if let Some(existing) = replaced {
+            existing.cancel();
+        }
+    }
+
+    /// # [`ActorContext::stashed`]
+    /// Returns the number of messages currently stashed.
+    #[must_use]
+    pub fn stashed(&self) -> usize {
+        self.stash.lock().len()
+    }
+
+    /// # [`ActorContext::defer_reply`]
+    /// Creates a [`crate::ReplyToken`] that the handler can reply through after it has returned, along with the
+    /// [`crate::Deferred`] reply, which the handler returns as its message's result so the requester can await it.
+    /// The requester receives an error if the token is dropped without a reply.
+    #[must_use]
+    pub fn defer_reply<R>(&self) -> (crate::ReplyToken<R>, crate::Deferred<R>) {
+        crate::Deferred::channel()
+    }
+
+    /// # [`ActorContext::current_metadata`]
+    /// Returns the [`crate::Metadata`] of the message being handled, if its sender attached any.
+    /// Messages sent while handling it carry the same metadata.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn current_metadata(&self) -> Option<crate::Metadata> {
+        crate::Metadata::current()
+    }
+
+    /// # [`ActorContext::sender`]
+    /// Returns the actor that sent the message being handled, or [`None`] if it was sent from outside of any actor.
+    /// This allows actors to reply to the sender later, or to treat senders differently.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn sender(&self) -> Option<crate::SenderId> {
+        crate::sender::Caller::sender().map(|sender| sender.to_sender_id(&self.system.system_id))
+    }
+}
+
+/// Delivers a message to the actor of type `A` with the given id, recording a dead letter if it no longer exists.
+pub(crate) fn deliver<A: Handler<M>, M: Message, D: Delegate>(system: Fluxion<D>, id: u64, message: M) -> Delivery {
+    Box::pin(async move {
+        match system.try_get_local::<A>(id).await {
+            Ok(actor) => {
+                let _ = actor.tell(message).await;
+            },
+            Err(e) => system.dead_letters.record::<M>(id, DeadLetterReason::lookup(&e)).await,
+        }
+    })
+}
+
+/// # [`AddActorError`]
+/// An error that might be returned when adding a named actor to the system.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AddActorError<E> {
+    /// The actor's [`Actor::initialize`] method returned an error.
+    Initialize(E),
+    /// Another actor has already been assigned the given name.
+    NameTaken(String),
+    /// The system has no shutdown phase with the given name.
+    UnknownShutdownPhase(String),
+    /// The owned actor with the given id was killed before a reference to it could be returned.
+    Killed(u64),
+    /// The system has no core with the given index.
+    UnknownCore(usize),
+    /// The actor's passivation could not be scheduled.
+    Schedule(ScheduleError),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for AddActorError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AddActorError::Initialize(e) => write!(f, "AddActorError: actor failed to initialize: {e}"),
+            AddActorError::NameTaken(name) => write!(f, "AddActorError: the name \"{name}\" is already taken"),
+            AddActorError::UnknownShutdownPhase(phase) => write!(f, "AddActorError: there is no shutdown phase named \"{phase}\""),
+            AddActorError::Killed(id) => write!(f, "AddActorError: actor {id} was killed while it was being added"),
+            AddActorError::UnknownCore(core) => write!(f, "AddActorError: there is no core {core}"),
+            AddActorError::Schedule(e) => write!(f, "AddActorError: passivation could not be scheduled: {e}"),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for AddActorError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            AddActorError::Initialize(e) => Some(e),
+            AddActorError::NameTaken(_) | AddActorError::UnknownShutdownPhase(_) | AddActorError::Killed(_) | AddActorError::UnknownCore(_) => None,
+            AddActorError::Schedule(e) => Some(e),
+        }
+    }
+}
+
+/// # [`GetActorError`]
+/// Why an actor could not be retrieved with [`crate::Fluxion::try_get`] or [`crate::Fluxion::try_get_local`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GetActorError {
+    /// No local actor has the given id.
+    NotFound(u64),
+    /// No local actor has been assigned the given name.
+    NameNotFound(String),
+    /// The actor exists, but is not of the requested type.
+    WrongType {
+        /// The actor's id
+        id: u64,
+        /// The type name of the actor
+        actual: &'static str,
+        /// The type name that was requested
+        expected: &'static str,
+    },
+    /// The system's [`crate::Delegate`] could not retrieve the foreign actor. Holds the actor's formatted [`crate::Identifier`].
+    #[cfg(feature = "foreign")]
+    ForeignNotFound(String),
+}
+
+impl core::fmt::Display for GetActorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GetActorError::NotFound(id) => write!(f, "GetActorError: no actor has the id {id}"),
+            GetActorError::NameNotFound(name) => write!(f, "GetActorError: no actor has the name \"{name}\""),
+            GetActorError::WrongType { id, actual, expected } => write!(f, "GetActorError: actor {id} is a {actual}, not a {expected}"),
+            #[cfg(feature = "foreign")]
+            GetActorError::ForeignNotFound(id) => write!(f, "GetActorError: the delegate could not retrieve {id}"),
+        }
+    }
 }
 
+impl core::error::Error for GetActorError {}
+
 /// # [`Handler`]
 pub trait Handler<M: Message>: Actor {
     fn handle_message<D: Delegate>(
@@ -69,8 +539,31 @@ pub trait Handler<M: Message>: Actor {
 pub(crate) struct ActorWrapper<T: Actor, D: Delegate>(pub T, pub Arc<ActorContext<D>>);
 
 impl<R: Actor, D: Delegate> slacktor::Actor for ActorWrapper<R, D> {
-    fn destroy(&self) -> impl core::future::Future<Output = ()> + Send {
-        self.0.deinitialize()
+    async fn destroy(&self) {
+        // Handlers still running are told to stop, and timers and topics must not deliver to the actor once it has stopped
+        self.1.gate.stop();
+        self.1.cancel_timers();
+        self.1.system.event_bus.remove_actor(self.1.id as u64);
+        self.1.system.broadcasts.remove_actor(self.1.id as u64);
+        self.1.system.health.remove_actor(self.1.id as u64);
+
+        if let Some(sweep) = self.1.passivation.lock().take() {
+            sweep.cancel();
+        }
+
+        // Idle actors get a chance to persist their state before deinitializing
+        if self.1.passivating.load(Ordering::Relaxed) {
+            self.0.passivate().await;
+        }
+
+        self.0.deinitialize().await;
+
+        if let Some(metrics) = &self.1.system.metrics {
+            metrics.actor_stopped(self.1.id as u64);
+        }
+
+        // Notify lifecycle subscribers now that the actor has fully stopped
+        self.1.system.lifecycle.publish(&LifecycleEvent::ActorStopped { id: self.1.id as u64 });
     }
 }
 
@@ -80,6 +573,89 @@ impl<R: Handler<M>, M: Message, D: Delegate> slacktor::actor::Handler<M> for Act
         &self,
         message: M,
     ) -> impl core::future::Future<Output = <M as Message>::Result> + Send {
-        self.0.handle_message(message, &self.1)
+        self.1.active.store(true, Ordering::Relaxed);
+        let handle = instrument!(self.0.handle_message(message, &self.1), "fluxion::handle", actor = self.1.id, message = core::any::type_name::<M>());
+
+        let handling = async move {
+            // The handler joins the call chain if deadlocks are detected
+            #[cfg(feature = "std")]
+            let handle = {
+                let call = self.1.system.deadlock_detection.then_some((&*self.1.system.system_id, self.1.id as u64));
+                crate::Metadata::scope_if(crate::Metadata::for_handler(call), handle)
+            };
+
+            let in_flight = InFlight::start(&self.1.stats.in_flight);
+
+            let result = match &self.1.system.metrics {
+                Some(metrics) => self.1.measure::<M, _>(metrics.as_ref(), in_flight, handle).await,
+                None => {
+                    let result = handle.await;
+                    in_flight.finish();
+                    result
+                },
+            };
+
+            self.1.stats.processed.fetch_add(1, Ordering::Relaxed);
+            result
+        };
+
+        // The handler runs as this actor, so that the messages it sends have it as their sender
+        #[cfg(feature = "std")]
+        {
+            crate::sender::Caller { system: self.1.system.system_id.clone(), actor: self.1.id as u64 }.handle(handling)
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            handling
+        }
+    }
+}
+
+impl<D> ActorContext<D> {
+    /// Handles a message, reporting it to the metrics sink.
+    async fn measure<M, F: Future>(&self, metrics: &dyn MetricsSink, in_flight: InFlight<'_>, handle: F) -> F::Output {
+        let actor = self.id as u64;
+        let message = core::any::type_name::<M>();
+        let timer = self.system.timer.as_ref();
+
+        metrics.message_started(actor, message, in_flight.count);
+
+        let started = timer.and_then(|timer| timer.now());
+        let result = handle.await;
+        let elapsed = started.zip(timer.and_then(|timer| timer.now())).map(|(started, ended)| ended.saturating_sub(started));
+
+        metrics.message_handled(actor, message, elapsed, in_flight.finish());
+
+        result
+    }
+}
+
+/// Counts a message as being handled until it finishes, or until its future is dropped.
+struct InFlight<'a> {
+    /// The actor's count of messages being handled
+    counter: &'a AtomicUsize,
+    /// The count once this message started
+    count: usize,
+}
+
+impl<'a> InFlight<'a> {
+    fn start(counter: &'a AtomicUsize) -> Self {
+        Self { counter, count: counter.fetch_add(1, Ordering::Relaxed) + 1 }
+    }
+
+    /// Stops counting the message, returning how many are still being handled.
+    fn finish(self) -> usize {
+        let remaining = self.counter.fetch_sub(1, Ordering::Relaxed) - 1;
+
+        // The count has already been decremented, so the drop must not run
+        core::mem::forget(self);
+
+        remaining
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::Relaxed);
     }
 }
@@ -0,0 +1,116 @@
+//! # Administration
+//! The [`AdminActor`] exposes administrative operations on a system as messages, so that a system can be administered
+//! remotely over the same foreign message machinery as any other actor. It is not added by default, and is added under
+//! the reserved name [`ADMIN_ACTOR_NAME`] with [`AdminActor::add`].
+//!
+//! With the `transport` feature, the messages are serializable, and can be exported like any other message:
+//! `exports.export::<AdminActor, ListActors>()`.
+
+use alloc::vec::Vec;
+
+use crate::{Actor, ActorContext, ActorInfo, AddActorError, Delegate, Fluxion, Handler, Message, MessageID};
+
+/// # [`ADMIN_ACTOR_NAME`]
+/// The name the [`AdminActor`] is added to a system under.
+pub const ADMIN_ACTOR_NAME: &str = "fluxion/admin";
+
+/// # [`AdminActor`]
+/// Handles [`ListActors`], [`KillActor`], and [`GetStats`] for the system it runs on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdminActor;
+
+impl AdminActor {
+    /// # [`AdminActor::add`]
+    /// Adds an admin actor to the system under [`ADMIN_ACTOR_NAME`], returning its id.
+    ///
+    /// # Errors
+    /// Returns an error if the name is already taken and the system's [`crate::NameConflictPolicy`] is to error.
+    pub async fn add<D: Delegate>(system: &Fluxion<D>) -> Result<u64, AddActorError<()>> {
+        system.add_named(ADMIN_ACTOR_NAME, AdminActor).await
+    }
+}
+
+impl Actor for AdminActor {
+    type Error = ();
+}
+
+/// # [`ListActors`]
+/// Returns an [`ActorInfo`] for every actor running on the system, as returned by [`Fluxion::inspect`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "transport", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListActors;
+
+impl Message for ListActors {
+    type Result = Vec<ActorInfo>;
+}
+
+impl MessageID for ListActors {
+    const ID: &'static str = "fluxion::admin::ListActors";
+}
+
+impl Handler<ListActors> for AdminActor {
+    async fn handle_message<D: Delegate>(&self, _message: ListActors, context: &ActorContext<D>) -> Vec<ActorInfo> {
+        context.system().inspect().await
+    }
+}
+
+/// # [`KillActor`]
+/// Kills the actor with the given id, returning false if there was no such actor.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "transport", derive(serde::Serialize, serde::Deserialize))]
+pub struct KillActor(pub u64);
+
+impl Message for KillActor {
+    type Result = bool;
+}
+
+impl MessageID for KillActor {
+    const ID: &'static str = "fluxion::admin::KillActor";
+}
+
+impl Handler<KillActor> for AdminActor {
+    async fn handle_message<D: Delegate>(&self, message: KillActor, context: &ActorContext<D>) -> bool {
+        context.system().kill_any(message.0).await
+    }
+}
+
+/// # [`GetStats`]
+/// Returns [`SystemStats`] summarizing the system.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "transport", derive(serde::Serialize, serde::Deserialize))]
+pub struct GetStats;
+
+impl Message for GetStats {
+    type Result = SystemStats;
+}
+
+impl MessageID for GetStats {
+    const ID: &'static str = "fluxion::admin::GetStats";
+}
+
+/// # [`SystemStats`]
+/// A summary of every actor running on a system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "transport", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct SystemStats {
+    /// The number of running actors
+    pub actors: usize,
+    /// The number of running actors that have at least one name
+    pub named: usize,
+    /// The number of messages handled by every running actor
+    pub processed: u64,
+    /// The number of messages currently being handled
+    pub in_flight: usize,
+}
+
+impl Handler<GetStats> for AdminActor {
+    async fn handle_message<D: Delegate>(&self, _message: GetStats, context: &ActorContext<D>) -> SystemStats {
+        context.system().inspect().await.iter().fold(SystemStats::default(), |stats, actor| SystemStats {
+            actors: stats.actors + 1,
+            named: stats.named + usize::from(!actor.names.is_empty()),
+            processed: stats.processed + actor.processed,
+            in_flight: stats.in_flight + actor.in_flight,
+        })
+    }
+}
@@ -0,0 +1,201 @@
+//! # Batching
+//! Actors that do expensive work per call, such as database writes or network flushes, can implement [`HandleBatch`]
+//! to handle many messages in one call. A [`Batcher`] accumulates messages sent through it, and delivers them to the
+//! actor once the batch is full or once a short window has elapsed since a message was added, whichever comes first.
+//! Each sender still receives the result of its own message.
+
+use core::time::Duration;
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use maitake_sync::{spin::Mutex, WaitQueue};
+
+use crate::{Actor, ActorContext, Delegate, Handler, LocalRef, Message, MessageSendError, MessageSender, ScheduleError};
+
+/// # [`HandleBatch`]
+/// Handles many messages of the same type in one call. Messages are delivered in batches by a [`Batcher`].
+pub trait HandleBatch<M: Message>: Actor {
+    /// # [`HandleBatch::handle_batch`]
+    /// Handles a batch of messages, returning the result of each in the same order.
+    /// Messages without a matching result fail with [`MessageSendError::UnknownError`].
+    fn handle_batch<D: Delegate>(&self, messages: Vec<M>, context: &ActorContext<D>) -> impl core::future::Future<Output = Vec<M::Result>> + Send;
+}
+
+/// Delivers a batch of messages to an actor that implements [`HandleBatch`].
+pub(crate) struct Batch<M>(Vec<M>);
+
+impl<M: Message> Message for Batch<M> {
+    type Result = Vec<M::Result>;
+}
+
+impl<A: HandleBatch<M>, M: Message> Handler<Batch<M>> for A {
+    async fn handle_message<D: Delegate>(&self, message: Batch<M>, context: &ActorContext<D>) -> Vec<M::Result> {
+        self.handle_batch(message.0, context).await
+    }
+}
+
+/// The result of a single message in a batch, which its sender waits on.
+struct Slot<R> {
+    /// The result, once the batch has been handled
+    result: Mutex<Option<Result<R, MessageSendError>>>,
+    /// Woken once the result is available
+    ready: WaitQueue,
+}
+
+impl<R> Slot<R> {
+    /// Stores the result and wakes the sender.
+    fn fill(&self, result: Result<R, MessageSendError>) {
+        *self.result.lock() = Some(result);
+        self.ready.wake_all();
+    }
+
+    /// Waits for the result.
+    async fn wait(&self) -> Result<R, MessageSendError> {
+        // The queue is never closed, so it can only finish with a result
+        self.ready.wait_for_value(|| self.result.lock().take()).await
+            .unwrap_or(Err(MessageSendError::UnknownError(Box::new(BatchFailed(String::from("the batch was abandoned"))))))
+    }
+}
+
+/// The batch currently being accumulated.
+struct Pending<M: Message> {
+    /// The messages in the batch
+    messages: Vec<M>,
+    /// Where the result of each message goes
+    slots: Vec<Arc<Slot<M::Result>>>,
+    /// Incremented whenever a batch is taken, so that senders can tell whether their batch was already delivered
+    generation: u64,
+}
+
+/// # [`Batcher`]
+/// Accumulates messages sent through it into batches, which are delivered to an actor that implements [`HandleBatch`].
+/// A batch is delivered once it holds `size` messages, or once `window` has elapsed since any of its messages was sent.
+pub struct Batcher<A: Actor, M: Message, D: Delegate> {
+    /// The actor batches are delivered to
+    target: LocalRef<A, D>,
+    /// The most messages in a batch
+    size: usize,
+    /// How long a message waits for its batch to fill
+    window: Duration,
+    /// The batch being accumulated
+    pending: Arc<Mutex<Pending<M>>>,
+}
+
+impl<A: Actor, M: Message, D: Delegate> Clone for Batcher<A, M, D> {
+    fn clone(&self) -> Self {
+        Self { target: self.target.clone(), size: self.size, window: self.window, pending: self.pending.clone() }
+    }
+}
+
+impl<A: HandleBatch<M>, M: Message, D: Delegate> Batcher<A, M, D> {
+    /// # [`Batcher::new`]
+    /// Creates a batcher that delivers batches of up to `size` messages to the target. A size of zero is treated as one.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoTimer`] if the target's system was built without a [`crate::Timer`], as the window can't elapse.
+    pub fn new(target: LocalRef<A, D>, size: usize, window: Duration) -> Result<Self, ScheduleError> {
+        if target.system.timer.is_none() {
+            return Err(ScheduleError::NoTimer);
+        }
+
+        Ok(Self {
+            target,
+            size: size.max(1),
+            window,
+            pending: Arc::new(Mutex::new(Pending { messages: Vec::new(), slots: Vec::new(), generation: 0 })),
+        })
+    }
+
+    /// Adds a message to the batch, delivering the batch if it is full, and waits for the message's result.
+    async fn push(&self, message: M) -> Result<M::Result, MessageSendError> {
+        let slot = Arc::new(Slot { result: Mutex::new(None), ready: WaitQueue::new() });
+
+        let (generation, full) = {
+            let mut pending = self.pending.lock();
+            pending.messages.push(message);
+            pending.slots.push(slot.clone());
+            (pending.generation, pending.messages.len() >= self.size)
+        };
+
+        if full {
+            self.flush(generation).await;
+            return slot.wait().await;
+        }
+
+        // Every sender delivers its own batch once the window elapses, so a cancelled sender can't strand the others
+        let timer = self.target.system.timer.as_ref().expect("batchers are only created for systems with a timer");
+        match crate::timer::timeout(timer.as_ref(), self.window, slot.wait()).await {
+            Some(result) => result,
+            None => {
+                self.flush(generation).await;
+                slot.wait().await
+            },
+        }
+    }
+
+    /// Delivers the batch with the given generation, unless it was already delivered.
+    async fn flush(&self, generation: u64) {
+        let (messages, slots) = {
+            let mut pending = self.pending.lock();
+            if pending.generation != generation {
+                return;
+            }
+
+            pending.generation += 1;
+            (core::mem::take(&mut pending.messages), core::mem::take(&mut pending.slots))
+        };
+
+        match self.target.send(Batch(messages)).await {
+            Ok(results) => {
+                let mut results = results.into_iter();
+
+                for slot in slots {
+                    slot.fill(results.next().ok_or_else(|| {
+                        MessageSendError::UnknownError(Box::new(BatchFailed(String::from("the handler returned no result for the message"))))
+                    }));
+                }
+            },
+            Err(e) => {
+                // Errors can't be cloned, so each sender receives a description of it
+                let reason = alloc::format!("{e}");
+
+                for slot in slots {
+                    slot.fill(Err(MessageSendError::UnknownError(Box::new(BatchFailed(reason.clone())))));
+                }
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: HandleBatch<M>, M: Message, D: Delegate> MessageSender<M> for Batcher<A, M, D> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        match self.target.system.default_timeout {
+            Some(timeout) => self.send_timeout(message, timeout).await,
+            None => self.push(message).await,
+        }
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        let timer = self.target.system.timer.as_ref().expect("batchers are only created for systems with a timer");
+
+        crate::timer::timeout(timer.as_ref(), timeout, self.push(message)).await
+            .unwrap_or(Err(MessageSendError::Timeout))
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.push(message).await.map(|_| ())
+    }
+}
+
+/// A batch could not be delivered, or did not produce a result for a message.
+#[derive(Debug)]
+struct BatchFailed(String);
+
+impl core::fmt::Display for BatchFailed {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "BatchFailed: {}", self.0)
+    }
+}
+
+impl core::error::Error for BatchFailed {}
+
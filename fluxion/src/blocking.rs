@@ -0,0 +1,152 @@
+//! # Blocking
+//! Code that can't await, such as FFI callbacks, drop implementations, and threads that aren't part of an async runtime,
+//! can still reach actors with the `blocking` feature. [`LocalRef::send_blocking`] and [`Fluxion::block_on_get`] run
+//! the send or lookup on the system's [`crate::Executor`], and block the calling thread until it finishes. Systems
+//! without an executor run it on the calling thread instead, which only works if the handlers don't rely on a runtime.
+//!
+//! <div class = "warning">
+//! Blocking a thread that the executor runs its tasks on can deadlock, as the task being waited for may never get to run.
+//! These functions should only be called from threads the executor doesn't use.
+//! </div>
+
+use core::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+
+use alloc::{boxed::Box, string::String, sync::Arc};
+
+use crate::{Actor, Delegate, Fluxion, Handler, Identifier, IndeterminateMessage, LocalRef, Message, MessageSendError, MessageSender};
+
+/// Wakes a thread that is blocked polling a future.
+struct Unpark(std::thread::Thread);
+
+impl std::task::Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls a future on the current thread, parking it whenever the future is pending.
+fn park_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+
+        std::thread::park();
+    }
+}
+
+/// An [`Identifier`] that owns its names, so that it can be moved onto the executor.
+enum OwnedIdentifier {
+    Local(u64),
+    LocalNamed(String),
+    #[cfg(feature = "foreign")]
+    Foreign(u64, String),
+    #[cfg(feature = "foreign")]
+    ForeignNamed(String, String),
+}
+
+impl OwnedIdentifier {
+    /// Copies the names out of an identifier.
+    fn new(id: Identifier<'_>) -> Self {
+        match id {
+            Identifier::Local(id) => Self::Local(id),
+            Identifier::LocalNamed(name) => Self::LocalNamed(String::from(name)),
+            #[cfg(feature = "foreign")]
+            Identifier::Foreign(id, system) => Self::Foreign(id, String::from(system)),
+            #[cfg(feature = "foreign")]
+            Identifier::ForeignNamed(name, system) => Self::ForeignNamed(String::from(name), String::from(system)),
+        }
+    }
+
+    /// Borrows the identifier.
+    fn get(&self) -> Identifier<'_> {
+        match self {
+            Self::Local(id) => Identifier::Local(*id),
+            Self::LocalNamed(name) => Identifier::LocalNamed(name),
+            #[cfg(feature = "foreign")]
+            Self::Foreign(id, system) => Identifier::Foreign(*id, system),
+            #[cfg(feature = "foreign")]
+            Self::ForeignNamed(name, system) => Identifier::ForeignNamed(name, system),
+        }
+    }
+}
+
+/// The error a blocking send fails with if the executor dropped it before it completed.
+#[derive(Debug)]
+struct Abandoned;
+
+impl core::fmt::Display for Abandoned {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "the executor dropped the send before it completed")
+    }
+}
+
+impl core::error::Error for Abandoned {}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::block_on`]
+    /// Runs a future to completion on the system's executor, blocking the current thread until it finishes.
+    /// Systems without an executor run it on the current thread instead.
+    /// Returns [`None`] if the executor dropped the future before it finished.
+    /// <div class = "warning">
+    /// Blocking a thread that the executor runs its tasks on can deadlock.
+    /// </div>
+    pub fn block_on<F>(&self, future: F) -> Option<F::Output>
+        where F: Future + Send + 'static, F::Output: Send + 'static {
+        let Some(executor) = &self.executor else {
+            return Some(park_on(future));
+        };
+
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        executor.spawn(Box::pin(async move {
+            let _ = sender.send(future.await);
+        }));
+
+        receiver.recv().ok()
+    }
+
+    /// # [`Fluxion::block_on_get`]
+    /// Retrieves an actor reference like [`Fluxion::get`], blocking the current thread until it is retrieved.
+    #[cfg(feature = "serde")]
+    pub fn block_on_get<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: serde::Serialize + for<'d> serde::Deserialize<'d> {
+        let system = self.clone();
+        let id = OwnedIdentifier::new(id);
+
+        self.block_on(async move { system.get::<A, M>(id.get()).await }).flatten()
+    }
+
+    /// # [`Fluxion::block_on_get`]
+    /// Retrieves an actor reference like [`Fluxion::get`], blocking the current thread until it is retrieved.
+    #[cfg(not(feature = "serde"))]
+    pub fn block_on_get<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> {
+        let system = self.clone();
+        let id = OwnedIdentifier::new(id);
+
+        self.block_on(async move { system.get::<A, M>(id.get()).await }).flatten()
+    }
+}
+
+impl<A: Actor, D: Delegate> LocalRef<A, D> {
+    /// # [`LocalRef::send_blocking`]
+    /// Sends a message like [`MessageSender::send`], blocking the current thread until the response arrives.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`MessageSender::send`], or [`MessageSendError::UnknownError`] if the system's executor
+    /// dropped the send before it completed.
+    pub fn send_blocking<M: Message>(&self, message: M) -> Result<M::Result, MessageSendError>
+        where A: Handler<M> {
+        let actor = self.clone();
+
+        self.system.block_on(async move { actor.send(message).await })
+            .unwrap_or_else(|| Err(MessageSendError::UnknownError(Box::new(Abandoned))))
+    }
+}
@@ -0,0 +1,117 @@
+//! # Broadcasts
+//! Actors can't be asked which messages they handle, so each spawned with [`crate::Spawn::handles`] is indexed by the
+//! message types given there. [`Fluxion::broadcast`] then delivers a message to every actor indexed under its type,
+//! without knowing their types. Actors are removed from the index when they stop.
+//!
+//! [`Fluxion::notify`] broadcasts a system-wide notification in the same way, and also hands it to the system's
+//! [`Delegate`] with the `foreign` feature, which forwards it to every foreign system it knows of. Actors that don't
+//! handle the notification never see it.
+
+use core::{any::{Any, TypeId}, future::Future, pin::Pin};
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use maitake_sync::spin::RwLock;
+
+use crate::{Delegate, Fluxion, Handler, IndeterminateMessage, Message, MessageSender};
+
+/// Creates a reference accepting messages of type `M` to the actor with the given id, which must be of the type the function was created for.
+type ResolveFn<M, D> = for<'a> fn(&'a Fluxion<D>, u64) -> Pin<Box<dyn Future<Output = Option<Arc<dyn MessageSender<M>>>> + Send + 'a>>;
+
+/// Creates a [`ResolveFn`] for messages of type `M` sent to actors of type `A`.
+fn resolve<A: Handler<M>, M: Message, D: Delegate>(system: &Fluxion<D>, id: u64) -> Pin<Box<dyn Future<Output = Option<Arc<dyn MessageSender<M>>>> + Send + '_>> {
+    Box::pin(async move {
+        let reference = system.get_local::<A>(id).await?;
+        Some(Arc::new(reference) as Arc<dyn MessageSender<M>>)
+    })
+}
+
+/// A message type an actor accepts broadcasts of, recorded before the actor is spawned.
+pub(crate) struct Handles {
+    /// The type of message
+    message: TypeId,
+    /// The actor's [`ResolveFn`] for the message type
+    resolve: Box<dyn Any + Send + Sync>,
+}
+
+impl Handles {
+    /// Records that actors of type `A` accept broadcasts of `M`.
+    pub(crate) fn new<A: Handler<M>, M: Message, D: Delegate>() -> Self {
+        let resolve: ResolveFn<M, D> = resolve::<A, M, D>;
+        Self { message: TypeId::of::<M>(), resolve: Box::new(resolve) }
+    }
+}
+
+/// The actors that accept broadcasts of each message type, keyed by the message's [`TypeId`] and then by the actor's id.
+#[derive(Default)]
+pub(crate) struct MessageIndex(RwLock<BTreeMap<TypeId, BTreeMap<u64, Box<dyn Any + Send + Sync>>>>);
+
+impl MessageIndex {
+    /// Indexes the actor under each of the message types it handles.
+    pub(crate) fn insert(&self, id: u64, handles: Vec<Handles>) {
+        if handles.is_empty() {
+            return;
+        }
+
+        let mut index = self.0.write();
+        for handles in handles {
+            index.entry(handles.message).or_default().insert(id, handles.resolve);
+        }
+    }
+
+    /// Removes the actor from the index, forgetting message types that no actors are left under.
+    pub(crate) fn remove_actor(&self, id: u64) {
+        let mut index = self.0.write();
+        index.retain(|_, actors| {
+            actors.remove(&id);
+            !actors.is_empty()
+        });
+    }
+
+    /// Returns the id and [`ResolveFn`] of every actor indexed under `M`, in ascending order of id.
+    pub(crate) fn resolvers<M: Message, D: Delegate>(&self) -> Vec<(u64, ResolveFn<M, D>)> {
+        self.0.read().get(&TypeId::of::<M>())
+            .map(|actors| actors.iter()
+                .filter_map(|(id, resolve)| resolve.downcast_ref::<ResolveFn<M, D>>().map(|resolve| (*id, *resolve)))
+                .collect())
+            .unwrap_or_default()
+    }
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::broadcast`]
+    /// Tells a copy of the message to every local actor that was spawned with [`crate::Spawn::handles`] for `M`,
+    /// concurrently, and returns how many accepted it. Results of handling the message are discarded.
+    pub async fn broadcast<M: Message + Clone>(&self, message: M) -> usize {
+        let mut targets = Vec::new();
+        for (id, resolve) in self.broadcasts.resolvers::<M, D>() {
+            if let Some(target) = resolve(self, id).await {
+                targets.push(target);
+            }
+        }
+
+        crate::gather::broadcast(&targets, message).await
+    }
+
+    /// # [`Fluxion::notify`]
+    /// Broadcasts a notification to every local actor that handles it, like [`Fluxion::broadcast`], and with the `foreign`
+    /// feature forwards it to foreign systems through [`Delegate::notify`]. Returns how many local actors accepted it.
+    #[cfg(feature = "serde")]
+    pub async fn notify<N: IndeterminateMessage + Clone>(&self, notification: N) -> usize
+        where N::Result: serde::Serialize + for<'d> serde::Deserialize<'d> {
+        #[cfg(feature = "foreign")]
+        self.delegate.notify(notification.clone()).await;
+
+        self.broadcast(notification).await
+    }
+
+    /// # [`Fluxion::notify`]
+    /// Broadcasts a notification to every local actor that handles it, like [`Fluxion::broadcast`], and with the `foreign`
+    /// feature forwards it to foreign systems through [`Delegate::notify`]. Returns how many local actors accepted it.
+    #[cfg(not(feature = "serde"))]
+    pub async fn notify<N: IndeterminateMessage + Clone>(&self, notification: N) -> usize {
+        #[cfg(feature = "foreign")]
+        self.delegate.notify(notification.clone()).await;
+
+        self.broadcast(notification).await
+    }
+}
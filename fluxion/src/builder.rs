@@ -0,0 +1,293 @@
+//! # Builder
+//! System wide configuration for a [`Fluxion`] instance is provided through [`FluxionBuilder`].
+
+use core::time::Duration;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use maitake_sync::RwLock;
+use slacktor::Slacktor;
+
+use crate::{metrics::{MailboxObserver, MetricsSink}, DeadLetters, Delegate, Executor, Fluxion, LoadShedding, Middleware, ShutdownPhase, Timer};
+
+/// # [`FluxionBuilder`]
+/// Configures and creates a [`Fluxion`] instance.
+/// [`Fluxion::new`] is equivalent to `Fluxion::builder(id, delegate).build()`.
+pub struct FluxionBuilder<D> {
+    /// The identifier of the system
+    id: Arc<str>,
+    /// The foreign delegate of the system
+    delegate: D,
+    /// The timer used for time based functionality
+    timer: Option<Arc<dyn Timer>>,
+    /// The timeout applied to every send that doesn't provide its own
+    default_timeout: Option<Duration>,
+    /// The executor used to run background tasks
+    executor: Option<Arc<dyn Executor>>,
+    /// The executors of each core that actors can be pinned to, in the order they were added
+    cores: Vec<Arc<dyn Executor>>,
+    /// How names that are already taken are handled
+    name_conflict_policy: NameConflictPolicy,
+    /// Where measurements of the system's actors are reported
+    metrics: Option<Arc<dyn MetricsSink>>,
+    /// Observes messages waiting for the system's actors
+    mailbox_observer: Option<Arc<dyn MailboxObserver>>,
+    /// The phases of [`Fluxion::shutdown`], in the order they run
+    shutdown_phases: Vec<ShutdownPhase>,
+    /// The middleware that runs around every actor's messages
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// When sends are rejected because too many messages are being handled
+    load_shedding: Option<LoadShedding>,
+    /// The number of idempotency keys remembered for deduplicating foreign messages
+    #[cfg(feature = "foreign")]
+    deduplication_capacity: usize,
+    /// Decides which local actors foreign systems may reach
+    #[cfg(feature = "foreign")]
+    access_policy: Option<Arc<dyn crate::AccessPolicy>>,
+    /// What happens to actors whose handlers panic
+    #[cfg(feature = "std")]
+    panic_policy: crate::PanicPolicy,
+    /// Whether call chains are recorded to detect deadlocks
+    #[cfg(feature = "std")]
+    deadlock_detection: bool,
+    /// The keys foreign messages' signatures are verified with
+    #[cfg(feature = "signing")]
+    signing: Option<crate::transport::signing::Signing>,
+}
+
+/// # [`NameConflictPolicy`]
+/// Decides what [`Fluxion::add_named`] does when another actor already has the given name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameConflictPolicy {
+    /// Return [`crate::AddActorError::NameTaken`] without adding the actor. This is the default.
+    #[default]
+    Error,
+    /// Assign the name to the new actor. The existing actor keeps running, but can only be reached by its id.
+    Overwrite,
+    /// Assign the name to the new actor, and kill the existing actor.
+    KillExisting,
+}
+
+impl<D: Delegate> FluxionBuilder<D> {
+    /// # [`FluxionBuilder::new`]
+    /// Creates a new builder for a system with the given id and delegate
+    #[must_use]
+    pub fn new(id: &str, delegate: D) -> Self {
+        Self {
+            id: id.into(),
+            delegate,
+            timer: None,
+            default_timeout: None,
+            executor: None,
+            cores: Vec::new(),
+            name_conflict_policy: NameConflictPolicy::default(),
+            metrics: None,
+            mailbox_observer: None,
+            shutdown_phases: Vec::new(),
+            middleware: Vec::new(),
+            load_shedding: None,
+            #[cfg(feature = "foreign")]
+            deduplication_capacity: 1024,
+            #[cfg(feature = "foreign")]
+            access_policy: None,
+            #[cfg(feature = "std")]
+            panic_policy: crate::PanicPolicy::default(),
+            #[cfg(feature = "std")]
+            deadlock_detection: false,
+            #[cfg(feature = "signing")]
+            signing: None,
+        }
+    }
+
+    /// # [`FluxionBuilder::timer`]
+    /// Sets the [`Timer`] used by the system. Without a timer, time based functionality such as
+    /// timeouts is unavailable and will be ignored.
+    #[must_use]
+    pub fn timer<T: Timer>(mut self, timer: T) -> Self {
+        self.timer = Some(Arc::new(timer));
+        self
+    }
+
+    /// # [`FluxionBuilder::default_timeout`]
+    /// Sets a timeout that applies to every message sent through a [`crate::MessageSender`] retrieved from this system.
+    /// This only has an effect if a [`Timer`] is also provided.
+    #[must_use]
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// # [`FluxionBuilder::executor`]
+    /// Sets the [`Executor`] used by the system to run background tasks.
+    /// Without an executor, background functionality such as scheduled messages is unavailable.
+    #[must_use]
+    pub fn executor<E: Executor>(mut self, executor: E) -> Self {
+        self.executor = Some(Arc::new(executor));
+        self
+    }
+
+    /// # [`FluxionBuilder::core`]
+    /// Adds the [`Executor`] of a core that actors can be pinned to with [`crate::Spawn::on_core`]. Cores are numbered
+    /// from zero in the order they are added. The executor should run its tasks on a single thread pinned to the core,
+    /// such as a thread-per-core runtime or a current thread Tokio runtime on a pinned thread.
+    #[must_use]
+    pub fn core<E: Executor>(mut self, executor: E) -> Self {
+        self.cores.push(Arc::new(executor));
+        self
+    }
+
+    /// # [`FluxionBuilder::runtime`]
+    /// Sets both the [`Executor`] and the [`Timer`] used by the system to the same runtime,
+    /// such as those provided with the `tokio`, `smol`, `async-std`, `embassy`, and `wasm` features.
+    #[must_use]
+    pub fn runtime<R: Executor + Timer + Clone>(self, runtime: R) -> Self {
+        self.executor(runtime.clone()).timer(runtime)
+    }
+
+    /// # [`FluxionBuilder::name_conflict_policy`]
+    /// Sets how [`Fluxion::add_named`] handles names that are already taken.
+    /// Defaults to [`NameConflictPolicy::Error`].
+    #[must_use]
+    pub fn name_conflict_policy(mut self, policy: NameConflictPolicy) -> Self {
+        self.name_conflict_policy = policy;
+        self
+    }
+
+    /// # [`FluxionBuilder::metrics`]
+    /// Sets the [`MetricsSink`] that the system reports handled messages and failed sends to.
+    #[must_use]
+    pub fn metrics<M: MetricsSink>(mut self, sink: M) -> Self {
+        self.metrics = Some(Arc::new(sink));
+        self
+    }
+
+    /// # [`FluxionBuilder::mailbox_observer`]
+    /// Sets the [`MailboxObserver`] that is told when messages arrive at the system's actors, and when they start being handled.
+    #[must_use]
+    pub fn mailbox_observer<O: MailboxObserver>(mut self, observer: O) -> Self {
+        self.mailbox_observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// # [`FluxionBuilder::shutdown_phase`]
+    /// Adds a phase to [`Fluxion::shutdown`], which runs after every phase added before it.
+    /// Actors are assigned to the phase with [`Fluxion::set_shutdown_phase`], and any that have not stopped
+    /// once the deadline elapses are force-killed. Adding a phase with an existing name replaces its deadline.
+    #[must_use]
+    pub fn shutdown_phase(mut self, name: &str, deadline: Duration) -> Self {
+        match self.shutdown_phases.iter_mut().find(|phase| phase.name == name) {
+            Some(phase) => phase.deadline = deadline,
+            None => self.shutdown_phases.push(ShutdownPhase { name: String::from(name), deadline }),
+        }
+        self
+    }
+
+    /// # [`FluxionBuilder::middleware`]
+    /// Adds [`Middleware`] that runs around every message sent to the system's actors, after any middleware added before it.
+    #[must_use]
+    pub fn middleware<M: Middleware>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// # [`FluxionBuilder::load_shedding`]
+    /// Rejects low priority sends while the system's actors are handling too many messages at once, as configured by the [`LoadShedding`].
+    #[must_use]
+    pub fn load_shedding(mut self, shedding: LoadShedding) -> Self {
+        self.load_shedding = Some(shedding);
+        self
+    }
+
+    /// # [`FluxionBuilder::deduplication_capacity`]
+    /// Sets how many idempotency keys are remembered by [`Fluxion::deduplicate`] before the oldest are forgotten.
+    /// Defaults to 1024. A capacity of zero disables deduplication.
+    #[cfg(feature = "foreign")]
+    #[must_use]
+    pub fn deduplication_capacity(mut self, capacity: usize) -> Self {
+        self.deduplication_capacity = capacity;
+        self
+    }
+
+    /// # [`FluxionBuilder::access_policy`]
+    /// Sets the [`crate::AccessPolicy`] that decides which local actors foreign systems may resolve and message.
+    /// Without a policy, every actor the system exports may be reached.
+    #[cfg(feature = "foreign")]
+    #[must_use]
+    pub fn access_policy<P: crate::AccessPolicy>(mut self, policy: P) -> Self {
+        self.access_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// # [`FluxionBuilder::signing`]
+    /// Verifies the signatures of messages from foreign systems with the given keys, rejecting those with an invalid
+    /// signature as described in [`crate::transport::signing`]. The same keys should be given to the system's delegate,
+    /// with [`crate::transport::PeerDelegate::with_signing`], to sign the messages it sends.
+    #[cfg(feature = "signing")]
+    #[must_use]
+    pub fn signing(mut self, signing: crate::transport::signing::Signing) -> Self {
+        self.signing = Some(signing);
+        self
+    }
+
+    /// # [`FluxionBuilder::panic_policy`]
+    /// Sets what happens to actors whose handlers panic. Defaults to [`crate::PanicPolicy::Resume`].
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn panic_policy(mut self, policy: crate::PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// # [`FluxionBuilder::deadlock_detection`]
+    /// Records the chain of actors waiting on each message in its [`crate::Metadata`], so that a send to an actor that
+    /// handles one message at a time and is already waiting on the send fails with [`crate::MessageSendError::WouldDeadlock`]
+    /// instead of waiting forever. This costs an allocation per handled message, so it is disabled by default.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn deadlock_detection(mut self, enabled: bool) -> Self {
+        self.deadlock_detection = enabled;
+        self
+    }
+
+    /// # [`FluxionBuilder::build`]
+    /// Creates the configured [`Fluxion`] instance
+    #[must_use]
+    pub fn build(self) -> Fluxion<D> {
+        let dead_letters = Arc::new(DeadLetters::new(&self.id));
+
+        Fluxion {
+            slacktor: Arc::new(RwLock::new(Slacktor::new())),
+            system_id: self.id,
+            delegate: Arc::new(self.delegate),
+            actor_ids: Arc::default(),
+            timer: self.timer,
+            default_timeout: self.default_timeout,
+            executor: self.executor,
+            cores: self.cores.into(),
+            dead_letters,
+            lifecycle: Arc::default(),
+            registry: Arc::default(),
+            generation: Arc::default(),
+            groups: Arc::default(),
+            broadcasts: Arc::default(),
+            health: Arc::default(),
+            shutdown_request: Arc::default(),
+            event_bus: Arc::default(),
+            name_conflict_policy: self.name_conflict_policy,
+            metrics: self.metrics,
+            mailbox_observer: self.mailbox_observer,
+            shutdown_phases: self.shutdown_phases.into(),
+            middleware: self.middleware.into(),
+            load: Arc::new(crate::shed::Load::new(self.load_shedding)),
+            #[cfg(feature = "foreign")]
+            deduplication: Arc::new(crate::dedup::Deduplication::new(self.deduplication_capacity)),
+            #[cfg(feature = "foreign")]
+            access_policy: self.access_policy,
+            #[cfg(feature = "std")]
+            panic_policy: self.panic_policy,
+            #[cfg(feature = "std")]
+            deadlock_detection: self.deadlock_detection,
+            #[cfg(feature = "signing")]
+            signing: self.signing,
+        }
+    }
+}
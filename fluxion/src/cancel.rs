@@ -0,0 +1,214 @@
+//! # Cancellation
+//! Handlers that do long running work can stop early by checking the [`CancellationToken`] returned by
+//! [`ActorContext::cancellation`], which is cancelled once the actor is stopped, whether by being killed or by the
+//! system shutting down. Cancellation is cooperative: a handler that never checks its token runs to completion.
+//!
+//! With the `std` feature, a message sent with [`crate::MessageSender::send_cancellable`] can also be cancelled by its
+//! sender, through the returned [`Cancellable`]'s [`CancelHandle`] or by dropping the [`Cancellable`] before it
+//! completes. Messages sent while handling a cancellable message are cancelled along with it. Handlers run on the task
+//! that sent them their message, so dropping the send usually drops the handler too, but actors pinned to a core
+//! keep handling messages that were handed over, and cancelling explicitly lets the handler stop without the
+//! sender giving up on its response. Foreign systems are not told about cancellations.
+
+use core::future::Future;
+#[cfg(feature = "std")]
+use core::{cell::RefCell, pin::Pin, task::{Context, Poll}};
+
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use maitake_sync::WaitQueue;
+
+use crate::{ActorContext, Delegate};
+#[cfg(feature = "std")]
+use crate::MessageSendError;
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// The cancellation signal of the cancellable message whose send is currently being polled on this thread
+    static REQUEST: RefCell<Option<Signal>> = const { RefCell::new(None) };
+}
+
+/// A signal that is cancelled once the queue is closed, which wakes every task waiting on it.
+type Signal = Arc<WaitQueue>;
+
+/// Waits for the signal to be cancelled.
+async fn cancelled(signal: &WaitQueue) {
+    // The queue is only ever closed, so waiting on it only completes once it is cancelled
+    while signal.wait().await.is_ok() {}
+}
+
+/// # [`CancellationToken`]
+/// Tells a handler that it should stop, as described in the [module documentation](self).
+#[derive(Clone)]
+pub struct CancellationToken {
+    /// Cancelled once the actor is stopped
+    actor: Signal,
+    /// Cancelled by the sender of the message being handled, if it was sent with [`crate::MessageSender::send_cancellable`]
+    request: Option<Signal>,
+}
+
+impl CancellationToken {
+    /// # [`CancellationToken::is_cancelled`]
+    /// Returns true if the handler should stop.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.actor.is_closed() || self.request.as_ref().is_some_and(|request| request.is_closed())
+    }
+
+    /// # [`CancellationToken::cancelled`]
+    /// Waits until the handler should stop, for example to race against the handler's work.
+    pub async fn cancelled(&self) {
+        let mut actor = core::pin::pin!(cancelled(&self.actor));
+        let mut request = core::pin::pin!(async {
+            match &self.request {
+                Some(request) => cancelled(request).await,
+                None => core::future::pending().await,
+            }
+        });
+
+        core::future::poll_fn(|cx| {
+            if actor.as_mut().poll(cx).is_ready() || request.as_mut().poll(cx).is_ready() {
+                core::task::Poll::Ready(())
+            } else {
+                core::task::Poll::Pending
+            }
+        }).await;
+    }
+}
+
+impl<D: Delegate> ActorContext<D> {
+    /// # [`ActorContext::cancellation`]
+    /// Returns a [`CancellationToken`] that is cancelled once this actor is stopped, or, with the `std` feature, once
+    /// the sender of the message being handled cancels it.
+    #[must_use]
+    pub fn cancellation(&self) -> CancellationToken {
+        #[cfg(feature = "std")]
+        let request = current_request();
+        #[cfg(not(feature = "std"))]
+        let request = None;
+
+        CancellationToken { actor: self.gate.stopping.clone(), request }
+    }
+}
+
+/// # [`CancelHandle`]
+/// Cancels a message sent with [`crate::MessageSender::send_cancellable`].
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct CancelHandle(Signal);
+
+#[cfg(feature = "std")]
+impl CancelHandle {
+    /// # [`CancelHandle::cancel`]
+    /// Cancels the message's handler, and those of the messages it sent. Handlers that don't check their
+    /// [`CancellationToken`] are unaffected.
+    pub fn cancel(&self) {
+        self.0.close();
+    }
+
+    /// # [`CancelHandle::is_cancelled`]
+    /// Returns true if the message has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+/// Returns the cancellation signal of the cancellable message currently being sent, if any.
+#[cfg(feature = "std")]
+pub(crate) fn current_request() -> Option<Signal> {
+    REQUEST.with(|request| request.borrow().clone())
+}
+
+/// Runs a future with the given cancellation signal installed, if there is one.
+/// This carries a cancellable message's signal onto another task, such as a core's executor.
+#[cfg(feature = "std")]
+pub(crate) fn scope_request<F: Future>(signal: Option<Signal>, future: F) -> Requested<F> {
+    Requested { signal, future: Box::pin(future) }
+}
+
+/// A future running with the cancellation signal of the message it was sent for, returned by [`scope_request`].
+#[cfg(feature = "std")]
+pub(crate) struct Requested<F> {
+    /// The signal, if the message was cancellable
+    signal: Option<Signal>,
+    future: Pin<Box<F>>,
+}
+
+#[cfg(feature = "std")]
+impl<F: Future> Future for Requested<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        let Some(signal) = this.signal.clone() else {
+            return this.future.as_mut().poll(cx);
+        };
+
+        let previous = REQUEST.with(|request| request.replace(Some(signal)));
+        let result = this.future.as_mut().poll(cx);
+        REQUEST.with(|request| request.replace(previous));
+
+        result
+    }
+}
+
+/// # [`Cancellable`]
+/// A message sent with [`crate::MessageSender::send_cancellable`], which resolves to its response.
+/// Dropping it before it resolves cancels the message.
+#[cfg(feature = "std")]
+#[must_use = "the message is only sent once the send is awaited"]
+pub struct Cancellable<'a, R> {
+    /// Cancels the message
+    handle: CancelHandle,
+    /// The send, which runs with the message's cancellation signal installed
+    send: Pin<Box<dyn Future<Output = Result<R, MessageSendError>> + Send + 'a>>,
+    /// Whether the send has resolved
+    finished: bool,
+}
+
+#[cfg(feature = "std")]
+impl<'a, R> Cancellable<'a, R> {
+    /// Wraps a send, so that it can be cancelled.
+    pub(crate) fn new(send: Pin<Box<dyn Future<Output = Result<R, MessageSendError>> + Send + 'a>>) -> Self {
+        Self { handle: CancelHandle(Arc::default()), send, finished: false }
+    }
+
+    /// # [`Cancellable::handle`]
+    /// Returns a [`CancelHandle`] that cancels the message, for example from another task.
+    #[must_use]
+    pub fn handle(&self) -> CancelHandle {
+        self.handle.clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> Future for Cancellable<'_, R> {
+    type Output = Result<R, MessageSendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        // Install the signal for the duration of the poll, and put back whatever was current before
+        let previous = REQUEST.with(|request| request.replace(Some(this.handle.0.clone())));
+        let result = this.send.as_mut().poll(cx);
+        REQUEST.with(|request| request.replace(previous));
+
+        if result.is_ready() {
+            this.finished = true;
+        }
+
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> Drop for Cancellable<'_, R> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.handle.cancel();
+        }
+    }
+}
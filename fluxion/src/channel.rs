@@ -0,0 +1,87 @@
+//! # Channels
+//! Fluxion exposes several streams of events, such as dead letters, that any number of subscribers can listen to.
+//! This module provides the small broadcast channel that backs them without depending on any executor.
+
+use alloc::{collections::VecDeque, sync::{Arc, Weak}, vec::Vec};
+use maitake_sync::{spin::Mutex, WaitQueue};
+
+/// The state shared between a [`Publisher`] and a single [`Subscription`].
+struct Shared<T> {
+    /// Values that have been published but not yet received
+    queue: Mutex<VecDeque<T>>,
+    /// Woken whenever a value is published, and closed when the publisher is dropped
+    wait: WaitQueue,
+}
+
+/// # [`Subscription`]
+/// Receives every value published after the subscription was created, in order.
+/// Values are buffered until they are received, so subscriptions that are never read from should be dropped.
+pub struct Subscription<T>(Arc<Shared<T>>);
+
+impl<T> Subscription<T> {
+    /// # [`Subscription::recv`]
+    /// Waits for the next value. Returns [`None`] once the source of the values has been dropped
+    /// and every buffered value has been received.
+    pub async fn recv(&self) -> Option<T> {
+        match self.0.wait.wait_for_value(|| self.0.queue.lock().pop_front()).await {
+            Ok(value) => Some(value),
+            // The queue may still contain values published right before it was closed.
+            Err(_) => self.try_recv(),
+        }
+    }
+
+    /// # [`Subscription::try_recv`]
+    /// Returns the next value if one is buffered, without waiting.
+    #[must_use]
+    pub fn try_recv(&self) -> Option<T> {
+        self.0.queue.lock().pop_front()
+    }
+}
+
+/// Publishes values to every live [`Subscription`].
+pub(crate) struct Publisher<T> {
+    subscribers: Mutex<Vec<Weak<Shared<T>>>>,
+}
+
+impl<T> Default for Publisher<T> {
+    fn default() -> Self {
+        Self { subscribers: Mutex::new(Vec::new()) }
+    }
+}
+
+impl<T: Clone> Publisher<T> {
+    /// Creates a new subscription that receives every value published from now on.
+    pub(crate) fn subscribe(&self) -> Subscription<T> {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            wait: WaitQueue::new(),
+        });
+
+        self.subscribers.lock().push(Arc::downgrade(&shared));
+
+        Subscription(shared)
+    }
+
+    /// Sends a value to every subscription, removing subscriptions that have been dropped.
+    pub(crate) fn publish(&self, value: &T) {
+        self.subscribers.lock().retain(|subscriber| {
+            let Some(subscriber) = subscriber.upgrade() else {
+                return false;
+            };
+
+            subscriber.queue.lock().push_back(value.clone());
+            subscriber.wait.wake();
+            true
+        });
+    }
+}
+
+impl<T> Drop for Publisher<T> {
+    fn drop(&mut self) {
+        for subscriber in self.subscribers.lock().drain(..) {
+            if let Some(subscriber) = subscriber.upgrade() {
+                subscriber.wait.close();
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! # Circuit Breakers
+//! When a foreign system fails, every sender waiting on it can end up stuck or failing slowly, which in turn slows down
+//! whatever is waiting on them. A [`CircuitBreaker`] stops this from cascading: once sends through it fail a number of
+//! times in a row it opens, and rejects every send immediately with [`MessageSendError::CircuitOpen`]. After a cooldown
+//! it half-opens, letting a single send through to test the target, which closes the circuit again if it succeeds.
+
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use maitake_sync::spin::Mutex;
+
+use crate::{Delegate, Fluxion, Message, MessageSendError, MessageSender, RetryPolicy, ScheduleError, Timer};
+
+/// # [`CircuitState`]
+/// Whether a [`CircuitBreaker`] is letting sends through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Sends are let through, and consecutive failures are counted.
+    Closed,
+    /// Sends are rejected until the cooldown elapses.
+    Open,
+    /// A single send is let through to test whether the target has recovered.
+    HalfOpen,
+}
+
+/// The state of a [`CircuitBreaker`], along with what it needs to decide when to change.
+enum Circuit {
+    /// Counting consecutive failures
+    Closed(u32),
+    /// Opened at the given time, if the timer could tell
+    Open(Option<Duration>),
+    /// Testing the target, and whether the test send is in progress
+    HalfOpen(bool),
+}
+
+/// # [`CircuitBreaker`]
+/// A [`MessageSender`] that stops sending to a failing target, as described in the [module documentation](self).
+pub struct CircuitBreaker<M: Message> {
+    /// The sender messages are sent through
+    inner: Arc<dyn MessageSender<M>>,
+    /// The timer used to measure the cooldown
+    timer: Arc<dyn Timer>,
+    /// How many consecutive failures open the circuit
+    threshold: u32,
+    /// How long the circuit stays open before half-opening
+    cooldown: Duration,
+    /// Decides whether an error counts as a failure of the target
+    failure_on: Arc<dyn Fn(&MessageSendError) -> bool + Send + Sync>,
+    /// The circuit's state
+    circuit: Mutex<Circuit>,
+}
+
+impl<M: Message> CircuitBreaker<M> {
+    /// # [`CircuitBreaker::new`]
+    /// Creates a circuit breaker for `inner` that opens after `threshold` consecutive failures, and half-opens after `cooldown`.
+    /// By default, only [`RetryPolicy::is_transient`] errors count as failures.
+    ///
+    /// The cooldown is measured with [`Timer::now`]. If the system's timer can't read a clock, the circuit half-opens
+    /// on the first send after it opened.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoTimer`] if the system was built without a [`Timer`].
+    pub fn new<D: Delegate>(system: &Fluxion<D>, inner: Arc<dyn MessageSender<M>>, threshold: u32, cooldown: Duration) -> Result<Self, ScheduleError> {
+        let timer = system.timer.clone().ok_or(ScheduleError::NoTimer)?;
+
+        Ok(Self {
+            inner,
+            timer,
+            threshold: threshold.max(1),
+            cooldown,
+            failure_on: Arc::new(RetryPolicy::is_transient),
+            circuit: Mutex::new(Circuit::Closed(0)),
+        })
+    }
+
+    /// # [`CircuitBreaker::failure_on`]
+    /// Counts only the errors for which the predicate returns true as failures of the target.
+    #[must_use]
+    pub fn failure_on(mut self, predicate: impl Fn(&MessageSendError) -> bool + Send + Sync + 'static) -> Self {
+        self.failure_on = Arc::new(predicate);
+        self
+    }
+
+    /// # [`CircuitBreaker::state`]
+    /// Returns whether the circuit is currently letting sends through.
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        match *self.circuit.lock() {
+            Circuit::Closed(_) => CircuitState::Closed,
+            Circuit::Open(_) => CircuitState::Open,
+            Circuit::HalfOpen(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// # [`CircuitBreaker::reset`]
+    /// Closes the circuit, for example once the target is known to have recovered.
+    pub fn reset(&self) {
+        *self.circuit.lock() = Circuit::Closed(0);
+    }
+
+    /// Decides whether a send may go through, half-opening the circuit if its cooldown has elapsed.
+    fn admit(&self) -> Result<(), MessageSendError> {
+        let mut circuit = self.circuit.lock();
+
+        match *circuit {
+            Circuit::Closed(_) => Ok(()),
+            Circuit::HalfOpen(true) => Err(MessageSendError::CircuitOpen),
+            Circuit::HalfOpen(false) => {
+                *circuit = Circuit::HalfOpen(true);
+                Ok(())
+            },
+            Circuit::Open(since) => {
+                let elapsed = since.zip(self.timer.now()).is_none_or(|(since, now)| now.saturating_sub(since) >= self.cooldown);
+
+                if !elapsed {
+                    return Err(MessageSendError::CircuitOpen);
+                }
+
+                *circuit = Circuit::HalfOpen(true);
+                Ok(())
+            },
+        }
+    }
+
+    /// Records the outcome of a send that was let through.
+    fn record<T>(&self, result: &Result<T, MessageSendError>) {
+        let failed = result.as_ref().is_err_and(|e| (self.failure_on)(e));
+        let mut circuit = self.circuit.lock();
+
+        *circuit = match (&*circuit, failed) {
+            (_, false) => Circuit::Closed(0),
+            (Circuit::Closed(failures), true) if failures + 1 < self.threshold => Circuit::Closed(failures + 1),
+            (_, true) => Circuit::Open(self.timer.now()),
+        };
+    }
+
+    /// Sends through the circuit, rejecting the send if it is open.
+    async fn call<T>(&self, send: impl core::future::Future<Output = Result<T, MessageSendError>>) -> Result<T, MessageSendError> {
+        self.admit()?;
+        let trial = Trial(self);
+
+        let result = send.await;
+        core::mem::forget(trial);
+        self.record(&result);
+
+        result
+    }
+}
+
+/// Lets another send test a half-open circuit if the current test send is cancelled before it finishes.
+struct Trial<'a, M: Message>(&'a CircuitBreaker<M>);
+
+impl<M: Message> Drop for Trial<'_, M> {
+    fn drop(&mut self) {
+        let mut circuit = self.0.circuit.lock();
+        if let Circuit::HalfOpen(true) = *circuit {
+            *circuit = Circuit::HalfOpen(false);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Message> MessageSender<M> for CircuitBreaker<M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.call(self.inner.send(message)).await
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        self.call(self.inner.send_timeout(message, timeout)).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.call(self.inner.tell(message)).await
+    }
+
+    async fn send_idempotent(&self, key: &str, message: M) -> Result<M::Result, MessageSendError> {
+        self.call(self.inner.send_idempotent(key, message)).await
+    }
+}
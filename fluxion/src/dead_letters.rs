@@ -0,0 +1,141 @@
+//! # Dead Letters
+//! Messages that can not be delivered, either because their target does not exist or because the send failed,
+//! are reported to the system's [`DeadLetters`] instead of being silently lost. This includes foreign messages whose
+//! target could not be looked up through the system's [`crate::Delegate`], but not local lookups such as
+//! [`crate::Fluxion::try_get`] that fail before anything is sent, as their caller is told why they failed.
+//! With the `std` feature, each dead letter records the actor whose handler sent it, if any.
+
+use core::{any::TypeId, sync::atomic::{AtomicU64, Ordering}};
+
+use alloc::{string::{String, ToString}, sync::Arc};
+use maitake_sync::spin::RwLock;
+
+use crate::{channel::Publisher, GetActorError, Message, MessageSender, Subscription};
+
+/// # [`DeadLetterReason`]
+/// Describes why a message could not be delivered.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum DeadLetterReason {
+    /// No actor capable of handling the message could be found for the target.
+    NotFound,
+    /// The target was found, but it is not an actor of the type the message was sent to.
+    WrongType {
+        /// The type name of the actor
+        actual: &'static str,
+        /// The type name the message was sent to
+        expected: &'static str,
+    },
+    /// The target was found, but sending the message failed. Contains the error's description.
+    SendFailed(String),
+}
+
+impl DeadLetterReason {
+    /// Returns why a message could not be delivered to a target that could not be retrieved.
+    pub(crate) fn lookup(error: &GetActorError) -> Self {
+        match *error {
+            GetActorError::WrongType { actual, expected, .. } => DeadLetterReason::WrongType { actual, expected },
+            _ => DeadLetterReason::NotFound,
+        }
+    }
+}
+
+/// # [`DeadLetter`]
+/// A record of a message that could not be delivered.
+/// The message itself is not retained, as it is consumed by the failed send.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    /// The actor the message was addressed to, formatted from its [`crate::Identifier`]
+    pub target: String,
+    /// The actor whose handler sent the message, or [`None`] if it was sent from outside of any handler
+    #[cfg(feature = "std")]
+    pub sender: Option<crate::SenderId>,
+    /// The type name of the undeliverable message, or its type identifier if it came from a foreign system
+    pub message: &'static str,
+    /// Why the message could not be delivered
+    pub reason: DeadLetterReason,
+}
+
+impl Message for DeadLetter {
+    type Result = ();
+}
+
+/// # [`DeadLetters`]
+/// Collects undeliverable messages for a single system.
+/// Dead letters can be observed by subscribing with [`DeadLetters::subscribe`], or by registering an actor
+/// that handles [`DeadLetter`] with [`DeadLetters::set_handler`].
+#[derive(Default)]
+pub struct DeadLetters {
+    /// The id of the system, which senders are identified relative to
+    system: String,
+    /// The total number of dead letters recorded
+    count: AtomicU64,
+    /// Subscriptions to dead letters
+    publisher: Publisher<DeadLetter>,
+    /// The actor that dead letters are delivered to
+    handler: RwLock<Option<Arc<dyn MessageSender<DeadLetter>>>>,
+}
+
+impl DeadLetters {
+    /// Creates the dead letters of the system with the given id.
+    pub(crate) fn new(system: &str) -> Self {
+        Self { system: system.to_string(), ..Self::default() }
+    }
+
+    /// # [`DeadLetters::count`]
+    /// Returns the total number of dead letters recorded by the system.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// # [`DeadLetters::subscribe`]
+    /// Returns a [`Subscription`] that receives every dead letter recorded from now on.
+    #[must_use]
+    pub fn subscribe(&self) -> Subscription<DeadLetter> {
+        self.publisher.subscribe()
+    }
+
+    /// # [`DeadLetters::set_handler`]
+    /// Sets an actor that every dead letter will be sent to, replacing the previous handler.
+    /// Failures to deliver to the handler are not themselves recorded as dead letters.
+    pub fn set_handler(&self, handler: Arc<dyn MessageSender<DeadLetter>>) {
+        *self.handler.write() = Some(handler);
+    }
+
+    /// # [`DeadLetters::clear_handler`]
+    /// Removes the handler set by [`DeadLetters::set_handler`].
+    pub fn clear_handler(&self) {
+        *self.handler.write() = None;
+    }
+
+    /// Records a message of type `M` that could not be delivered to `target`.
+    pub(crate) async fn record<M: 'static>(&self, target: impl core::fmt::Display, reason: DeadLetterReason) {
+        // Dead letters about dead letters would only ever be sent to the handler that just failed.
+        if TypeId::of::<M>() == TypeId::of::<DeadLetter>() {
+            return;
+        }
+
+        self.record_named(target, core::any::type_name::<M>(), reason).await;
+    }
+
+    /// Records a message that could not be delivered to `target`, given its type's name rather than its type.
+    /// Foreign messages that are refused before they are decoded are recorded with their type identifier this way.
+    pub(crate) async fn record_named(&self, target: impl core::fmt::Display, message: &'static str, reason: DeadLetterReason) {
+        let letter = DeadLetter {
+            target: alloc::format!("{target}"),
+            #[cfg(feature = "std")]
+            sender: crate::sender::Caller::acting().map(|caller| caller.to_sender_id(&self.system)),
+            message,
+            reason,
+        };
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.publisher.publish(&letter);
+
+        let handler = self.handler.read().clone();
+        if let Some(handler) = handler {
+            let _ = handler.tell(letter).await;
+        }
+    }
+}
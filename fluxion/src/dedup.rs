@@ -0,0 +1,109 @@
+//! # Deduplication
+//! Delegates that retry sends may deliver the same foreign message more than once. Senders can attach an idempotency key
+//! with [`crate::MessageSender::send_idempotent`], and the receiving system remembers the serialized response to each key
+//! in a bounded cache, so that a duplicate is answered with the original response instead of being handled again.
+
+use core::future::Future;
+
+use alloc::{collections::{BTreeMap, VecDeque}, string::String, sync::Arc, vec::Vec};
+use maitake_sync::{spin, WaitQueue};
+
+/// A cached response, or a response that is still being produced.
+enum Entry {
+    /// The message is being handled, and duplicates wait on the queue until it closes.
+    Handling(Arc<WaitQueue>),
+    /// The message was handled, and duplicates receive this response.
+    Handled(Vec<u8>),
+}
+
+/// The contents of a [`Deduplication`] cache.
+#[derive(Default)]
+struct Entries {
+    /// The entry for each key
+    entries: BTreeMap<String, Entry>,
+    /// Keys in the order they were first seen, so that the oldest can be evicted
+    order: VecDeque<String>,
+}
+
+/// A bounded cache of responses to messages that were sent with an idempotency key.
+pub(crate) struct Deduplication {
+    /// The number of keys remembered before the oldest is forgotten
+    capacity: usize,
+    /// The cached responses
+    entries: spin::Mutex<Entries>,
+}
+
+impl Deduplication {
+    /// Creates a cache that remembers up to `capacity` keys. A capacity of zero disables deduplication.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { capacity, entries: spin::Mutex::default() }
+    }
+
+    /// Runs `handle` unless a message with the same key has already been handled, in which case its response is returned.
+    /// If a message with the same key is being handled, this waits for it to finish first.
+    /// Errors are not cached, so a duplicate of a message that failed is handled again.
+    pub(crate) async fn run<E>(&self, key: &str, handle: impl Future<Output = Result<Vec<u8>, E>>) -> Result<Vec<u8>, E> {
+        if self.capacity == 0 {
+            return handle.await;
+        }
+
+        let handling = loop {
+            let waiting = {
+                let mut entries = self.entries.lock();
+
+                match entries.entries.get(key) {
+                    Some(Entry::Handled(response)) => return Ok(response.clone()),
+                    Some(Entry::Handling(queue)) => queue.clone(),
+                    None => break entries.insert(key, self.capacity),
+                }
+            };
+
+            // The queue is only ever closed, so this completes once the original message has been handled
+            let _ = waiting.wait().await;
+        };
+
+        let result = handle.await;
+
+        {
+            let mut entries = self.entries.lock();
+
+            // The entry may have been evicted while the message was being handled, in which case the response is not cached
+            match &result {
+                Ok(response) => if let Some(entry) = entries.entries.get_mut(key) {
+                    *entry = Entry::Handled(response.clone());
+                },
+                Err(_) => entries.remove(key),
+            }
+        }
+
+        // Wake any duplicates, which either receive the cached response or handle the message themselves
+        handling.close();
+
+        result
+    }
+}
+
+impl Entries {
+    /// Marks a key as being handled, evicting the oldest keys to stay within `capacity`.
+    fn insert(&mut self, key: &str, capacity: usize) -> Arc<WaitQueue> {
+        while self.order.len() >= capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+
+        let queue = Arc::new(WaitQueue::new());
+        self.entries.insert(String::from(key), Entry::Handling(queue.clone()));
+        self.order.push_back(String::from(key));
+
+        queue
+    }
+
+    /// Forgets a key.
+    fn remove(&mut self, key: &str) {
+        if self.entries.remove(key).is_some() {
+            self.order.retain(|existing| existing != key);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! # Discovery
+//! Retrieving an actor with [`crate::Identifier::ForeignNamed`] requires its name to be resolved to an id by the foreign
+//! system, which answers from its own registry of named actors. Delegates keep the answers in a [`NameCache`], so that
+//! repeated lookups of the same name don't each cost a round trip, and invalidate them when the foreign system reports
+//! that the actor has died.
+
+use alloc::{collections::BTreeMap, string::String};
+use maitake_sync::spin::RwLock;
+
+/// # [`NameCache`]
+/// Caches the ids that names on a single foreign system resolved to.
+#[derive(Default)]
+pub struct NameCache {
+    /// The id each name resolved to
+    names: RwLock<BTreeMap<String, u64>>,
+}
+
+impl NameCache {
+    /// # [`NameCache::new`]
+    /// Creates an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`NameCache::get`]
+    /// Returns the id the name last resolved to, if it is cached.
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.names.read().get(name).copied()
+    }
+
+    /// # [`NameCache::insert`]
+    /// Caches the id a name resolved to, replacing any previous id.
+    pub fn insert(&self, name: &str, id: u64) {
+        self.names.write().insert(String::from(name), id);
+    }
+
+    /// # [`NameCache::invalidate`]
+    /// Forgets every name that resolved to the given actor, for example because it died.
+    pub fn invalidate(&self, id: u64) {
+        self.names.write().retain(|_, cached| *cached != id);
+    }
+
+    /// # [`NameCache::remove`]
+    /// Forgets a single name.
+    pub fn remove(&self, name: &str) {
+        self.names.write().remove(name);
+    }
+
+    /// # [`NameCache::clear`]
+    /// Forgets every name, for example because invalidations may have been missed while disconnected.
+    pub fn clear(&self) {
+        self.names.write().clear();
+    }
+}
@@ -0,0 +1,131 @@
+//! # Draining
+//! Actors are drained before being removed when no message may be lost, for example during a rolling restart.
+//! A draining actor refuses new messages with [`MessageSendError::Draining`], but every message that was already
+//! sent to it is handled before it is removed and [`crate::Actor::deinitialize`] runs.
+//!
+//! Shutting the system down does the same to each actor as it is stopped, refusing new messages with
+//! [`MessageSendError::SystemShuttingDown`] instead.
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use alloc::sync::Arc;
+use maitake_sync::WaitQueue;
+
+use crate::{mailbox::Mailbox, MessageSendError};
+
+/// The gate admits messages.
+const OPEN: u8 = 0;
+/// The gate refuses messages, as the actor is draining.
+const DRAINING: u8 = 1;
+/// The gate refuses messages, as the system is shutting down.
+const SHUTTING_DOWN: u8 = 2;
+
+/// Admits messages to a single actor until it starts draining, and tracks those that have not finished.
+pub(crate) struct Gate {
+    /// Whether new messages are admitted, and if not why
+    state: AtomicU8,
+    /// The number of admitted messages that have not finished
+    pending: AtomicUsize,
+    /// Woken whenever the last pending message finishes
+    idle: WaitQueue,
+    /// Closed once the actor is stopped, which cancels its handlers' [`crate::CancellationToken`]s
+    pub(crate) stopping: Arc<WaitQueue>,
+    /// Holds the admitted messages that are waiting for the actor to be able to handle them
+    pub(crate) mailbox: Mailbox,
+}
+
+impl Gate {
+    /// Creates an open gate for an actor that handles at most `limit` messages at once, if it has a limit.
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            state: AtomicU8::new(OPEN),
+            pending: AtomicUsize::new(0),
+            idle: WaitQueue::new(),
+            stopping: Arc::default(),
+            mailbox: Mailbox::new(limit),
+        }
+    }
+
+    /// Admits a message, which is pending until the returned pass is dropped.
+    pub(crate) fn enter(&self) -> Result<Pass<'_>, MessageSendError> {
+        // Counting the message before checking ensures that a drain either refuses it or waits for it
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let pass = Pass(self);
+
+        match self.state.load(Ordering::SeqCst) {
+            OPEN => Ok(pass),
+            DRAINING => Err(MessageSendError::Draining),
+            _ => Err(MessageSendError::SystemShuttingDown),
+        }
+    }
+
+    /// Admits a message that is handed to another task, which is pending until the returned pass is dropped.
+    pub(crate) fn enter_owned(self: &Arc<Self>) -> Result<OwnedPass, MessageSendError> {
+        // The borrowed pass is forgotten rather than dropped, so that the message stays pending until the owned pass is
+        core::mem::forget(self.enter()?);
+        Ok(OwnedPass(self.clone()))
+    }
+
+    /// Finishes an admitted message, waking any drain once none are pending.
+    fn leave(&self) {
+        if self.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.idle.wake_all();
+        }
+    }
+
+    /// Returns true if new messages are admitted.
+    pub(crate) fn is_open(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == OPEN
+    }
+
+    /// Refuses any new messages, unless the system is already shutting down.
+    pub(crate) fn close(&self) {
+        self.state.fetch_max(DRAINING, Ordering::SeqCst);
+    }
+
+    /// Refuses any new messages because the system is shutting down, and cancels the handlers still running.
+    pub(crate) fn shut_down(&self) {
+        self.state.store(SHUTTING_DOWN, Ordering::SeqCst);
+        self.stop();
+    }
+
+    /// Returns true once the actor has started stopping.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopping.is_closed()
+    }
+
+    /// Cancels the handlers still running, as the actor is being stopped.
+    pub(crate) fn stop(&self) {
+        self.stopping.close();
+    }
+
+    /// Refuses any new messages, and waits for every admitted message to finish.
+    pub(crate) async fn drain(&self) {
+        self.close();
+        self.settle().await;
+    }
+
+    /// Waits for every admitted message to finish.
+    pub(crate) async fn settle(&self) {
+        // The queue is never closed, so waiting can't fail
+        let _ = self.idle.wait_for(|| self.pending.load(Ordering::SeqCst) == 0).await;
+    }
+}
+
+/// A message admitted by a [`Gate`], which finishes when this is dropped.
+pub(crate) struct Pass<'a>(&'a Gate);
+
+impl Drop for Pass<'_> {
+    fn drop(&mut self) {
+        self.0.leave();
+    }
+}
+
+/// Counts an admitted message that is handled on another task, until it is dropped.
+pub(crate) struct OwnedPass(Arc<Gate>);
+
+impl Drop for OwnedPass {
+    fn drop(&mut self) {
+        self.0.leave();
+    }
+}
@@ -0,0 +1,108 @@
+//! # Event Bus
+//! Actors can subscribe to string topics with [`crate::Fluxion::subscribe_topic`], and receive every message
+//! of the subscribed type that is published to a matching topic with [`crate::Fluxion::publish`].
+//!
+//! Topics are made of segments separated by `/`. In a subscription's pattern, a `*` segment matches any single segment,
+//! so `metrics/*` matches `metrics/cpu` but not `metrics/cpu/0`.
+//!
+//! Actors may also subscribe to a message type with [`crate::Fluxion::subscribe`], receiving every message of that type
+//! regardless of the topic it is published to.
+
+use core::any::{Any, TypeId};
+
+use alloc::{string::String, vec::Vec};
+use maitake_sync::spin::RwLock;
+
+use crate::{actor::deliver, scheduler::Delivery, Delegate, Fluxion, Handler, Message};
+
+/// Delivers a published message, which must be of the type the function was created for, to the actor with the given id.
+type DeliverFn<D> = fn(Fluxion<D>, u64, &dyn Any) -> Option<Delivery>;
+
+/// A single actor's subscription to a topic pattern.
+struct TopicSubscription<D> {
+    /// The pattern the subscription matches topics against, or [`None`] if it matches every topic
+    pattern: Option<String>,
+    /// The subscribed actor's id
+    id: u64,
+    /// The type of message the actor subscribed to
+    message: TypeId,
+    /// Delivers a message to the actor
+    deliver: DeliverFn<D>,
+}
+
+/// The topic subscriptions of a single system.
+pub(crate) struct EventBus<D> {
+    subscriptions: RwLock<Vec<TopicSubscription<D>>>,
+}
+
+impl<D> Default for EventBus<D> {
+    fn default() -> Self {
+        Self { subscriptions: RwLock::new(Vec::new()) }
+    }
+}
+
+impl<D: Delegate> EventBus<D> {
+    /// Subscribes the actor to messages of type `M` published to topics matching the pattern, or to every topic if there is no pattern.
+    /// Subscribing the same actor to the same pattern and message twice has no effect.
+    pub(crate) fn subscribe<A: Handler<M>, M: Message + Clone>(&self, pattern: Option<&str>, id: u64) {
+        let mut subscriptions = self.subscriptions.write();
+
+        let message = TypeId::of::<M>();
+        if subscriptions.iter().any(|s| s.id == id && s.message == message && s.pattern.as_deref() == pattern) {
+            return;
+        }
+
+        subscriptions.push(TopicSubscription {
+            pattern: pattern.map(String::from),
+            id,
+            message,
+            deliver: deliver_any::<A, M, D>,
+        });
+    }
+
+    /// Removes the actor's subscription to the pattern, or to every topic if there is no pattern, for messages of type `M`, returning true if there were any.
+    pub(crate) fn unsubscribe<M: Message>(&self, pattern: Option<&str>, id: u64) -> bool {
+        let mut subscriptions = self.subscriptions.write();
+        let before = subscriptions.len();
+
+        let message = TypeId::of::<M>();
+        subscriptions.retain(|s| !(s.id == id && s.message == message && s.pattern.as_deref() == pattern));
+
+        subscriptions.len() != before
+    }
+
+    /// Removes every subscription belonging to the actor.
+    pub(crate) fn remove_actor(&self, id: u64) {
+        self.subscriptions.write().retain(|s| s.id != id);
+    }
+
+    /// Returns the deliveries of the message to every actor subscribed to a matching pattern.
+    pub(crate) fn deliveries<M: Message + Clone>(&self, system: &Fluxion<D>, topic: &str, message: &M) -> Vec<Delivery> {
+        let message_type = TypeId::of::<M>();
+
+        self.subscriptions.read().iter()
+            .filter(|s| s.message == message_type && s.pattern.as_deref().is_none_or(|pattern| matches(pattern, topic)))
+            .filter_map(|s| (s.deliver)(system.clone(), s.id, message))
+            .collect()
+    }
+}
+
+/// Creates a [`DeliverFn`] for messages of type `M` sent to actors of type `A`.
+fn deliver_any<A: Handler<M>, M: Message + Clone, D: Delegate>(system: Fluxion<D>, id: u64, message: &dyn Any) -> Option<Delivery> {
+    let message = message.downcast_ref::<M>()?.clone();
+    Some(deliver::<A, M, D>(system, id, message))
+}
+
+/// Returns true if the topic matches the pattern, treating `*` segments in the pattern as wildcards.
+fn matches(pattern: &str, topic: &str) -> bool {
+    let mut pattern = pattern.split('/');
+    let mut topic = topic.split('/');
+
+    loop {
+        match (pattern.next(), topic.next()) {
+            (None, None) => return true,
+            (Some(p), Some(t)) if p == "*" || p == t => {},
+            _ => return false,
+        }
+    }
+}
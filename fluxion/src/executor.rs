@@ -0,0 +1,107 @@
+//! # Executors
+//! Functionality that runs in the background, such as scheduled messages, needs to spawn tasks.
+//! Fluxion does this through a user provided [`Executor`], so that it stays independent of any particular runtime.
+//!
+//! Handlers run on the task that sent them their message, so CPU heavy work in a handler stalls that task and everything
+//! waiting on it. [`ActorContext::run_blocking`] moves such work onto the executor's blocking pool, if it has one.
+//!
+//! Systems can also be given one executor per core with [`crate::FluxionBuilder::core`], which should each run their
+//! tasks on a single thread pinned to that core. Actors spawned with [`crate::Spawn::on_core`] have every message handed
+//! to their core's executor, so that their state stays in that core's cache, whichever task sent the message.
+
+use core::{future::Future, pin::Pin};
+
+use alloc::{boxed::Box, sync::Arc};
+use maitake_sync::{spin::Mutex, WaitQueue};
+
+use crate::{ActorContext, Delegate, ScheduleError};
+
+/// # [`Executor`]
+/// Provides Fluxion with the ability to spawn tasks on whatever executor the user is running.
+/// For example, with Tokio this is just a wrapper around `tokio::spawn`.
+pub trait Executor: Send + Sync + 'static {
+    /// # [`Executor::spawn`]
+    /// Runs the given future to completion in the background.
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>);
+
+    /// # [`Executor::spawn_blocking`]
+    /// Runs the given closure on a thread where it may block, such as Tokio's `spawn_blocking` pool.
+    /// By default the closure runs on the calling thread, which is correct but blocks whatever task called it.
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+        task();
+    }
+}
+
+/// The result of work handed to an executor, which the task that handed it off waits on.
+struct Outcome<R> {
+    /// The work's result, once it has finished
+    result: Mutex<Option<R>>,
+    /// Woken once the result is available, and closed if the work never finishes
+    ready: WaitQueue,
+}
+
+impl<R> Outcome<R> {
+    /// Creates an outcome, along with the [`Abandon`] that fills it.
+    fn new() -> (Arc<Self>, Abandon<R>) {
+        let outcome = Arc::new(Self { result: Mutex::new(None), ready: WaitQueue::new() });
+        (outcome.clone(), Abandon(outcome))
+    }
+
+    /// Waits for the result, returning [`ScheduleError::Abandoned`] if the work was dropped before it finished.
+    async fn wait(&self) -> Result<R, ScheduleError> {
+        // The result is stored before the queue is closed, so check for it either way
+        let waited = self.ready.wait_for_value(|| self.result.lock().take()).await;
+        waited.ok()
+            .or_else(|| self.result.lock().take())
+            .ok_or(ScheduleError::Abandoned)
+    }
+}
+
+/// Fills an [`Outcome`], and closes its queue when dropped, so that work that panicked doesn't strand the waiting task.
+struct Abandon<R>(Arc<Outcome<R>>);
+
+impl<R> Abandon<R> {
+    /// Stores the result and wakes the waiting task.
+    fn fill(self, result: R) {
+        *self.0.result.lock() = Some(result);
+        self.0.ready.wake_all();
+    }
+}
+
+impl<R> Drop for Abandon<R> {
+    fn drop(&mut self) {
+        self.0.ready.close();
+    }
+}
+
+/// Runs a future on the given executor, and waits for its output on the current task.
+/// The future runs to completion even if the wait is cancelled.
+pub(crate) async fn run_on<T: Send + 'static>(executor: &dyn Executor, future: impl Future<Output = T> + Send + 'static) -> Result<T, ScheduleError> {
+    let (outcome, abandon) = Outcome::new();
+
+    executor.spawn(Box::pin(async move {
+        abandon.fill(future.await);
+    }));
+
+    outcome.wait().await
+}
+
+impl<D: Delegate> ActorContext<D> {
+    /// # [`ActorContext::run_blocking`]
+    /// Runs a CPU heavy or blocking closure with [`Executor::spawn_blocking`], and waits for its result without blocking
+    /// the task the handler is running on. Other messages are still handled in the meantime, unless the actor limits
+    /// its [`crate::Actor::MAX_CONCURRENCY`].
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoExecutor`] if the system has no [`Executor`], or [`ScheduleError::Abandoned`] if the
+    /// closure panicked or the executor dropped it before it ran.
+    pub async fn run_blocking<R: Send + 'static>(&self, task: impl FnOnce() -> R + Send + 'static) -> Result<R, ScheduleError> {
+        let executor = self.system.executor.as_ref().ok_or(ScheduleError::NoExecutor)?;
+
+        let (outcome, abandon) = Outcome::new();
+
+        executor.spawn_blocking(Box::new(move || abandon.fill(task())));
+
+        outcome.wait().await
+    }
+}
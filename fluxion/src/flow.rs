@@ -0,0 +1,105 @@
+//! # Flow Control
+//! A fast system sending to a slow one could otherwise queue an unbounded number of foreign messages on the receiver.
+//! With credit based flow control, the receiving system grants the sender a window of credits, each of which allows
+//! one message to be sent. The sender suspends once its credits are exhausted and asks for more with
+//! [`crate::Delegate::request_credits`], and the receiver grants more with [`crate::Delegate::grant_credits`]
+//! as it finishes handling the messages it was sent.
+
+use maitake_sync::Semaphore;
+
+/// # [`Credits`]
+/// The credits a sender has left to send messages to a single foreign system.
+pub struct Credits(Semaphore);
+
+impl Credits {
+    /// # [`Credits::new`]
+    /// Creates a window with the given number of credits, as initially granted by the receiver.
+    #[must_use]
+    pub fn new(initial: usize) -> Self {
+        Self(Semaphore::new(initial))
+    }
+
+    /// # [`Credits::try_acquire`]
+    /// Uses up a credit, returning false if there were none left.
+    pub fn try_acquire(&self) -> bool {
+        self.0.try_acquire(1).map(maitake_sync::semaphore::Permit::forget).is_ok()
+    }
+
+    /// # [`Credits::acquire`]
+    /// Uses up a credit, waiting until one is granted if there are none left.
+    /// Returns false if the window was closed while waiting.
+    pub async fn acquire(&self) -> bool {
+        self.0.acquire(1).await.map(maitake_sync::semaphore::Permit::forget).is_ok()
+    }
+
+    /// # [`Credits::grant`]
+    /// Adds credits granted by the receiver, waking any senders waiting for them.
+    pub fn grant(&self, credits: usize) {
+        self.0.add_permits(credits);
+    }
+
+    /// # [`Credits::available`]
+    /// Returns the number of credits left.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.0.available_permits()
+    }
+
+    /// # [`Credits::close`]
+    /// Closes the window, for example because the connection it belongs to was lost, failing any waiting senders.
+    pub fn close(&self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn uses_up_credits() {
+        let credits = Credits::new(2);
+
+        assert!(credits.try_acquire());
+        assert!(credits.try_acquire());
+        assert!(!credits.try_acquire());
+        assert_eq!(credits.available(), 0);
+
+        credits.grant(1);
+        assert_eq!(credits.available(), 1);
+        assert!(credits.try_acquire());
+    }
+
+    #[tokio::test]
+    async fn waits_for_credits_to_be_granted() {
+        let credits = Arc::new(Credits::new(0));
+        let waiting = tokio::spawn({
+            let credits = credits.clone();
+            async move { credits.acquire().await }
+        });
+
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        credits.grant(1);
+        assert!(waiting.await.unwrap());
+        assert_eq!(credits.available(), 0);
+    }
+
+    #[tokio::test]
+    async fn fails_waiting_senders_when_closed() {
+        let credits = Arc::new(Credits::new(0));
+        let waiting = tokio::spawn({
+            let credits = credits.clone();
+            async move { credits.acquire().await }
+        });
+
+        tokio::task::yield_now().await;
+        credits.close();
+
+        assert!(!waiting.await.unwrap());
+        assert!(!credits.try_acquire());
+    }
+}
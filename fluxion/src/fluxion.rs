@@ -1,11 +1,16 @@
 
-use alloc::sync::Arc;
-use maitake_sync::RwLock;
+use core::{any::Any, future::Future, pin::Pin, sync::atomic::{AtomicBool, AtomicU64, Ordering}, time::Duration};
+
+use alloc::{boxed::Box, sync::{Arc, Weak}};
+use maitake_sync::{spin, RwLock};
 use slacktor::Slacktor;
 
-use crate::{Actor, ActorContext, ActorWrapper, Delegate, Handler, Identifier, IndeterminateMessage, LocalRef, MessageSender};
+use crate::{channel::Publisher, event_bus::EventBus, Actor, ActorContext, AddActorError, ActorFailure, GetActorError, ActorWrapper, DeadLetterReason, DeadLetters, Delegate, Executor, FluxionBuilder, Gathered, Handler, Identifier, IndeterminateMessage, IndeterminateStreamMessage, LifecycleEvent, LocalRef, Message, MessageSender, NameConflictPolicy, ScheduleError, ScheduleHandle, ShutdownPhase, ShutdownReport, StopTimeout, StreamHandler, StreamSender, Subscription, Timer};
 use alloc::string::String;
-use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::ops::Bound;
+use crate::{drain::Gate, inspect::ActorStats, metrics::MetricsSink, owned::Owner, registry::Registry, spawn::SpawnOptions, trace::instrument, ActorInfo};
 
 
 
@@ -16,18 +21,145 @@ pub struct Fluxion<D> {
     /// This is wrapped in an [`Arc`] and [`RwLock`] to allow concurrent access from different tasks.
     /// The [`RwLock`] is used instead of a mutex because it can be assumed that actor references
     /// will be retrieved more often than actors are created.
-    slacktor: Arc<RwLock<Slacktor>>,
+    pub(crate) slacktor: Arc<RwLock<Slacktor>>,
     /// A mapping of string actor names to their slacktor ids.
-    actor_ids: Arc<RwLock<BTreeMap<String, u64>>>,
+    pub(crate) actor_ids: Arc<RwLock<BTreeMap<String, u64>>>,
     /// The identifier of this system as a string
-    system_id: Arc<str>,
+    pub(crate) system_id: Arc<str>,
     /// The foreign delegate of this system
-    delegate: Arc<D>,
+    pub(crate) delegate: Arc<D>,
+    /// The timer used for timeouts, if one was provided
+    pub(crate) timer: Option<Arc<dyn Timer>>,
+    /// The timeout applied to sends that don't specify their own
+    pub(crate) default_timeout: Option<Duration>,
+    /// The executor used to run background tasks, if one was provided
+    pub(crate) executor: Option<Arc<dyn Executor>>,
+    /// The executors of each core that actors can be pinned to
+    pub(crate) cores: Arc<[Arc<dyn Executor>]>,
+    /// Records messages that could not be delivered
+    pub(crate) dead_letters: Arc<DeadLetters>,
+    /// Publishes changes in the lifecycle of local actors
+    pub(crate) lifecycle: Arc<Publisher<LifecycleEvent>>,
+    /// Every local actor, keyed by the actor's id.
+    pub(crate) registry: Arc<Registry<D>>,
+    /// Changes whenever a name is assigned or removed, or an actor is killed, so that [`crate::NamedRef`]s re-resolve
+    pub(crate) generation: Arc<AtomicU64>,
+    /// The members of every group, keyed by label
+    pub(crate) groups: Arc<crate::group::Groups>,
+    /// The actors that accept broadcasts of each message type
+    pub(crate) broadcasts: Arc<crate::broadcast::MessageIndex>,
+    /// The latest result of probing each actor's health
+    pub(crate) health: Arc<crate::health::HealthResults>,
+    /// Whether the system has been asked to shut down
+    pub(crate) shutdown_request: Arc<crate::shutdown::ShutdownRequest>,
+    /// How [`Fluxion::add_named`] handles names that are already taken
+    pub(crate) name_conflict_policy: NameConflictPolicy,
+    /// Actor subscriptions to topics
+    pub(crate) event_bus: Arc<EventBus<D>>,
+    /// Where measurements of local actors are reported, if anywhere
+    pub(crate) metrics: Option<Arc<dyn MetricsSink>>,
+    /// Observes messages waiting for local actors, if anything does
+    pub(crate) mailbox_observer: Option<Arc<dyn crate::metrics::MailboxObserver>>,
+    /// The phases of [`Fluxion::shutdown`], in the order they run
+    pub(crate) shutdown_phases: Arc<[ShutdownPhase]>,
+    /// The middleware that runs around every actor's messages
+    pub(crate) middleware: crate::middleware::Layers,
+    /// The number of messages being handled by local actors, and when sends are rejected because of it
+    pub(crate) load: Arc<crate::shed::Load>,
+    /// Responses to foreign messages that were sent with an idempotency key
+    #[cfg(feature = "foreign")]
+    pub(crate) deduplication: Arc<crate::dedup::Deduplication>,
+    /// Decides which local actors foreign systems may reach, if any are restricted
+    #[cfg(feature = "foreign")]
+    pub(crate) access_policy: Option<Arc<dyn crate::AccessPolicy>>,
+    /// What happens to actors whose handlers panic
+    #[cfg(feature = "std")]
+    pub(crate) panic_policy: crate::PanicPolicy,
+    /// Whether sends that would wait on an actor already waiting on them fail instead
+    #[cfg(feature = "std")]
+    pub(crate) deadlock_detection: bool,
+    /// The keys foreign messages' signatures are verified with
+    #[cfg(feature = "signing")]
+    pub(crate) signing: Option<crate::transport::signing::Signing>,
+}
+
+/// Kills the actor with the given id, which must be of the type the function was created for.
+/// If a gate is given, the actor is only killed if it is still the one the gate belongs to, and not another actor that reused its id.
+pub(crate) type KillFn<D> = for<'a> fn(&'a Fluxion<D>, u64, Option<Arc<Gate>>) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>>;
+
+/// A local actor's entry in the system's registry, which allows it to be killed and inspected without knowing its type.
+pub(crate) struct Registered<D> {
+    /// A clone of the actor's `slacktor::ActorHandle`, from which references are created
+    pub(crate) handle: Box<dyn Any + Send + Sync>,
+    /// Kills the actor
+    pub(crate) kill: KillFn<D>,
+    /// The type name of the actor
+    pub(crate) actor: &'static str,
+    /// The actor's counters
+    pub(crate) stats: Arc<ActorStats>,
+    /// The index of the shutdown phase the actor is stopped in, if it was assigned one
+    pub(crate) phase: Option<usize>,
+    /// Admits messages to the actor until it is drained
+    pub(crate) gate: Arc<Gate>,
+    /// The middleware that runs around the actor's messages, after the system's
+    pub(crate) middleware: crate::middleware::Layers,
+    /// Limits how fast the actor accepts messages, if it was added with a rate limit
+    pub(crate) rate_limit: Option<Arc<crate::rate::TokenBucket>>,
+    /// What happens to the actor if one of its handlers panics, if it overrides the system's policy
+    #[cfg(feature = "std")]
+    pub(crate) panic_policy: Option<crate::PanicPolicy>,
+    /// Shared by every reference to the actor, if it was started as owned by its references
+    pub(crate) owner: Option<Weak<Owner<D>>>,
+    /// The system's own share of the actor's owner, held while the actor is named
+    pub(crate) retained: Option<Arc<Owner<D>>>,
+    /// The executor of the core the actor is pinned to, if it was pinned to one
+    pub(crate) core: Option<Arc<dyn Executor>>,
+    /// The labels of the groups the actor is in
+    pub(crate) groups: Vec<String>,
+}
+
+/// Creates a [`KillFn`] for actors of type `A`.
+fn killer<A: Actor, D: Delegate>(system: &Fluxion<D>, id: u64, gate: Option<Arc<Gate>>) -> Pin<Box<dyn Future<Output = bool> + Send + '_>> {
+    Box::pin(system.kill_exact::<A>(id, gate))
 }
 
 impl<D> Clone for Fluxion<D> {
     fn clone(&self) -> Self {
-        Self { slacktor: self.slacktor.clone(), system_id: self.system_id.clone(), delegate: self.delegate.clone(), actor_ids: self.actor_ids.clone() }
+        Self {
+            slacktor: self.slacktor.clone(),
+            system_id: self.system_id.clone(),
+            delegate: self.delegate.clone(),
+            actor_ids: self.actor_ids.clone(),
+            timer: self.timer.clone(),
+            default_timeout: self.default_timeout,
+            executor: self.executor.clone(),
+            cores: self.cores.clone(),
+            dead_letters: self.dead_letters.clone(),
+            lifecycle: self.lifecycle.clone(),
+            registry: self.registry.clone(),
+            generation: self.generation.clone(),
+            groups: self.groups.clone(),
+            broadcasts: self.broadcasts.clone(),
+            health: self.health.clone(),
+            shutdown_request: self.shutdown_request.clone(),
+            event_bus: self.event_bus.clone(),
+            name_conflict_policy: self.name_conflict_policy,
+            metrics: self.metrics.clone(),
+            mailbox_observer: self.mailbox_observer.clone(),
+            shutdown_phases: self.shutdown_phases.clone(),
+            middleware: self.middleware.clone(),
+            load: self.load.clone(),
+            #[cfg(feature = "foreign")]
+            deduplication: self.deduplication.clone(),
+            #[cfg(feature = "foreign")]
+            access_policy: self.access_policy.clone(),
+            #[cfg(feature = "std")]
+            panic_policy: self.panic_policy,
+            #[cfg(feature = "std")]
+            deadlock_detection: self.deadlock_detection,
+            #[cfg(feature = "signing")]
+            signing: self.signing.clone(),
+        }
     }
 }
 
@@ -36,12 +168,14 @@ impl<D: Delegate> Fluxion<D> {
     /// Creates a new [`Fluxion`] instance with the given system id and delegate
     #[must_use]
     pub fn new(id: &str, delegate: D) -> Self {
-        Self {
-            slacktor: Arc::new(RwLock::new(Slacktor::new())),
-            system_id: id.into(),
-            delegate: Arc::new(delegate),
-            actor_ids: Arc::default(),
-        }
+        FluxionBuilder::new(id, delegate).build()
+    }
+
+    /// # [`Fluxion::builder`]
+    /// Creates a [`FluxionBuilder`], which allows system wide options such as the [`Timer`] to be configured.
+    #[must_use]
+    pub fn builder(id: &str, delegate: D) -> FluxionBuilder<D> {
+        FluxionBuilder::new(id, delegate)
     }
 
     /// # [`Fluxion::get_delegate`]
@@ -65,32 +199,128 @@ impl<D: Delegate> Fluxion<D> {
         self.actor_ids.read().await.get(name).copied()
     }
 
+    /// # [`Fluxion::actor_names`]
+    /// Returns the names of every named actor on the local system, in sorted order.
+    pub async fn actor_names(&self) -> Vec<String> {
+        self.actor_ids.read().await.keys().cloned().collect()
+    }
+
+    /// # [`Fluxion::actors_with_prefix`]
+    /// Returns the name and id of every named actor whose name starts with the given prefix, sorted by name.
+    /// This allows names to be used hierarchically, for example retrieving every actor named `worker/*`.
+    pub async fn actors_with_prefix(&self, prefix: &str) -> Vec<(String, u64)> {
+        self.actor_ids.read().await
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(name, _)| name.starts_with(prefix))
+            .map(|(name, id)| (name.clone(), *id))
+            .collect()
+    }
+
+    /// # [`Fluxion::remove_name`]
+    /// Removes a name from the registry, returning the id it referred to.
+    /// The actor itself is not killed, and can still be accessed by its id.
+    pub async fn remove_name(&self, name: &str) -> Option<u64> {
+        let id = self.actor_ids.write().await.remove(name)?;
+        self.generation.fetch_add(1, Ordering::Release);
+
+        // Owned actors are only kept alive by their name until it is removed
+        self.release(id);
+        Some(id)
+    }
+
+    /// # [`Fluxion::inspect`]
+    /// Returns a snapshot of every actor running on the local system, ordered by id.
+    /// Uptimes are only available if the system's [`Timer`] can read a clock with [`Timer::now`].
+    pub async fn inspect(&self) -> Vec<ActorInfo> {
+        // Group the names by the actor they refer to
+        let mut names = BTreeMap::<u64, Vec<String>>::new();
+        for (name, id) in &*self.actor_ids.read().await {
+            names.entry(*id).or_default().push(name.clone());
+        }
+
+        let now = self.timer.as_ref().and_then(|timer| timer.now());
+
+        self.registry.collect(|id, registered| Some(registered.stats.snapshot(id, names.remove(&id).unwrap_or_default(), registered.actor, now)))
+    }
+
     /// # [`Fluxion::add_named`]
     /// Adds an actor to the local instance, returning its id and assigning
     /// the given name to it for retrieval by [`Fluxion::get_actor_id`].
     /// This is handy when using actors with static names on a foreign system.
+    /// If the name is already taken, the system's [`NameConflictPolicy`] decides what happens.
     /// <div class = "info">
     /// Locks the underlying RwLock as write. This will block "management" functionalities such as adding, removing, and retrieving actors, but
     /// will not block any messages.
     /// </div>
-    /// <div class = "warn">
-    ///     If an actor with a duplicate name is added, it will overwrite the original actor's name.
-    ///     The original actor won't be killed, but it may become inaccessible.
-    /// </div>
     /// 
     /// # Errors
-    /// Returns an error if the actor failed to initialize.
+    /// Returns [`AddActorError::Initialize`] if the actor failed to initialize,
+    /// or [`AddActorError::NameTaken`] if another actor already has the given name and the policy is [`NameConflictPolicy::Error`].
     /// On an error, the actor will not be spawned, and the name will not be assigned.
-    pub async fn add_named<A: Actor>(&self, name: &str, actor: A) -> Result<u64, A::Error> {
-        // Add the actor, assigning an id
-        let id = self.add(actor).await?;
+    /// If the name was taken while the actor was initializing, the actor will be deinitialized.
+    pub async fn add_named<A: Actor>(&self, name: &str, actor: A) -> Result<u64, AddActorError<A::Error>> {
+        instrument!(self.spawn_named(name, actor, SpawnOptions::default()), "fluxion::add", system = %self.system_id, actor = core::any::type_name::<A>(), name)
+            .await.map(|(id, _)| id)
+    }
+
+    /// # [`Fluxion::add_named_with_passivation`]
+    /// Adds a named actor like [`Fluxion::add_named`], but passivates it once it has received no messages for roughly
+    /// the given duration. An idle actor is passivated after between one and two periods: its [`Actor::passivate`] hook is called,
+    /// its name is removed, and it is killed.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`Fluxion::add_named`], or [`AddActorError::Schedule`] if the system was built without
+    /// a [`Timer`] or an [`Executor`]. On an error, the actor will not be spawned, and the name will not be assigned.
+    pub async fn add_named_with_passivation<A: Actor>(&self, name: &str, actor: A, idle: Duration) -> Result<u64, AddActorError<A::Error>> {
+        self.spawn(actor).named(name).passivate_after(idle).start().await
+    }
 
-        // Store the actor's name in the actor_ids map
+    /// Adds a named actor with the given options, returning its id and context.
+    pub(crate) async fn spawn_named<A: Actor>(&self, name: &str, mut actor: A, options: SpawnOptions) -> Result<(u64, Arc<ActorContext<D>>), AddActorError<A::Error>> {
+        // Fail early if the name is taken, to avoid initializing the actor needlessly
+        if self.name_conflict_policy == NameConflictPolicy::Error && self.actor_ids.read().await.contains_key(name) {
+            return Err(AddActorError::NameTaken(String::from(name)));
+        }
+
+        // Run the actor's initialization code
+        self.initialize(Some(name), &mut actor).await.map_err(AddActorError::Initialize)?;
+
+        // Lock the slacktor instance and then the names for the remainder of the spawn, so that the name can't be taken
+        // in the meantime. Kills lock them in the same order, as they remove the killed actor's names.
+        let mut system = self.slacktor.write().await;
         let mut actor_ids = self.actor_ids.write().await;
-        actor_ids.insert(String::from(name), id as u64);
 
-        // Return the actor's id.
-        Ok(id)
+        // The name may have been taken while the actor was initializing
+        if self.name_conflict_policy == NameConflictPolicy::Error && actor_ids.contains_key(name) {
+            drop(actor_ids);
+            drop(system);
+            actor.deinitialize().await;
+            return Err(AddActorError::NameTaken(String::from(name)));
+        }
+
+        // Spawn the actor and store its name in the actor_ids map, replacing any existing actor
+        let (id, context) = self.insert_locked(&mut system, actor, options);
+        let existing = actor_ids.insert(String::from(name), id)
+            .filter(|existing| *existing != id)
+            .and_then(|existing| self.registry.get(existing, |registered| (existing, registered.gate.clone())));
+        self.generation.fetch_add(1, Ordering::Release);
+        drop(actor_ids);
+        drop(system);
+
+        // Kill the replaced actor once the names are unlocked, as its deinitialization may need them. It may have
+        // stopped meanwhile and had its id reused, so it is only killed if it is still the actor that held the name.
+        // An overwritten actor keeps running, but if it is owned its name no longer keeps it alive.
+        match (self.name_conflict_policy, existing) {
+            (NameConflictPolicy::KillExisting, Some((existing, gate))) => { self.kill_replaced(existing, gate).await; },
+            (_, Some((existing, _))) => self.release(existing),
+            (_, None) => (),
+        }
+
+        // Notify lifecycle subscribers
+        self.lifecycle.publish(&LifecycleEvent::ActorStarted { id, name: Some(String::from(name)) });
+
+        // Return the actor's id and context.
+        Ok((id, context))
     }
 
     /// # [`Fluxion::add`]
@@ -103,67 +333,329 @@ impl<D: Delegate> Fluxion<D> {
     /// # Errors
     /// Returns an error if the actor failed to initialize.
     /// On an error, the actor will not be spawned.
-    pub async fn add<A: Actor>(&self, mut actor: A) -> Result<u64, A::Error> {
+    pub async fn add<A: Actor>(&self, actor: A) -> Result<u64, A::Error> {
+        self.add_with_middleware(actor, []).await
+    }
 
-        // Run the actor's initialization code
-        actor.initialize().await?;
+    /// # [`Fluxion::add_with_middleware`]
+    /// Adds an actor like [`Fluxion::add`], with [`crate::Middleware`] that runs around its messages after the system's middleware.
+    ///
+    /// # Errors
+    /// Returns an error if the actor failed to initialize.
+    /// On an error, the actor will not be spawned.
+    pub async fn add_with_middleware<A: Actor>(&self, actor: A, middleware: impl IntoIterator<Item = Arc<dyn crate::Middleware>>) -> Result<u64, A::Error> {
+        let options = SpawnOptions { middleware: middleware.into_iter().collect(), ..SpawnOptions::default() };
+        self.add_with(actor, options).await.map(|(id, _)| id)
+    }
+
+    /// # [`Fluxion::add_with_rate_limit`]
+    /// Adds an actor like [`Fluxion::add`], which accepts messages no faster than the given [`crate::RateLimit`].
+    ///
+    /// # Errors
+    /// Returns [`AddActorError::Initialize`] if the actor failed to initialize,
+    /// or [`AddActorError::Schedule`] if the system was built without a [`Timer`]. On an error, the actor will not be spawned.
+    pub async fn add_with_rate_limit<A: Actor>(&self, actor: A, limit: crate::RateLimit) -> Result<u64, AddActorError<A::Error>> {
+        self.spawn(actor).rate_limit(limit).start().await
+    }
+
+    /// # [`Fluxion::add_all`]
+    /// Adds every actor like [`Fluxion::add`], returning their ids in the same order.
+    /// Every actor is initialized before any are spawned, and they are then spawned while the underlying RwLock is
+    /// locked once, rather than once per actor, which makes starting thousands of actors at once considerably cheaper.
+    ///
+    /// # Errors
+    /// Returns the first error an actor failed to initialize with. On an error, none of the actors will be spawned,
+    /// and those that had already initialized will be deinitialized.
+    pub async fn add_all<A: Actor>(&self, actors: impl IntoIterator<Item = A>) -> Result<Vec<u64>, A::Error> {
+        // Run every actor's initialization code before spawning any of them
+        let mut initialized = Vec::new();
+        for mut actor in actors {
+            if let Err(error) = self.initialize(None, &mut actor).await {
+                for actor in initialized {
+                    actor.deinitialize().await;
+                }
+
+                return Err(error);
+            }
+
+            initialized.push(actor);
+        }
+
+        // Spawn them all under a single lock
+        let mut system = self.slacktor.write().await;
+        let ids = initialized.into_iter()
+            .map(|actor| self.insert_locked(&mut system, actor, SpawnOptions::default()).0)
+            .collect::<Vec<_>>();
+        drop(system);
+
+        // Notify lifecycle subscribers
+        for id in &ids {
+            self.lifecycle.publish(&LifecycleEvent::ActorStarted { id: *id, name: None });
+        }
+
+        Ok(ids)
+    }
 
+    /// Adds an unnamed actor with the given options, returning its id and context.
+    pub(crate) async fn add_with<A: Actor>(&self, mut actor: A, options: SpawnOptions) -> Result<(u64, Arc<ActorContext<D>>), A::Error> {
+        instrument!(async {
+            // Run the actor's initialization code
+            self.initialize(None, &mut actor).await?;
+
+            // Spawn the actor
+            let (id, context) = self.insert(actor, options).await;
+
+            // Notify lifecycle subscribers
+            self.lifecycle.publish(&LifecycleEvent::ActorStarted { id, name: None });
+
+            // Return the actor's id and context.
+            Ok((id, context))
+        }, "fluxion::add", system = %self.system_id, actor = core::any::type_name::<A>()).await
+    }
+
+    /// Runs an actor's initialization code, notifying lifecycle subscribers if it fails.
+    async fn initialize<A: Actor>(&self, name: Option<&str>, actor: &mut A) -> Result<(), A::Error> {
+        let result = actor.initialize().await;
+
+        if result.is_err() {
+            self.lifecycle.publish(&LifecycleEvent::ActorFailed {
+                id: None,
+                name: name.map(String::from),
+                actor: core::any::type_name::<A>(),
+                error: ActorFailure::Initialize,
+            });
+        }
+
+        result
+    }
+
+    /// Spawns an initialized actor on the slacktor instance, returning its id and context.
+    async fn insert<A: Actor>(&self, actor: A, options: SpawnOptions) -> (u64, Arc<ActorContext<D>>) {
         // Lock the underlying slacktor instance as write
         let mut system = self.slacktor.write().await;
 
+        self.insert_locked(&mut system, actor, options)
+    }
+
+    /// Spawns an initialized actor on the already locked slacktor instance, returning its id and context.
+    fn insert_locked<A: Actor>(&self, system: &mut Slacktor, actor: A, options: SpawnOptions) -> (u64, Arc<ActorContext<D>>) {
         // Wrap the actor
-        let actor = ActorWrapper(actor, Arc::new(
+        let stats = Arc::new(ActorStats::new(self.timer.as_ref().and_then(|timer| timer.now())));
+        let gate = Arc::new(Gate::new(A::MAX_CONCURRENCY));
+        let context = Arc::new(
             ActorContext {
                 system: self.clone(),
-                id: system.next_id()
+                id: system.next_id(),
+                stash: spin::Mutex::default(),
+                timers: Arc::default(),
+                active: AtomicBool::new(false),
+                passivating: AtomicBool::new(false),
+                passivation: spin::Mutex::default(),
+                stats: stats.clone(),
+                requests: AtomicU64::new(0),
+                gate: gate.clone(),
             }
-        ));
+        );
+        let actor = ActorWrapper(actor, context.clone());
 
-        // Spawn the actor on the slacktor instance
-        let id = system.spawn(actor);
+        // Spawn the actor on the slacktor instance, keeping a handle for creating references
+        let index = system.spawn(actor);
+        let handle = system.get::<ActorWrapper<A, D>>(index).cloned()
+            .expect("actor should exist immediately after it was spawned");
+        let id = index as u64;
 
+        // Owned actors are retained by the system until they are either named or handed their first reference
+        let retained = options.owned.then(|| Arc::new(Owner::new(self.clone(), id)));
 
-        // Return the actor's id.
-        Ok(id as u64)
+        // Record how to kill and inspect the actor without knowing its type
+        self.groups.join(id, &options.groups);
+        self.broadcasts.insert(id, options.handles);
+        self.registry.insert(id, Registered {
+            handle: Box::new(handle),
+            kill: killer::<A, D>,
+            actor: core::any::type_name::<A>(),
+            stats,
+            phase: options.phase,
+            gate,
+            middleware: options.middleware,
+            rate_limit: options.rate_limit,
+            #[cfg(feature = "std")]
+            panic_policy: options.panic_policy,
+            owner: retained.as_ref().map(Arc::downgrade),
+            retained,
+            core: options.core,
+            groups: options.groups,
+        });
+
+        (id, context)
     }
 
     /// # [`Fluxion::kill`]
-    /// Given an actor's id, kills the actor
+    /// Given an actor's id, kills the actor, returning true if an actor was killed, or false if no `A` had the id.
+    /// Returns once the actor's [`Actor::deinitialize`] has completed, so awaiting the kill confirms that it has stopped.
+    /// Any names the actor had are removed, so they can be given to another actor.
     /// 
     /// <div class = "info">
-    /// Locks the underlying RwLock as write. This will block "management" functionalities such as adding, removing, and retrieving actors, but
-    /// will not block any messages.
+    /// Locks the underlying RwLock as write until the actor has deinitialized. This will block "management" functionalities
+    /// such as adding, removing, and retrieving actors, but will not block any messages.
     /// </div>
-    pub async fn kill<A: Actor>(&self, id: u64) {
+    pub async fn kill<A: Actor>(&self, id: u64) -> bool {
+        self.kill_exact::<A>(id, None).await
+    }
+
+    /// Kills the actor with the given id like [`Fluxion::kill`], but only if it is still the actor the gate belongs to.
+    async fn kill_exact<A: Actor>(&self, id: u64, gate: Option<Arc<Gate>>) -> bool {
         // Realistically, it should not be possible for this conversion to ever fail.
         // If the input id is more than usize::MAX, it is most likely an error on the caller's part,
         // as it should be impossible to allocate over usize::MAX actors at all, because
         // each actor has an overhead of more than one byte.
         // We just fail silently here, as it is the same case as the actor not existing.
-        let Ok(id) = id.try_into() else {
-            return;
+        let Ok(index) = usize::try_from(id) else {
+            return false;
         };
 
-        // Lock the underylying slacktor instance as write and kill the actor
-        self.slacktor.write().await.kill::<ActorWrapper<A, D>>(id).await;
+        // Lock the underylying slacktor instance as write once, for both killing the actor and shrinking the instance
+        let mut system = self.slacktor.write().await;
+
+        // The id may have been reused by another actor since the gate was read
+        if let Some(gate) = &gate
+            && self.registry.get(id, |registered| Arc::ptr_eq(&registered.gate, gate)) != Some(true) {
+            return false;
+        }
+
+        let killed = system.kill::<ActorWrapper<A, D>>(index).await.is_some();
+        if killed {
+            self.unregister(id);
+            self.forget_names(&[id]).await;
+            self.generation.fetch_add(1, Ordering::Release);
+        }
+
+        system.shrink();
+
+        killed
+    }
+
+
+    /// # [`Fluxion::kill_many`]
+    /// Kills every actor with one of the given ids, like [`Fluxion::kill`], returning how many were killed.
+    /// The underlying RwLock is locked once for all of the actors, rather than once per actor.
+    /// Ids of actors that don't exist or are not an `A` are skipped.
+    ///
+    /// <div class = "info">
+    /// Locks the underlying RwLock as write until every actor has deinitialized. This will block "management"
+    /// functionalities such as adding, removing, and retrieving actors, but will not block any messages.
+    /// </div>
+    pub async fn kill_many<A: Actor>(&self, ids: impl IntoIterator<Item = u64>) -> usize {
+        let mut system = self.slacktor.write().await;
+
+        let mut killed = Vec::new();
+        for id in ids {
+            // Ids over usize::MAX can't exist, as in [`Fluxion::kill`]
+            let Ok(index) = usize::try_from(id) else {
+                continue;
+            };
+
+            if system.kill::<ActorWrapper<A, D>>(index).await.is_some() {
+                self.unregister(id);
+                killed.push(id);
+            }
+        }
+
+        if !killed.is_empty() {
+            self.forget_names(&killed).await;
+            self.generation.fetch_add(1, Ordering::Release);
+        }
+
+        // Shrink the slacktor instance once every actor is gone
+        system.shrink();
+
+        killed.len()
+    }
+
+    /// Removes a killed actor's registry entry, and removes it from its groups.
+    fn unregister(&self, id: u64) {
+        if let Some(registered) = self.registry.remove(id) {
+            self.groups.leave(id, &registered.groups);
+        }
+    }
+
+    /// Removes every name that refers to one of the killed actors. This must be called before the slacktor instance
+    /// is unlocked, as the ids may be reused by the next actor spawned, which must not be reachable by the old names.
+    async fn forget_names(&self, ids: &[u64]) {
+        self.actor_ids.write().await.retain(|_, id| !ids.contains(id));
+    }
+
+    /// # [`Fluxion::kill_named`]
+    /// Kills the actor with the given name, whatever its type, like [`Fluxion::kill_any`], and removes the name.
+    /// Returns the killed actor's id once it has deinitialized, or [`None`] if no actor had the name.
+    pub async fn kill_named(&self, name: &str) -> Option<u64> {
+        let id = self.remove_name(name).await?;
+        self.kill_any(id).await;
+
+        Some(id)
+    }
+
+    /// # [`Fluxion::kill_any`]
+    /// Kills the actor with the given id whatever its type, like [`Fluxion::kill`], which allows management tooling
+    /// to stop actors generically. Returns true if an actor was killed, once it has deinitialized.
+    pub async fn kill_any(&self, id: u64) -> bool {
+        // Copy the function out so that the lock isn't held while killing
+        let killer = self.registry.get(id, |registered| registered.kill);
 
-        // Shrink the slacktor instance
-        self.slacktor.write().await.shrink();
+        match killer {
+            Some(killer) => killer(self, id, None).await,
+            None => false,
+        }
     }
 
+    /// Kills an actor whose name was given to another, if it is still the actor the gate belongs to.
+    async fn kill_replaced(&self, id: u64, gate: Arc<Gate>) -> bool {
+        let killer = self.registry.get(id, |registered| registered.kill);
+
+        match killer {
+            Some(killer) => killer(self, id, Some(gate)).await,
+            None => false,
+        }
+    }
 
     /// # [`Fluxion::get_local`]
     /// Gets an actor that is known to reside on the local system.
     /// This allows messages that are not serializable to still be used even if Fluxion is compiled with foreign message support.
     /// This function also allows retrieving an actor handle that is capable of sending multiple different messages.
     pub async fn get_local<A: Actor>(&self, id: u64) -> Option<LocalRef<A, D>> {
-        // If the id refers to a local actor, lock the slacktor
-        // instance as read, and retrieve the handle.
-        // The handle is then cloned and returned
-        self.slacktor.read().await.get::<ActorWrapper<A, D>>(
-            id.try_into().ok()? // If overflow, then the actor does not exist.
-        ).cloned()
-        .map(|handle| LocalRef(handle, id))
+        self.try_get_local::<A>(id).await.ok()
+    }
+
+    /// # [`Fluxion::try_get_local`]
+    /// Gets an actor that is known to reside on the local system, like [`Fluxion::get_local`].
+    ///
+    /// # Errors
+    /// Returns [`GetActorError::NotFound`] if no actor has the id, or [`GetActorError::WrongType`] if the actor is not an `A`.
+    #[allow(clippy::unused_async)] // Kept async so that lookups can wait again without breaking callers
+    pub async fn try_get_local<A: Actor>(&self, id: u64) -> Result<LocalRef<A, D>, GetActorError> {
+        // Copy the entry out, without waiting on the slacktor instance
+        let (actual, handle, gate, middleware, rate_limit, owner, core) = self.registry
+            .get(id, |registered| (
+                registered.actor,
+                registered.handle.downcast_ref::<slacktor::ActorHandle<ActorWrapper<A, D>>>().cloned(),
+                registered.gate.clone(),
+                registered.middleware.clone(),
+                registered.rate_limit.clone(),
+                registered.owner.clone(),
+                registered.core.clone(),
+            ))
+            .ok_or(GetActorError::NotFound(id))?;
+
+        // An owned actor whose last reference was dropped is about to be killed, so it can't be retrieved anymore
+        let owner = match owner {
+            Some(owner) => Some(owner.upgrade().ok_or(GetActorError::NotFound(id))?),
+            None => None,
+        };
+
+        // The actor is registered, so if its handle isn't for an `A` it must be of another type
+        let handle = handle.ok_or(GetActorError::WrongType { id, actual, expected: core::any::type_name::<A>() })?;
+
+        Ok(LocalRef { handle, id, system: self.clone(), gate, layers: middleware, rate: rate_limit, owner, executor: core })
     }
 
     /// # [`Fluxion::get`]
@@ -174,66 +666,440 @@ impl<D: Delegate> Fluxion<D> {
             #[cfg(not(feature="foreign"))] id: impl Into<Identifier>
         ) -> Option<Arc<dyn MessageSender<M>>>
         where M::Result: serde::Serialize + for<'d> serde::Deserialize<'d> {
+        self.try_get::<A, M>(id).await.ok()
+    }
 
+    /// # [`Fluxion::get`]
+    /// Retrieves an actor reference capable of communicating using the given message via the given ID.
+    #[cfg(not(feature = "serde"))]
+    pub async fn get<'a, A: Handler<M>, M: IndeterminateMessage>(&self,
+            id: impl Into<Identifier<'a>>,
+        ) -> Option<Arc<dyn MessageSender<M>>> {
+        self.try_get::<A, M>(id).await.ok()
+    }
+
+    /// # [`Fluxion::try_get`]
+    /// Retrieves an actor reference like [`Fluxion::get`], returning why it could not be retrieved if it failed.
+    ///
+    /// # Errors
+    /// Returns [`GetActorError::NotFound`] or [`GetActorError::NameNotFound`] if no local actor has the id or name,
+    /// [`GetActorError::WrongType`] if the local actor is not an `A`, or [`GetActorError::ForeignNotFound`]
+    /// if the delegate could not retrieve the foreign actor.
+    #[cfg(feature = "serde")]
+    pub async fn try_get<'a, A: Handler<M>, M: IndeterminateMessage>(&self,
+            #[cfg(feature="foreign")] id: impl Into<Identifier<'a>>,
+            #[cfg(not(feature="foreign"))] id: impl Into<Identifier>
+        ) -> Result<Arc<dyn MessageSender<M>>, GetActorError>
+        where M::Result: serde::Serialize + for<'d> serde::Deserialize<'d> {
         match id.into() {
             Identifier::Local(id) => {
                 // Get the local ref and wrap in an arc
-                self.get_local::<A>(id).await
+                self.try_get_local::<A>(id).await
                     .map(|h| Arc::new(h) as Arc<dyn MessageSender<M>>)
             },
             Identifier::LocalNamed(name) => {
                 // Get the actor's id by name
-                let id = self.get_actor_id(name).await?;
+                let id = self.get_actor_id(name).await
+                    .ok_or_else(|| GetActorError::NameNotFound(String::from(name)))?;
 
                 // Get the local ref and wrap in an arc
-                self.get_local::<A>(id).await
+                self.try_get_local::<A>(id).await
                     .map(|h| Arc::new(h) as Arc<dyn MessageSender<M>>)
             },
             #[cfg(feature = "foreign")]
             id => {
                 // Send the request on to the delegate
-                self.delegate.get_actor::<A, M>(id).await
+                let Some(sender) = instrument!(self.delegate.get_actor::<A, M>(id), "fluxion::get_actor", target = %id, message = core::any::type_name::<M>()).await else {
+                    // The message is lost along with its foreign target, so the failed lookup is recorded
+                    self.dead_letters.record::<M>(id, DeadLetterReason::NotFound).await;
+                    return Err(GetActorError::ForeignNotFound(alloc::format!("{id}")));
+                };
+
+                // Apply the system's timeouts to the foreign sender
+                Ok(self.wrap_foreign(sender, id))
             },
         }
     }
 
-    /// # [`Fluxion::get`]
-    /// Retrieves an actor reference capable of communicating using the given message via the given ID.
+    /// # [`Fluxion::try_get`]
+    /// Retrieves an actor reference like [`Fluxion::get`], returning why it could not be retrieved if it failed.
+    ///
+    /// # Errors
+    /// Returns [`GetActorError::NotFound`] or [`GetActorError::NameNotFound`] if no local actor has the id or name,
+    /// or [`GetActorError::WrongType`] if the local actor is not an `A`.
     #[cfg(not(feature = "serde"))]
-    pub async fn get<'a, A: Handler<M>, M: IndeterminateMessage>(&self,
+    pub async fn try_get<'a, A: Handler<M>, M: IndeterminateMessage>(&self,
             id: impl Into<Identifier<'a>>,
-        ) -> Option<Arc<dyn MessageSender<M>>> {
-
+        ) -> Result<Arc<dyn MessageSender<M>>, GetActorError> {
         match id.into() {
             Identifier::Local(id) => {
                 // Get the local ref and wrap in an arc
-                self.get_local::<A>(id).await
+                self.try_get_local::<A>(id).await
                     .map(|h| Arc::new(h) as Arc<dyn MessageSender<M>>)
             },
             Identifier::LocalNamed(name) => {
                 // Get the actor's id by name
-                let id = self.get_actor_id(name).await?;
+                let id = self.get_actor_id(name).await
+                    .ok_or_else(|| GetActorError::NameNotFound(String::from(name)))?;
 
                 // Get the local ref and wrap in an arc
-                self.get_local::<A>(id).await
+                self.try_get_local::<A>(id).await
                     .map(|h| Arc::new(h) as Arc<dyn MessageSender<M>>)
             },
             #[cfg(feature = "foreign")]
             id => {
                 // Send the request on to the delegate
-                self.delegate.get_actor::<A, M>(id).await
+                let Some(sender) = instrument!(self.delegate.get_actor::<A, M>(id), "fluxion::get_actor", target = %id, message = core::any::type_name::<M>()).await else {
+                    // The message is lost along with its foreign target, so the failed lookup is recorded
+                    self.dead_letters.record::<M>(id, DeadLetterReason::NotFound).await;
+                    return Err(GetActorError::ForeignNotFound(alloc::format!("{id}")));
+                };
+
+                // Apply the system's timeouts to the foreign sender
+                Ok(self.wrap_foreign(sender, id))
+            },
+        }
+    }
+
+    /// # [`Fluxion::get_stream`]
+    /// Retrieves a [`StreamSender`] for the actor with the given ID, which sends a [`crate::StreamMessage`] and streams back its items.
+    /// Foreign actors are retrieved through [`Delegate::get_stream_actor`].
+    pub async fn get_stream<'a, A: StreamHandler<M>, M: IndeterminateStreamMessage>(&self, id: impl Into<Identifier<'a>>) -> Option<Arc<dyn StreamSender<M>>> {
+        match id.into() {
+            Identifier::Local(id) => self.get_local::<A>(id).await
+                .map(|h| Arc::new(h) as Arc<dyn StreamSender<M>>),
+            Identifier::LocalNamed(name) => {
+                let id = self.get_actor_id(name).await?;
+
+                self.get_local::<A>(id).await
+                    .map(|h| Arc::new(h) as Arc<dyn StreamSender<M>>)
             },
+            #[cfg(feature = "foreign")]
+            id => instrument!(self.delegate.get_stream_actor::<A, M>(id), "fluxion::get_actor", target = %id, message = core::any::type_name::<M>())
+                .await,
+        }
+    }
+
+    /// Wraps a sender returned by the delegate so that it respects the system's timer and default timeout,
+    /// and reports failed sends as dead letters.
+    #[cfg(feature = "foreign")]
+    fn wrap_foreign<M: crate::Message>(&self, sender: Arc<dyn MessageSender<M>>, id: Identifier<'_>) -> Arc<dyn MessageSender<M>> {
+        Arc::new(crate::ForeignSender {
+            inner: sender,
+            target: alloc::format!("{id}"),
+            timer: self.timer.clone(),
+            default_timeout: self.default_timeout,
+            dead_letters: self.dead_letters.clone(),
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    /// # [`Fluxion::dead_letters`]
+    /// Returns the system's [`DeadLetters`], which records messages that could not be delivered.
+    #[must_use]
+    pub fn dead_letters(&self) -> &DeadLetters {
+        &self.dead_letters
+    }
+
+    /// # [`Fluxion::deduplicate`]
+    /// Handles a foreign message that was sent with an idempotency key, unless a message with the same key was already handled,
+    /// in which case its serialized response is returned instead. If a message with the same key is still being handled,
+    /// this waits for it to finish. This is used by delegates when dispatching foreign messages, and the key should be
+    /// scoped to the target actor and message by the caller. Errors are not cached.
+    ///
+    /// # Errors
+    /// Returns any error returned by `handle`.
+    #[cfg(feature = "foreign")]
+    pub async fn deduplicate<E>(&self, key: &str, handle: impl Future<Output = Result<Vec<u8>, E>>) -> Result<Vec<u8>, E> {
+        self.deduplication.run(key, handle).await
+    }
+
+    /// # [`Fluxion::lifecycle_events`]
+    /// Returns a [`Subscription`] that receives a [`LifecycleEvent`] whenever a local actor starts, stops, or fails.
+    #[must_use]
+    pub fn lifecycle_events(&self) -> Subscription<LifecycleEvent> {
+        self.lifecycle.subscribe()
+    }
+
+    /// # [`Fluxion::overload_events`]
+    /// Returns a [`Subscription`] that receives an [`crate::OverloadEvent`] whenever the system starts or stops shedding load.
+    /// Events are only published if the system was built with [`crate::LoadShedding`].
+    #[must_use]
+    pub fn overload_events(&self) -> Subscription<crate::OverloadEvent> {
+        self.load.events.subscribe()
+    }
+
+    /// # [`Fluxion::send_after`]
+    /// Delivers a message to the target once the given delay has elapsed, unless it is cancelled first.
+    /// The target can be any [`MessageSender`], such as one returned by [`Fluxion::get`].
+    /// The message is sent with [`MessageSender::tell`], so its result is discarded.
+    ///
+    /// # Errors
+    /// Returns an error if the system was built without a [`Timer`] or an [`Executor`].
+    pub fn send_after<M: Message>(&self, delay: Duration, target: Arc<dyn MessageSender<M>>, message: M) -> Result<ScheduleHandle, ScheduleError> {
+        crate::scheduler::schedule_once(self.timer.as_ref(), self.executor.as_ref(), delay, move |_| {
+            Some(Box::pin(async move {
+                let _ = target.tell(message).await;
+            }))
+        })
+    }
+
+    /// # [`Fluxion::scatter_gather`]
+    /// Sends a copy of the message to every target at once, and collects their responses until every target has answered
+    /// or the timeout elapses. Targets that failed or did not answer in time are returned as errors, alongside the responses
+    /// that did arrive. The timeout is ignored if the system was built without a [`Timer`].
+    pub async fn scatter_gather<M: Message + Clone>(&self, targets: &[Arc<dyn MessageSender<M>>], message: M, timeout: Duration) -> Gathered<M::Result> {
+        crate::gather::scatter_gather(self.timer.as_deref(), targets, message, timeout).await
+    }
+
+    /// # [`Fluxion::send_interval`]
+    /// Delivers a copy of the message to the target every `period` until it is cancelled.
+    /// The first message is delivered after one period has elapsed.
+    ///
+    /// # Errors
+    /// Returns an error if the system was built without a [`Timer`] or an [`Executor`].
+    pub fn send_interval<M: Message + Clone>(&self, period: Duration, target: Arc<dyn MessageSender<M>>, message: M) -> Result<ScheduleHandle, ScheduleError> {
+        crate::scheduler::schedule(self.timer.as_ref(), self.executor.as_ref(), period, move || {
+            let message = message.clone();
+            let target = target.clone();
+
+            Some(Box::pin(async move {
+                let _ = target.tell(message).await;
+            }))
+        })
+    }
+
+    /// # [`Fluxion::subscribe_topic`]
+    /// Subscribes the local actor with the given id to every message of type `M` published to a topic matching the pattern.
+    /// A `*` segment in the pattern matches any single segment of a topic, so `metrics/*` matches `metrics/cpu`.
+    /// Subscriptions are removed automatically when the actor is removed from the system.
+    /// Returns false if no actor of type `A` has the given id.
+    pub async fn subscribe_topic<A: Handler<M>, M: Message + Clone>(&self, pattern: &str, id: u64) -> bool {
+        if self.get_local::<A>(id).await.is_none() {
+            return false;
+        }
+
+        self.event_bus.subscribe::<A, M>(Some(pattern), id);
+        true
+    }
+
+    /// # [`Fluxion::unsubscribe_topic`]
+    /// Removes the subscription of the actor with the given id to messages of type `M` on the pattern.
+    /// Returns false if there was no such subscription.
+    #[must_use]
+    pub fn unsubscribe_topic<M: Message>(&self, pattern: &str, id: u64) -> bool {
+        self.event_bus.unsubscribe::<M>(Some(pattern), id)
+    }
+
+    /// # [`Fluxion::subscribe`]
+    /// Subscribes the local actor with the given id to every message of type `M` passed to [`Fluxion::publish`], regardless of its topic.
+    /// Messages are dispatched through the actor's [`Handler<M>`] implementation.
+    /// Subscriptions are removed automatically when the actor is removed from the system.
+    /// Returns false if no actor of type `A` has the given id.
+    pub async fn subscribe<A: Handler<M>, M: Message + Clone>(&self, id: u64) -> bool {
+        if self.get_local::<A>(id).await.is_none() {
+            return false;
+        }
+
+        self.event_bus.subscribe::<A, M>(None, id);
+        true
+    }
+
+    /// # [`Fluxion::unsubscribe`]
+    /// Removes the subscription of the actor with the given id to every message of type `M`.
+    /// Subscriptions to specific topics are not affected. Returns false if there was no such subscription.
+    #[must_use]
+    pub fn unsubscribe<M: Message>(&self, id: u64) -> bool {
+        self.event_bus.unsubscribe::<M>(None, id)
+    }
+
+    /// # [`Fluxion::publish`]
+    /// Delivers a copy of the message to every actor subscribed to a pattern matching the topic, or to every message of its type, in the order they subscribed,
+    /// and returns the number of actors the message was delivered to. Results of handling the message are discarded.
+    pub async fn publish<M: Message + Clone>(&self, topic: &str, message: M) -> usize {
+        let deliveries = self.event_bus.deliveries(self, topic, &message);
+        let count = deliveries.len();
+
+        for delivery in deliveries {
+            delivery.await;
+        }
+
+        count
+    }
+
+    /// # [`Fluxion::set_shutdown_phase`]
+    /// Assigns the local actor with the given id to a phase added with [`FluxionBuilder::shutdown_phase`],
+    /// replacing any phase it was assigned before. Returns false if there is no such actor or phase.
+    #[must_use]
+    pub fn set_shutdown_phase(&self, id: u64, phase: &str) -> bool {
+        let Some(index) = self.shutdown_phases.iter().position(|existing| existing.name == phase) else {
+            return false;
+        };
+
+        self.registry.update(id, |registered| registered.phase = Some(index)).is_some()
+    }
+
+    /// Refuses new messages to the actor with the given id while the system shuts down,
+    /// waits for it to finish handling the messages it was already sent, and kills it.
+    async fn stop(&self, id: u64) {
+        if let Some(gate) = self.registry.get(id, |registered| registered.gate.clone()) {
+            gate.shut_down();
+            gate.settle().await;
+        }
+
+        self.kill_any(id).await;
+    }
+
+    /// # [`Fluxion::drain`]
+    /// Stops every local actor from accepting new messages, which fail with [`crate::MessageSendError::Draining`],
+    /// waits for every message already sent to them to be handled, and then shuts the system down with [`Fluxion::shutdown`].
+    pub async fn drain(&self) -> ShutdownReport {
+        let gates = self.registry.collect(|_, registered| Some(registered.gate.clone()));
+
+        // Close every gate before waiting on any, so that actors can't keep each other busy
+        for gate in &gates {
+            gate.close();
         }
+
+        for gate in gates {
+            gate.drain().await;
+        }
+
+        self.shutdown().await
     }
 
     /// # [`Fluxion::shutdown`]
     /// Removes all actors from the system and deallocates the underlying slab.
+    /// Actors are stopped in the order of their [`ShutdownPhase`]s, and those without a phase are stopped last.
+    /// Returns a [`ShutdownReport`] listing the actors that did not stop before their phase's deadline.
+    ///
+    /// Once an actor starts stopping, messages sent to it fail with [`crate::MessageSendError::SystemShuttingDown`],
+    /// and it is only killed once it has finished handling the messages it was already sent. Actors in later phases
+    /// keep accepting messages until their own phase, so earlier actors can still hand work to them while they stop.
     /// 
     /// <div class = "info">
     /// Locks the underlying RwLock as write. This will block "management" functionalities such as adding, removing, and retrieving actors, but
     /// will not block any messages.
     /// </div>
-    pub async fn shutdown(&self) {
+    pub async fn shutdown(&self) -> ShutdownReport {
+        self.request_shutdown();
+        let mut report = ShutdownReport::default();
+
+        for (index, phase) in self.shutdown_phases.iter().enumerate() {
+            let mut remaining = self.registry.collect(|id, registered| (registered.phase == Some(index)).then_some((id, registered.actor)))
+                .into_iter()
+                .collect::<VecDeque<_>>();
+
+            // Stop the phase's actors one at a time, so that those remaining once the deadline elapses are known
+            let stop = async {
+                while let Some((id, _)) = remaining.front() {
+                    self.stop(*id).await;
+                    remaining.pop_front();
+                }
+            };
+
+            match &self.timer {
+                Some(timer) => { crate::timer::timeout(timer.as_ref(), phase.deadline, stop).await; },
+                None => stop.await,
+            }
+
+            report.timed_out.extend(remaining.into_iter().map(|(id, actor)| StopTimeout { id, actor, phase: phase.name.clone() }));
+        }
+
+        // Refuse messages to every remaining actor, and wait for those without a phase to finish the ones they were sent.
+        // Actors that timed out have already had their chance, and are force-killed without waiting again.
+        let gates = self.registry.collect(|_, registered| {
+            registered.gate.shut_down();
+            registered.phase.is_none().then(|| registered.gate.clone())
+        });
+
+        for gate in gates {
+            gate.settle().await;
+        }
+
+        // Stop the actors without a phase, and force-kill any that timed out
         self.slacktor.write().await.shutdown().await;
+        self.registry.clear();
+        self.actor_ids.write().await.clear();
+        self.groups.clear();
+        self.generation.fetch_add(1, Ordering::Release);
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records whether it was initialized and deinitialized.
+    #[derive(Default, Clone)]
+    struct Named {
+        initialized: Arc<AtomicBool>,
+        deinitialized: Arc<AtomicBool>,
+    }
+
+    impl Actor for Named {
+        type Error = ();
+
+        async fn initialize(&mut self) -> Result<(), Self::Error> {
+            self.initialized.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+
+        async fn deinitialize(&self) {
+            self.deinitialized.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn system(policy: NameConflictPolicy) -> Fluxion<()> {
+        Fluxion::builder("test", ()).name_conflict_policy(policy).build()
+    }
+
+    #[tokio::test]
+    async fn refuses_taken_names_by_default() {
+        let system = system(NameConflictPolicy::default());
+        let (existing, replacement) = (Named::default(), Named::default());
+
+        let id = system.add_named("named", existing.clone()).await.unwrap();
+        let added = system.add_named("named", replacement.clone()).await;
+
+        assert!(matches!(added, Err(AddActorError::NameTaken(name)) if name == "named"));
+        assert_eq!(system.get_actor_id("named").await, Some(id));
+
+        // The name is checked before the replacement is initialized, so it never starts
+        assert!(!replacement.initialized.load(Ordering::Relaxed));
+        assert!(!existing.deinitialized.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn overwrites_taken_names() {
+        let system = system(NameConflictPolicy::Overwrite);
+        let existing = Named::default();
+
+        let replaced = system.add_named("named", existing.clone()).await.unwrap();
+        let id = system.add_named("named", Named::default()).await.unwrap();
+
+        assert_ne!(id, replaced);
+        assert_eq!(system.get_actor_id("named").await, Some(id));
+
+        // The replaced actor keeps running, and can still be reached by its id
+        assert!(system.get_local::<Named>(replaced).await.is_some());
+        assert!(!existing.deinitialized.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn kills_actors_whose_name_is_taken() {
+        let system = system(NameConflictPolicy::KillExisting);
+        let existing = Named::default();
+
+        let replaced = system.add_named("named", existing.clone()).await.unwrap();
+        let id = system.add_named("named", Named::default()).await.unwrap();
+
+        // The new actor is added before the replaced actor is killed, so they never share an id
+        assert_ne!(id, replaced);
+        assert_eq!(system.get_actor_id("named").await, Some(id));
+        assert!(system.get_local::<Named>(replaced).await.is_none());
+        assert!(existing.deinitialized.load(Ordering::Relaxed));
     }
 }
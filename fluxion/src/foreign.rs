@@ -5,7 +5,7 @@
 use alloc::sync::Arc;
 
 #[cfg(feature="foreign")]
-use crate::{Handler, Identifier, MessageSender, IndeterminateMessage};
+use crate::{Handler, Identifier, MessageSender, IndeterminateMessage, IndeterminateStreamMessage, StreamHandler, StreamSender};
 
 
 
@@ -26,6 +26,54 @@ pub trait Delegate: Send + Sync + 'static {
     #[cfg(all(feature="foreign", feature="serde"))]
     fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier) -> impl core::future::Future<Output = Option<Arc<dyn MessageSender<M>>>> + Send
         where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a>;
+
+    /// # [`Delegate::get_stream_actor`]
+    /// Retrieves a [`StreamSender`] for the given foreign actor. The foreign system should send the items back in chunks
+    /// as they are produced, which the delegate feeds into the stream through a [`crate::ItemSender`].
+    /// Delegates that can't stream from foreign actors return [`None`], which is the default.
+    #[cfg(feature="foreign")]
+    fn get_stream_actor<A: StreamHandler<M>, M: IndeterminateStreamMessage>(&self, id: Identifier) -> impl core::future::Future<Output = Option<Arc<dyn StreamSender<M>>>> + Send {
+        let _ = id;
+        async { None }
+    }
+
+    /// # [`Delegate::notify`]
+    /// Forwards a notification passed to [`crate::Fluxion::notify`] to every foreign system the delegate knows of,
+    /// which should broadcast it to their own actors with [`crate::Fluxion::broadcast`]. Does nothing by default.
+    #[cfg(all(feature="foreign", not(feature="serde")))]
+    fn notify<N: IndeterminateMessage + Clone>(&self, notification: N) -> impl core::future::Future<Output = ()> + Send {
+        let _ = notification;
+        async {}
+    }
+
+    /// # [`Delegate::notify`]
+    /// Forwards a notification passed to [`crate::Fluxion::notify`] to every foreign system the delegate knows of,
+    /// which should broadcast it to their own actors with [`crate::Fluxion::broadcast`]. Does nothing by default.
+    #[cfg(all(feature="foreign", feature="serde"))]
+    fn notify<N: IndeterminateMessage + Clone>(&self, notification: N) -> impl core::future::Future<Output = ()> + Send
+        where N::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        let _ = notification;
+        async {}
+    }
+
+    /// # [`Delegate::grant_credits`]
+    /// Called when the given foreign system grants this system credits to send it `credits` more messages,
+    /// as described in [`crate::Credits`]. Delegates that implement flow control wake any senders waiting for credits.
+    /// Does nothing by default.
+    #[cfg(feature="foreign")]
+    fn grant_credits(&self, system: &str, credits: usize) -> impl core::future::Future<Output = ()> + Send {
+        let _ = (system, credits);
+        async {}
+    }
+
+    /// # [`Delegate::request_credits`]
+    /// Called when a sender has exhausted its credits for the given foreign system, to ask the system for more.
+    /// The sender suspends until they are granted. Does nothing by default.
+    #[cfg(feature="foreign")]
+    fn request_credits(&self, system: &str) -> impl core::future::Future<Output = ()> + Send {
+        let _ = system;
+        async {}
+    }
 }
 
 // Delegate is implemented for () as a no-op
@@ -58,5 +106,31 @@ impl<D: Delegate> Delegate for alloc::sync::Arc<D> {
     fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier) -> impl core::future::Future<Output = Option<Arc<dyn MessageSender<M>>>> + Send {
         D::get_actor::<A, M>(self, id)
     }
+
+    #[cfg(feature="foreign")]
+    fn get_stream_actor<A: StreamHandler<M>, M: IndeterminateStreamMessage>(&self, id: Identifier) -> impl core::future::Future<Output = Option<Arc<dyn StreamSender<M>>>> + Send {
+        D::get_stream_actor::<A, M>(self, id)
+    }
+
+    #[cfg(all(feature="foreign", not(feature="serde")))]
+    fn notify<N: IndeterminateMessage + Clone>(&self, notification: N) -> impl core::future::Future<Output = ()> + Send {
+        D::notify(self, notification)
+    }
+
+    #[cfg(all(feature="foreign", feature="serde"))]
+    fn notify<N: IndeterminateMessage + Clone>(&self, notification: N) -> impl core::future::Future<Output = ()> + Send
+        where N::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        D::notify(self, notification)
+    }
+
+    #[cfg(feature="foreign")]
+    fn grant_credits(&self, system: &str, credits: usize) -> impl core::future::Future<Output = ()> + Send {
+        D::grant_credits(self, system, credits)
+    }
+
+    #[cfg(feature="foreign")]
+    fn request_credits(&self, system: &str) -> impl core::future::Future<Output = ()> + Send {
+        D::request_credits(self, system)
+    }
 }
 
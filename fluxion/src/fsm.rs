@@ -0,0 +1,241 @@
+//! # Finite State Machines
+//! Actors that move between a fixed set of states are common enough that Fluxion provides [`FsmActor`],
+//! which wraps a [`State`] and handles messages by asking the current state for its next state.
+//!
+//! Each state may run code when it is entered or exited, and the transitions that are allowed between states
+//! are declared with `#[derive(Transitions)]`. The derive macro checks that every declared target is a variant of the
+//! state enum at compile time, and any transition that was not declared is rejected, leaving the state unchanged.
+//! The message that asked for a rejected transition is answered with a [`TransitionRejected`] error in place of the result
+//! its state returned, so the messages an [`FsmActor`] handles must have a [`Result`] whose error can be created from one.
+//!
+//! ```ignore
+//! #[derive(Transitions)]
+//! enum Door {
+//!     #[transitions(Open, Locked)]
+//!     Closed,
+//!     #[transitions(Closed)]
+//!     Open,
+//!     #[transitions(Closed)]
+//!     Locked { code: u32 },
+//! }
+//!
+//! impl Transition<OpenDoor> for Door {
+//!     async fn transition(&mut self, _message: OpenDoor) -> (Option<Self>, Result<(), TransitionRejected>) {
+//!         // Opening a locked door fails with a TransitionRejected, as a locked door may only move to closed
+//!         (Some(Door::Open), Ok(()))
+//!     }
+//! }
+//! ```
+
+use core::{future::Future, sync::atomic::{AtomicU64, Ordering}};
+
+use maitake_sync::Mutex;
+
+use crate::{Actor, ActorContext, Delegate, Handler, Message};
+
+pub use fluxion_macro::Transitions;
+
+/// # [`Transitions`]
+/// Declares which transitions are allowed between states.
+/// This should usually be implemented with `#[derive(Transitions)]` rather than by hand.
+pub trait Transitions {
+    /// # [`Transitions::allows`]
+    /// Returns true if the machine may move from `self` to `next`.
+    fn allows(&self, next: &Self) -> bool;
+
+    /// # [`Transitions::name`]
+    /// Returns the name of the state, as reported by [`TransitionRejected`].
+    /// The derive macro returns the name of the variant. By default, this is the name of the type.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+/// # [`TransitionRejected`]
+/// The error a message is answered with when the state handling it returned a state that [`Transitions::allows`] does
+/// not allow the machine to move to. The machine stays in the state it was in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionRejected {
+    /// The name of the state the machine was in, and stays in
+    pub from: &'static str,
+    /// The name of the state the machine was asked to move to
+    pub to: &'static str,
+}
+
+impl core::fmt::Display for TransitionRejected {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "the transition from {} to {} is not allowed", self.from, self.to)
+    }
+}
+
+impl core::error::Error for TransitionRejected {}
+
+/// # [`State`]
+/// A state of an [`FsmActor`].
+pub trait State: Transitions + Send + Sync + Sized + 'static {
+    /// # [`State::on_enter`]
+    /// Called when the machine enters this state, including the initial state when the actor is added to the system.
+    fn on_enter(&mut self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// # [`State::on_exit`]
+    /// Called when the machine leaves this state, including the final state when the actor is removed from the system.
+    fn on_exit(&mut self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// # [`Transition`]
+/// Handles a message of type `M` in a [`State`], returning the message's result and optionally the state to move to.
+pub trait Transition<M: Message>: State {
+    /// # [`Transition::transition`]
+    /// Handles the message. If a state is returned, and [`Transitions::allows`] moving to it,
+    /// the current state is exited and the returned state is entered. Otherwise, the message is answered with a
+    /// [`TransitionRejected`] error rather than the returned result.
+    fn transition(&mut self, message: M) -> impl Future<Output = (Option<Self>, M::Result)> + Send;
+}
+
+/// # [`FsmActor`]
+/// An actor that handles every message `M` its state implements [`Transition<M>`] for, as long as the message's result
+/// is a [`Result`] whose error can be created from a [`TransitionRejected`].
+/// Messages are handled one at a time, as each may change the state.
+pub struct FsmActor<S> {
+    /// The current state
+    state: Mutex<S>,
+    /// The number of transitions that were rejected because they were not allowed
+    rejected: AtomicU64,
+}
+
+impl<S: State> FsmActor<S> {
+    /// # [`FsmActor::new`]
+    /// Creates a state machine in the given initial state.
+    /// The state is entered when the actor is added to the system.
+    #[must_use]
+    pub fn new(initial: S) -> Self {
+        Self {
+            state: Mutex::new(initial),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// # [`FsmActor::with_state`]
+    /// Calls the given function with the current state, waiting for any in-progress transition to finish.
+    pub async fn with_state<R>(&self, f: impl FnOnce(&S) -> R) -> R {
+        f(&*self.state.lock().await)
+    }
+
+    /// # [`FsmActor::rejected_transitions`]
+    /// Returns the number of transitions that were not allowed by [`Transitions::allows`] and so were not taken.
+    #[must_use]
+    pub fn rejected_transitions(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: State> Actor for FsmActor<S> {
+    type Error = ();
+
+    async fn initialize(&mut self) -> Result<(), Self::Error> {
+        self.state.get_mut().on_enter().await;
+        Ok(())
+    }
+
+    async fn deinitialize(&self) {
+        self.state.lock().await.on_exit().await;
+    }
+}
+
+impl<S: Transition<M>, M: Message<Result = Result<T, E>>, T, E: From<TransitionRejected>> Handler<M> for FsmActor<S> {
+    async fn handle_message<D: Delegate>(&self, message: M, _context: &ActorContext<D>) -> M::Result {
+        let mut state = self.state.lock().await;
+
+        let (next, result) = state.transition(message).await;
+
+        if let Some(mut next) = next {
+            if !state.allows(&next) {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(E::from(TransitionRejected { from: state.name(), to: next.name() }));
+            }
+
+            state.on_exit().await;
+            next.on_enter().await;
+            *state = next;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Fluxion;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Door {
+        Closed,
+        Open,
+        Locked,
+    }
+
+    impl Transitions for Door {
+        fn allows(&self, next: &Self) -> bool {
+            matches!((self, next), (Door::Closed, Door::Open | Door::Locked) | (Door::Open | Door::Locked, Door::Closed))
+        }
+
+        fn name(&self) -> &'static str {
+            match *self {
+                Door::Closed => "Closed",
+                Door::Open => "Open",
+                Door::Locked => "Locked",
+            }
+        }
+    }
+
+    impl State for Door {}
+
+    /// Asks the door to move to the given state.
+    struct Move(Door);
+
+    impl Message for Move {
+        type Result = Result<(), TransitionRejected>;
+    }
+
+    impl Transition<Move> for Door {
+        async fn transition(&mut self, message: Move) -> (Option<Self>, Result<(), TransitionRejected>) {
+            (Some(message.0), Ok(()))
+        }
+    }
+
+    /// Asks the door which state it is in.
+    struct Current;
+
+    impl Message for Current {
+        type Result = Result<Door, TransitionRejected>;
+    }
+
+    impl Transition<Current> for Door {
+        async fn transition(&mut self, _message: Current) -> (Option<Self>, Result<Door, TransitionRejected>) {
+            (None, Ok(*self))
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_transitions_that_are_not_allowed() {
+        let system = Fluxion::new("test", ());
+        let id = system.add(FsmActor::new(Door::Closed)).await.unwrap();
+        let door = system.get_local::<FsmActor<Door>>(id).await.unwrap();
+
+        assert_eq!(door.send(Move(Door::Locked)).await.unwrap(), Ok(()));
+        assert_eq!(door.send(Current).await.unwrap(), Ok(Door::Locked));
+
+        // A locked door may only be closed, so it stays locked
+        assert_eq!(door.send(Move(Door::Open)).await.unwrap(), Err(TransitionRejected { from: "Locked", to: "Open" }));
+        assert_eq!(door.send(Current).await.unwrap(), Ok(Door::Locked));
+
+        assert_eq!(door.send(Move(Door::Closed)).await.unwrap(), Ok(()));
+        assert_eq!(door.send(Move(Door::Open)).await.unwrap(), Ok(()));
+        assert_eq!(door.send(Current).await.unwrap(), Ok(Door::Open));
+    }
+}
@@ -0,0 +1,99 @@
+//! # Scatter-Gather
+//! [`crate::Fluxion::scatter_gather`] sends the same message to many actors at once, such as every shard of a sharded
+//! query, and collects whatever responses arrive before a timeout. Targets that fail or don't respond in time are reported
+//! alongside the responses, so that a single slow or failed target doesn't fail the whole query.
+
+use core::{future::Future, pin::Pin, task::Poll, time::Duration};
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use crate::{Message, MessageSendError, MessageSender, Timer};
+
+/// # [`Gathered`]
+/// The responses collected by [`crate::Fluxion::scatter_gather`]. Each response and error is paired with the index of
+/// the target it came from, and every target appears exactly once in one of the two lists.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Gathered<R> {
+    /// The responses of the targets that answered in time, in the order of the targets
+    pub responses: Vec<(usize, R)>,
+    /// The errors of the targets that failed, or [`MessageSendError::Timeout`] for those that did not answer in time
+    pub errors: Vec<(usize, MessageSendError)>,
+}
+
+impl<R> Gathered<R> {
+    /// # [`Gathered::is_complete`]
+    /// Returns true if every target answered successfully.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Sends the message to every target concurrently, and gathers their responses until they have all answered or the timeout elapses.
+pub(crate) async fn scatter_gather<M: Message + Clone>(timer: Option<&dyn Timer>, targets: &[Arc<dyn MessageSender<M>>], message: M, timeout: Duration) -> Gathered<M::Result> {
+    type PendingSend<'a, R> = Pin<Box<dyn Future<Output = Result<R, MessageSendError>> + Send + 'a>>;
+
+    let mut sends: Vec<Option<PendingSend<'_, M::Result>>> = targets.iter().map(|target| Some(target.send(message.clone()))).collect();
+    let mut outcomes: Vec<Option<Result<M::Result, MessageSendError>>> = targets.iter().map(|_| None).collect();
+
+    // Every send is polled on this task, so no executor is needed
+    let gather = core::future::poll_fn(|cx| {
+        for (send, outcome) in sends.iter_mut().zip(outcomes.iter_mut()) {
+            if let Some(pending) = send
+                && let Poll::Ready(result) = pending.as_mut().poll(cx) {
+                *outcome = Some(result);
+                *send = None;
+            }
+        }
+
+        if sends.iter().all(Option::is_none) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    });
+
+    match timer {
+        Some(timer) => {
+            let _ = crate::timer::timeout(timer, timeout, gather).await;
+        },
+        None => gather.await,
+    }
+
+    let mut gathered = Gathered { responses: Vec::new(), errors: Vec::new() };
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        match outcome {
+            Some(Ok(response)) => gathered.responses.push((index, response)),
+            Some(Err(error)) => gathered.errors.push((index, error)),
+            None => gathered.errors.push((index, MessageSendError::Timeout)),
+        }
+    }
+
+    gathered
+}
+
+/// Tells every target the message concurrently, returning how many accepted it.
+pub(crate) async fn broadcast<M: Message + Clone>(targets: &[Arc<dyn MessageSender<M>>], message: M) -> usize {
+    let mut tells = targets.iter().map(|target| Some(target.tell(message.clone()))).collect::<Vec<_>>();
+    let mut delivered = 0;
+
+    // Every tell is polled on this task, so no executor is needed
+    core::future::poll_fn(|cx| {
+        for tell in &mut tells {
+            if let Some(pending) = tell
+                && let Poll::Ready(result) = pending.as_mut().poll(cx) {
+                delivered += usize::from(result.is_ok());
+                *tell = None;
+            }
+        }
+
+        if tells.iter().all(Option::is_none) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }).await;
+
+    delivered
+}
@@ -0,0 +1,133 @@
+//! # Groups
+//! Actors can be tagged with any number of group labels when they are spawned with [`crate::Spawn::group`].
+//! [`Fluxion::group`] then returns a [`Group`], which operates on every actor with the label at once, such as
+//! broadcasting a message to every `ingest` worker or shutting them all down. Actors leave their groups when they are killed.
+
+use alloc::{collections::{BTreeMap, BTreeSet}, string::String, sync::Arc, vec::Vec};
+use maitake_sync::spin::RwLock;
+
+use crate::{Delegate, Fluxion, Handler, Message, MessageSender};
+
+/// The members of every group, keyed by label.
+#[derive(Default)]
+pub(crate) struct Groups(RwLock<BTreeMap<String, BTreeSet<u64>>>);
+
+impl Groups {
+    /// Adds the actor to each of the given groups.
+    pub(crate) fn join(&self, id: u64, labels: &[String]) {
+        if labels.is_empty() {
+            return;
+        }
+
+        let mut groups = self.0.write();
+        for label in labels {
+            groups.entry(label.clone()).or_default().insert(id);
+        }
+    }
+
+    /// Removes the actor from each of the given groups, forgetting groups that are left empty.
+    pub(crate) fn leave(&self, id: u64, labels: &[String]) {
+        if labels.is_empty() {
+            return;
+        }
+
+        let mut groups = self.0.write();
+        for label in labels {
+            if let Some(members) = groups.get_mut(label) {
+                members.remove(&id);
+                if members.is_empty() {
+                    groups.remove(label);
+                }
+            }
+        }
+    }
+
+    /// Returns the ids of every actor in the group, in ascending order.
+    fn members(&self, label: &str) -> Vec<u64> {
+        self.0.read().get(label).map(|members| members.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// Returns how many actors are in the group.
+    fn len(&self, label: &str) -> usize {
+        self.0.read().get(label).map_or(0, BTreeSet::len)
+    }
+
+    /// Removes every actor from every group.
+    pub(crate) fn clear(&self) {
+        self.0.write().clear();
+    }
+}
+
+/// # [`Group`]
+/// Every local actor tagged with a label, created by [`Fluxion::group`]. The group is looked up again by each operation,
+/// so actors spawned into or killed from it in the meantime are taken into account.
+pub struct Group<D: Delegate> {
+    /// The system the group's actors are on
+    system: Fluxion<D>,
+    /// The group's label
+    label: String,
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::group`]
+    /// Returns the group of local actors tagged with the given label. The group may be empty.
+    #[must_use]
+    pub fn group(&self, label: &str) -> Group<D> {
+        Group { system: self.clone(), label: String::from(label) }
+    }
+}
+
+impl<D: Delegate> Group<D> {
+    /// # [`Group::label`]
+    /// Returns the group's label.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// # [`Group::members`]
+    /// Returns the ids of every actor in the group, in ascending order.
+    #[must_use]
+    pub fn members(&self) -> Vec<u64> {
+        self.system.groups.members(&self.label)
+    }
+
+    /// # [`Group::len`]
+    /// Returns how many actors are in the group.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.system.groups.len(&self.label)
+    }
+
+    /// # [`Group::is_empty`]
+    /// Returns true if no actors are in the group.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// # [`Group::broadcast`]
+    /// Tells a copy of the message to every actor in the group that is an `A`, concurrently, returning how many accepted it.
+    /// Members of other types are skipped.
+    pub async fn broadcast<A: Handler<M>, M: Message + Clone>(&self, message: M) -> usize {
+        let mut targets = Vec::<Arc<dyn MessageSender<M>>>::new();
+        for id in self.members() {
+            if let Ok(reference) = self.system.try_get_local::<A>(id).await {
+                targets.push(Arc::new(reference));
+            }
+        }
+
+        crate::gather::broadcast(&targets, message).await
+    }
+
+    /// # [`Group::shutdown`]
+    /// Kills every actor in the group one at a time, whatever its type, returning how many were killed.
+    pub async fn shutdown(&self) -> usize {
+        let mut killed = 0;
+        for id in self.members() {
+            killed += usize::from(self.system.kill_any(id).await);
+        }
+
+        killed
+    }
+}
@@ -0,0 +1,42 @@
+//! # Guards
+//! A [`Guard`] rejects messages before they reach an actor's handler, returning [`MessageSendError::Rejected`] to the
+//! sender, for example to limit who may send an actor a message. Guards are [`Middleware`], so they are added to a single
+//! actor with [`crate::Fluxion::add_with_middleware`], or to every actor with [`crate::FluxionBuilder::middleware`].
+
+use core::any::Any;
+
+use alloc::boxed::Box;
+
+use crate::{Dispatch, Message, MessageSendError, Middleware};
+
+/// The predicate of a [`Guard`], which returns true for messages that are allowed through.
+type Predicate = Box<dyn Fn(&Dispatch, &dyn Any) -> bool + Send + Sync>;
+
+/// # [`Guard`]
+/// [`Middleware`] that rejects the messages for which its predicate returns false.
+pub struct Guard(Predicate);
+
+impl Guard {
+    /// # [`Guard::new`]
+    /// Creates a guard that checks every message, allowing through those for which the predicate returns true.
+    pub fn new(predicate: impl Fn(&Dispatch) -> bool + Send + Sync + 'static) -> Self {
+        Self(Box::new(move |dispatch, _| predicate(dispatch)))
+    }
+
+    /// # [`Guard::message`]
+    /// Creates a guard that only checks messages of type `M`, allowing through those for which the predicate returns true.
+    /// Messages of any other type are always allowed through.
+    pub fn message<M: Message>(predicate: impl Fn(&Dispatch, &M) -> bool + Send + Sync + 'static) -> Self {
+        Self(Box::new(move |dispatch, message| message.downcast_ref::<M>().is_none_or(|message| predicate(dispatch, message))))
+    }
+}
+
+impl Middleware for Guard {
+    fn before(&self, dispatch: &mut Dispatch, message: &dyn Any) -> Result<(), MessageSendError> {
+        if (self.0)(dispatch, message) {
+            Ok(())
+        } else {
+            Err(MessageSendError::Rejected)
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! # Hashing
+//! Routing and sharding need hashes that are identical across runs and across systems,
+//! which rules out the randomly seeded hashers used by standard collections.
+
+use core::hash::{Hash, Hasher};
+
+/// Hashes a value with FNV-1a, finalized with [`mix`], so that the result is stable across runs and platforms.
+/// Integers are hashed as little-endian bytes, and `usize` and `isize` as 64-bit integers, so that the result doesn't
+/// depend on the platform's byte order or pointer width, although keys should still prefer fixed-size integers.
+pub(crate) fn stable_hash<K: Hash + ?Sized>(key: &K) -> u64 {
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+
+        fn write_u16(&mut self, i: u16) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u32(&mut self, i: u32) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u64(&mut self, i: u64) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u128(&mut self, i: u128) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_usize(&mut self, i: usize) {
+            self.write_u64(i as u64);
+        }
+    }
+
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    key.hash(&mut hasher);
+
+    // FNV spreads short, similar keys poorly, so the result is finalized with a stronger mix.
+    mix(hasher.finish())
+}
+
+/// The splitmix64 finalizer.
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
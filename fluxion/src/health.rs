@@ -0,0 +1,169 @@
+//! # Health Checks
+//! Actors can implement [`HealthCheck`] to report whether they are [`Health::Healthy`], [`Health::Degraded`], or
+//! [`Health::Unhealthy`], and are probed once they are spawned with [`crate::Spawn::health_check`].
+//! [`Fluxion::check_health`] probes every such actor at once, and [`Fluxion::start_health_checks`] does so periodically.
+//! The latest results are kept for [`Fluxion::health_report`].
+//!
+//! Probes are sent like any other message, so they wait behind the actor's [`crate::Actor::MAX_CONCURRENCY`] limit.
+//! An actor that is stuck in a handler, for example because two actors are waiting on each other, doesn't answer within
+//! the deadline, and a [`LifecycleEvent::ActorUnresponsive`] is published when it first stops answering.
+
+use core::{future::Future, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use maitake_sync::spin::Mutex;
+
+use crate::{Actor, ActorContext, Delegate, Fluxion, Handler, LifecycleEvent, Message, MessageSendError, ScheduleError, ScheduleHandle};
+
+/// # [`Health`]
+/// How an actor reports its own health from [`HealthCheck::check_health`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Health {
+    /// The actor is working as expected.
+    Healthy,
+    /// The actor is working, but not as well as it should, for example because a dependency is slow.
+    Degraded,
+    /// The actor is not working.
+    Unhealthy,
+}
+
+/// # [`HealthCheck`]
+/// Reports an actor's health when it is probed, as described in the [module documentation](self).
+pub trait HealthCheck: Actor {
+    /// # [`HealthCheck::check_health`]
+    /// Returns the actor's current health. This should be quick, as it is raced against the probe's deadline.
+    fn check_health<D: Delegate>(&self, context: &ActorContext<D>) -> impl Future<Output = Health> + Send;
+}
+
+/// Probes an actor that implements [`HealthCheck`].
+#[derive(Clone, Copy)]
+pub(crate) struct CheckHealth;
+
+impl Message for CheckHealth {
+    type Result = Health;
+}
+
+impl<A: HealthCheck> Handler<CheckHealth> for A {
+    async fn handle_message<D: Delegate>(&self, _message: CheckHealth, context: &ActorContext<D>) -> Health {
+        self.check_health(context).await
+    }
+}
+
+/// # [`ActorHealth`]
+/// The result of probing a single actor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ActorHealth {
+    /// The actor's id
+    pub id: u64,
+    /// The type name of the actor
+    pub actor: &'static str,
+    /// The health the actor reported, or [`None`] if it didn't answer within the deadline.
+    /// Actors whose handler panicked or that rejected the probe are reported as [`Health::Unhealthy`].
+    pub health: Option<Health>,
+}
+
+/// The latest result of probing each actor, keyed by the actor's id.
+#[derive(Default)]
+pub(crate) struct HealthResults(Mutex<BTreeMap<u64, ActorHealth>>);
+
+impl HealthResults {
+    /// Forgets the actor's latest result.
+    pub(crate) fn remove_actor(&self, id: u64) {
+        let removed = self.0.lock().remove(&id);
+        drop(removed);
+    }
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::check_health`]
+    /// Probes every local actor spawned with [`crate::Spawn::health_check`] concurrently, and returns their health ordered
+    /// by id. Actors that don't answer within the deadline are reported without a health, and a
+    /// [`LifecycleEvent::ActorUnresponsive`] is published for each that answered the previous probe. Actors that are
+    /// being stopped are left out.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoTimer`] if the system has no [`crate::Timer`] to enforce the deadline with.
+    pub async fn check_health(&self, deadline: Duration) -> Result<Vec<ActorHealth>, ScheduleError> {
+        let timer = self.timer.as_deref().ok_or(ScheduleError::NoTimer)?;
+
+        let mut probed = Vec::new();
+        let mut targets = Vec::new();
+        for (id, resolve) in self.broadcasts.resolvers::<CheckHealth, D>() {
+            let actor = self.registry.get(id, |registered| registered.actor);
+            if let Some(actor) = actor
+                && let Some(target) = resolve(self, id).await {
+                probed.push((id, actor));
+                targets.push(target);
+            }
+        }
+
+        let gathered = crate::gather::scatter_gather(Some(timer), &targets, CheckHealth, deadline).await;
+
+        let mut report = gathered.responses.into_iter()
+            .map(|(index, health)| (index, Some(health)))
+            .chain(gathered.errors.into_iter().filter_map(|(index, error)| match error {
+                MessageSendError::Timeout => Some((index, None)),
+                // The actor is being stopped, so its health no longer matters
+                MessageSendError::Draining | MessageSendError::SystemShuttingDown => None,
+                _ => Some((index, Some(Health::Unhealthy))),
+            }))
+            .map(|(index, health)| {
+                let (id, actor) = probed[index];
+                ActorHealth { id, actor, health }
+            })
+            .collect::<Vec<_>>();
+        report.sort_unstable_by_key(|health| health.id);
+
+        let mut unresponsive = Vec::new();
+        {
+            let mut results = self.health.0.lock();
+            for health in &report {
+                let answered_before = results.get(&health.id).is_none_or(|previous| previous.health.is_some());
+                if health.health.is_none() && answered_before {
+                    unresponsive.push(LifecycleEvent::ActorUnresponsive { id: health.id, actor: health.actor, deadline });
+                }
+
+                // Actors that stopped while they were being probed are not recorded again
+                if self.registry.contains(health.id) {
+                    results.insert(health.id, health.clone());
+                }
+            }
+        }
+
+        for event in &unresponsive {
+            self.lifecycle.publish(event);
+        }
+
+        Ok(report)
+    }
+
+    /// # [`Fluxion::start_health_checks`]
+    /// Runs [`Fluxion::check_health`] with the given deadline once every interval, until the returned handle is cancelled
+    /// or the system is asked to shut down. Each round starts once the previous has finished.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoTimer`] or [`ScheduleError::NoExecutor`] if the system lacks either.
+    pub fn start_health_checks(&self, interval: Duration, deadline: Duration) -> Result<ScheduleHandle, ScheduleError> {
+        let system = self.clone();
+
+        crate::scheduler::schedule(self.timer.as_ref(), self.executor.as_ref(), interval, move || {
+            if system.is_shutdown_requested() {
+                return None;
+            }
+
+            let system = system.clone();
+            Some(Box::pin(async move {
+                let _ = system.check_health(deadline).await;
+            }))
+        })
+    }
+
+    /// # [`Fluxion::health_report`]
+    /// Returns the latest result of probing each local actor, ordered by id.
+    /// Actors that have not been probed yet, or have since stopped, are left out.
+    #[must_use]
+    pub fn health_report(&self) -> Vec<ActorHealth> {
+        self.health.0.lock().values().cloned().collect()
+    }
+}
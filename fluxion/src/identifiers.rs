@@ -6,7 +6,7 @@
 /// # [`Identifier`]
 /// Identifies an individual actor on a given system. There are two variants: one for actors on the current system, and one on a foreign system.
 /// These are called [`Identifier::Local`] and [`Identifier::Foreign`] respectively.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Identifier<'a> {
     /// Identifies an actor on the current system. Contains the actor's id as a 64-bit integer.
     Local(u64),
@@ -34,9 +34,26 @@ impl From<u64> for Identifier<'_> {
     }
 }
 
+impl core::fmt::Display for Identifier<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Identifier::Local(id) => write!(f, "{id}"),
+            Identifier::LocalNamed(name) => write!(f, "{name}"),
+            #[cfg(feature = "foreign")]
+            Identifier::Foreign(id, system) => write!(f, "{id}@{system}"),
+            #[cfg(feature = "foreign")]
+            Identifier::ForeignNamed(name, system) => write!(f, "{name}@{system}"),
+        }
+    }
+}
+
 /// # [`MessageID`]
 /// Every foreign message is required to have a unique ID.
 /// This is automatically populated by the `message` proc macro.
 pub trait MessageID {
     const ID: &'static str;
+
+    /// The version of the message's schema, set with `#[message(version = N)]`.
+    /// Defaults to 1, and should be incremented whenever the serialized form of the message changes.
+    const VERSION: u32 = 1;
 }
\ No newline at end of file
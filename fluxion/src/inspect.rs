@@ -0,0 +1,57 @@
+//! # Inspection
+//! [`crate::Fluxion::inspect`] returns a snapshot of every actor running on the local system,
+//! which can be used to build an admin endpoint or a REPL on top of a system.
+
+use core::{sync::atomic::{AtomicU64, AtomicUsize, Ordering}, time::Duration};
+
+use alloc::{string::String, vec::Vec};
+
+/// # [`ActorInfo`]
+/// A snapshot of a single actor running on the local system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "transport", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ActorInfo {
+    /// The actor's id
+    pub id: u64,
+    /// Every name that refers to the actor, in sorted order
+    pub names: Vec<String>,
+    /// The type name of the actor
+    pub actor: String,
+    /// How long the actor has been running, if the system's [`crate::Timer`] can read a clock
+    pub uptime: Option<Duration>,
+    /// The number of messages the actor has finished handling
+    pub processed: u64,
+    /// The number of messages the actor is currently handling
+    pub in_flight: usize,
+}
+
+/// Counters kept for every local actor, shared between its context and the system's registry.
+#[derive(Debug, Default)]
+pub(crate) struct ActorStats {
+    /// The time the actor was added, according to the system's timer
+    pub(crate) started: Option<Duration>,
+    /// The number of messages the actor has finished handling
+    pub(crate) processed: AtomicU64,
+    /// The number of messages the actor is currently handling
+    pub(crate) in_flight: AtomicUsize,
+}
+
+impl ActorStats {
+    /// Creates the counters of an actor added at the given time.
+    pub(crate) fn new(started: Option<Duration>) -> Self {
+        Self { started, ..Self::default() }
+    }
+
+    /// Takes a snapshot of the actor, given its id, names, type name, and the current time.
+    pub(crate) fn snapshot(&self, id: u64, names: Vec<String>, actor: &'static str, now: Option<Duration>) -> ActorInfo {
+        ActorInfo {
+            id,
+            names,
+            actor: String::from(actor),
+            uptime: self.started.zip(now).map(|(started, now)| now.saturating_sub(started)),
+            processed: self.processed.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
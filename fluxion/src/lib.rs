@@ -5,8 +5,19 @@
 
 extern crate alloc;
 
+#[cfg(feature = "std")]
+extern crate std;
+
 pub use const_format::concatcp;
-pub use fluxion_macro::{actor, generic_message, message};
+pub use fluxion_macro::{actor, generic_message, message, message_enum};
+
+/// Items used by the code generated by fluxion's macros.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::sync::Arc;
+}
+
+mod trace;
 
 mod fluxion;
 pub use fluxion::*;
@@ -26,4 +37,160 @@ pub use references::*;
 mod foreign;
 pub use foreign::*;
 
+mod timer;
+pub use timer::*;
+
+mod executor;
+pub use executor::*;
+
+mod scheduler;
+pub use scheduler::*;
+
+mod event_bus;
+
+mod hash;
+
+#[cfg(feature = "foreign")]
+mod dedup;
+
+#[cfg(feature = "foreign")]
+mod discovery;
+#[cfg(feature = "foreign")]
+pub use discovery::*;
+
+#[cfg(feature = "foreign")]
+mod network;
+#[cfg(feature = "foreign")]
+pub use network::*;
+
+#[cfg(feature = "foreign")]
+mod access;
+#[cfg(feature = "foreign")]
+pub use access::*;
+
+mod router;
+pub use router::*;
+
+#[cfg(all(feature = "foreign", feature = "serde"))]
+mod sharding;
+#[cfg(all(feature = "foreign", feature = "serde"))]
+pub use sharding::*;
+
+#[cfg(feature = "transport")]
+mod membership;
+#[cfg(feature = "transport")]
+pub use membership::*;
+
+mod builder;
+pub use builder::*;
+
+mod spawn;
+pub use spawn::Spawn;
+
+mod owned;
+
+mod registry;
+
+mod channel;
+pub use channel::Subscription;
+
+mod dead_letters;
+pub use dead_letters::*;
+
+mod lifecycle;
+pub use lifecycle::*;
+
+mod inspect;
+pub use inspect::ActorInfo;
+
+mod shutdown;
+pub use shutdown::*;
+
+mod drain;
+
+mod mailbox;
+pub use mailbox::PriorityMessage;
+
+mod batch;
+pub use batch::*;
+
+mod stream;
+pub use stream::*;
+
+mod flow;
+pub use flow::Credits;
+
+pub mod fsm;
+
+pub mod persistence;
+
+pub mod metrics;
+
+pub mod admin;
+
+#[cfg(feature = "testkit")]
+pub mod testkit;
+
+mod middleware;
+pub use middleware::*;
+
+mod guard;
+pub use guard::*;
+
+mod rate;
+pub use rate::RateLimit;
+
+mod shed;
+pub use shed::{LoadShedding, OverloadEvent};
+
+mod reliable;
+pub use reliable::*;
+
+mod retry;
+pub use retry::*;
+
+mod circuit;
+pub use circuit::*;
+
+mod gather;
+pub use gather::Gathered;
+
+mod group;
+pub use group::Group;
+
+mod broadcast;
+
+mod health;
+pub use health::{ActorHealth, Health, HealthCheck};
+
+mod reply;
+pub use reply::*;
+
+mod cancel;
+pub use cancel::*;
+
+#[cfg(feature = "std")]
+mod metadata;
+#[cfg(feature = "std")]
+pub use metadata::*;
+
+#[cfg(feature = "std")]
+mod sender;
+#[cfg(feature = "std")]
+pub use sender::SenderId;
+
+#[cfg(feature = "std")]
+mod panic;
+#[cfg(feature = "std")]
+pub use panic::*;
+
+#[cfg(feature = "blocking")]
+mod blocking;
+
+#[cfg(any(feature = "tokio", feature = "smol", feature = "async-std", feature = "embassy", all(feature = "wasm", target_arch = "wasm32")))]
+pub mod runtime;
+
+#[cfg(feature = "transport")]
+pub mod transport;
+
 pub use slacktor::Message;
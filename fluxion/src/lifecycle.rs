@@ -0,0 +1,73 @@
+//! # Lifecycle
+//! Every [`crate::Fluxion`] instance publishes an event whenever an actor starts, stops, fails, or stops answering health checks.
+//! These can be observed with [`crate::Fluxion::lifecycle_events`], for example to build dashboards
+//! or to allow other actors to react when a peer dies.
+
+use alloc::string::String;
+
+/// # [`LifecycleEvent`]
+/// A change in the lifecycle of an actor on the local system.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum LifecycleEvent {
+    /// The actor was initialized and added to the system.
+    ActorStarted {
+        /// The actor's id
+        id: u64,
+        /// The name the actor was added with, if any
+        name: Option<String>,
+    },
+    /// The actor was removed from the system and has been deinitialized.
+    ActorStopped {
+        /// The actor's id
+        id: u64,
+    },
+    /// The actor failed, and was not added to or was removed from the system.
+    ActorFailed {
+        /// The actor's id, if it had been assigned one
+        id: Option<u64>,
+        /// The name the actor was added with, if any
+        name: Option<String>,
+        /// The type name of the actor
+        actor: &'static str,
+        /// How the actor failed
+        error: ActorFailure,
+    },
+    /// The actor was replaced by a new instance, for example by a [`crate::Router`].
+    /// The new instance is reported as started and the old one as stopped as usual, in addition to this event.
+    ActorRestarted {
+        /// The new instance's id
+        id: u64,
+        /// The id of the instance that was replaced
+        replaced: u64,
+    },
+    /// The actor didn't answer a health check within its deadline, after answering the previous one.
+    /// See [`crate::Fluxion::check_health`].
+    ActorUnresponsive {
+        /// The actor's id
+        id: u64,
+        /// The type name of the actor
+        actor: &'static str,
+        /// How long the actor was given to answer
+        deadline: core::time::Duration,
+    },
+}
+
+/// # [`ActorFailure`]
+/// Describes how an actor failed.
+/// Actor errors are not required to implement any traits, so the error itself is not included.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ActorFailure {
+    /// [`crate::Actor::initialize`] returned an error.
+    Initialize,
+    /// A handler panicked, as described in [`crate::PanicPolicy`]. The actor is only removed from the system
+    /// if the policy is [`crate::PanicPolicy::Stop`].
+    #[cfg(feature = "std")]
+    Panicked {
+        /// The type name of the message being handled
+        message: &'static str,
+        /// The message the panic was raised with
+        panic: String,
+    },
+}
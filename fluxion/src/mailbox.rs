@@ -0,0 +1,309 @@
+//! # Mailboxes
+//! Messages are handled by the tasks that send them, so most actors never queue messages at all. An actor that limits
+//! its [`crate::Actor::MAX_CONCURRENCY`] does, as messages sent while it is busy wait for it to finish another first.
+//! The messages waiting form the actor's mailbox, which can be inspected with [`LocalRef::mailbox_len`] and
+//! [`LocalRef::peek_types`]. [`LocalRef::purge`] drops the waiting messages of a type, failing their sends with
+//! [`MessageSendError::Purged`], which helps an actor that is wedged behind a backlog of stale work.
+//!
+//! Messages are admitted in the order they were sent, except for [`PriorityMessage`]s sent with
+//! [`LocalRef::send_priority`], which are admitted ahead of every normal message waiting.
+
+use core::{any::TypeId, future::Future, pin::pin, sync::atomic::{AtomicUsize, Ordering}, task::Poll};
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use maitake_sync::{semaphore::Permit, spin::Mutex, Semaphore, WaitQueue};
+
+use crate::{trace::instrument, Actor, Delegate, Handler, LocalRef, Message, MessageSendError};
+
+/// # [`PriorityMessage`]
+/// A message that may be sent with [`LocalRef::send_priority`], which admits it to the actor ahead of every normal
+/// message waiting in its mailbox. Priority messages are admitted in the order they were sent, and only wait for the
+/// messages the actor is already handling. Like the mailbox itself, this only matters for actors that limit their
+/// [`Actor::MAX_CONCURRENCY`], as other actors never make messages wait.
+pub trait PriorityMessage: Message {}
+
+/// The messages of one type waiting for an actor.
+struct Waiting {
+    /// The type name of the messages
+    message: &'static str,
+    /// How many are waiting
+    count: usize,
+    /// Incremented whenever the messages are purged, which fails those that were waiting at the time
+    purges: u64,
+}
+
+/// Limits how many messages an actor handles at once, and tracks those waiting.
+pub(crate) struct Mailbox {
+    /// Limits how many messages the actor handles at once, if it set [`crate::Actor::MAX_CONCURRENCY`]
+    concurrency: Option<Semaphore>,
+    /// The messages waiting, keyed by their type
+    waiting: Mutex<BTreeMap<TypeId, Waiting>>,
+    /// Woken whenever messages are purged
+    purged: WaitQueue,
+    /// How many priority messages are waiting
+    priority: AtomicUsize,
+    /// Woken once no priority messages are waiting
+    prioritised: WaitQueue,
+    /// Held by the normal message whose turn it is to be admitted next
+    turn: maitake_sync::Mutex<()>,
+}
+
+/// Counts a priority message as waiting until it is admitted or its send is cancelled,
+/// letting normal messages through once no priority messages are left.
+struct Prioritised<'a>(&'a Mailbox);
+
+impl Drop for Prioritised<'_> {
+    fn drop(&mut self) {
+        if self.0.priority.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.prioritised.wake_all();
+        }
+    }
+}
+
+/// Counts a message as waiting until it is dropped, whether it was admitted, purged, or its send was cancelled.
+struct Queued<'a> {
+    /// The mailbox the message is waiting in
+    mailbox: &'a Mailbox,
+    /// The type of the message
+    message: TypeId,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        let mut waiting = self.mailbox.waiting.lock();
+        if let Some(entry) = waiting.get_mut(&self.message) {
+            entry.count -= 1;
+            if entry.count == 0 {
+                waiting.remove(&self.message);
+            }
+        }
+    }
+}
+
+impl Mailbox {
+    /// Creates a mailbox for an actor that handles at most `limit` messages at once, if it has a limit.
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            concurrency: limit.map(|limit| Semaphore::new(limit.max(1))),
+            waiting: Mutex::new(BTreeMap::new()),
+            purged: WaitQueue::new(),
+            priority: AtomicUsize::new(0),
+            prioritised: WaitQueue::new(),
+            turn: maitake_sync::Mutex::new(()),
+        }
+    }
+
+    /// Waits until the actor can handle a message of type `M`, returning the permit to hold while it is handled.
+    /// Priority messages are admitted ahead of every normal message waiting.
+    /// Fails with [`MessageSendError::Purged`] if messages of the type are purged while this one is waiting.
+    pub(crate) async fn admit<M: 'static>(&self, priority: bool) -> Result<Option<Permit<'_>>, MessageSendError> {
+        let Some(concurrency) = &self.concurrency else {
+            return Ok(None);
+        };
+
+        let message = TypeId::of::<M>();
+        let purges = {
+            let mut waiting = self.waiting.lock();
+            let entry = waiting.entry(message).or_insert(Waiting { message: core::any::type_name::<M>(), count: 0, purges: 0 });
+            entry.count += 1;
+            entry.purges
+        };
+        let _queued = Queued { mailbox: self, message };
+
+        let purged = || self.waiting.lock().get(&message).is_some_and(|entry| entry.purges != purges);
+
+        let mut acquire = pin!(self.acquire(concurrency, priority));
+        loop {
+            let mut woken = pin!(self.purged.wait());
+
+            let admitted = core::future::poll_fn(|cx| {
+                if let Poll::Ready(permit) = acquire.as_mut().poll(cx) {
+                    return Poll::Ready(Some(permit));
+                }
+
+                // Check again once registered for a wakeup, so that a purge made meanwhile isn't missed
+                if woken.as_mut().poll(cx).is_ready() || purged() {
+                    return Poll::Ready(None);
+                }
+
+                Poll::Pending
+            }).await;
+
+            match admitted {
+                Some(permit) => return Ok(permit),
+                None if purged() => return Err(MessageSendError::Purged),
+                // Messages of another type were purged
+                None => {},
+            }
+        }
+    }
+
+    /// Acquires a permit to handle a message. Priority messages wait for a permit straight away. Normal messages take
+    /// turns, and the one whose turn it is only waits for a permit once no priority messages are waiting, handing it back
+    /// if one started waiting meanwhile, so that a priority message never waits behind a normal one.
+    async fn acquire<'a>(&'a self, concurrency: &'a Semaphore, priority: bool) -> Option<Permit<'a>> {
+        // The semaphore is never closed, so acquiring a permit can't fail
+        if priority {
+            self.priority.fetch_add(1, Ordering::SeqCst);
+            let _waiting = Prioritised(self);
+            return concurrency.acquire(1).await.ok();
+        }
+
+        let _turn = self.turn.lock().await;
+        loop {
+            let _ = self.prioritised.wait_for(|| self.priority.load(Ordering::SeqCst) == 0).await;
+
+            let permit = concurrency.acquire(1).await.ok()?;
+            if self.priority.load(Ordering::SeqCst) == 0 {
+                return Some(permit);
+            }
+            drop(permit);
+        }
+    }
+
+    /// Returns how many messages are waiting.
+    fn len(&self) -> usize {
+        self.waiting.lock().values().map(|entry| entry.count).sum()
+    }
+
+    /// Returns the type name of each type of message waiting, along with how many are waiting.
+    fn types(&self) -> Vec<(&'static str, usize)> {
+        let mut types = self.waiting.lock().values().map(|entry| (entry.message, entry.count)).collect::<Vec<_>>();
+        types.sort_unstable();
+        types
+    }
+
+    /// Fails every message of type `M` that is waiting, returning how many there were.
+    fn purge<M: 'static>(&self) -> usize {
+        let purged = match self.waiting.lock().get_mut(&TypeId::of::<M>()) {
+            Some(entry) => {
+                entry.purges += 1;
+                entry.count
+            },
+            None => 0,
+        };
+
+        if purged > 0 {
+            self.purged.wake_all();
+        }
+
+        purged
+    }
+}
+
+impl<A: Actor, D: Delegate> LocalRef<A, D> {
+    /// # [`LocalRef::mailbox_len`]
+    /// Returns how many messages are waiting for the actor to handle them, as described in the [module documentation](self).
+    /// This is always zero for actors that don't limit their [`Actor::MAX_CONCURRENCY`].
+    #[must_use]
+    pub fn mailbox_len(&self) -> usize {
+        self.gate.mailbox.len()
+    }
+
+    /// # [`LocalRef::peek_types`]
+    /// Returns the type name of each type of message waiting for the actor, along with how many are waiting,
+    /// sorted by type name.
+    #[must_use]
+    pub fn peek_types(&self) -> Vec<(&'static str, usize)> {
+        self.gate.mailbox.types()
+    }
+
+    /// # [`LocalRef::purge`]
+    /// Drops every message of type `M` waiting for the actor, whose sends fail with [`MessageSendError::Purged`],
+    /// and returns how many were dropped. Messages the actor is already handling are unaffected.
+    pub fn purge<M: 'static>(&self) -> usize {
+        self.gate.mailbox.purge::<M>()
+    }
+
+    /// # [`LocalRef::send_priority`]
+    /// Sends a message like [`LocalRef::send`], admitting it to the actor ahead of every normal message waiting in its
+    /// mailbox, as described in [`PriorityMessage`].
+    ///
+    /// # Errors
+    /// Returns the same errors as [`crate::MessageSender::send`].
+    pub async fn send_priority<M: PriorityMessage>(&self, message: M) -> Result<M::Result, MessageSendError>
+        where A: Handler<M> {
+        let _pass = self.gate.enter()?;
+        let send = instrument!(self.dispatch(message, true), "fluxion::send_priority", actor = self.id, message = core::any::type_name::<M>());
+
+        match self.system.default_timeout {
+            Some(timeout) => self.timed::<M>(send, timeout).await,
+            None => send.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+
+    use super::*;
+
+    /// Waits for the given number of messages to be waiting in the mailbox.
+    async fn waiting(mailbox: &Mailbox, count: usize) {
+        while mailbox.len() < count {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Admits a message of type `M` on a new task, recording its name once it is admitted.
+    fn admit<M: 'static>(mailbox: &Arc<Mailbox>, admitted: &Arc<Mutex<Vec<&'static str>>>, name: &'static str, priority: bool) -> tokio::task::JoinHandle<Result<(), MessageSendError>> {
+        let (mailbox, admitted) = (mailbox.clone(), admitted.clone());
+        tokio::spawn(async move {
+            let _permit = mailbox.admit::<M>(priority).await?;
+            admitted.lock().push(name);
+            Ok(())
+        })
+    }
+
+    #[tokio::test]
+    async fn admits_priority_messages_first() {
+        let mailbox = Arc::new(Mailbox::new(Some(1)));
+        let admitted = Arc::new(Mutex::new(Vec::new()));
+        let busy = mailbox.admit::<u8>(false).await.unwrap();
+
+        let first = admit::<u8>(&mailbox, &admitted, "first", false);
+        waiting(&mailbox, 1).await;
+        let second = admit::<u8>(&mailbox, &admitted, "second", false);
+        waiting(&mailbox, 2).await;
+        let priority = admit::<u16>(&mailbox, &admitted, "priority", true);
+        waiting(&mailbox, 3).await;
+
+        assert_eq!(mailbox.types(), [("u16", 1), ("u8", 2)]);
+
+        drop(busy);
+        for task in [first, second, priority] {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(*admitted.lock(), ["priority", "first", "second"]);
+        assert_eq!(mailbox.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn purges_waiting_messages_of_one_type() {
+        let mailbox = Arc::new(Mailbox::new(Some(1)));
+        let admitted = Arc::new(Mutex::new(Vec::new()));
+        let busy = mailbox.admit::<u8>(false).await.unwrap();
+
+        let purged = admit::<u8>(&mailbox, &admitted, "purged", false);
+        let kept = admit::<u16>(&mailbox, &admitted, "kept", false);
+        waiting(&mailbox, 2).await;
+
+        assert_eq!(mailbox.purge::<u8>(), 1);
+        assert!(matches!(purged.await.unwrap(), Err(MessageSendError::Purged)));
+
+        drop(busy);
+        kept.await.unwrap().unwrap();
+        assert_eq!(*admitted.lock(), ["kept"]);
+    }
+
+    #[tokio::test]
+    async fn never_queues_without_a_limit() {
+        let mailbox = Mailbox::new(None);
+
+        assert!(mailbox.admit::<u8>(false).await.unwrap().is_none());
+        assert_eq!(mailbox.purge::<u8>(), 0);
+        assert_eq!(mailbox.len(), 0);
+    }
+}
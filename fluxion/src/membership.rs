@@ -0,0 +1,345 @@
+//! # Membership
+//! Systems in a cluster learn about each other through gossip. Every system runs a [`Membership`], which periodically
+//! increments its own heartbeat and exchanges the heartbeats it knows of with one other system, through its [`Delegate`].
+//! A system whose heartbeat stops increasing is first suspected, and then declared failed, so that a single lost
+//! round of gossip doesn't evict a healthy system.
+//!
+//! Changes are published as [`MembershipEvent`]s, which user code can react to, and which can be applied to a
+//! [`crate::ShardRegion`] with [`crate::ShardRegion::apply_membership`] to move shards away from failed systems.
+//! The [`MembershipActor`] must be exported by the transport for [`Gossip`]: `exports.export::<MembershipActor, Gossip>()`.
+
+use core::{sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use alloc::{borrow::ToOwned, boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use maitake_sync::spin::Mutex;
+
+use crate::{channel::Publisher, Actor, ActorContext, AddActorError, Delegate, Fluxion, Handler, Identifier, Message, MessageID, ScheduleError, ScheduleHandle, Subscription};
+
+/// # [`MEMBERSHIP_ACTOR_NAME`]
+/// The name the [`MembershipActor`] is added to every system in the cluster under.
+pub const MEMBERSHIP_ACTOR_NAME: &str = "fluxion/membership";
+
+/// # [`NodeStatus`]
+/// Whether a system in the cluster is believed to be running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    /// The system's heartbeat is increasing.
+    Alive,
+    /// The system's heartbeat has not increased for a while.
+    Suspect,
+    /// The system's heartbeat has not increased for long enough that it is considered to have failed.
+    Failed,
+}
+
+/// # [`NodeInfo`]
+/// A system in the cluster, as seen by the local system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    /// The system's id
+    pub system: String,
+    /// The latest heartbeat heard from the system
+    pub heartbeat: u64,
+    /// Whether the system is believed to be running
+    pub status: NodeStatus,
+}
+
+/// # [`MembershipEvent`]
+/// A change in the membership of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MembershipEvent {
+    /// A system was heard from for the first time, or again after it had failed.
+    NodeJoined(String),
+    /// A system's heartbeat has not increased for a while.
+    NodeSuspected(String),
+    /// A suspected system's heartbeat increased again.
+    NodeRecovered(String),
+    /// A system's heartbeat has not increased for long enough that it is considered to have failed.
+    NodeFailed(String),
+}
+
+/// # [`MembershipConfig`]
+/// How quickly systems whose heartbeat stops increasing are suspected and declared failed, in rounds of gossip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MembershipConfig {
+    /// The number of rounds without a heartbeat after which a system is suspected. Defaults to 3.
+    pub suspect_after: u64,
+    /// The number of rounds without a heartbeat after which a system is declared failed. Defaults to 8.
+    pub fail_after: u64,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        Self { suspect_after: 3, fail_after: 8 }
+    }
+}
+
+/// # [`Heartbeat`]
+/// The latest heartbeat of a single system, as exchanged by [`Gossip`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Heartbeat {
+    /// The system's id
+    pub system: String,
+    /// The system's heartbeat, which it increments every round
+    pub heartbeat: u64,
+}
+
+/// # [`Gossip`]
+/// Sends every heartbeat the sender knows of, and is answered with every heartbeat the receiver knows of.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Gossip(pub Vec<Heartbeat>);
+
+impl Message for Gossip {
+    type Result = Vec<Heartbeat>;
+}
+
+impl MessageID for Gossip {
+    const ID: &'static str = "fluxion::membership::Gossip";
+}
+
+/// A system known to the local system.
+struct Node {
+    /// The latest heartbeat heard from the system
+    heartbeat: u64,
+    /// The local round in which the heartbeat last increased
+    updated: u64,
+    /// Whether the system is believed to be running
+    status: NodeStatus,
+}
+
+/// The membership state shared by a [`Membership`] and its [`MembershipActor`].
+struct Nodes {
+    /// The local system's id
+    local: String,
+    /// Every system known to the local system, including itself
+    nodes: Mutex<BTreeMap<String, Node>>,
+    /// The number of rounds of gossip the local system has started
+    round: AtomicU64,
+    /// How quickly silent systems are suspected and declared failed
+    config: MembershipConfig,
+    /// Publishes changes in membership
+    events: Publisher<MembershipEvent>,
+}
+
+impl Nodes {
+    /// Returns every heartbeat known to the local system.
+    fn digest(&self) -> Vec<Heartbeat> {
+        self.nodes.lock().iter()
+            .map(|(system, node)| Heartbeat { system: system.clone(), heartbeat: node.heartbeat })
+            .collect()
+    }
+
+    /// Merges heartbeats received from another system, publishing any changes in membership.
+    fn merge(&self, heartbeats: Vec<Heartbeat>) {
+        let round = self.round.load(Ordering::Relaxed);
+        let mut events = Vec::new();
+
+        {
+            let mut nodes = self.nodes.lock();
+
+            for Heartbeat { system, heartbeat } in heartbeats {
+                // Only the local system increments its own heartbeat
+                if system == self.local {
+                    continue;
+                }
+
+                let Some(node) = nodes.get_mut(&system) else {
+                    events.push(MembershipEvent::NodeJoined(system.clone()));
+                    nodes.insert(system, Node { heartbeat, updated: round, status: NodeStatus::Alive });
+                    continue;
+                };
+
+                if heartbeat <= node.heartbeat {
+                    continue;
+                }
+
+                node.heartbeat = heartbeat;
+                node.updated = round;
+
+                match node.status {
+                    NodeStatus::Alive => {},
+                    NodeStatus::Suspect => events.push(MembershipEvent::NodeRecovered(system)),
+                    NodeStatus::Failed => events.push(MembershipEvent::NodeJoined(system)),
+                }
+                node.status = NodeStatus::Alive;
+            }
+        }
+
+        for event in &events {
+            self.events.publish(event);
+        }
+    }
+
+    /// Starts a new round, incrementing the local heartbeat and updating the status of systems that have gone silent.
+    fn tick(&self) {
+        let round = self.round.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut events = Vec::new();
+
+        {
+            let mut nodes = self.nodes.lock();
+
+            for (system, node) in nodes.iter_mut() {
+                if *system == self.local {
+                    node.heartbeat += 1;
+                    node.updated = round;
+                    continue;
+                }
+
+                let silent = round - node.updated;
+
+                if node.status != NodeStatus::Failed && silent >= self.config.fail_after {
+                    node.status = NodeStatus::Failed;
+                    events.push(MembershipEvent::NodeFailed(system.clone()));
+                } else if node.status == NodeStatus::Alive && silent >= self.config.suspect_after {
+                    node.status = NodeStatus::Suspect;
+                    events.push(MembershipEvent::NodeSuspected(system.clone()));
+                }
+            }
+        }
+
+        for event in &events {
+            self.events.publish(event);
+        }
+    }
+
+    /// Chooses the system to gossip with this round, cycling through every system that has not failed and every seed.
+    fn target(&self, seeds: &[String]) -> Option<String> {
+        let mut targets = self.nodes.lock().iter()
+            .filter(|(system, node)| **system != self.local && node.status != NodeStatus::Failed)
+            .map(|(system, _)| system.clone())
+            .collect::<Vec<_>>();
+
+        // Seeds are always candidates, so that a partitioned system can rejoin the cluster
+        for seed in seeds {
+            if *seed != self.local && !targets.contains(seed) {
+                targets.push(seed.clone());
+            }
+        }
+
+        let round = usize::try_from(self.round.load(Ordering::Relaxed)).unwrap_or_default();
+        targets.get(round.checked_rem(targets.len())?).cloned()
+    }
+}
+
+/// # [`MembershipActor`]
+/// Answers [`Gossip`] from other systems in the cluster. It is added under [`MEMBERSHIP_ACTOR_NAME`] by [`Membership::new`].
+pub struct MembershipActor(Arc<Nodes>);
+
+impl Actor for MembershipActor {
+    type Error = ();
+}
+
+impl Handler<Gossip> for MembershipActor {
+    async fn handle_message<D: Delegate>(&self, message: Gossip, _context: &ActorContext<D>) -> Vec<Heartbeat> {
+        self.0.merge(message.0);
+        self.0.digest()
+    }
+}
+
+/// # [`Membership`]
+/// The local system's view of the cluster, kept up to date by gossiping with the other systems.
+pub struct Membership<D> {
+    /// The system gossip is sent from
+    system: Fluxion<D>,
+    /// The systems that are always gossiped with, even before they are known
+    seeds: Arc<[String]>,
+    /// The known systems
+    nodes: Arc<Nodes>,
+}
+
+impl<D> Clone for Membership<D> {
+    fn clone(&self) -> Self {
+        Self { system: self.system.clone(), seeds: self.seeds.clone(), nodes: self.nodes.clone() }
+    }
+}
+
+impl<D: Delegate> Membership<D> {
+    /// # [`Membership::new`]
+    /// Joins the cluster through the given seed systems, adding a [`MembershipActor`] to the system under [`MEMBERSHIP_ACTOR_NAME`].
+    /// Initially, the local system is the only known member. Gossip is exchanged once [`Membership::gossip_every`] is called.
+    ///
+    /// # Errors
+    /// Returns an error if the name is already taken and the system's [`crate::NameConflictPolicy`] is to error.
+    pub async fn new(system: &Fluxion<D>, seeds: &[&str], config: MembershipConfig) -> Result<Self, AddActorError<()>> {
+        let local = system.get_id().to_owned();
+
+        let mut nodes = BTreeMap::new();
+        nodes.insert(local.clone(), Node { heartbeat: 0, updated: 0, status: NodeStatus::Alive });
+
+        let nodes = Arc::new(Nodes {
+            local,
+            nodes: Mutex::new(nodes),
+            round: AtomicU64::new(0),
+            config,
+            events: Publisher::default(),
+        });
+
+        system.add_named(MEMBERSHIP_ACTOR_NAME, MembershipActor(nodes.clone())).await?;
+
+        Ok(Self {
+            system: system.clone(),
+            seeds: seeds.iter().map(|&seed| seed.to_owned()).collect(),
+            nodes,
+        })
+    }
+
+    /// # [`Membership::gossip`]
+    /// Runs a single round of gossip: increments the local heartbeat, updates the status of silent systems,
+    /// and exchanges heartbeats with one other system.
+    pub async fn gossip(&self) {
+        self.nodes.tick();
+
+        let Some(target) = self.nodes.target(&self.seeds) else {
+            return;
+        };
+
+        let Some(peer) = self.system.get::<MembershipActor, Gossip>(Identifier::ForeignNamed(MEMBERSHIP_ACTOR_NAME, &target)).await else {
+            return;
+        };
+
+        // A system that can't be reached is detected by its heartbeat no longer increasing
+        if let Ok(heartbeats) = peer.send(Gossip(self.nodes.digest())).await {
+            self.nodes.merge(heartbeats);
+        }
+    }
+
+    /// # [`Membership::gossip_every`]
+    /// Runs a round of gossip every `period`, until the returned handle is cancelled.
+    ///
+    /// # Errors
+    /// Returns an error if the system was built without a [`crate::Timer`] or an [`crate::Executor`].
+    pub fn gossip_every(&self, period: Duration) -> Result<ScheduleHandle, ScheduleError> {
+        let membership = self.clone();
+
+        crate::scheduler::schedule(self.system.timer.as_ref(), self.system.executor.as_ref(), period, move || {
+            let membership = membership.clone();
+            Some(Box::pin(async move { membership.gossip().await }))
+        })
+    }
+
+    /// # [`Membership::nodes`]
+    /// Returns every known system, including the local one.
+    #[must_use]
+    pub fn nodes(&self) -> Vec<NodeInfo> {
+        self.nodes.nodes.lock().iter()
+            .map(|(system, node)| NodeInfo { system: system.clone(), heartbeat: node.heartbeat, status: node.status })
+            .collect()
+    }
+
+    /// # [`Membership::alive`]
+    /// Returns the ids of every system that is believed to be running, including the local one.
+    #[must_use]
+    pub fn alive(&self) -> Vec<String> {
+        self.nodes.nodes.lock().iter()
+            .filter(|(_, node)| node.status != NodeStatus::Failed)
+            .map(|(system, _)| system.clone())
+            .collect()
+    }
+
+    /// # [`Membership::events`]
+    /// Returns a [`Subscription`] that receives a [`MembershipEvent`] whenever the membership of the cluster changes.
+    #[must_use]
+    pub fn events(&self) -> Subscription<MembershipEvent> {
+        self.nodes.events.subscribe()
+    }
+}
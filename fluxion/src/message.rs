@@ -14,19 +14,45 @@ pub enum MessageSendError {
     #[cfg(feature = "serde")]
     SerializationError {
         message: alloc::string::String,
-        source: alloc::boxed::Box<dyn core::error::Error>,
+        source: alloc::boxed::Box<dyn core::error::Error + Send + Sync>,
     },
     #[cfg(feature = "serde")]
     DeserializationError {
         message: alloc::string::String,
-        source: alloc::boxed::Box<dyn core::error::Error>,
+        source: alloc::boxed::Box<dyn core::error::Error + Send + Sync>,
     },
     #[cfg(feature = "foreign")]
     DelegateError {
         message: alloc::string::String,
-        source: alloc::boxed::Box<dyn core::error::Error>,
+        source: alloc::boxed::Box<dyn core::error::Error + Send + Sync>,
     },
-    UnknownError(alloc::boxed::Box<dyn Error>),
+    /// No response was received before the send's timeout elapsed.
+    Timeout,
+    /// The actor is being drained, and no longer accepts new messages.
+    Draining,
+    /// The system is shutting down, and the actor has stopped, or is about to stop, accepting new messages.
+    SystemShuttingDown,
+    /// The message was rejected by an open [`crate::CircuitBreaker`] without being sent.
+    CircuitOpen,
+    /// The message was rejected by one of the actor's [`crate::Guard`]s without being handled.
+    Rejected,
+    /// The actor's [`crate::RateLimit`] was exceeded, and it fails messages over the limit instead of delaying them.
+    RateLimited,
+    /// The system was handling too many messages at once, and shed the message as configured by its [`crate::LoadShedding`].
+    Overloaded,
+    /// The foreign system the actor lives on stopped responding, so the message was not sent or its response will never arrive.
+    #[cfg(feature = "foreign")]
+    PeerUnreachable,
+    /// The actor's handler panicked while handling the message. Holds the message the panic was raised with.
+    #[cfg(feature = "std")]
+    Panicked(alloc::string::String),
+    /// The actor only handles one message at a time, and is already waiting on this send further up the call chain,
+    /// so the message could never be handled. Only detected by systems built with [`crate::FluxionBuilder::deadlock_detection`].
+    #[cfg(feature = "std")]
+    WouldDeadlock,
+    /// The message was waiting for the actor when messages of its type were purged with [`crate::LocalRef::purge`].
+    Purged,
+    UnknownError(alloc::boxed::Box<dyn Error + Send + Sync>),
 }
 
 impl core::fmt::Display for MessageSendError {
@@ -38,6 +64,20 @@ impl core::fmt::Display for MessageSendError {
             MessageSendError::DeserializationError { message, source: _ } => message.clone(),
             #[cfg(feature = "foreign")]
             MessageSendError::DelegateError { message, source: _ } => message.clone(),
+            MessageSendError::Timeout => alloc::string::String::from("timed out waiting for a response"),
+            MessageSendError::Draining => alloc::string::String::from("the actor is draining and no longer accepts messages"),
+            MessageSendError::SystemShuttingDown => alloc::string::String::from("the system is shutting down"),
+            MessageSendError::CircuitOpen => alloc::string::String::from("the circuit breaker is open"),
+            MessageSendError::Rejected => alloc::string::String::from("the message was rejected by a guard"),
+            MessageSendError::RateLimited => alloc::string::String::from("the actor's rate limit was exceeded"),
+            MessageSendError::Overloaded => alloc::string::String::from("the system is overloaded"),
+            MessageSendError::Purged => alloc::string::String::from("the message was purged from the actor's mailbox"),
+            #[cfg(feature = "foreign")]
+            MessageSendError::PeerUnreachable => alloc::string::String::from("the foreign system stopped responding"),
+            #[cfg(feature = "std")]
+            MessageSendError::Panicked(panic) => alloc::format!("the handler panicked: {panic}"),
+            #[cfg(feature = "std")]
+            MessageSendError::WouldDeadlock => alloc::string::String::from("the actor is already waiting on this send, which would deadlock"),
             MessageSendError::UnknownError(e) => alloc::format!("{e}"),
         };
 
@@ -54,6 +94,11 @@ impl core::error::Error for MessageSendError {
             Self::DeserializationError { message: _, source } => Some(source.as_ref()),
             #[cfg(feature = "foreign")]
             Self::DelegateError { message: _, source } => Some(source.as_ref()),
+            Self::Timeout | Self::Draining | Self::SystemShuttingDown | Self::CircuitOpen | Self::Rejected | Self::RateLimited | Self::Overloaded | Self::Purged => None,
+            #[cfg(feature = "foreign")]
+            Self::PeerUnreachable => None,
+            #[cfg(feature = "std")]
+            Self::Panicked(_) | Self::WouldDeadlock => None,
             Self::UnknownError(e) => Some(e.as_ref()),
         }
     }
@@ -76,6 +121,50 @@ impl<T> IndeterminateMessage for T
 where T: Message + MessageID + serde::Serialize + for<'a> serde::Deserialize<'a>,
     Self::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {}
 
+/// # [`MessageUpgrade`]
+/// Upgrades a message from the previous version of its schema, so that foreign systems that have not yet been upgraded
+/// can keep sending it during a rolling upgrade. Each version names the one before it, and older versions are upgraded
+/// one step at a time until they reach the current version.
+///
+/// The oldest version's [`MessageUpgrade::Previous`] is [`NoPreviousVersion`], which can never be constructed.
+#[cfg(feature = "serde")]
+pub trait MessageUpgrade: MessageID + serde::de::DeserializeOwned {
+    /// The previous version of this message, whose [`MessageID::VERSION`] must be lower.
+    type Previous: MessageUpgrade;
+
+    /// # [`MessageUpgrade::upgrade`]
+    /// Converts the previous version of this message into this version.
+    fn upgrade(previous: Self::Previous) -> Self;
+}
+
+/// # [`NoPreviousVersion`]
+/// Ends a chain of [`MessageUpgrade`]s, as the [`MessageUpgrade::Previous`] of a message's oldest version.
+/// This type has no values, so its upgrade can be written as `match previous {}`.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy)]
+pub enum NoPreviousVersion {}
+
+#[cfg(feature = "serde")]
+impl MessageID for NoPreviousVersion {
+    const ID: &'static str = "fluxion::NoPreviousVersion";
+    const VERSION: u32 = 0;
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NoPreviousVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+        Err(serde::de::Error::custom("the message has no previous version"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl MessageUpgrade for NoPreviousVersion {
+    type Previous = NoPreviousVersion;
+
+    fn upgrade(previous: Self::Previous) -> Self {
+        previous
+    }
+}
 
 /// # [`IndeterminateMessage`]
 /// An indeterminate message is a message for which it has not yet been determined whether it will be serialized.
@@ -0,0 +1,186 @@
+//! # Metadata
+//! Messages can carry [`Metadata`], such as a correlation id, without it being part of the message itself.
+//! Metadata is attached to a future with [`Metadata::scope`], and every message sent while that future runs carries it.
+//! Because local messages are handled by the sending task, handlers see the sender's metadata through
+//! [`crate::ActorContext::current_metadata`], and any messages they send in turn carry it too.
+//! The bundled transport also carries the metadata to foreign systems.
+//!
+//! Systems built with [`crate::FluxionBuilder::deadlock_detection`] also record the chain of actors whose handlers are
+//! waiting on the message in its metadata. A send that would wait on an actor already in the chain, which only handles one
+//! message at a time, can never complete, so it fails with [`crate::MessageSendError::WouldDeadlock`] instead.
+//!
+//! Metadata is tracked per thread while a future is being polled, so it requires the `std` feature.
+
+use core::{cell::RefCell, future::Future, pin::Pin, task::{Context, Poll}, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+
+std::thread_local! {
+    /// The metadata of the future currently being polled on this thread
+    static CURRENT: RefCell<Option<Metadata>> = const { RefCell::new(None) };
+}
+
+/// # [`Metadata`]
+/// Information that travels alongside messages, such as the correlation id of the request that caused them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "transport", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    /// The id shared by every message sent on behalf of a single request
+    correlation_id: Option<String>,
+    /// Any other entries
+    entries: BTreeMap<String, String>,
+    /// How long the message being handled waited for its actor, which is only set locally
+    #[cfg_attr(feature = "transport", serde(skip))]
+    queue_time: Option<Duration>,
+    /// The identity of the foreign system the message arrived from, which is only set by the receiving transport
+    #[cfg_attr(feature = "transport", serde(skip))]
+    peer: Option<String>,
+    /// The system and actor id of each actor whose handler is waiting on the message, outermost first
+    call_chain: Vec<(String, u64)>,
+}
+
+impl Metadata {
+    /// # [`Metadata::new`]
+    /// Creates empty metadata.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`Metadata::with_correlation_id`]
+    /// Sets the correlation id.
+    #[must_use]
+    pub fn with_correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(String::from(correlation_id));
+        self
+    }
+
+    /// # [`Metadata::correlation_id`]
+    /// Returns the correlation id, if one was set.
+    #[must_use]
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// # [`Metadata::insert`]
+    /// Sets an entry, returning its previous value.
+    pub fn insert(&mut self, key: &str, value: &str) -> Option<String> {
+        self.entries.insert(String::from(key), String::from(value))
+    }
+
+    /// # [`Metadata::get`]
+    /// Returns the value of an entry.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// # [`Metadata::queue_time`]
+    /// Returns how long the message being handled waited between arriving at the actor and the actor starting to handle it.
+    /// This is only available to handlers, and only if the system's [`crate::Timer`] can read a clock.
+    #[must_use]
+    pub fn queue_time(&self) -> Option<Duration> {
+        self.queue_time
+    }
+
+    /// # [`Metadata::peer`]
+    /// Returns the identity of the foreign system the message being handled arrived from, if the transport it arrived
+    /// over authenticated the system, such as with a client certificate. Foreign systems can't set this themselves.
+    #[must_use]
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    /// # [`Metadata::call_chain`]
+    /// Returns the system and actor id of each actor whose handler is waiting on the message being handled, outermost
+    /// first, ending with the actor handling it. This is only recorded by systems built with
+    /// [`crate::FluxionBuilder::deadlock_detection`].
+    #[must_use]
+    pub fn call_chain(&self) -> &[(String, u64)] {
+        &self.call_chain
+    }
+
+    /// # [`Metadata::current`]
+    /// Returns the metadata of the future currently running, if it has any.
+    #[must_use]
+    pub fn current() -> Option<Metadata> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// # [`Metadata::scope`]
+    /// Runs the future with this metadata, which is carried by every message it sends.
+    pub fn scope<F: Future>(self, future: F) -> Scoped<F> {
+        Scoped { metadata: Some(self), future: Box::pin(future) }
+    }
+
+    /// Returns the current metadata with the given queue time, or [`None`] if there is neither.
+    pub(crate) fn with_queue_time(queue_time: Option<Duration>) -> Option<Metadata> {
+        match (Self::current(), queue_time) {
+            (None, None) => None,
+            (current, queue_time) => Some(Metadata { queue_time, ..current.unwrap_or_default() }),
+        }
+    }
+
+    /// Returns the metadata a handler runs with, which is the current metadata with the handling actor appended to the
+    /// call chain if it is given, or [`None`] to keep the current metadata.
+    pub(crate) fn for_handler(call: Option<(&str, u64)>) -> Option<Metadata> {
+        let (system, actor) = call?;
+
+        let mut metadata = Self::current().unwrap_or_default();
+        metadata.call_chain.push((String::from(system), actor));
+        Some(metadata)
+    }
+
+    /// Returns the current metadata without its call chain, for a message that is handled without its sender waiting on it.
+    pub(crate) fn detached() -> Option<Metadata> {
+        Self::current().map(|metadata| Metadata { call_chain: Vec::new(), ..metadata })
+    }
+
+    /// Returns true if the given actor is in the call chain of the current metadata.
+    pub(crate) fn in_call_chain(system: &str, actor: u64) -> bool {
+        CURRENT.with(|current| current.borrow().as_ref()
+            .is_some_and(|metadata| metadata.call_chain.iter().any(|(caller, id)| caller == system && *id == actor)))
+    }
+
+    /// Returns the metadata with the given peer identity, or [`None`] if there is neither.
+    #[cfg(feature = "transport")]
+    pub(crate) fn with_peer(metadata: Option<Metadata>, peer: Option<&str>) -> Option<Metadata> {
+        match (metadata, peer) {
+            (metadata, None) => metadata,
+            (metadata, Some(peer)) => Some(Metadata { peer: Some(String::from(peer)), ..metadata.unwrap_or_default() }),
+        }
+    }
+
+    /// Runs the future with the given metadata if there is any, or with the current metadata otherwise.
+    pub(crate) fn scope_if<F: Future>(metadata: Option<Metadata>, future: F) -> Scoped<F> {
+        Scoped { metadata, future: Box::pin(future) }
+    }
+}
+
+/// # [`Scoped`]
+/// A future running with [`Metadata`], returned by [`Metadata::scope`].
+pub struct Scoped<F> {
+    /// The metadata, which is only taken while the future is being polled
+    metadata: Option<Metadata>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Scoped<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        // Without metadata of its own, the future runs with whatever metadata is already current
+        let Some(metadata) = this.metadata.take() else {
+            return this.future.as_mut().poll(cx);
+        };
+
+        // Install the metadata for the duration of the poll, and put back whatever was current before
+        let previous = CURRENT.with(|current| current.replace(Some(metadata)));
+        let result = this.future.as_mut().poll(cx);
+        this.metadata = CURRENT.with(|current| current.replace(previous));
+
+        result
+    }
+}
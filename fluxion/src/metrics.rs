@@ -0,0 +1,246 @@
+//! # Metrics
+//! A system built with a [`MetricsSink`] reports every message its actors handle, how long each took, how many messages each
+//! actor is handling at once, and every send that failed. Fluxion has no mailboxes, as messages are handled by the sending task,
+//! so the number of messages an actor is handling at once takes the place of its mailbox length.
+//!
+//! [`InMemoryMetrics`] keeps per-actor counters and latency histograms that can be read at any time.
+//! With the `metrics` feature, [`MetricsRecorder`] forwards everything to the `metrics` crate instead.
+//! Handling times are only measured if the system's [`crate::Timer`] can read a clock with [`crate::Timer::now`].
+//!
+//! A [`MailboxObserver`] is told when each message arrives at an actor and when the actor starts handling it, which
+//! allows building lag monitors. A message waits between the two while the actor is at its [`crate::Actor::MAX_CONCURRENCY`].
+
+use core::time::Duration;
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use maitake_sync::spin::Mutex;
+
+/// # [`MetricsSink`]
+/// Receives measurements from a system, set with [`crate::FluxionBuilder::metrics`].
+/// Every method does nothing by default, so sinks only need to implement what they record.
+pub trait MetricsSink: Send + Sync + 'static {
+    /// # [`MetricsSink::message_started`]
+    /// Called when an actor starts handling a message, with the number of messages it is now handling including this one.
+    fn message_started(&self, actor: u64, message: &'static str, in_flight: usize) {
+        let _ = (actor, message, in_flight);
+    }
+
+    /// # [`MetricsSink::message_handled`]
+    /// Called when an actor finishes handling a message, with how long it took if a clock is available,
+    /// and the number of messages it is still handling.
+    fn message_handled(&self, actor: u64, message: &'static str, elapsed: Option<Duration>, in_flight: usize) {
+        let _ = (actor, message, elapsed, in_flight);
+    }
+
+    /// # [`MetricsSink::send_failed`]
+    /// Called when a send to the given target fails or times out.
+    fn send_failed(&self, target: &str, message: &'static str) {
+        let _ = (target, message);
+    }
+
+    /// # [`MetricsSink::actor_stopped`]
+    /// Called when an actor is removed from the system, after which it will not be reported again.
+    fn actor_stopped(&self, actor: u64) {
+        let _ = actor;
+    }
+}
+
+/// # [`MailboxObserver`]
+/// Observes messages waiting for an actor to handle them, set with [`crate::FluxionBuilder::mailbox_observer`].
+/// Timestamps are read with [`crate::Timer::now`], and are [`None`] if the system's timer can't read a clock.
+/// Every method does nothing by default.
+pub trait MailboxObserver: Send + Sync + 'static {
+    /// # [`MailboxObserver::enqueued`]
+    /// Called when a message arrives at an actor, before it waits for the actor to be able to handle it.
+    fn enqueued(&self, actor: u64, message: &'static str, at: Option<Duration>) {
+        let _ = (actor, message, at);
+    }
+
+    /// # [`MailboxObserver::dequeued`]
+    /// Called when the actor starts handling a message, with how long the message waited if a clock is available.
+    fn dequeued(&self, actor: u64, message: &'static str, at: Option<Duration>, waited: Option<Duration>) {
+        let _ = (actor, message, at, waited);
+    }
+}
+
+/// # [`LATENCY_BUCKETS`]
+/// The upper bounds of the buckets of a [`Histogram`]. Durations above the last bound are counted in a final bucket.
+pub const LATENCY_BUCKETS: [Duration; 6] = [
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+];
+
+/// # [`Histogram`]
+/// Counts durations in the buckets given by [`LATENCY_BUCKETS`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// The count of each bucket, with the last counting durations above every bound
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl Histogram {
+    /// # [`Histogram::record`]
+    /// Counts a duration in its bucket.
+    pub fn record(&mut self, duration: Duration) {
+        let bucket = LATENCY_BUCKETS.iter().position(|bound| duration <= *bound).unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// # [`Histogram::count`]
+    /// Returns the number of durations recorded.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// # [`Histogram::buckets`]
+    /// Returns each bucket's upper bound and count, ending with the bucket of durations above every bound, whose bound is [`None`].
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BUCKETS.iter().map(|bound| Some(*bound)).chain([None]).zip(self.counts.iter().copied())
+    }
+}
+
+/// # [`ActorMetrics`]
+/// The measurements recorded for a single actor by [`InMemoryMetrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ActorMetrics {
+    /// The number of messages the actor has finished handling
+    pub processed: u64,
+    /// The number of messages the actor is currently handling
+    pub in_flight: usize,
+    /// The most messages the actor has handled at once
+    pub max_in_flight: usize,
+    /// The total time spent handling messages that were timed
+    pub processing_time: Duration,
+    /// How long each timed message took to handle
+    pub latency: Histogram,
+}
+
+/// Everything recorded by an [`InMemoryMetrics`].
+#[derive(Default)]
+struct Recorded {
+    /// The measurements of each running actor
+    actors: BTreeMap<u64, ActorMetrics>,
+    /// The number of messages handled, by message type
+    messages: BTreeMap<&'static str, u64>,
+    /// The number of failed sends, by target
+    failures: BTreeMap<String, u64>,
+}
+
+/// # [`InMemoryMetrics`]
+/// A [`MetricsSink`] that keeps its measurements in memory. Clones share the same measurements,
+/// so a clone can be kept to read them after the original is given to the system.
+#[derive(Clone, Default)]
+pub struct InMemoryMetrics {
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl InMemoryMetrics {
+    /// # [`InMemoryMetrics::new`]
+    /// Creates an empty set of measurements.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`InMemoryMetrics::actor`]
+    /// Returns the measurements of a running actor, if it has handled any messages.
+    #[must_use]
+    pub fn actor(&self, actor: u64) -> Option<ActorMetrics> {
+        self.recorded.lock().actors.get(&actor).cloned()
+    }
+
+    /// # [`InMemoryMetrics::actors`]
+    /// Returns the measurements of every running actor that has handled a message, ordered by id.
+    #[must_use]
+    pub fn actors(&self) -> Vec<(u64, ActorMetrics)> {
+        self.recorded.lock().actors.iter().map(|(id, metrics)| (*id, metrics.clone())).collect()
+    }
+
+    /// # [`InMemoryMetrics::handled`]
+    /// Returns how many messages of the given type have been handled by any actor,
+    /// where the type is given by [`core::any::type_name`].
+    #[must_use]
+    pub fn handled(&self, message: &str) -> u64 {
+        self.recorded.lock().messages.get(message).copied().unwrap_or(0)
+    }
+
+    /// # [`InMemoryMetrics::failures`]
+    /// Returns how many sends to the given target have failed.
+    /// Local actors are targeted by their id, and foreign actors by their [`crate::Identifier`]'s display form.
+    #[must_use]
+    pub fn failures(&self, target: &str) -> u64 {
+        self.recorded.lock().failures.get(target).copied().unwrap_or(0)
+    }
+}
+
+impl MetricsSink for InMemoryMetrics {
+    fn message_started(&self, actor: u64, _message: &'static str, in_flight: usize) {
+        let mut recorded = self.recorded.lock();
+        let metrics = recorded.actors.entry(actor).or_default();
+
+        metrics.in_flight = in_flight;
+        metrics.max_in_flight = metrics.max_in_flight.max(in_flight);
+    }
+
+    fn message_handled(&self, actor: u64, message: &'static str, elapsed: Option<Duration>, in_flight: usize) {
+        let mut recorded = self.recorded.lock();
+        *recorded.messages.entry(message).or_default() += 1;
+
+        let metrics = recorded.actors.entry(actor).or_default();
+        metrics.processed += 1;
+        metrics.in_flight = in_flight;
+
+        if let Some(elapsed) = elapsed {
+            metrics.processing_time += elapsed;
+            metrics.latency.record(elapsed);
+        }
+    }
+
+    fn send_failed(&self, target: &str, _message: &'static str) {
+        *self.recorded.lock().failures.entry(String::from(target)).or_default() += 1;
+    }
+
+    fn actor_stopped(&self, actor: u64) {
+        self.recorded.lock().actors.remove(&actor);
+    }
+}
+
+/// # [`MetricsRecorder`]
+/// A [`MetricsSink`] that reports to the `metrics` crate's global recorder, labelled by actor id and message type:
+/// - `fluxion_messages_handled_total`, a counter of handled messages
+/// - `fluxion_handler_duration_seconds`, a histogram of handling times
+/// - `fluxion_messages_in_flight`, a gauge of the messages each actor is handling
+/// - `fluxion_send_failures_total`, a counter of failed sends, labelled by target and message type
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsRecorder;
+
+#[cfg(feature = "metrics")]
+impl MetricsSink for MetricsRecorder {
+    #[allow(clippy::cast_precision_loss)]
+    fn message_started(&self, actor: u64, _message: &'static str, in_flight: usize) {
+        ::metrics::gauge!("fluxion_messages_in_flight", "actor" => alloc::format!("{actor}")).set(in_flight as f64);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn message_handled(&self, actor: u64, message: &'static str, elapsed: Option<Duration>, in_flight: usize) {
+        let actor = alloc::format!("{actor}");
+
+        ::metrics::counter!("fluxion_messages_handled_total", "actor" => actor.clone(), "message" => message).increment(1);
+        ::metrics::gauge!("fluxion_messages_in_flight", "actor" => actor.clone()).set(in_flight as f64);
+
+        if let Some(elapsed) = elapsed {
+            ::metrics::histogram!("fluxion_handler_duration_seconds", "actor" => actor, "message" => message).record(elapsed.as_secs_f64());
+        }
+    }
+
+    fn send_failed(&self, target: &str, message: &'static str) {
+        ::metrics::counter!("fluxion_send_failures_total", "target" => String::from(target), "message" => message).increment(1);
+    }
+}
@@ -0,0 +1,119 @@
+//! # Middleware
+//! [`Middleware`] runs around every message sent to a local actor, for example to log messages, measure them, check that
+//! their sender is allowed to send them, or add to their [`crate::Metadata`]. Middleware added with
+//! [`crate::FluxionBuilder::middleware`] runs around every actor's messages, and middleware added with
+//! [`crate::Fluxion::add_with_middleware`] runs around a single actor's messages.
+//!
+//! The system's middleware runs before the actor's, and the `after` hooks run in the reverse order of the `before` hooks.
+//! Messages are handled by the sending task, so middleware runs on the sender's task too.
+
+use core::{any::Any, future::Future};
+
+use alloc::sync::Arc;
+
+use crate::{Message, MessageSendError};
+
+/// # [`Middleware`]
+/// Hooks that run before and after a message is handled. Both do nothing by default.
+pub trait Middleware: Send + Sync + 'static {
+    /// # [`Middleware::before`]
+    /// Called before the message is handled, with the message, which can be downcast to its type.
+    ///
+    /// # Errors
+    /// Returning an error rejects the message, which is returned to the sender without running any further middleware.
+    fn before(&self, dispatch: &mut Dispatch, message: &dyn Any) -> Result<(), MessageSendError> {
+        let _ = (dispatch, message);
+        Ok(())
+    }
+
+    /// # [`Middleware::after`]
+    /// Called after the message was handled, with its result, which can be downcast to the message's result type.
+    /// This is not called for messages that were rejected, or whose sender stopped waiting on them.
+    fn after(&self, dispatch: &Dispatch, result: &dyn Any) {
+        let _ = (dispatch, result);
+    }
+}
+
+/// # [`Dispatch`]
+/// Describes a message passing through [`Middleware`].
+#[derive(Debug)]
+pub struct Dispatch {
+    /// The id of the actor the message was sent to
+    actor: u64,
+    /// The type name of the message
+    message_type: &'static str,
+    /// The metadata the message is handled with
+    #[cfg(feature = "std")]
+    metadata: Option<crate::Metadata>,
+}
+
+impl Dispatch {
+    /// Describes a message of type `M` being sent to the given actor, with the current metadata.
+    pub(crate) fn new<M>(actor: u64) -> Self {
+        Self {
+            actor,
+            message_type: core::any::type_name::<M>(),
+            #[cfg(feature = "std")]
+            metadata: crate::Metadata::current(),
+        }
+    }
+
+    /// # [`Dispatch::actor`]
+    /// Returns the id of the actor the message was sent to.
+    #[must_use]
+    pub fn actor(&self) -> u64 {
+        self.actor
+    }
+
+    /// # [`Dispatch::message_type`]
+    /// Returns the type name of the message.
+    #[must_use]
+    pub fn message_type(&self) -> &'static str {
+        self.message_type
+    }
+
+    /// # [`Dispatch::metadata`]
+    /// Returns the [`crate::Metadata`] the message is handled with, if it has any.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn metadata(&self) -> Option<&crate::Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// # [`Dispatch::metadata_mut`]
+    /// Returns the [`crate::Metadata`] the message is handled with, which `before` hooks can change.
+    /// Changes made by `after` hooks have no effect.
+    #[cfg(feature = "std")]
+    pub fn metadata_mut(&mut self) -> &mut Option<crate::Metadata> {
+        &mut self.metadata
+    }
+}
+
+/// A list of middleware, in the order their `before` hooks run.
+pub(crate) type Layers = Arc<[Arc<dyn Middleware>]>;
+
+/// Sends a message through the system's middleware and the actor's middleware.
+pub(crate) async fn intercept<M: Message, F: Future<Output = M::Result>>(system: &[Arc<dyn Middleware>], actor: &[Arc<dyn Middleware>], id: u64, message: M, send: impl FnOnce(M) -> F) -> Result<M::Result, MessageSendError> {
+    // Most actors have no middleware, so avoid any overhead for them
+    if system.is_empty() && actor.is_empty() {
+        return Ok(send(message).await);
+    }
+
+    let mut dispatch = Dispatch::new::<M>(id);
+
+    for layer in system.iter().chain(actor) {
+        layer.before(&mut dispatch, &message)?;
+    }
+
+    // Handle the message with whatever metadata the middleware left
+    #[cfg(feature = "std")]
+    let result = crate::Metadata::scope_if(dispatch.metadata.clone(), send(message)).await;
+    #[cfg(not(feature = "std"))]
+    let result = send(message).await;
+
+    for layer in system.iter().chain(actor).rev() {
+        layer.after(&dispatch, &result);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,204 @@
+//! # Local Networks
+//! A [`LocalNetwork`] connects several [`Fluxion`] systems in the same process, so that foreign messages can be sent
+//! between them without sockets or a transport. Each system's [`LocalDelegate`] resolves foreign actors by looking them
+//! up on the target system directly, and messages are handled just like local ones, on the sending task.
+//!
+//! Messages are passed between systems as they are. With the `transport` feature, [`LocalNetwork::serialize`] makes
+//! every message and result round-trip through bincode instead, catching types that would fail to cross a real network.
+
+use core::marker::PhantomData;
+#[cfg(feature = "transport")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{collections::BTreeMap, string::{String, ToString}, sync::{Arc, Weak}, vec::Vec};
+use maitake_sync::spin::Mutex;
+
+use crate::{Actor, Delegate, Fluxion, FluxionBuilder, Handler, Identifier, IndeterminateMessage, LocalRef, Message, MessageSendError, MessageSender};
+
+/// The state shared by a [`LocalNetwork`] and its systems' delegates.
+#[derive(Default)]
+struct NetworkState {
+    /// The systems on the network, keyed by their id
+    systems: Mutex<BTreeMap<String, Fluxion<LocalDelegate>>>,
+    /// Whether messages and results round-trip through bincode
+    #[cfg(feature = "transport")]
+    serialize: AtomicBool,
+}
+
+/// # [`LocalNetwork`]
+/// Systems in the same process that can send each other foreign messages, as described in the [module documentation](self).
+/// Clones refer to the same network.
+#[derive(Clone, Default)]
+pub struct LocalNetwork(Arc<NetworkState>);
+
+impl LocalNetwork {
+    /// # [`LocalNetwork::new`]
+    /// Creates a network with no systems.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`LocalNetwork::with_systems`]
+    /// Creates a network with a system for each id, which are returned in the same order.
+    #[must_use]
+    pub fn with_systems<'a>(ids: impl IntoIterator<Item = &'a str>) -> (Self, Vec<Fluxion<LocalDelegate>>) {
+        let network = Self::new();
+        let systems = ids.into_iter().map(|id| network.system(id)).collect();
+
+        (network, systems)
+    }
+
+    /// # [`LocalNetwork::system`]
+    /// Adds a system with the given id to the network, replacing any system that already had the id.
+    #[must_use]
+    pub fn system(&self, id: &str) -> Fluxion<LocalDelegate> {
+        self.system_with(id, |builder| builder)
+    }
+
+    /// # [`LocalNetwork::system_with`]
+    /// Adds a system with the given id to the network, configured by the function, for example to give it a timer.
+    #[must_use]
+    pub fn system_with(&self, id: &str, configure: impl FnOnce(FluxionBuilder<LocalDelegate>) -> FluxionBuilder<LocalDelegate>) -> Fluxion<LocalDelegate> {
+        let delegate = LocalDelegate { network: Arc::downgrade(&self.0) };
+        let system = configure(Fluxion::builder(id, delegate)).build();
+
+        self.0.systems.lock().insert(id.to_string(), system.clone());
+        system
+    }
+
+    /// # [`LocalNetwork::get`]
+    /// Returns the system on the network with the given id.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<Fluxion<LocalDelegate>> {
+        self.0.systems.lock().get(id).cloned()
+    }
+
+    /// # [`LocalNetwork::remove`]
+    /// Removes a system from the network, so that other systems can no longer find its actors.
+    /// Senders that were already retrieved keep working.
+    pub fn remove(&self, id: &str) -> Option<Fluxion<LocalDelegate>> {
+        self.0.systems.lock().remove(id)
+    }
+
+    /// # [`LocalNetwork::serialize`]
+    /// Sets whether every message and result sent over the network round-trips through bincode.
+    /// This only affects senders retrieved afterwards.
+    #[cfg(feature = "transport")]
+    pub fn serialize(&self, serialize: bool) {
+        self.0.serialize.store(serialize, Ordering::Relaxed);
+    }
+}
+
+/// # [`LocalDelegate`]
+/// The [`Delegate`] of each system on a [`LocalNetwork`].
+pub struct LocalDelegate {
+    /// The network the system is on
+    network: Weak<NetworkState>,
+}
+
+impl LocalDelegate {
+    /// Returns a reference to the actor on another system on the network, if it exists.
+    async fn find<A: Handler<M>, M: Message>(&self, id: Identifier<'_>) -> Option<LocalRef<A, LocalDelegate>> {
+        let network = self.network.upgrade()?;
+        let remote = |system: &str| network.systems.lock().get(system).cloned();
+
+        let (remote, id) = match id {
+            Identifier::Foreign(id, system) => (remote(system)?, id),
+            Identifier::ForeignNamed(name, system) => {
+                let remote = remote(system)?;
+                let id = remote.get_actor_id(name).await?;
+                (remote, id)
+            },
+            Identifier::Local(_) | Identifier::LocalNamed(_) => return None,
+        };
+
+        remote.get_local::<A>(id).await
+    }
+}
+
+impl Delegate for LocalDelegate {
+    #[cfg(not(feature = "serde"))]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> {
+        let actor = self.find::<A, M>(id).await?;
+        Some(Arc::new(LocalSender { actor, _message: PhantomData }))
+    }
+
+    #[cfg(all(feature = "serde", not(feature = "transport")))]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        let actor = self.find::<A, M>(id).await?;
+        Some(Arc::new(LocalSender { actor, _message: PhantomData }))
+    }
+
+    #[cfg(feature = "transport")]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        let actor = self.find::<A, M>(id).await?;
+
+        let serialize = self.network.upgrade().is_some_and(|network| network.serialize.load(Ordering::Relaxed));
+        if serialize {
+            return Some(Arc::new(SerializingSender { actor, _message: PhantomData }));
+        }
+
+        Some(Arc::new(LocalSender { actor, _message: PhantomData }))
+    }
+}
+
+/// Sends messages to an actor on another system on a [`LocalNetwork`] as they are.
+struct LocalSender<A: Actor, M> {
+    /// The actor on the other system
+    actor: LocalRef<A, LocalDelegate>,
+    /// The type of message sent
+    _message: PhantomData<fn(M)>,
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: Message> MessageSender<M> for LocalSender<A, M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.actor.send(message).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.actor.tell(message).await
+    }
+}
+
+/// Sends messages to an actor on another system on a [`LocalNetwork`], round-tripping them and their results through bincode.
+#[cfg(feature = "transport")]
+struct SerializingSender<A: Actor, M> {
+    /// The actor on the other system
+    actor: LocalRef<A, LocalDelegate>,
+    /// The type of message sent
+    _message: PhantomData<fn(M)>,
+}
+
+#[cfg(feature = "transport")]
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: IndeterminateMessage> MessageSender<M> for SerializingSender<A, M>
+where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        let response = self.actor.send(round_trip(&message)?).await?;
+        round_trip(&response)
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.actor.tell(round_trip(&message)?).await
+    }
+}
+
+/// Encodes a value with bincode and decodes it again, as if it had been sent to another process.
+#[cfg(feature = "transport")]
+fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> Result<T, MessageSendError> {
+    use crate::transport::serialize::{BincodeSerializer, MessageSerializer};
+
+    let bytes = BincodeSerializer::serialize(value).map_err(|e| MessageSendError::SerializationError {
+        message: e.to_string(),
+        source: alloc::boxed::Box::new(e),
+    })?;
+
+    BincodeSerializer::deserialize(&bytes).map_err(|e| MessageSendError::DeserializationError {
+        message: e.to_string(),
+        source: alloc::boxed::Box::new(e),
+    })
+}
@@ -0,0 +1,68 @@
+//! # Owned Actors
+//! Actors started with [`crate::Spawn::start_owned`] are owned by their references. Every [`crate::LocalRef`] to such an
+//! actor shares its ownership, as does the system for as long as the actor is named. Once the last of them is dropped,
+//! nothing can reach the actor anymore, so it is killed on the system's [`crate::Executor`] and [`crate::Actor::deinitialize`]
+//! is called. This suits short-lived actors, such as one per request or per connection, that would otherwise have to be
+//! killed by hand.
+//!
+//! Owned actors can still be killed or drained explicitly, and can be retrieved like any other actor while they are alive,
+//! in which case the retrieved reference shares their ownership too.
+
+use core::{future::Future, pin::Pin};
+
+use alloc::boxed::Box;
+
+use crate::{Delegate, Fluxion};
+
+/// Kills the owned actor with the given id if nothing owns it anymore.
+type ReclaimFn<D> = fn(Fluxion<D>, u64) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Kills an owned actor when the last reference to it is dropped.
+pub(crate) struct Owner<D> {
+    /// The system the actor runs on
+    system: Fluxion<D>,
+    /// The id of the actor
+    id: u64,
+    /// Kills the actor once it has been dropped
+    reclaim: ReclaimFn<D>,
+}
+
+impl<D: Delegate> Owner<D> {
+    /// Creates the owner of the actor with the given id.
+    pub(crate) fn new(system: Fluxion<D>, id: u64) -> Self {
+        Self { system, id, reclaim: |system, id| Box::pin(async move { system.reclaim(id).await }) }
+    }
+}
+
+impl<D> Drop for Owner<D> {
+    fn drop(&mut self) {
+        // Systems without an executor can't spawn owned actors, so there is always one here
+        let Some(executor) = &self.system.executor else {
+            return;
+        };
+
+        executor.spawn((self.reclaim)(self.system.clone(), self.id));
+    }
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// Kills the owned actor with the given id if nothing owns it anymore.
+    /// The id may have been reused since the owner was dropped, in which case the new actor is left alone,
+    /// as it is either not owned or still has owners of its own.
+    async fn reclaim(&self, id: u64) {
+        let orphaned = self.registry.get(id, |registered| registered.owner.as_ref().is_some_and(|owner| owner.strong_count() == 0))
+            .unwrap_or(false);
+
+        if orphaned {
+            self.kill_any(id).await;
+        }
+    }
+
+    /// Stops the system from owning the actor with the given id, after it was started or lost its name.
+    /// The actor is killed if no references to it remain.
+    pub(crate) fn release(&self, id: u64) {
+        // Take the owner out so that it is dropped after the lock is released
+        let retained = self.registry.update(id, |registered| registered.retained.take()).flatten();
+        drop(retained);
+    }
+}
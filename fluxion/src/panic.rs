@@ -0,0 +1,77 @@
+//! # Panic Isolation
+//! A handler that panics would otherwise unwind through the task that sent it the message, taking down whatever else
+//! that task was doing. With the `std` feature, panics in local handlers are caught instead. The send fails with
+//! [`MessageSendError::Panicked`], lifecycle subscribers are notified with [`LifecycleEvent::ActorFailed`], and the
+//! actor is kept or stopped according to its [`PanicPolicy`], which is the system's unless it was spawned with its own.
+//!
+//! The panic is still reported by the process's panic hook, and panics can only be caught if the binary unwinds on panic.
+
+use core::{any::Any, future::Future, panic::AssertUnwindSafe, task::Poll};
+
+use alloc::{boxed::Box, string::String};
+
+use crate::{Actor, ActorFailure, Delegate, Fluxion, LifecycleEvent, Message, MessageSendError};
+
+/// # [`PanicPolicy`]
+/// Decides what happens to an actor after one of its handlers panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Keep the actor, so that it goes on handling messages. This is the default.
+    /// Any state the handler was changing when it panicked is left as it was.
+    #[default]
+    Resume,
+    /// Remove the actor from the system, as if it had been killed.
+    Stop,
+}
+
+/// Polls a future, catching any panic raised while polling it.
+async fn catch_unwind<F: Future>(future: F) -> Result<F::Output, Box<dyn Any + Send>> {
+    let mut future = core::pin::pin!(future);
+
+    core::future::poll_fn(|cx| match std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+        Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+        Ok(Poll::Pending) => Poll::Pending,
+        Err(panic) => Poll::Ready(Err(panic)),
+    }).await
+}
+
+/// Returns the message a panic was raised with, if it was raised with a string.
+fn describe(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        return String::from(*message);
+    }
+
+    panic.downcast_ref::<String>().cloned().unwrap_or_else(|| String::from("the panic did not have a message"))
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// Handles a message sent to a local actor, catching any panic in its handler and applying the system's [`PanicPolicy`].
+    pub(crate) async fn isolate<A: Actor, M: Message>(&self, id: u64, handle: impl Future<Output = Result<M::Result, MessageSendError>>) -> Result<M::Result, MessageSendError> {
+        let panic = match catch_unwind(handle).await {
+            Ok(result) => return result,
+            Err(panic) => describe(panic.as_ref()),
+        };
+
+        let name = self.actor_ids.read().await.iter()
+            .find(|(_, actor)| **actor == id)
+            .map(|(name, _)| name.clone());
+
+        self.lifecycle.publish(&LifecycleEvent::ActorFailed {
+            id: Some(id),
+            name,
+            actor: core::any::type_name::<A>(),
+            error: ActorFailure::Panicked { message: core::any::type_name::<M>(), panic: panic.clone() },
+        });
+
+        // The actor's own policy overrides the system's
+        let policy = self.registry.get(id, |registered| registered.panic_policy)
+            .flatten()
+            .unwrap_or(self.panic_policy);
+
+        if policy == PanicPolicy::Stop {
+            self.kill::<A>(id).await;
+        }
+
+        Err(MessageSendError::Panicked(panic))
+    }
+}
@@ -0,0 +1,627 @@
+//! # Persistence
+//! Event sourced actors keep their state as a sequence of events, which are written to an [`EventStore`] before being applied.
+//! When the actor is added to a system again, for example after a restart, the events are replayed to rebuild its state
+//! before it handles any new messages.
+//!
+//! An actor opts in by implementing [`PersistentActor`], which gives it [`PersistentActor::persist`], and by owning a [`Journal`]
+//! that names the actor's events within the store. It is then added to the system wrapped in [`Persistent`], which replays
+//! the journal when the actor is initialized.
+//!
+//! Replaying every event can become slow for long-lived actors, so a journal may also take snapshots of the actor's state
+//! according to a [`SnapshotPolicy`], with [`Journal::with_snapshots`]. Recovery then starts from the latest snapshot in the
+//! [`SnapshotStore`], and only replays the events persisted after it.
+//!
+//! Messages that should only be sent once an event is persisted are added to an [`Outbox`], and sent by
+//! [`PersistentActor::persist_with`] after the event is written.
+//!
+//! With the `encryption` feature, stores of raw bytes can be wrapped in [`Encrypted`] so that events and snapshots are
+//! encrypted at rest.
+//!
+//! ```ignore
+//! impl PersistentActor for Counter {
+//!     type Event = i64;
+//!     type Snapshot = ();
+//!
+//!     fn journal(&self) -> &Journal<i64> {
+//!         &self.journal
+//!     }
+//!
+//!     fn recover(&self, event: &i64) {
+//!         self.count.fetch_add(*event, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! impl Handler<Add> for Counter {
+//!     async fn handle_message<D: Delegate>(&self, message: Add, _context: &ActorContext<D>) -> Result<(), JournalError> {
+//!         self.persist(message.0).await
+//!     }
+//! }
+//!
+//! let id = system.add(Persistent(Counter::new(Journal::new(store, "counter")))).await?;
+//! ```
+
+use core::{future::Future, sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use maitake_sync::{spin, Mutex};
+
+use crate::{Actor, ActorContext, Delegate, Fluxion, Handler, Message, MessageSendError, ScheduleHandle};
+
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "encryption")]
+pub use encryption::*;
+
+mod outbox;
+pub use outbox::*;
+
+/// # [`JournalError`]
+/// An error that might be returned when reading or writing events.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JournalError {
+    /// An event was written with a sequence number other than the next one in the journal,
+    /// usually because two actors share a persistence id.
+    SequenceConflict {
+        /// The sequence number the store expected
+        expected: u64,
+        /// The sequence number the event was written with
+        found: u64,
+    },
+    /// The store failed to read or write events.
+    Store(Box<dyn core::error::Error + Send + Sync>),
+    /// An event or snapshot could not be encrypted or decrypted, for example because its key was retired
+    /// or its contents were tampered with.
+    Encryption {
+        /// The sequence number of the event, or of the last event before the snapshot
+        sequence: u64,
+        /// Why encryption or decryption failed
+        reason: String,
+    },
+}
+
+impl core::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            JournalError::SequenceConflict { expected, found } => write!(f, "JournalError: expected sequence number {expected}, found {found}"),
+            JournalError::Store(e) => write!(f, "JournalError: the event store failed: {e}"),
+            JournalError::Encryption { sequence, reason } => write!(f, "JournalError: could not encrypt or decrypt sequence number {sequence}: {reason}"),
+        }
+    }
+}
+
+impl core::error::Error for JournalError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            JournalError::SequenceConflict { .. } | JournalError::Encryption { .. } => None,
+            JournalError::Store(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+/// # [`EventStore`]
+/// Stores the events of every persistent actor, keyed by persistence id.
+/// Events are numbered from 1 within each persistence id.
+#[async_trait::async_trait]
+pub trait EventStore<E>: Send + Sync + 'static {
+    /// # [`EventStore::append`]
+    /// Appends an event with the given sequence number.
+    ///
+    /// # Errors
+    /// Returns [`JournalError::SequenceConflict`] if `sequence` is not one more than the last stored sequence number,
+    /// or [`JournalError::Store`] if the event could not be written.
+    async fn append(&self, persistence_id: &str, sequence: u64, event: &E) -> Result<(), JournalError>;
+
+    /// # [`EventStore::read`]
+    /// Returns every event with a sequence number of at least `from`, in order, along with its sequence number.
+    ///
+    /// # Errors
+    /// Returns [`JournalError::Store`] if the events could not be read.
+    async fn read(&self, persistence_id: &str, from: u64) -> Result<Vec<(u64, E)>, JournalError>;
+}
+
+/// # [`InMemoryEventStore`]
+/// An [`EventStore`] that keeps events in memory. Events survive actors being killed and re-added,
+/// but not the process exiting, which makes it best suited to tests and caches.
+pub struct InMemoryEventStore<E> {
+    /// The events of each persistence id, where the event at index `i` has sequence number `i + 1`
+    events: spin::Mutex<BTreeMap<String, Vec<E>>>,
+}
+
+impl<E> Default for InMemoryEventStore<E> {
+    fn default() -> Self {
+        Self { events: spin::Mutex::new(BTreeMap::new()) }
+    }
+}
+
+impl<E> InMemoryEventStore<E> {
+    /// # [`InMemoryEventStore::new`]
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl<E: Clone + Send + Sync + 'static> EventStore<E> for InMemoryEventStore<E> {
+    async fn append(&self, persistence_id: &str, sequence: u64, event: &E) -> Result<(), JournalError> {
+        let mut events = self.events.lock();
+        let journal = events.entry(String::from(persistence_id)).or_default();
+
+        let expected = journal.len() as u64 + 1;
+        if sequence != expected {
+            return Err(JournalError::SequenceConflict { expected, found: sequence });
+        }
+
+        journal.push(event.clone());
+        Ok(())
+    }
+
+    async fn read(&self, persistence_id: &str, from: u64) -> Result<Vec<(u64, E)>, JournalError> {
+        let events = self.events.lock();
+        let Some(journal) = events.get(persistence_id) else {
+            return Ok(Vec::new());
+        };
+
+        Ok((1..).zip(journal.iter().cloned()).skip_while(|(sequence, _)| *sequence < from).collect())
+    }
+}
+
+/// # [`SnapshotStore`]
+/// Stores the latest snapshot of every persistent actor's state, keyed by persistence id.
+/// A snapshot lets an actor recover without replaying the events that came before it.
+#[async_trait::async_trait]
+pub trait SnapshotStore<S>: Send + Sync + 'static {
+    /// # [`SnapshotStore::save`]
+    /// Saves a snapshot of the state after the event with the given sequence number, replacing any previous snapshot.
+    ///
+    /// # Errors
+    /// Returns [`JournalError::Store`] if the snapshot could not be written.
+    async fn save(&self, persistence_id: &str, sequence: u64, snapshot: &S) -> Result<(), JournalError>;
+
+    /// # [`SnapshotStore::load`]
+    /// Returns the latest snapshot along with the sequence number it was taken at, if there is one.
+    ///
+    /// # Errors
+    /// Returns [`JournalError::Store`] if the snapshot could not be read.
+    async fn load(&self, persistence_id: &str) -> Result<Option<(u64, S)>, JournalError>;
+}
+
+/// # [`InMemorySnapshotStore`]
+/// A [`SnapshotStore`] that keeps snapshots in memory.
+pub struct InMemorySnapshotStore<S> {
+    /// The latest snapshot of each persistence id, along with its sequence number
+    snapshots: spin::Mutex<BTreeMap<String, (u64, S)>>,
+}
+
+impl<S> Default for InMemorySnapshotStore<S> {
+    fn default() -> Self {
+        Self { snapshots: spin::Mutex::new(BTreeMap::new()) }
+    }
+}
+
+impl<S> InMemorySnapshotStore<S> {
+    /// # [`InMemorySnapshotStore::new`]
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Clone + Send + Sync + 'static> SnapshotStore<S> for InMemorySnapshotStore<S> {
+    async fn save(&self, persistence_id: &str, sequence: u64, snapshot: &S) -> Result<(), JournalError> {
+        self.snapshots.lock().insert(String::from(persistence_id), (sequence, snapshot.clone()));
+        Ok(())
+    }
+
+    async fn load(&self, persistence_id: &str) -> Result<Option<(u64, S)>, JournalError> {
+        Ok(self.snapshots.lock().get(persistence_id).cloned())
+    }
+}
+
+/// # [`FileSnapshotStore`]
+/// A [`SnapshotStore`] that keeps each actor's latest snapshot in a file in a directory.
+/// Snapshots are stored as raw bytes, so actors using this store encode their state in [`PersistentActor::snapshot`]
+/// with whatever format they already use.
+/// <div class = "info">
+/// Files are read and written with blocking calls.
+/// </div>
+#[cfg(feature = "std")]
+pub struct FileSnapshotStore {
+    /// The directory snapshots are written to
+    directory: std::path::PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FileSnapshotStore {
+    /// # [`FileSnapshotStore::new`]
+    /// Creates a store that writes snapshots to the given directory, creating it if needed.
+    ///
+    /// # Errors
+    /// Returns an error if the directory could not be created.
+    pub fn new(directory: impl Into<std::path::PathBuf>) -> std::io::Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Self { directory })
+    }
+
+    /// Returns the path of the snapshot file for the given persistence id.
+    /// The id is hex encoded, as it may contain characters that are not allowed in file names.
+    fn path(&self, persistence_id: &str) -> std::path::PathBuf {
+        use core::fmt::Write;
+
+        let mut name = String::with_capacity(persistence_id.len() * 2 + 9);
+        for byte in persistence_id.bytes() {
+            let _ = write!(name, "{byte:02x}");
+        }
+        name.push_str(".snapshot");
+
+        self.directory.join(name)
+    }
+}
+
+#[cfg(feature = "std")]
+#[async_trait::async_trait]
+impl SnapshotStore<Vec<u8>> for FileSnapshotStore {
+    async fn save(&self, persistence_id: &str, sequence: u64, snapshot: &Vec<u8>) -> Result<(), JournalError> {
+        let path = self.path(persistence_id);
+        let partial = path.with_extension("partial");
+
+        // The snapshot is written next to the old one and then moved over it, so that a crash never leaves a torn snapshot
+        let mut contents = Vec::with_capacity(snapshot.len() + 8);
+        contents.extend_from_slice(&sequence.to_le_bytes());
+        contents.extend_from_slice(snapshot);
+
+        std::fs::write(&partial, contents).map_err(|e| JournalError::Store(Box::new(e)))?;
+        std::fs::rename(&partial, &path).map_err(|e| JournalError::Store(Box::new(e)))
+    }
+
+    async fn load(&self, persistence_id: &str) -> Result<Option<(u64, Vec<u8>)>, JournalError> {
+        let mut contents = match std::fs::read(self.path(persistence_id)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(JournalError::Store(Box::new(e))),
+        };
+
+        let Some(sequence) = contents.get(..8) else {
+            return Err(JournalError::Store(Box::new(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))));
+        };
+        let sequence = u64::from_le_bytes(sequence.try_into().unwrap_or_default());
+
+        Ok(Some((sequence, contents.split_off(8))))
+    }
+}
+
+/// # [`SnapshotPolicy`]
+/// Decides when a [`Journal`] takes snapshots. Snapshots are only ever taken after an event is persisted,
+/// and a policy with neither trigger set never takes any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    /// Take a snapshot once this many events have been persisted since the last one
+    every: Option<u64>,
+    /// Take a snapshot on the first event persisted after each interval elapses
+    interval: Option<Duration>,
+}
+
+impl SnapshotPolicy {
+    /// # [`SnapshotPolicy::new`]
+    /// Creates a policy that never takes snapshots.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`SnapshotPolicy::every`]
+    /// Takes a snapshot once the given number of events have been persisted since the last snapshot.
+    #[must_use]
+    pub fn every(mut self, events: u64) -> Self {
+        self.every = Some(events.max(1));
+        self
+    }
+
+    /// # [`SnapshotPolicy::interval`]
+    /// Takes a snapshot on the first event persisted after each period elapses.
+    /// This requires the system to have been built with a [`crate::Timer`] and an [`crate::Executor`],
+    /// and is ignored otherwise.
+    #[must_use]
+    pub fn interval(mut self, period: Duration) -> Self {
+        self.interval = Some(period);
+        self
+    }
+}
+
+/// Where a journal's snapshots are kept, and when they are taken.
+struct Snapshots<S> {
+    /// The store snapshots are written to
+    store: Arc<dyn SnapshotStore<S>>,
+    /// When snapshots are taken
+    policy: SnapshotPolicy,
+    /// Set each time the policy's interval elapses, and cleared when a snapshot is taken
+    due: Arc<AtomicBool>,
+    /// The task that sets `due`, once it has been started
+    ticker: spin::Mutex<Option<ScheduleHandle>>,
+}
+
+impl<S> Snapshots<S> {
+    /// Returns true if a snapshot should be taken, given the number of events persisted since the last one.
+    fn due(&self, since: u64) -> bool {
+        self.policy.every.is_some_and(|every| since >= every) || self.due.load(Ordering::Relaxed)
+    }
+}
+
+/// The position of a journal within its actor's events.
+#[derive(Default)]
+struct Cursor {
+    /// The sequence number of the last event written or replayed
+    sequence: u64,
+    /// The number of events written or replayed since the last snapshot
+    since_snapshot: u64,
+}
+
+/// # [`Journal`]
+/// A persistent actor's view of an [`EventStore`], and optionally a [`SnapshotStore`],
+/// which tracks the sequence number of the actor's last event.
+pub struct Journal<E, S = ()> {
+    /// The store events are read from and written to
+    store: Arc<dyn EventStore<E>>,
+    /// The id the actor's events are stored under
+    persistence_id: String,
+    /// The journal's position. This is locked for the whole of a write, so that concurrent writes are given
+    /// consecutive sequence numbers, and are applied and snapshotted in that order.
+    cursor: Mutex<Cursor>,
+    /// Where snapshots are kept, if the journal takes them
+    snapshots: Option<Snapshots<S>>,
+}
+
+impl<E: 'static, S: 'static> Journal<E, S> {
+    /// # [`Journal::new`]
+    /// Creates a journal for the events stored under the given persistence id.
+    /// The journal starts empty, and is filled in when the actor is recovered.
+    pub fn new(store: Arc<dyn EventStore<E>>, persistence_id: &str) -> Self {
+        Self {
+            store,
+            persistence_id: String::from(persistence_id),
+            cursor: Mutex::new(Cursor::default()),
+            snapshots: None,
+        }
+    }
+
+    /// # [`Journal::with_snapshots`]
+    /// Takes snapshots of the actor's state according to the given policy, and recovers from the latest snapshot
+    /// rather than replaying every event.
+    #[must_use]
+    pub fn with_snapshots(mut self, store: Arc<dyn SnapshotStore<S>>, policy: SnapshotPolicy) -> Self {
+        self.snapshots = Some(Snapshots {
+            store,
+            policy,
+            due: Arc::new(AtomicBool::new(false)),
+            ticker: spin::Mutex::new(None),
+        });
+        self
+    }
+
+    /// # [`Journal::persistence_id`]
+    /// Returns the id the actor's events are stored under.
+    #[must_use]
+    pub fn persistence_id(&self) -> &str {
+        &self.persistence_id
+    }
+
+    /// # [`Journal::sequence`]
+    /// Returns the sequence number of the last event written or replayed, waiting for any in-progress write to finish.
+    pub async fn sequence(&self) -> u64 {
+        self.cursor.lock().await.sequence
+    }
+
+    /// Writes an event to the store and applies it, then takes a snapshot if one is due.
+    /// A snapshot that fails to save is retried after the next event.
+    pub(crate) async fn write(&self, event: &E, apply: impl FnOnce(&E), snapshot: impl FnOnce() -> Option<S>) -> Result<u64, JournalError> {
+        let mut cursor = self.cursor.lock().await;
+        let sequence = cursor.sequence + 1;
+
+        self.store.append(&self.persistence_id, sequence, event).await?;
+        cursor.sequence = sequence;
+        cursor.since_snapshot += 1;
+
+        apply(event);
+
+        if let Some(snapshots) = &self.snapshots
+            && snapshots.due(cursor.since_snapshot)
+            && let Some(snapshot) = snapshot()
+            && snapshots.store.save(&self.persistence_id, sequence, &snapshot).await.is_ok() {
+            cursor.since_snapshot = 0;
+            snapshots.due.store(false, Ordering::Relaxed);
+        }
+
+        Ok(sequence)
+    }
+
+    /// Restores the latest snapshot, if it is newer than the journal's position, and then applies every event after it in order.
+    pub(crate) async fn recover(&self, restore: impl FnOnce(S), mut apply: impl FnMut(E)) -> Result<(), JournalError> {
+        let mut cursor = self.cursor.lock().await;
+
+        if let Some(snapshots) = &self.snapshots
+            && let Some((sequence, snapshot)) = snapshots.store.load(&self.persistence_id).await?
+            && sequence > cursor.sequence {
+            restore(snapshot);
+            cursor.sequence = sequence;
+            cursor.since_snapshot = 0;
+        }
+
+        for (sequence, event) in self.store.read(&self.persistence_id, cursor.sequence + 1).await? {
+            apply(event);
+            cursor.sequence = sequence;
+            cursor.since_snapshot += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Starts marking snapshots as due each time the policy's interval elapses, if it has one and has not been started yet.
+    pub(crate) fn start_interval<D: Delegate>(&self, system: &Fluxion<D>) {
+        let Some(snapshots) = &self.snapshots else {
+            return;
+        };
+        let Some(interval) = snapshots.policy.interval else {
+            return;
+        };
+
+        let mut ticker = snapshots.ticker.lock();
+        if ticker.is_some() {
+            return;
+        }
+
+        let due = Arc::downgrade(&snapshots.due);
+        let started = crate::scheduler::schedule(system.timer.as_ref(), system.executor.as_ref(), interval, move || {
+            due.upgrade()?.store(true, Ordering::Relaxed);
+            Some(Box::pin(async {}))
+        });
+
+        *ticker = started.ok();
+    }
+
+    /// Stops marking snapshots as due.
+    pub(crate) fn stop_interval(&self) {
+        if let Some(ticker) = self.snapshots.as_ref().and_then(|snapshots| snapshots.ticker.lock().take()) {
+            ticker.cancel();
+        }
+    }
+}
+
+/// # [`PersistentActor`]
+/// An actor whose state is rebuilt from the events in its [`Journal`], and optionally from a snapshot.
+/// It must be added to the system wrapped in [`Persistent`] for its state to be recovered.
+pub trait PersistentActor: Actor {
+    /// # [`PersistentActor::Event`]
+    /// The type of the events that make up the actor's state.
+    type Event: Send + Sync + 'static;
+
+    /// # [`PersistentActor::Snapshot`]
+    /// The type of a snapshot of the actor's state. Actors that don't take snapshots should use `()`.
+    type Snapshot: Send + Sync + 'static;
+
+    /// # [`PersistentActor::journal`]
+    /// Returns the journal the actor's events are written to.
+    fn journal(&self) -> &Journal<Self::Event, Self::Snapshot>;
+
+    /// # [`PersistentActor::recover`]
+    /// Applies an event to the actor's state. This is called for each stored event when the actor is recovered,
+    /// and for each new event once [`PersistentActor::persist`] has written it.
+    fn recover(&self, event: &Self::Event);
+
+    /// # [`PersistentActor::snapshot`]
+    /// Returns a snapshot of the actor's current state, or [`None`] to skip this snapshot.
+    /// This is only called if the actor's journal was created with [`Journal::with_snapshots`].
+    fn snapshot(&self) -> Option<Self::Snapshot> {
+        None
+    }
+
+    /// # [`PersistentActor::restore`]
+    /// Replaces the actor's state with a snapshot. This is called when the actor is recovered,
+    /// before the events persisted after the snapshot are replayed.
+    fn restore(&self, snapshot: Self::Snapshot) {
+        let _ = snapshot;
+    }
+
+    /// # [`PersistentActor::persist`]
+    /// Writes an event to the journal, and then applies it with [`PersistentActor::recover`].
+    /// If the journal's [`SnapshotPolicy`] says a snapshot is due, one is taken afterwards.
+    ///
+    /// # Errors
+    /// Returns an error if the event could not be written, in which case it is not applied.
+    /// Failing to save a snapshot is not an error.
+    fn persist(&self, event: Self::Event) -> impl Future<Output = Result<(), JournalError>> + Send {
+        async move {
+            self.journal().write(&event, |event| self.recover(event), || self.snapshot()).await?;
+            Ok(())
+        }
+    }
+
+    /// # [`PersistentActor::persist_with`]
+    /// Persists an event like [`PersistentActor::persist`], and then sends the messages in the outbox in the order they
+    /// were added, as described in [`Outbox`]. Returns the errors of the messages that couldn't be sent, each paired
+    /// with the index of the message in the outbox.
+    ///
+    /// # Errors
+    /// Returns an error if the event could not be written, in which case it is not applied and none of the messages are sent.
+    fn persist_with(&self, event: Self::Event, outbox: Outbox) -> impl Future<Output = Result<Vec<(usize, MessageSendError)>, JournalError>> + Send {
+        async move {
+            self.persist(event).await?;
+            Ok(outbox.send().await)
+        }
+    }
+}
+
+/// # [`RecoveryError`]
+/// An error that might be returned when a [`Persistent`] actor is initialized.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RecoveryError<E> {
+    /// The actor's snapshot or events could not be read.
+    Journal(JournalError),
+    /// The actor's own [`Actor::initialize`] method returned an error.
+    Initialize(E),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for RecoveryError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RecoveryError::Journal(e) => write!(f, "RecoveryError: state could not be recovered: {e}"),
+            RecoveryError::Initialize(e) => write!(f, "RecoveryError: actor failed to initialize: {e}"),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for RecoveryError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            RecoveryError::Journal(e) => Some(e),
+            RecoveryError::Initialize(e) => Some(e),
+        }
+    }
+}
+
+/// # [`Persistent`]
+/// Wraps a [`PersistentActor`], replaying its journal when it is added to the system and before its own
+/// [`Actor::initialize`] is called. Messages are handled by the wrapped actor, with its [`Actor::MAX_CONCURRENCY`].
+///
+/// Traits built on [`Handler`], such as [`crate::HealthCheck`], reach the wrapped actor through its handlers.
+/// [`crate::Handoff`] is not forwarded, as a replacement recovers its state from the journal instead.
+pub struct Persistent<A>(pub A);
+
+impl<A: PersistentActor> Actor for Persistent<A> {
+    type Error = RecoveryError<A::Error>;
+
+    const MAX_CONCURRENCY: Option<usize> = A::MAX_CONCURRENCY;
+
+    async fn initialize(&mut self) -> Result<(), Self::Error> {
+        let actor = &self.0;
+        actor.journal()
+            .recover(|snapshot| actor.restore(snapshot), |event| actor.recover(&event))
+            .await
+            .map_err(RecoveryError::Journal)?;
+
+        self.0.initialize().await.map_err(RecoveryError::Initialize)
+    }
+
+    async fn deinitialize(&self) {
+        self.0.journal().stop_interval();
+        self.0.deinitialize().await;
+    }
+
+    async fn passivate(&self) {
+        self.0.passivate().await;
+    }
+}
+
+impl<A: PersistentActor + Handler<M>, M: Message> Handler<M> for Persistent<A> {
+    async fn handle_message<D: Delegate>(&self, message: M, context: &ActorContext<D>) -> M::Result {
+        // The snapshot interval needs the system's timer, which is first available here
+        self.0.journal().start_interval(context.system());
+        self.0.handle_message(message, context).await
+    }
+}
@@ -0,0 +1,156 @@
+//! # Encryption
+//! [`Encrypted`] wraps an [`EventStore`] or [`SnapshotStore`] of raw bytes, encrypting every event and snapshot with
+//! XChaCha20-Poly1305 before it is written, and decrypting it again when it is read. Each ciphertext is bound to its
+//! persistence id and sequence number, so events can't be swapped between actors or reordered without being detected.
+//!
+//! Keys are identified by a number that is stored alongside each ciphertext. [`Encrypted::rotate`] starts encrypting with
+//! a new key while keeping the old ones, so that everything written before the rotation can still be read. Stores are
+//! append only, so a key may only be removed with [`Encrypted::retire`] once nothing that was encrypted with it is read
+//! anymore, for example once a newer snapshot has been taken and the older events are no longer replayed.
+
+use alloc::{collections::BTreeMap, string::ToString, vec::Vec};
+use chacha20poly1305::{aead::{Aead, AeadCore, KeyInit, OsRng, Payload}, XChaCha20Poly1305, XNonce};
+use maitake_sync::spin::RwLock;
+
+use super::{EventStore, JournalError, SnapshotStore};
+
+/// The length of the key id that prefixes each ciphertext.
+const KEY_ID_LENGTH: usize = 4;
+
+/// The length of the random nonce that follows the key id.
+const NONCE_LENGTH: usize = 24;
+
+/// The keys of an [`Encrypted`] store.
+struct Keys {
+    /// The id of the key new events and snapshots are encrypted with
+    current: u32,
+    /// Every key that can be decrypted with, keyed by its id
+    ciphers: BTreeMap<u32, XChaCha20Poly1305>,
+}
+
+/// # [`Encrypted`]
+/// A store of raw bytes whose contents are encrypted at rest, as described in the [module documentation](self).
+/// Actors using it encode their events and snapshots as bytes with whatever format they already use.
+pub struct Encrypted<S> {
+    /// The store the ciphertexts are written to
+    store: S,
+    /// The keys the store is encrypted with
+    keys: RwLock<Keys>,
+}
+
+impl<S> Encrypted<S> {
+    /// # [`Encrypted::new`]
+    /// Encrypts the given store with a 256 bit key, identified by `id`.
+    #[must_use]
+    pub fn new(store: S, id: u32, key: &[u8; 32]) -> Self {
+        Self {
+            store,
+            keys: RwLock::new(Keys { current: id, ciphers: BTreeMap::from([(id, XChaCha20Poly1305::new(key.into()))]) }),
+        }
+    }
+
+    /// # [`Encrypted::rotate`]
+    /// Encrypts everything written from now on with a new key, identified by `id`. Previous keys are kept for reading.
+    /// Rotating to an id that is already in use replaces its key.
+    pub fn rotate(&self, id: u32, key: &[u8; 32]) {
+        let mut keys = self.keys.write();
+        keys.ciphers.insert(id, XChaCha20Poly1305::new(key.into()));
+        keys.current = id;
+    }
+
+    /// # [`Encrypted::retire`]
+    /// Forgets an old key, so that anything encrypted with it can no longer be read.
+    /// The current key can't be retired, and `false` is returned if it is given or the key is unknown.
+    pub fn retire(&self, id: u32) -> bool {
+        let mut keys = self.keys.write();
+        id != keys.current && keys.ciphers.remove(&id).is_some()
+    }
+
+    /// # [`Encrypted::current_key`]
+    /// Returns the id of the key new events and snapshots are encrypted with.
+    #[must_use]
+    pub fn current_key(&self) -> u32 {
+        self.keys.read().current
+    }
+
+    /// Encrypts the contents written under a persistence id and sequence number with the current key.
+    fn encrypt(&self, persistence_id: &str, sequence: u64, plaintext: &[u8]) -> Result<Vec<u8>, JournalError> {
+        let keys = self.keys.read();
+        let cipher = keys.ciphers.get(&keys.current)
+            .ok_or_else(|| encryption_error(sequence, "the current key is missing"))?;
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = associated_data(persistence_id, sequence);
+        let ciphertext = cipher.encrypt(&nonce, Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| encryption_error(sequence, "the contents could not be encrypted"))?;
+
+        let mut contents = Vec::with_capacity(KEY_ID_LENGTH + NONCE_LENGTH + ciphertext.len());
+        contents.extend_from_slice(&keys.current.to_le_bytes());
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&ciphertext);
+
+        Ok(contents)
+    }
+
+    /// Decrypts the contents read from a persistence id and sequence number with the key they were encrypted with.
+    fn decrypt(&self, persistence_id: &str, sequence: u64, contents: &[u8]) -> Result<Vec<u8>, JournalError> {
+        if contents.len() < KEY_ID_LENGTH + NONCE_LENGTH {
+            return Err(encryption_error(sequence, "the contents are truncated"));
+        }
+        let (id, rest) = contents.split_at(KEY_ID_LENGTH);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+        let id = u32::from_le_bytes(id.try_into().unwrap_or_default());
+
+        let keys = self.keys.read();
+        let cipher = keys.ciphers.get(&id)
+            .ok_or_else(|| encryption_error(sequence, &alloc::format!("key {id} is unknown")))?;
+
+        let aad = associated_data(persistence_id, sequence);
+        cipher.decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| encryption_error(sequence, "the contents do not match their key or position"))
+    }
+}
+
+/// Returns the data each ciphertext is bound to, which is its persistence id and sequence number.
+fn associated_data(persistence_id: &str, sequence: u64) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(persistence_id.len() + 8);
+    aad.extend_from_slice(&sequence.to_le_bytes());
+    aad.extend_from_slice(persistence_id.as_bytes());
+    aad
+}
+
+/// Creates a [`JournalError::Encryption`] for the contents with the given sequence number.
+fn encryption_error(sequence: u64, reason: &str) -> JournalError {
+    JournalError::Encryption { sequence, reason: reason.to_string() }
+}
+
+#[async_trait::async_trait]
+impl<S: EventStore<Vec<u8>>> EventStore<Vec<u8>> for Encrypted<S> {
+    async fn append(&self, persistence_id: &str, sequence: u64, event: &Vec<u8>) -> Result<(), JournalError> {
+        let contents = self.encrypt(persistence_id, sequence, event)?;
+        self.store.append(persistence_id, sequence, &contents).await
+    }
+
+    async fn read(&self, persistence_id: &str, from: u64) -> Result<Vec<(u64, Vec<u8>)>, JournalError> {
+        self.store.read(persistence_id, from).await?
+            .into_iter()
+            .map(|(sequence, contents)| Ok((sequence, self.decrypt(persistence_id, sequence, &contents)?)))
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: SnapshotStore<Vec<u8>>> SnapshotStore<Vec<u8>> for Encrypted<S> {
+    async fn save(&self, persistence_id: &str, sequence: u64, snapshot: &Vec<u8>) -> Result<(), JournalError> {
+        let contents = self.encrypt(persistence_id, sequence, snapshot)?;
+        self.store.save(persistence_id, sequence, &contents).await
+    }
+
+    async fn load(&self, persistence_id: &str) -> Result<Option<(u64, Vec<u8>)>, JournalError> {
+        let Some((sequence, contents)) = self.store.load(persistence_id).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some((sequence, self.decrypt(persistence_id, sequence, &contents)?)))
+    }
+}
@@ -0,0 +1,203 @@
+//! # Outbox
+//! A handler that persists an event and tells other actors about it must not send those messages before the event is
+//! written, or a failed write, or the process stopping part way through, would leave the recipients acting on an event
+//! that never happened. Messages added to an [`Outbox`] and passed to [`super::PersistentActor::persist_with`] are only sent
+//! once the event has been written to the [`super::EventStore`], and are dropped if it could not be.
+//!
+//! Messages are sent at most once. If the process stops after the event is written but before they are sent, they are
+//! never sent, and replaying the event when the actor is recovered doesn't send them again.
+//!
+//! ```ignore
+//! impl Handler<Withdraw> for Account {
+//!     async fn handle_message<D: Delegate>(&self, message: Withdraw, _context: &ActorContext<D>) -> Result<(), JournalError> {
+//!         let outbox = Outbox::new().tell(self.ledger.clone(), Withdrawn(message.0));
+//!         self.persist_with(-message.0, outbox).await?;
+//!         Ok(())
+//!     }
+//! }
+//! ```
+
+use core::{future::Future, pin::Pin};
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use crate::{Message, MessageSendError, MessageSender};
+
+/// A message waiting in an outbox, which is only sent once it is polled.
+type Deferred = Pin<Box<dyn Future<Output = Result<(), MessageSendError>> + Send>>;
+
+/// # [`Outbox`]
+/// Messages to send once an event has been persisted, as described in the [module documentation](self).
+#[derive(Default)]
+#[must_use = "the messages are only sent once the outbox is passed to PersistentActor::persist_with"]
+pub struct Outbox {
+    /// The messages to send, in the order they were added
+    messages: Vec<Deferred>,
+}
+
+impl Outbox {
+    /// # [`Outbox::new`]
+    /// Creates an empty outbox.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # [`Outbox::tell`]
+    /// Adds a message to send to the target without waiting for its response, once the event has been persisted.
+    pub fn tell<M: Message>(mut self, target: Arc<dyn MessageSender<M>>, message: M) -> Self {
+        self.messages.push(Box::pin(async move { target.tell(message).await }));
+        self
+    }
+
+    /// # [`Outbox::len`]
+    /// Returns how many messages are in the outbox.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// # [`Outbox::is_empty`]
+    /// Returns true if the outbox has no messages.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Sends every message in the order they were added, returning the errors of those that couldn't be sent,
+    /// each paired with the index of the message.
+    pub(crate) async fn send(self) -> Vec<(usize, MessageSendError)> {
+        let mut errors = Vec::new();
+        for (index, message) in self.messages.into_iter().enumerate() {
+            if let Err(e) = message.await {
+                errors.push((index, e));
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicI64, Ordering};
+
+    use alloc::string::String;
+    use maitake_sync::spin::Mutex;
+
+    use super::*;
+    use crate::{persistence::{EventStore, InMemoryEventStore, Journal, JournalError, PersistentActor}, Actor};
+
+    struct Note(u32);
+
+    impl Message for Note {
+        type Result = ();
+    }
+
+    /// Records the notes it is sent, or refuses them.
+    struct Recipient {
+        notes: Mutex<Vec<u32>>,
+        refuse: bool,
+    }
+
+    impl Recipient {
+        fn new(refuse: bool) -> Arc<Self> {
+            Arc::new(Self { notes: Mutex::new(Vec::new()), refuse })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MessageSender<Note> for Recipient {
+        async fn send(&self, message: Note) -> Result<(), MessageSendError> {
+            if self.refuse {
+                return Err(MessageSendError::Draining);
+            }
+
+            self.notes.lock().push(message.0);
+            Ok(())
+        }
+    }
+
+    /// A store that can't write anything.
+    struct Broken;
+
+    #[async_trait::async_trait]
+    impl EventStore<i64> for Broken {
+        async fn append(&self, _persistence_id: &str, _sequence: u64, _event: &i64) -> Result<(), JournalError> {
+            Err(JournalError::Store(String::from("the store is broken").into()))
+        }
+
+        async fn read(&self, _persistence_id: &str, _from: u64) -> Result<Vec<(u64, i64)>, JournalError> {
+            Ok(Vec::new())
+        }
+    }
+
+    struct Account {
+        journal: Journal<i64>,
+        balance: AtomicI64,
+    }
+
+    impl Account {
+        fn new(store: Arc<dyn EventStore<i64>>) -> Self {
+            Self { journal: Journal::new(store, "account"), balance: AtomicI64::new(0) }
+        }
+    }
+
+    impl Actor for Account {
+        type Error = ();
+    }
+
+    impl PersistentActor for Account {
+        type Event = i64;
+        type Snapshot = ();
+
+        fn journal(&self) -> &Journal<i64> {
+            &self.journal
+        }
+
+        fn recover(&self, event: &i64) {
+            self.balance.fetch_add(*event, Ordering::Relaxed);
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_messages_once_the_event_is_written() {
+        let store = Arc::new(InMemoryEventStore::<i64>::new());
+        let account = Account::new(store.clone());
+        let recipient = Recipient::new(false);
+
+        let outbox = Outbox::new().tell::<Note>(recipient.clone(), Note(1)).tell::<Note>(recipient.clone(), Note(2));
+        assert_eq!(outbox.len(), 2);
+        assert!(recipient.notes.lock().is_empty());
+
+        let errors = account.persist_with(5, outbox).await.unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(*recipient.notes.lock(), [1, 2]);
+        assert_eq!(account.balance.load(Ordering::Relaxed), 5);
+        assert_eq!(store.read("account", 1).await.unwrap(), [(1, 5)]);
+    }
+
+    #[tokio::test]
+    async fn drops_messages_if_the_event_is_not_written() {
+        let account = Account::new(Arc::new(Broken));
+        let recipient = Recipient::new(false);
+
+        let written = account.persist_with(5, Outbox::new().tell::<Note>(recipient.clone(), Note(1))).await;
+        assert!(matches!(written, Err(JournalError::Store(_))));
+        assert!(recipient.notes.lock().is_empty());
+        assert_eq!(account.balance.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn returns_the_messages_that_could_not_be_sent() {
+        let account = Account::new(Arc::new(InMemoryEventStore::<i64>::new()));
+        let recipient = Recipient::new(false);
+        let refusing = Recipient::new(true);
+
+        let outbox = Outbox::new().tell::<Note>(refusing, Note(1)).tell::<Note>(recipient.clone(), Note(2));
+        let errors = account.persist_with(5, outbox).await.unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], (0, MessageSendError::Draining)));
+        assert_eq!(*recipient.notes.lock(), [2]);
+    }
+}
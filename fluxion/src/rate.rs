@@ -0,0 +1,92 @@
+//! # Rate Limiting
+//! An actor added with [`crate::Fluxion::add_with_rate_limit`] accepts at most a given number of messages per second,
+//! along with bursts of up to a given size. Senders that exceed the limit either wait until the actor accepts another
+//! message, or fail immediately with [`MessageSendError::RateLimited`], depending on the [`RateLimit`].
+//!
+//! The limit is measured with [`Timer::now`], so messages are not limited if the system's timer can't read a clock.
+
+use core::time::Duration;
+
+use alloc::sync::Arc;
+use maitake_sync::spin::Mutex;
+
+use crate::{MessageSendError, Timer};
+
+/// # [`RateLimit`]
+/// How many messages an actor accepts, and what happens to messages sent beyond that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The number of messages accepted per second
+    per_second: u32,
+    /// The number of messages accepted at once after the actor has been idle
+    burst: u32,
+    /// Whether messages over the limit fail instead of waiting
+    fail_fast: bool,
+}
+
+impl RateLimit {
+    /// # [`RateLimit::new`]
+    /// Accepts `per_second` messages per second, and bursts of up to `burst` messages.
+    /// Senders over the limit wait until the message is accepted. Zero values are treated as one.
+    #[must_use]
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        Self { per_second: per_second.max(1), burst: burst.max(1), fail_fast: false }
+    }
+
+    /// # [`RateLimit::fail_fast`]
+    /// Fails messages over the limit with [`MessageSendError::RateLimited`] instead of waiting.
+    #[must_use]
+    pub fn fail_fast(mut self) -> Self {
+        self.fail_fast = true;
+        self
+    }
+}
+
+/// Enforces a [`RateLimit`] for a single actor, using the generic cell rate algorithm.
+pub(crate) struct TokenBucket {
+    /// The limit being enforced
+    limit: RateLimit,
+    /// The timer used to read the time and to wait
+    timer: Arc<dyn Timer>,
+    /// The time at which the bucket would be empty if no more messages arrived
+    theoretical_arrival: Mutex<Duration>,
+}
+
+impl TokenBucket {
+    /// Creates a bucket that starts full.
+    pub(crate) fn new(limit: RateLimit, timer: Arc<dyn Timer>) -> Self {
+        Self { limit, timer, theoretical_arrival: Mutex::new(Duration::ZERO) }
+    }
+
+    /// Admits a single message, waiting until it is within the limit unless the limit fails fast.
+    pub(crate) async fn admit(&self) -> Result<(), MessageSendError> {
+        let interval = Duration::from_secs(1) / self.limit.per_second;
+        let tolerance = interval * (self.limit.burst - 1);
+
+        loop {
+            let Some(now) = self.timer.now() else {
+                return Ok(());
+            };
+
+            let wait = {
+                let mut theoretical_arrival = self.theoretical_arrival.lock();
+                let arrival = (*theoretical_arrival).max(now);
+                let allowed_at = arrival.saturating_sub(tolerance);
+
+                if allowed_at <= now {
+                    *theoretical_arrival = arrival + interval;
+                    return Ok(());
+                }
+
+                allowed_at - now
+            };
+
+            if self.limit.fail_fast {
+                return Err(MessageSendError::RateLimited);
+            }
+
+            // Another sender may take the slot while this one sleeps, in which case it waits again
+            self.timer.sleep(wait).await;
+        }
+    }
+}
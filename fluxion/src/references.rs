@@ -1,8 +1,12 @@
 //! # References
 //! [`ActorRef`]s, or Actor References, are the primary method through which actors control each other.
 
-use crate::{Actor, ActorWrapper, Delegate, Handler, Message, MessageSendError};
-use alloc::boxed::Box;
+use core::{future::Future, sync::atomic::Ordering, time::Duration};
+
+use crate::{drain::Gate, Actor, ActorWrapper, Delegate, Fluxion, GetActorError, Handler, Message, MessageSendError, RetryPolicy, RetrySender};
+use alloc::{boxed::Box, string::String, sync::Arc};
+use maitake_sync::spin::Mutex;
+use crate::trace::instrument;
 
 /// # [`ActorRef`]
 /// This trait provides methods for actors to communicate with and control each other.
@@ -22,26 +26,291 @@ pub trait MessageSender<M: Message>: Send + Sync + 'static {
     /// For [`LocalRef`], the message send will never fail, however delegates may return an error upon sending.
     /// These errors are generally not recoverable, and should be interpreted as meaning that the
     /// target actor no longer exists/is no longer accessible.
+    /// If the system has a default timeout, this will also return [`MessageSendError::Timeout`] once it elapses.
     async fn send(&self, message: M) -> Result<M::Result, MessageSendError>;
+
+    /// Sends the given message and waits at most `timeout` for a response.
+    ///
+    /// The default implementation has no way to measure time, and ignores the timeout.
+    /// Senders retrieved from a [`Fluxion`] instance that was provided with a [`crate::Timer`] override this.
+    ///
+    /// # Errors
+    /// Returns [`MessageSendError::Timeout`] if no response is received in time,
+    /// in addition to any errors returned by [`MessageSender::send`].
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        let _ = timeout;
+        self.send(message).await
+    }
+
+    /// Sends the given message without waiting for the handler's response.
+    ///
+    /// Delegates should override this to hand the message off to their transport and return immediately,
+    /// without waiting for the foreign system to respond. The default implementation sends the message and discards the response.
+    /// [`LocalRef`] hands the message to the system's [`crate::Executor`] if it has one, and otherwise runs the handler
+    /// on the caller's task. Either way, the system's default timeout applies to however long this waits.
+    ///
+    /// # Errors
+    /// Returns an error if the message could not be delivered.
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.send(message).await.map(|_| ())
+    }
+
+    /// Sends the given message with an idempotency key and waits for a response.
+    /// If a message with the same key already reached the receiving system, it is not handled again,
+    /// and the response to the original message is returned instead.
+    ///
+    /// Delegates should override this to carry the key to the foreign system, which deduplicates it with [`Fluxion::deduplicate`].
+    /// Local sends are never duplicated, so the default implementation ignores the key.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`MessageSender::send`].
+    async fn send_idempotent(&self, key: &str, message: M) -> Result<M::Result, MessageSendError> {
+        let _ = key;
+        self.send(message).await
+    }
+
+    /// Sends a message like [`MessageSender::send`], returning a [`crate::Cancellable`] through which the sender can
+    /// cancel the message's handler, as described in [`crate::CancellationToken`]. Dropping the [`crate::Cancellable`]
+    /// before it resolves cancels the message too.
+    #[cfg(feature = "std")]
+    fn send_cancellable(&self, message: M) -> crate::Cancellable<'_, M::Result> {
+        crate::Cancellable::new(self.send(message))
+    }
+
+    /// Wraps the sender in a [`RetrySender`], which retries failed sends according to the policy.
+    fn with_retry(self, policy: RetryPolicy) -> RetrySender<M>
+        where Self: Sized, M: Clone {
+        RetrySender::new(Arc::new(self), policy)
+    }
 }
 
-pub struct LocalRef<A: Actor, D: Delegate>(
-    pub(crate) slacktor::ActorHandle<ActorWrapper<A, D>>,
-    pub(crate) u64,
-);
+pub struct LocalRef<A: Actor, D: Delegate> {
+    /// The actor's handle in the underlying slacktor instance
+    pub(crate) handle: slacktor::ActorHandle<ActorWrapper<A, D>>,
+    /// The actor's id
+    pub(crate) id: u64,
+    /// The system the actor belongs to
+    pub(crate) system: Fluxion<D>,
+    /// Admits messages while the actor is running, and holds its mailbox
+    pub(crate) gate: Arc<Gate>,
+    /// The middleware the actor was spawned with
+    pub(crate) layers: crate::middleware::Layers,
+    /// The actor's rate limit, if it has one
+    pub(crate) rate: Option<Arc<crate::rate::TokenBucket>>,
+    /// The actor that owns this one, if it was spawned as an owned child
+    pub(crate) owner: Option<Arc<crate::owned::Owner<D>>>,
+    /// The executor of the core the actor is pinned to, if it is pinned to one
+    pub(crate) executor: Option<Arc<dyn crate::Executor>>,
+}
 
 impl<A: Actor, D: Delegate> LocalRef<A, D> {
     /// # [`LocalRef::get_id`]
     /// Retrieves the actor's ID
     #[must_use]
     pub fn get_id(&self) -> u64 {
-        self.1
+        self.id
+    }
+
+    /// # [`LocalRef::drain`]
+    /// Stops the actor from accepting new messages, which fail with [`MessageSendError::Draining`],
+    /// waits for every message already sent to it to be handled, and then removes it from the system.
+    pub async fn drain(&self) {
+        self.gate.drain().await;
+        self.system.kill::<A>(self.id).await;
     }
 }
 
 impl<A: Actor, D: Delegate> Clone for LocalRef<A, D> {
     fn clone(&self) -> Self {
-        Self(self.0.clone(), self.1)
+        Self {
+            handle: self.handle.clone(),
+            id: self.id,
+            system: self.system.clone(),
+            gate: self.gate.clone(),
+            layers: self.layers.clone(),
+            rate: self.rate.clone(),
+            owner: self.owner.clone(),
+            executor: self.executor.clone(),
+        }
+    }
+}
+
+impl<A: Actor, D: Delegate> LocalRef<A, D> {
+    /// Sends a message to the actor through the system's load shedding, the actor's rate limit, and the system's and the actor's middleware.
+    /// Messages to actors pinned to a core are handed to that core's executor once admitted.
+    /// Priority messages are admitted ahead of the normal messages waiting in the actor's mailbox.
+    pub(crate) async fn dispatch<M: Message>(&self, message: M, priority: bool) -> Result<M::Result, MessageSendError>
+        where A: Handler<M> {
+        // An actor that handles one message at a time can't handle this one while it waits on it further up the chain
+        #[cfg(feature = "std")]
+        if matches!(A::MAX_CONCURRENCY, Some(0 | 1)) && self.system.deadlock_detection && crate::Metadata::in_call_chain(&self.system.system_id, self.id) {
+            return Err(MessageSendError::WouldDeadlock);
+        }
+
+        let _admitted = self.system.load.admit::<M>(self.id)?;
+
+        if let Some(rate_limit) = &self.rate {
+            rate_limit.admit().await?;
+        }
+
+        let Some(core) = &self.executor else {
+            return self.deliver(message, priority).await;
+        };
+
+        let actor = self.clone();
+
+        // The handler runs on the core's executor, so a cancellable message's signal has to be carried there
+        #[cfg(feature = "std")]
+        let deliver = crate::cancel::scope_request(crate::cancel::current_request(), async move { actor.deliver(message, priority).await });
+        #[cfg(not(feature = "std"))]
+        let deliver = async move { actor.deliver(message, priority).await };
+
+        crate::executor::run_on(core.as_ref(), deliver).await
+            .unwrap_or_else(|e| Err(MessageSendError::UnknownError(Box::new(e))))
+    }
+
+    /// Waits for the actor to be able to handle the message, and runs the middleware and the actor's handler on the current task.
+    /// Panics in the handler are caught as described in [`crate::PanicPolicy`].
+    async fn deliver<M: Message>(&self, message: M, priority: bool) -> Result<M::Result, MessageSendError>
+        where A: Handler<M> {
+        let timer = self.system.timer.as_ref();
+        let enqueued = timer.and_then(|timer| timer.now());
+        if let Some(observer) = &self.system.mailbox_observer {
+            observer.enqueued(self.id, core::any::type_name::<M>(), enqueued);
+        }
+
+        let _permit = self.gate.mailbox.admit::<M>(priority).await?;
+
+        let dequeued = enqueued.and(timer).and_then(|timer| timer.now());
+        let queue_time = enqueued.zip(dequeued).map(|(enqueued, dequeued)| dequeued.saturating_sub(enqueued));
+        if let Some(observer) = &self.system.mailbox_observer {
+            observer.dequeued(self.id, core::any::type_name::<M>(), dequeued, queue_time);
+        }
+
+        let handle = crate::middleware::intercept(&self.system.middleware, &self.layers, self.id, message, |message| self.handle.send(message));
+
+        // The handler sees how long the message waited in its metadata
+        #[cfg(feature = "std")]
+        let handle = crate::Metadata::scope_if(crate::Metadata::with_queue_time(queue_time), handle);
+
+        #[cfg(feature = "std")]
+        {
+            self.system.isolate::<A, M>(self.id, handle).await
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            handle.await
+        }
+    }
+}
+
+impl<A: Actor, D: Delegate> LocalRef<A, D> {
+    /// # [`LocalRef::send`]
+    /// Sends a message like [`MessageSender::send`]. Calling this directly on a [`LocalRef`], rather than through
+    /// `dyn MessageSender`, avoids allocating the boxed future that trait objects require, so hot paths that know
+    /// the actor's type send without allocating.
+    ///
+    /// If the system has a default timeout, the message is sent with [`LocalRef::send_timeout`], and its caveats apply.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`MessageSender::send`].
+    #[inline]
+    pub async fn send<M: Message>(&self, message: M) -> Result<M::Result, MessageSendError>
+        where A: Handler<M> {
+        match self.system.default_timeout {
+            // The timed send admits the message itself, so it only takes one pass through the gate
+            Some(timeout) => self.send_timeout(message, timeout).await,
+            None => {
+                let _pass = self.gate.enter()?;
+                instrument!(self.dispatch(message, false), "fluxion::send", actor = self.id, message = core::any::type_name::<M>()).await
+            },
+        }
+    }
+
+    /// # [`LocalRef::send_timeout`]
+    /// Sends a message like [`MessageSender::send_timeout`], without allocating as described in [`LocalRef::send`].
+    ///
+    /// The handler runs on the current task, so a timeout that elapses while it is running drops its future partway
+    /// through: whatever it had not yet done when it last yielded never happens, and the actor may be left with some of
+    /// the message's effects but not others. Handlers that must run to completion should be sent without a timeout, or
+    /// told with [`LocalRef::tell`] on a system with an [`crate::Executor`], which runs them on their own task.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`MessageSender::send_timeout`].
+    pub async fn send_timeout<M: Message>(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError>
+        where A: Handler<M> {
+        let _pass = self.gate.enter()?;
+        let send = instrument!(self.dispatch(message, false), "fluxion::send", actor = self.id, message = core::any::type_name::<M>(), ?timeout);
+
+        self.timed::<M>(send, timeout).await
+    }
+
+    /// Fails a send with [`MessageSendError::Timeout`] if it takes longer than the timeout, if the system has a timer,
+    /// and counts it in the metrics if it fails.
+    pub(crate) async fn timed<M: Message>(&self, send: impl Future<Output = Result<M::Result, MessageSendError>>, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        let Some(timer) = &self.system.timer else {
+            return send.await;
+        };
+
+        let result = crate::timer::timeout(timer.as_ref(), timeout, send).await
+            .unwrap_or(Err(MessageSendError::Timeout));
+
+        if let (Err(_), Some(metrics)) = (&result, &self.system.metrics) {
+            metrics.send_failed(&alloc::format!("{}", self.id), core::any::type_name::<M>());
+        }
+
+        result
+    }
+
+    /// # [`LocalRef::tell`]
+    /// Sends a message like [`MessageSender::tell`].
+    ///
+    /// If the system has an [`crate::Executor`], the message is handed to it and this returns without waiting for the
+    /// handler, so messages told this way may be handled in any order. Handing it over boxes the message along with a
+    /// clone of this reference, so unlike [`LocalRef::send`] this allocates once per message. Sends that fail once the
+    /// message has been handed over are recorded as dead letters. Without an executor, the handler runs on the current
+    /// task without allocating, and the system's default timeout applies as it does to [`LocalRef::send`].
+    ///
+    /// # Errors
+    /// Returns the same errors as [`MessageSender::tell`].
+    #[inline]
+    pub async fn tell<M: Message>(&self, message: M) -> Result<(), MessageSendError>
+        where A: Handler<M> {
+        self.tell_holding(message, ()).await
+    }
+
+    /// Tells the actor a message like [`LocalRef::tell`], keeping `held` until the handler finishes, wherever it runs.
+    /// Routers use this to count a message against its recipient for as long as it is being handled.
+    pub(crate) async fn tell_holding<M: Message, H: Send + 'static>(&self, message: M, held: H) -> Result<(), MessageSendError>
+        where A: Handler<M> {
+        let Some(executor) = &self.system.executor else {
+            let _held = held;
+            let _pass = self.gate.enter()?;
+            let tell = instrument!(self.dispatch(message, false), "fluxion::tell", actor = self.id, message = core::any::type_name::<M>());
+
+            match self.system.default_timeout {
+                Some(timeout) => self.timed::<M>(tell, timeout).await?,
+                None => tell.await?,
+            };
+            return Ok(());
+        };
+
+        let pass = self.gate.enter_owned()?;
+        let actor = self.clone();
+        let tell = instrument!(async move {
+            let _held = held;
+            let _pass = pass;
+            if let Err(e) = actor.dispatch(message, false).await {
+                actor.system.dead_letters.record::<M>(actor.id, crate::DeadLetterReason::SendFailed(alloc::format!("{e}"))).await;
+            }
+        }, "fluxion::tell", actor = self.id, message = core::any::type_name::<M>());
+
+        // The handler still sees the teller as its sender, and the teller's metadata, but isn't in its call chain
+        #[cfg(feature = "std")]
+        let tell = crate::sender::Caller::act_if(crate::sender::Caller::acting(), crate::Metadata::scope_if(crate::Metadata::detached(), tell));
+
+        executor.spawn(Box::pin(tell));
+        Ok(())
     }
 }
 
@@ -49,6 +318,157 @@ impl<A: Actor, D: Delegate> Clone for LocalRef<A, D> {
 impl<A: Handler<M>, M: Message, D: Delegate> MessageSender<M> for LocalRef<A, D> {
     #[inline]
     async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
-        Ok(self.0.send(message).await)
+        LocalRef::send(self, message).await
+    }
+
+    #[inline]
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        LocalRef::send_timeout(self, message, timeout).await
+    }
+
+    #[inline]
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        LocalRef::tell(self, message).await
+    }
+}
+
+/// # [`NamedRef`]
+/// A reference to the local actor with a given name, created with [`Fluxion::named`]. The name is resolved on the first
+/// send, and the resulting [`LocalRef`] is reused until a name is assigned or removed or an actor is killed anywhere in the
+/// system, after which the name is resolved again. This avoids looking the name up on every send, while still following
+/// the name to a replacement actor.
+pub struct NamedRef<A: Actor, D: Delegate> {
+    /// The system the actor runs on
+    system: Fluxion<D>,
+    /// The name of the actor
+    name: String,
+    /// The reference the name last resolved to, along with the system's generation at the time
+    resolved: Mutex<Option<(u64, LocalRef<A, D>)>>,
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::named`]
+    /// Creates a [`NamedRef`] to the local actor with the given name, which need not exist yet.
+    #[must_use]
+    pub fn named<A: Actor>(&self, name: &str) -> NamedRef<A, D> {
+        NamedRef { system: self.clone(), name: String::from(name), resolved: Mutex::new(None) }
+    }
+}
+
+impl<A: Actor, D: Delegate> NamedRef<A, D> {
+    /// # [`NamedRef::name`]
+    /// Returns the name the reference resolves.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// # [`NamedRef::resolve`]
+    /// Returns the [`LocalRef`] the name currently refers to, resolving it again only if it may have changed.
+    ///
+    /// # Errors
+    /// Returns [`GetActorError::NameNotFound`] if no actor has the name, or [`GetActorError::WrongType`] if the actor
+    /// with the name is not an `A`.
+    pub async fn resolve(&self) -> Result<LocalRef<A, D>, GetActorError> {
+        // Read the generation before resolving, so that a change made meanwhile causes the next send to resolve again
+        let generation = self.system.generation.load(Ordering::Acquire);
+
+        let cached = self.resolved.lock().as_ref()
+            .filter(|(resolved_at, _)| *resolved_at == generation)
+            .map(|(_, reference)| reference.clone());
+
+        if let Some(reference) = cached {
+            return Ok(reference);
+        }
+
+        let id = self.system.get_actor_id(&self.name).await
+            .ok_or_else(|| GetActorError::NameNotFound(self.name.clone()))?;
+        let reference = self.system.try_get_local::<A>(id).await?;
+
+        *self.resolved.lock() = Some((generation, reference.clone()));
+        Ok(reference)
+    }
+
+    /// Resolves the name for a message of type `M`, failing the send and recording a dead letter if it can't be.
+    async fn target<M: 'static>(&self) -> Result<LocalRef<A, D>, MessageSendError> {
+        match self.resolve().await {
+            Ok(reference) => Ok(reference),
+            Err(e) => {
+                self.system.dead_letters.record::<M>(&self.name, crate::DeadLetterReason::lookup(&e)).await;
+                Err(MessageSendError::UnknownError(Box::new(e)))
+            },
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: Message, D: Delegate> MessageSender<M> for NamedRef<A, D> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.target::<M>().await?.send(message).await
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        self.target::<M>().await?.send_timeout(message, timeout).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.target::<M>().await?.tell(message).await
+    }
+}
+
+/// Wraps a [`MessageSender`] provided by a delegate, applying the system's timer to it
+/// and recording failed sends as dead letters.
+#[cfg(feature = "foreign")]
+pub(crate) struct ForeignSender<M: Message> {
+    pub(crate) inner: alloc::sync::Arc<dyn MessageSender<M>>,
+    pub(crate) target: alloc::string::String,
+    pub(crate) timer: Option<alloc::sync::Arc<dyn crate::Timer>>,
+    pub(crate) default_timeout: Option<Duration>,
+    pub(crate) dead_letters: alloc::sync::Arc<crate::DeadLetters>,
+    pub(crate) metrics: Option<alloc::sync::Arc<dyn crate::metrics::MetricsSink>>,
+}
+
+#[cfg(feature = "foreign")]
+impl<M: Message> ForeignSender<M> {
+    /// Runs the given send, applying the timeout if there is one and a timer is available,
+    /// and records a dead letter if it fails.
+    async fn run<R>(&self, timeout: Option<Duration>, send: impl core::future::Future<Output = Result<R, MessageSendError>> + Send) -> Result<R, MessageSendError> {
+        let send = instrument!(send, "fluxion::foreign_send", target = %self.target, message = core::any::type_name::<M>(), ?timeout);
+
+        let result = match (timeout, &self.timer) {
+            (Some(timeout), Some(timer)) => crate::timer::timeout(timer.as_ref(), timeout, send).await
+                .unwrap_or(Err(MessageSendError::Timeout)),
+            _ => send.await,
+        };
+
+        if let Err(e) = &result {
+            if let Some(metrics) = &self.metrics {
+                metrics.send_failed(&self.target, core::any::type_name::<M>());
+            }
+
+            self.dead_letters.record::<M>(&self.target, crate::DeadLetterReason::SendFailed(alloc::format!("{e}"))).await;
+        }
+
+        result
+    }
+}
+
+#[cfg(feature = "foreign")]
+#[async_trait::async_trait]
+impl<M: Message> MessageSender<M> for ForeignSender<M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.run(self.default_timeout, self.inner.send(message)).await
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        self.run(Some(timeout), self.inner.send(message)).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.run(self.default_timeout, self.inner.tell(message)).await
+    }
+
+    async fn send_idempotent(&self, key: &str, message: M) -> Result<M::Result, MessageSendError> {
+        self.run(self.default_timeout, self.inner.send_idempotent(key, message)).await
     }
 }
@@ -0,0 +1,81 @@
+//! # Registry
+//! Every local actor has an entry in the system's [`Registry`], which is how references are created, and how actors are
+//! killed and inspected without knowing their types. Each entry holds a clone of the actor's handle, so retrieving a
+//! reference never waits on the slacktor instance's lock, which adding and killing actors hold across awaits. Entries
+//! are spread across shards by id, so a lookup only contends with changes to actors in the same shard, and only for as
+//! long as it takes to copy the entry.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use maitake_sync::spin::RwLock;
+
+use crate::fluxion::Registered;
+
+/// The number of shards the registry's entries are spread across.
+const SHARDS: usize = 16;
+
+/// Every local actor's [`Registered`] entry, keyed by the actor's id.
+pub(crate) struct Registry<D> {
+    /// The entries, in the shard chosen by their id
+    shards: [RwLock<BTreeMap<u64, Registered<D>>>; SHARDS],
+}
+
+impl<D> Default for Registry<D> {
+    fn default() -> Self {
+        Self { shards: core::array::from_fn(|_| RwLock::new(BTreeMap::new())) }
+    }
+}
+
+impl<D> Registry<D> {
+    /// Returns the shard the entry with the given id is stored in.
+    #[allow(clippy::cast_possible_truncation)]
+    fn shard(&self, id: u64) -> &RwLock<BTreeMap<u64, Registered<D>>> {
+        &self.shards[(id % SHARDS as u64) as usize]
+    }
+
+    /// Reads from the entry with the given id, if there is one.
+    pub(crate) fn get<R>(&self, id: u64, read: impl FnOnce(&Registered<D>) -> R) -> Option<R> {
+        self.shard(id).read().get(&id).map(read)
+    }
+
+    /// Changes the entry with the given id, if there is one.
+    pub(crate) fn update<R>(&self, id: u64, update: impl FnOnce(&mut Registered<D>) -> R) -> Option<R> {
+        self.shard(id).write().get_mut(&id).map(update)
+    }
+
+    /// Returns true if there is an entry with the given id.
+    pub(crate) fn contains(&self, id: u64) -> bool {
+        self.shard(id).read().contains_key(&id)
+    }
+
+    /// Adds an entry, replacing any with the same id.
+    pub(crate) fn insert(&self, id: u64, registered: Registered<D>) {
+        let replaced = self.shard(id).write().insert(id, registered);
+        drop(replaced);
+    }
+
+    /// Removes the entry with the given id, returning it so that it is dropped after the shard is unlocked.
+    pub(crate) fn remove(&self, id: u64) -> Option<Registered<D>> {
+        self.shard(id).write().remove(&id)
+    }
+
+    /// Reads from every entry, returning the values that were produced ordered by id.
+    /// Each shard is read in turn, so entries added or removed meanwhile may or may not be seen.
+    pub(crate) fn collect<R>(&self, mut read: impl FnMut(u64, &Registered<D>) -> Option<R>) -> Vec<R> {
+        let mut values = self.shards.iter()
+            .flat_map(|shard| shard.read().iter()
+                .filter_map(|(id, registered)| read(*id, registered).map(|value| (*id, value)))
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        values.sort_unstable_by_key(|(id, _)| *id);
+        values.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Removes every entry.
+    pub(crate) fn clear(&self) {
+        for shard in &self.shards {
+            let entries = core::mem::take(&mut *shard.write());
+            drop(entries);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! # Reliable Delivery
+//! [`ReliableSender`] wraps any [`MessageSender`] to deliver messages at least once. The receiving handler must explicitly
+//! acknowledge each message by returning [`Acknowledgement::Ack`], and a message that is rejected, times out, or fails to send
+//! is delivered again. Messages that are still unacknowledged after every attempt are kept, and can be redelivered later
+//! with [`ReliableSender::redeliver`], for example once a foreign system becomes reachable again.
+//!
+//! Unacknowledged messages can also be written to an [`EventStore`], so that they survive the sending system restarting.
+//! Because a message may be delivered more than once, handlers of reliable messages should be idempotent.
+
+use core::{sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use maitake_sync::spin;
+
+use crate::{persistence::{EventStore, Journal, JournalError}, Delegate, Fluxion, Message, MessageSendError, MessageSender, Timer};
+
+/// # [`Acknowledgement`]
+/// The result of handling a message sent through a [`ReliableSender`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Acknowledgement {
+    /// The message was handled, and should not be delivered again.
+    Ack,
+    /// The message could not be handled yet, and should be delivered again.
+    Nack,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Acknowledgement {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(*self == Acknowledgement::Ack)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Acknowledgement {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(if bool::deserialize(deserializer)? { Acknowledgement::Ack } else { Acknowledgement::Nack })
+    }
+}
+
+/// # [`OutboxEvent`]
+/// An event written to a [`ReliableSender`]'s store, from which its unacknowledged messages are recovered.
+#[derive(Debug, Clone)]
+pub enum OutboxEvent<M> {
+    /// A message was accepted for delivery.
+    Sent {
+        /// The id of the delivery
+        delivery: u64,
+        /// The message being delivered
+        message: M,
+    },
+    /// A message was acknowledged by its receiver.
+    Acknowledged {
+        /// The id of the delivery
+        delivery: u64,
+    },
+}
+
+/// # [`ReliableSender`]
+/// Delivers messages to a [`MessageSender`] at least once, redelivering each until its handler returns [`Acknowledgement::Ack`].
+pub struct ReliableSender<M: Message> {
+    /// The sender messages are delivered through
+    inner: Arc<dyn MessageSender<M>>,
+    /// The timer used to wait between attempts
+    timer: Option<Arc<dyn Timer>>,
+    /// How long to wait for an acknowledgement before redelivering, if not the inner sender's default
+    timeout: Option<Duration>,
+    /// How long to wait between attempts
+    delay: Duration,
+    /// How many times a message is delivered before giving up
+    attempts: u32,
+    /// The messages that have not been acknowledged, by delivery id
+    pending: spin::Mutex<BTreeMap<u64, M>>,
+    /// The id of the next delivery
+    next: AtomicU64,
+    /// Where unacknowledged messages are persisted, if anywhere
+    journal: Option<Journal<OutboxEvent<M>>>,
+}
+
+impl<M: Message<Result = Acknowledgement> + Clone> ReliableSender<M> {
+    /// # [`ReliableSender::new`]
+    /// Creates a sender that delivers messages through `target`, using the system's timer to wait between attempts.
+    /// By default, each message is delivered up to 5 times, with no delay between attempts.
+    pub fn new<D: Delegate>(system: &Fluxion<D>, target: Arc<dyn MessageSender<M>>) -> Self {
+        Self {
+            inner: target,
+            timer: system.timer.clone(),
+            timeout: None,
+            delay: Duration::ZERO,
+            attempts: 5,
+            pending: spin::Mutex::new(BTreeMap::new()),
+            next: AtomicU64::new(1),
+            journal: None,
+        }
+    }
+
+    /// # [`ReliableSender::timeout`]
+    /// Sets how long to wait for an acknowledgement before delivering a message again.
+    /// By default, the inner sender's own timeout applies, which is usually the system's default timeout.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// # [`ReliableSender::redelivery_delay`]
+    /// Sets how long to wait between attempts. This is ignored if the system has no [`Timer`].
+    #[must_use]
+    pub fn redelivery_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// # [`ReliableSender::max_attempts`]
+    /// Sets how many times a message is delivered before [`MessageSender::send`] gives up and returns the last result.
+    #[must_use]
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// # [`ReliableSender::persisted`]
+    /// Writes unacknowledged messages to the given store under the given persistence id,
+    /// and recovers any that were left unacknowledged by a previous sender with the same id.
+    /// Recovered messages are not delivered until [`ReliableSender::redeliver`] is called.
+    ///
+    /// # Errors
+    /// Returns an error if the store's events could not be read.
+    pub async fn persisted(mut self, store: Arc<dyn EventStore<OutboxEvent<M>>>, persistence_id: &str) -> Result<Self, JournalError> {
+        let journal = Journal::new(store, persistence_id);
+        let mut pending = BTreeMap::new();
+        let mut last = 0;
+
+        journal.recover(|()| {}, |event| match event {
+            OutboxEvent::Sent { delivery, message } => {
+                last = last.max(delivery);
+                pending.insert(delivery, message);
+            },
+            OutboxEvent::Acknowledged { delivery } => {
+                pending.remove(&delivery);
+            },
+        }).await?;
+
+        // Deliveries continue on from the highest id that was recovered, so that ids are never reused
+        *self.next.get_mut() = (last + 1).max(*self.next.get_mut());
+        self.pending = spin::Mutex::new(pending);
+        self.journal = Some(journal);
+
+        Ok(self)
+    }
+
+    /// # [`ReliableSender::pending`]
+    /// Returns the number of messages that have not been acknowledged.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// # [`ReliableSender::redeliver`]
+    /// Delivers every unacknowledged message again, in the order they were first sent, returning the number that were acknowledged.
+    pub async fn redeliver(&self) -> usize {
+        let pending: Vec<_> = self.pending.lock().iter().map(|(delivery, message)| (*delivery, message.clone())).collect();
+
+        let mut acknowledged = 0;
+        for (delivery, message) in pending {
+            if let Ok(Acknowledgement::Ack) = self.deliver(delivery, message).await {
+                acknowledged += 1;
+            }
+        }
+
+        acknowledged
+    }
+
+    /// Delivers a message until it is acknowledged or runs out of attempts, returning the last result.
+    async fn deliver(&self, delivery: u64, message: M) -> Result<Acknowledgement, MessageSendError> {
+        let mut attempt = 1;
+
+        loop {
+            let result = match self.timeout {
+                Some(timeout) => self.inner.send_timeout(message.clone(), timeout).await,
+                None => self.inner.send(message.clone()).await,
+            };
+
+            if let Ok(Acknowledgement::Ack) = result {
+                self.acknowledge(delivery).await;
+                return result;
+            }
+
+            if attempt >= self.attempts {
+                return result;
+            }
+            attempt += 1;
+
+            if let Some(timer) = &self.timer
+                && !self.delay.is_zero() {
+                timer.sleep(self.delay).await;
+            }
+        }
+    }
+
+    /// Forgets an acknowledged message.
+    async fn acknowledge(&self, delivery: u64) {
+        if self.pending.lock().remove(&delivery).is_none() {
+            return;
+        }
+
+        // If this fails, the message is redelivered after a restart, which at-least-once delivery allows
+        if let Some(journal) = &self.journal {
+            let _ = journal.write(&OutboxEvent::Acknowledged { delivery }, |_| {}, || None).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Message<Result = Acknowledgement> + Clone> MessageSender<M> for ReliableSender<M> {
+    /// Delivers the message until it is acknowledged, returning the last result if it never is.
+    /// The message remains pending until it is acknowledged, even if this returns.
+    ///
+    /// # Errors
+    /// Returns an error if the message could not be persisted, in which case it is not sent,
+    /// or if the last attempt to deliver it failed.
+    async fn send(&self, message: M) -> Result<Acknowledgement, MessageSendError> {
+        let delivery = self.next.fetch_add(1, Ordering::Relaxed);
+
+        // The message is persisted before it is sent, so that it can't be lost if the system stops mid-send
+        if let Some(journal) = &self.journal {
+            journal.write(&OutboxEvent::Sent { delivery, message: message.clone() }, |_| {}, || None).await
+                .map_err(|e| MessageSendError::UnknownError(Box::new(e)))?;
+        }
+        self.pending.lock().insert(delivery, message.clone());
+
+        self.deliver(delivery, message).await
+    }
+}
@@ -0,0 +1,215 @@
+//! # Forwarding
+//! A message wrapped in an [`Envelope`] carries a [`ReplyTo`] address, which the actor that finally handles it answers
+//! directly. This lets an intermediate actor, such as a dispatcher, [`Envelope::forward`] the message to another actor
+//! without waiting for and relaying the response itself. The original requester sends the message with [`Envelope::ask`],
+//! which waits on the reply address rather than on the intermediate actor.
+//!
+//! Reply addresses only exist in memory, so envelopes can only be forwarded between local actors.
+//!
+//! # Deferred Replies
+//! A handler can also answer a message after it has returned, by replying through a [`ReplyToken`] created with
+//! [`ActorContext::defer_reply`]. The message's result is the [`Deferred`] reply, which the requester awaits once
+//! the send returns, while the handler moves the token into a spawned task or stores it until it can reply.
+//!
+//! # Reentrant Requests
+//! A handler that awaits a send holds on to its actor until the response arrives, so an actor that handles one message
+//! at a time can't handle anything else meanwhile, and two such actors requesting each other wait forever.
+//! [`ActorContext::request_reentrant`] instead sends the request on the system's [`crate::Executor`] and returns at once.
+//! The response is delivered back to the requesting actor as a [`Response`] message, which it handles like any other,
+//! so it keeps handling its other messages while it waits.
+
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicBool, Ordering}, task::{Context, Poll}};
+
+use alloc::{boxed::Box, sync::Arc};
+use maitake_sync::{spin::Mutex, WaitCell};
+
+use crate::{actor::deliver, ActorContext, Delegate, Handler, Message, MessageSendError, MessageSender, ScheduleError};
+
+/// The state shared by a [`ReplyTo`] and the requester waiting on it.
+struct Slot<R> {
+    /// The reply, or the error the request failed with, once it is known
+    reply: Mutex<Option<Result<R, MessageSendError>>>,
+    /// Woken when the reply is sent, or when the reply address is dropped
+    ready: WaitCell,
+    /// Whether the reply address was dropped
+    closed: AtomicBool,
+}
+
+/// # [`ReplyTo`]
+/// The address a reply is sent to. The requester stops waiting if it is dropped without a reply.
+pub struct ReplyTo<R>(Arc<Slot<R>>);
+
+/// # [`ReplyToken`]
+/// The address a [deferred reply](ActorContext::defer_reply) is sent to.
+pub type ReplyToken<R> = ReplyTo<R>;
+
+impl<R> ReplyTo<R> {
+    /// # [`ReplyTo::reply`]
+    /// Sends the reply to the requester.
+    pub fn reply(self, reply: R) {
+        *self.0.reply.lock() = Some(Ok(reply));
+        self.0.ready.wake();
+    }
+
+    /// Fails the request with the given error.
+    pub(crate) fn fail(self, error: MessageSendError) {
+        *self.0.reply.lock() = Some(Err(error));
+        self.0.ready.wake();
+    }
+}
+
+impl<R> Drop for ReplyTo<R> {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.ready.wake();
+    }
+}
+
+/// # [`Deferred`]
+/// A reply that is sent through a [`ReplyTo`] address, which resolves once it arrives.
+///
+/// # Errors
+/// Resolves to an error if the reply address is dropped without a reply, or if the request failed.
+pub struct Deferred<R>(Arc<Slot<R>>);
+
+impl<R> Deferred<R> {
+    /// Creates a reply address along with the reply sent to it.
+    pub(crate) fn channel() -> (ReplyTo<R>, Self) {
+        let slot = Arc::new(Slot { reply: Mutex::new(None), ready: WaitCell::new(), closed: AtomicBool::new(false) });
+        (ReplyTo(slot.clone()), Self(slot))
+    }
+}
+
+impl<R> Future for Deferred<R> {
+    type Output = Result<R, MessageSendError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            if let Some(reply) = self.0.reply.lock().take() {
+                return Poll::Ready(reply);
+            }
+
+            // The reply is sent before the address is dropped, so it has already been taken above if there was one
+            if self.0.closed.load(Ordering::Acquire) && self.0.reply.lock().is_none() {
+                return Poll::Ready(Err(MessageSendError::UnknownError(Box::new(NoReply))));
+            }
+
+            // Wait for the reply or for the address to be dropped, checking again after registering so no wakeup is missed
+            if self.0.ready.poll_wait(cx).is_ready() || self.0.reply.lock().is_some() || self.0.closed.load(Ordering::Acquire) {
+                continue;
+            }
+
+            return Poll::Pending;
+        }
+    }
+}
+
+/// # [`Envelope`]
+/// A message along with the address its response should be sent to.
+pub struct Envelope<M: Message> {
+    /// The message being requested
+    pub message: M,
+    /// Where the response to the message is sent
+    pub reply_to: ReplyTo<M::Result>,
+}
+
+impl<M: Message> Message for Envelope<M> {
+    type Result = ();
+}
+
+impl<M: Message> Envelope<M> {
+    /// # [`Envelope::new`]
+    /// Wraps a message in an envelope, returning it along with the reply.
+    ///
+    /// The reply fails if the reply address is dropped without a reply.
+    #[must_use]
+    pub fn new(message: M) -> (Self, Deferred<M::Result>) {
+        let (reply_to, reply) = Deferred::channel();
+        (Self { message, reply_to }, reply)
+    }
+
+    /// # [`Envelope::ask`]
+    /// Sends the message to the target in an envelope, and waits for whichever actor finally handles it to reply.
+    ///
+    /// # Errors
+    /// Returns an error if the envelope could not be sent, or if it was dropped without a reply.
+    pub async fn ask(target: &dyn MessageSender<Envelope<M>>, message: M) -> Result<M::Result, MessageSendError> {
+        let (envelope, reply) = Self::new(message);
+        target.tell(envelope).await?;
+        reply.await
+    }
+
+    /// # [`Envelope::forward`]
+    /// Passes the envelope on to another actor, which replies to the original requester.
+    /// The forwarding actor does not wait for the reply.
+    ///
+    /// # Errors
+    /// Returns an error if the envelope could not be sent, in which case the requester receives an error too.
+    pub async fn forward(self, target: &dyn MessageSender<Envelope<M>>) -> Result<(), MessageSendError> {
+        target.tell(self).await
+    }
+
+    /// # [`Envelope::handle_with`]
+    /// Has the actor handle the message, and sends its response to the requester.
+    /// This implements [`Handler`] for envelopes of messages the actor can already handle:
+    /// `envelope.handle_with(self, context).await`.
+    pub async fn handle_with<A: Handler<M>, D: Delegate>(self, actor: &A, context: &ActorContext<D>) {
+        let Envelope { message, reply_to } = self;
+        reply_to.reply(actor.handle_message(message, context).await);
+    }
+}
+
+/// # [`Response`]
+/// The response to a request made with [`ActorContext::request_reentrant`], delivered to the requesting actor as a message.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Response<M: Message> {
+    /// The id [`ActorContext::request_reentrant`] returned for the request
+    pub request: u64,
+    /// The response, or the error the request failed with
+    pub result: Result<M::Result, MessageSendError>,
+}
+
+impl<M: Message> Message for Response<M> {
+    type Result = ();
+}
+
+impl<D: Delegate> ActorContext<D> {
+    /// # [`ActorContext::request_reentrant`]
+    /// Sends the message to the target on the system's [`crate::Executor`], and returns an id for the request without
+    /// waiting for the response. The response is delivered to this actor as a [`Response`] carrying the same id, as
+    /// described in the [module documentation](self). The actor's type must be provided, usually as
+    /// `context.request_reentrant::<Self, _>(target, message)`.
+    ///
+    /// The request is sent without the [`crate::Metadata`] of the message being handled, as this actor is no longer
+    /// waiting on it. If this actor has been removed by the time the response arrives, it is recorded as a dead letter.
+    ///
+    /// # Errors
+    /// Returns [`ScheduleError::NoExecutor`] if the system has no [`crate::Executor`] to send the request on.
+    pub fn request_reentrant<A: Handler<Response<M>>, M: Message>(&self, target: Arc<dyn MessageSender<M>>, message: M) -> Result<u64, ScheduleError> {
+        let executor = self.system.executor.as_ref().ok_or(ScheduleError::NoExecutor)?;
+
+        let request = self.requests.fetch_add(1, Ordering::Relaxed);
+        let system = self.system.clone();
+        let id = self.id as u64;
+
+        executor.spawn(Box::pin(async move {
+            let result = target.send(message).await;
+            deliver::<A, Response<M>, D>(system, id, Response { request, result }).await;
+        }));
+
+        Ok(request)
+    }
+}
+
+/// A reply address was dropped without a reply.
+#[derive(Debug)]
+struct NoReply;
+
+impl core::fmt::Display for NoReply {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "NoReply: the reply address was dropped without a reply")
+    }
+}
+
+impl core::error::Error for NoReply {}
@@ -0,0 +1,184 @@
+//! # Retries
+//! A [`RetryPolicy`] retries sends that fail with a transient error, such as a foreign system timing out or becoming
+//! unreachable, waiting longer between each attempt. Any [`MessageSender`] can be wrapped in a [`RetrySender`] that applies
+//! a policy with [`MessageSender::with_retry`], or with [`RetrySender::new`] for senders that are already behind an [`Arc`].
+//!
+//! Local sends only fail if the actor is draining or the send times out, so retries are mostly useful for foreign senders.
+//! A retried message may be handled more than once, so handlers of retried messages should be idempotent,
+//! or the message should be sent with [`MessageSender::send_idempotent`], which retries with the same key.
+
+use core::{future::Future, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use alloc::sync::Arc;
+
+use crate::{hash::mix, Delegate, Fluxion, Message, MessageSendError, MessageSender, Timer};
+
+/// # [`RetryPolicy`]
+/// Decides which failed sends are retried, how many times, and how long to wait between attempts.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// The timer used to wait between attempts
+    timer: Option<Arc<dyn Timer>>,
+    /// How many times a message is sent before giving up
+    max_attempts: u32,
+    /// How long to wait before the first retry
+    initial_backoff: Duration,
+    /// The longest wait between attempts
+    max_backoff: Duration,
+    /// How much the wait grows after each attempt
+    multiplier: u32,
+    /// The most random time added to each wait
+    jitter: Duration,
+    /// Decides whether an error is worth retrying
+    retry_on: Arc<dyn Fn(&MessageSendError) -> bool + Send + Sync>,
+}
+
+impl RetryPolicy {
+    /// # [`RetryPolicy::new`]
+    /// Creates a policy that uses the system's timer to wait between attempts.
+    /// By default, a message is sent up to 3 times, with no wait between attempts,
+    /// and only sends that fail with a [`RetryPolicy::is_transient`] error are retried.
+    pub fn new<D: Delegate>(system: &Fluxion<D>) -> Self {
+        Self {
+            timer: system.timer.clone(),
+            max_attempts: 3,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            multiplier: 2,
+            jitter: Duration::ZERO,
+            retry_on: Arc::new(Self::is_transient),
+        }
+    }
+
+    /// # [`RetryPolicy::max_attempts`]
+    /// Sets how many times a message is sent, including the first attempt, before the last error is returned.
+    #[must_use]
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// # [`RetryPolicy::backoff`]
+    /// Waits `initial` before the first retry, multiplying the wait by `multiplier` after each attempt up to `max`.
+    /// Waits are skipped if the system has no [`Timer`].
+    #[must_use]
+    pub fn backoff(mut self, initial: Duration, max: Duration, multiplier: u32) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self.multiplier = multiplier.max(1);
+        self
+    }
+
+    /// # [`RetryPolicy::jitter`]
+    /// Adds a random time of up to `jitter` to each wait, so that many senders failing at once don't all retry at once.
+    #[must_use]
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// # [`RetryPolicy::retry_on`]
+    /// Retries only the sends that fail with an error for which the predicate returns true.
+    #[must_use]
+    pub fn retry_on(mut self, predicate: impl Fn(&MessageSendError) -> bool + Send + Sync + 'static) -> Self {
+        self.retry_on = Arc::new(predicate);
+        self
+    }
+
+    /// # [`RetryPolicy::is_transient`]
+    /// The default predicate, which retries timeouts and errors returned by the system's delegate.
+    #[must_use]
+    pub fn is_transient(error: &MessageSendError) -> bool {
+        match error {
+            MessageSendError::Timeout => true,
+            #[cfg(feature = "foreign")]
+            MessageSendError::DelegateError { .. } | MessageSendError::PeerUnreachable => true,
+            _ => false,
+        }
+    }
+
+    /// Returns how long to wait after the given attempt failed, with `seed` choosing the jitter.
+    fn delay(&self, attempt: u32, seed: u64) -> Duration {
+        let growth = self.multiplier.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self.initial_backoff.saturating_mul(growth).min(self.max_backoff);
+
+        let jitter = u64::try_from(self.jitter.as_nanos()).unwrap_or(u64::MAX);
+        let jitter = match jitter {
+            0 => Duration::ZERO,
+            jitter => Duration::from_nanos(mix(seed) % jitter),
+        };
+
+        backoff.saturating_add(jitter)
+    }
+
+    /// Runs `send` until it succeeds, fails with an error that isn't retried, or runs out of attempts.
+    async fn run<M: Clone, T, F: Future<Output = Result<T, MessageSendError>>>(&self, seed: u64, message: M, send: impl Fn(M) -> F) -> Result<T, MessageSendError> {
+        let mut attempt = 1;
+
+        loop {
+            let error = match send(message.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(error) => error,
+            };
+
+            if attempt >= self.max_attempts || !(self.retry_on)(&error) {
+                return Err(error);
+            }
+
+            let delay = self.delay(attempt, seed.wrapping_add(u64::from(attempt)));
+            if let Some(timer) = &self.timer
+                && !delay.is_zero() {
+                timer.sleep(delay).await;
+            }
+
+            attempt += 1;
+        }
+    }
+}
+
+/// # [`RetrySender`]
+/// A [`MessageSender`] that retries failed sends according to a [`RetryPolicy`].
+pub struct RetrySender<M: Message> {
+    /// The sender messages are sent through
+    inner: Arc<dyn MessageSender<M>>,
+    /// Decides which failures are retried
+    policy: RetryPolicy,
+    /// Counts sends, so that each chooses different jitter
+    sends: AtomicU64,
+}
+
+impl<M: Message + Clone> RetrySender<M> {
+    /// # [`RetrySender::new`]
+    /// Creates a sender that sends messages through `inner`, retrying failures according to the policy.
+    #[must_use]
+    pub fn new(inner: Arc<dyn MessageSender<M>>, policy: RetryPolicy) -> Self {
+        Self { inner, policy, sends: AtomicU64::new(0) }
+    }
+
+    /// Returns the seed used to choose the jitter of the next send.
+    fn seed(&self) -> u64 {
+        self.sends.fetch_add(1, Ordering::Relaxed).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: Message + Clone> MessageSender<M> for RetrySender<M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.policy.run(self.seed(), message, |message| self.inner.send(message)).await
+    }
+
+    /// Sends the message, allowing each attempt at most `timeout` to respond.
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        self.policy.run(self.seed(), message, |message| self.inner.send_timeout(message, timeout)).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        self.policy.run(self.seed(), message, |message| self.inner.tell(message)).await
+    }
+
+    /// Sends the message with the same key on every attempt, so that it is only handled once
+    /// even if an attempt that timed out reached the receiving system.
+    async fn send_idempotent(&self, key: &str, message: M) -> Result<M::Result, MessageSendError> {
+        self.policy.run(self.seed(), message, |message| self.inner.send_idempotent(key, message)).await
+    }
+}
@@ -0,0 +1,565 @@
+//! # Routers
+//! A [`Router`] owns a pool of identical actors, and distributes messages across them according to a [`RoutingStrategy`].
+//! A [`HashRouter`] instead sends each message to the actor its key hashes to, so that equal keys always reach the same actor.
+//! An [`ActorPool`] queues messages in a single line shared by all of its actors, which each take the next message as soon
+//! as they are idle. Because routers and pools implement [`MessageSender`], they can be used anywhere a single actor
+//! reference could be.
+
+use core::{hash::Hash, sync::atomic::{AtomicU64, AtomicUsize, Ordering}, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use maitake_sync::{spin::RwLock, Semaphore};
+
+use crate::{actor::ExportState, hash::{mix, stable_hash}, Actor, Delegate, Handoff, Fluxion, Handler, LifecycleEvent, LocalRef, Message, MessageSendError, MessageSender};
+
+/// # [`RoutingStrategy`]
+/// Decides which member of a [`Router`]'s pool receives each message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum RoutingStrategy {
+    /// Each member receives a message in turn.
+    #[default]
+    RoundRobin,
+    /// Each message is sent to a member chosen at random.
+    Random,
+    /// Each message is sent to the member that is currently handling the fewest messages from this router.
+    /// Fluxion actors have no mailbox, so this is the closest equivalent to choosing the shortest mailbox.
+    LeastLoaded,
+}
+
+/// A single actor in a [`Router`]'s pool.
+struct Member<A: Actor, D: Delegate> {
+    /// A reference to the actor
+    reference: LocalRef<A, D>,
+    /// The number of messages sent by the router that the actor is currently handling
+    in_flight: AtomicUsize,
+}
+
+/// Decrements a member's in-flight count when dropped, so that cancelled sends are still accounted for.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Like [`InFlight`], but owns the member, so that it can be held by a told message's handler wherever it runs.
+struct Claimed<A: Actor, D: Delegate>(Arc<Member<A, D>>);
+
+impl<A: Actor, D: Delegate> Drop for Claimed<A, D> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns a slot to an [`ActorPool`] when dropped, once the message that took it has been handled.
+struct Slot(Arc<Semaphore>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.add_permits(1);
+    }
+}
+
+/// A pool of identical actors created by a factory, shared by the router types.
+struct Pool<A: Actor, D: Delegate> {
+    /// The system the pool's actors are added to
+    system: Fluxion<D>,
+    /// Creates the pool's actors
+    factory: Box<dyn Fn() -> A + Send + Sync>,
+    /// The pool's actors
+    members: RwLock<Vec<Arc<Member<A, D>>>>,
+}
+
+impl<A: Actor, D: Delegate> Pool<A, D> {
+    /// Creates a pool with `size` actors created by `factory`, killing any that were added if one fails to initialize.
+    async fn new(system: &Fluxion<D>, size: usize, factory: impl Fn() -> A + Send + Sync + 'static) -> Result<Self, A::Error> {
+        let pool = Self {
+            system: system.clone(),
+            factory: Box::new(factory),
+            members: RwLock::new(Vec::with_capacity(size)),
+        };
+
+        for _ in 0..size {
+            match pool.spawn((pool.factory)()).await {
+                Ok(member) => pool.members.write().push(member),
+                Err(e) => {
+                    pool.shutdown().await;
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(pool)
+    }
+
+    fn members(&self) -> Vec<u64> {
+        self.members.read().iter().map(|member| member.reference.get_id()).collect()
+    }
+
+    fn size(&self) -> usize {
+        self.members.read().len()
+    }
+
+    async fn restart(&self, index: usize) -> Result<Option<u64>, A::Error> {
+        if index >= self.size() {
+            return Ok(None);
+        }
+
+        self.replace(index, (self.factory)()).await
+    }
+
+    async fn restart_with_state(&self, index: usize) -> Result<Option<u64>, A::Error>
+        where A: Handoff {
+        let Some(existing) = self.members.read().get(index).cloned() else {
+            return Ok(None);
+        };
+
+        // The old actor keeps running until it is replaced, so a failed export just starts the replacement from scratch
+        let mut actor = (self.factory)();
+        if let Ok(Some(state)) = MessageSender::<ExportState<A::State>>::send(&existing.reference, ExportState::new()).await {
+            actor.import_state(state);
+        }
+
+        self.replace(index, actor).await
+    }
+
+    /// Adds the actor to the system in place of the member at the given position, and kills the member it replaced.
+    async fn replace(&self, index: usize, actor: A) -> Result<Option<u64>, A::Error> {
+        let member = self.spawn(actor).await?;
+        let id = member.reference.get_id();
+
+        // The pool may have shrunk while the new actor was initializing
+        let replaced = {
+            let mut members = self.members.write();
+            members.get_mut(index).map(|existing| core::mem::replace(existing, member))
+        };
+
+        let Some(replaced) = replaced else {
+            self.system.kill::<A>(id).await;
+            return Ok(None);
+        };
+
+        let replaced = replaced.reference.get_id();
+        self.system.kill::<A>(replaced).await;
+        self.system.lifecycle.publish(&LifecycleEvent::ActorRestarted { id, replaced });
+
+        Ok(Some(id))
+    }
+
+    async fn shutdown(&self) {
+        let members = core::mem::take(&mut *self.members.write());
+
+        for member in members {
+            self.system.kill::<A>(member.reference.get_id()).await;
+        }
+    }
+
+    /// Adds a new member to the system.
+    async fn spawn(&self, actor: A) -> Result<Arc<Member<A, D>>, A::Error> {
+        let id = self.system.add(actor).await?;
+
+        // The actor was just added, so it can only be missing if it was killed in the meantime.
+        let reference = self.system.get_local::<A>(id).await;
+
+        Ok(Arc::new(Member {
+            reference: reference.expect("actor should exist immediately after it was added"),
+            in_flight: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Sends a message to the given member using the given send, tracking it as in flight.
+    async fn route<R>(member: Option<Arc<Member<A, D>>>, send: impl AsyncFnOnce(&LocalRef<A, D>) -> Result<R, MessageSendError>) -> Result<R, MessageSendError> {
+        let Some(member) = member else {
+            return Err(MessageSendError::UnknownError(Box::new(EmptyPool)));
+        };
+
+        member.in_flight.fetch_add(1, Ordering::Relaxed);
+        let _in_flight = InFlight(&member.in_flight);
+
+        send(&member.reference).await
+    }
+
+    /// Tells a message to the given member, which counts it as in flight until its handler finishes.
+    async fn tell<M: Message>(member: Option<Arc<Member<A, D>>>, message: M) -> Result<(), MessageSendError>
+        where A: Handler<M> {
+        let Some(member) = member else {
+            return Err(MessageSendError::UnknownError(Box::new(EmptyPool)));
+        };
+
+        member.in_flight.fetch_add(1, Ordering::Relaxed);
+        member.reference.tell_holding(message, Claimed(member.clone())).await
+    }
+}
+
+/// # [`Router`]
+/// Owns a pool of identical actors created by a factory, and distributes messages across them.
+/// Members are added to the system when the router is created, and can be replaced individually with [`Router::restart`].
+pub struct Router<A: Actor, D: Delegate> {
+    /// The pool's actors
+    pool: Pool<A, D>,
+    /// How messages are distributed
+    strategy: RoutingStrategy,
+    /// The number of messages routed so far, used by [`RoutingStrategy::RoundRobin`]
+    next: AtomicUsize,
+    /// The state of the random number generator used by [`RoutingStrategy::Random`]
+    seed: AtomicU64,
+}
+
+impl<A: Actor, D: Delegate> Router<A, D> {
+    /// # [`Router::new`]
+    /// Creates a router with `size` actors created by `factory`, adding each to the system.
+    ///
+    /// # Errors
+    /// Returns an error if any actor fails to initialize. Actors that were already added are killed.
+    pub async fn new(system: &Fluxion<D>, size: usize, strategy: RoutingStrategy, factory: impl Fn() -> A + Send + Sync + 'static) -> Result<Self, A::Error> {
+        Ok(Self {
+            pool: Pool::new(system, size, factory).await?,
+            strategy,
+            next: AtomicUsize::new(0),
+            seed: AtomicU64::new(0x853c_49e6_748f_ea9b),
+        })
+    }
+
+    /// # [`Router::members`]
+    /// Returns the ids of the pool's actors, in pool order.
+    #[must_use]
+    pub fn members(&self) -> Vec<u64> {
+        self.pool.members()
+    }
+
+    /// # [`Router::size`]
+    /// Returns the number of actors in the pool.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.pool.size()
+    }
+
+    /// # [`Router::restart`]
+    /// Replaces the actor at the given position in the pool with a new actor from the factory,
+    /// and kills the old actor once it is no longer in the pool. Returns the new actor's id,
+    /// or [`None`] if the position is out of range.
+    ///
+    /// # Errors
+    /// Returns an error if the new actor fails to initialize, in which case the old actor is left in place.
+    pub async fn restart(&self, index: usize) -> Result<Option<u64>, A::Error> {
+        self.pool.restart(index).await
+    }
+
+    /// # [`Router::restart_with_state`]
+    /// Restarts the actor at the given position like [`Router::restart`], but first asks it to export its state
+    /// with [`Handoff::export_state`], which is imported by the new actor before it initializes.
+    /// Messages handled by the old actor after its state is exported are not reflected in the new actor's state.
+    ///
+    /// # Errors
+    /// Returns an error if the new actor fails to initialize, in which case the old actor is left in place.
+    pub async fn restart_with_state(&self, index: usize) -> Result<Option<u64>, A::Error>
+        where A: Handoff {
+        self.pool.restart_with_state(index).await
+    }
+
+    /// # [`Router::shutdown`]
+    /// Removes every actor from the pool and kills it.
+    pub async fn shutdown(&self) {
+        self.pool.shutdown().await;
+    }
+
+    /// Chooses the member to send the next message to, or [`None`] if the pool is empty.
+    fn choose(&self) -> Option<Arc<Member<A, D>>> {
+        let members = self.pool.members.read();
+
+        if members.is_empty() {
+            return None;
+        }
+
+        let member = match self.strategy {
+            RoutingStrategy::RoundRobin => &members[self.next.fetch_add(1, Ordering::Relaxed) % members.len()],
+            RoutingStrategy::Random => &members[self.random() % members.len()],
+            RoutingStrategy::LeastLoaded => members.iter()
+                .min_by_key(|member| member.in_flight.load(Ordering::Relaxed))?,
+        };
+
+        Some(member.clone())
+    }
+
+    /// Returns a pseudo-random number using splitmix64, which is plenty for spreading load.
+    #[allow(clippy::cast_possible_truncation)]
+    fn random(&self) -> usize {
+        mix(self.seed.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed).wrapping_add(0x9e37_79b9_7f4a_7c15)) as usize
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: Message, D: Delegate> MessageSender<M> for Router<A, D> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        Pool::route(self.choose(), async |member| member.send(message).await).await
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        Pool::route(self.choose(), async |member| member.send_timeout(message, timeout).await).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        Pool::tell(self.choose(), message).await
+    }
+}
+
+/// # [`HashableMessage`]
+/// A message that carries a key, such as an entity id, which a [`HashRouter`] uses to choose its recipient.
+pub trait HashableMessage: Message {
+    /// The type of the message's key
+    type Key: Hash + ?Sized;
+
+    /// # [`HashableMessage::key`]
+    /// Returns the message's key. Messages with equal keys are always sent to the same actor.
+    fn key(&self) -> &Self::Key;
+}
+
+/// The number of points each member occupies on a [`HashRouter`]'s ring, which evens out the share of keys each receives.
+const VIRTUAL_NODES: u64 = 64;
+
+/// # [`HashRouter`]
+/// Owns a pool of identical actors, and sends each [`HashableMessage`] to the actor its key hashes to
+/// on a consistent hash ring. Messages with equal keys always reach the same position in the pool,
+/// which makes this suitable for actors that hold per-entity state.
+///
+/// Restarting a member with [`HashRouter::restart`] keeps its position, so keys continue to map to the replacement.
+pub struct HashRouter<A: Actor, D: Delegate> {
+    /// The pool's actors
+    pool: Pool<A, D>,
+    /// Maps points on the ring to positions in the pool
+    ring: BTreeMap<u64, usize>,
+}
+
+impl<A: Actor, D: Delegate> HashRouter<A, D> {
+    /// # [`HashRouter::new`]
+    /// Creates a router with `size` actors created by `factory`, adding each to the system.
+    ///
+    /// # Errors
+    /// Returns an error if any actor fails to initialize. Actors that were already added are killed.
+    pub async fn new(system: &Fluxion<D>, size: usize, factory: impl Fn() -> A + Send + Sync + 'static) -> Result<Self, A::Error> {
+        let ring = (0..size)
+            .flat_map(|index| (0..VIRTUAL_NODES).map(move |node| (stable_hash(&(index as u64, node)), index)))
+            .collect();
+
+        Ok(Self {
+            pool: Pool::new(system, size, factory).await?,
+            ring,
+        })
+    }
+
+    /// # [`HashRouter::members`]
+    /// Returns the ids of the pool's actors, in pool order.
+    #[must_use]
+    pub fn members(&self) -> Vec<u64> {
+        self.pool.members()
+    }
+
+    /// # [`HashRouter::member_for`]
+    /// Returns the id of the actor that messages with the given key are sent to.
+    #[must_use]
+    pub fn member_for<K: Hash + ?Sized>(&self, key: &K) -> Option<u64> {
+        self.choose(key).map(|member| member.reference.get_id())
+    }
+
+    /// # [`HashRouter::restart`]
+    /// Replaces the actor at the given position in the pool with a new actor from the factory,
+    /// and kills the old actor once it is no longer in the pool. Returns the new actor's id,
+    /// or [`None`] if the position is out of range.
+    ///
+    /// # Errors
+    /// Returns an error if the new actor fails to initialize, in which case the old actor is left in place.
+    pub async fn restart(&self, index: usize) -> Result<Option<u64>, A::Error> {
+        self.pool.restart(index).await
+    }
+
+    /// # [`HashRouter::restart_with_state`]
+    /// Restarts the actor at the given position like [`HashRouter::restart`], but first asks it to export its state
+    /// with [`Handoff::export_state`], which is imported by the new actor before it initializes.
+    /// Messages handled by the old actor after its state is exported are not reflected in the new actor's state.
+    ///
+    /// # Errors
+    /// Returns an error if the new actor fails to initialize, in which case the old actor is left in place.
+    pub async fn restart_with_state(&self, index: usize) -> Result<Option<u64>, A::Error>
+        where A: Handoff {
+        self.pool.restart_with_state(index).await
+    }
+
+    /// # [`HashRouter::shutdown`]
+    /// Removes every actor from the pool and kills it.
+    /// Messages sent after the router is shut down return an error.
+    pub async fn shutdown(&self) {
+        self.pool.shutdown().await;
+    }
+
+    /// Chooses the member responsible for the given key, or [`None`] if the pool is empty.
+    fn choose<K: Hash + ?Sized>(&self, key: &K) -> Option<Arc<Member<A, D>>> {
+        let point = stable_hash(key);
+
+        // The key belongs to the first member at or after its point, wrapping around the ring.
+        let (_, index) = self.ring.range(point..).next()
+            .or_else(|| self.ring.iter().next())?;
+
+        self.pool.members.read().get(*index).cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: HashableMessage, D: Delegate> MessageSender<M> for HashRouter<A, D> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        Pool::route(self.choose(message.key()), async |member| member.send(message).await).await
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        Pool::route(self.choose(message.key()), async |member| member.send_timeout(message, timeout).await).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        let member = self.choose(message.key());
+        Pool::tell(member, message).await
+    }
+}
+
+/// # [`ActorPool`]
+/// Owns a pool of identical workers created by a factory, which share a single queue of messages.
+/// Each worker handles up to [`Actor::MAX_CONCURRENCY`] messages at once, or one if it sets no limit. Messages sent while
+/// every worker is busy wait in line, in the order they were sent, and the first worker to become idle takes the next
+/// one. A worker stuck on a slow message therefore never holds up messages that another worker could be handling, as it
+/// would with a [`Router`] that assigns messages to workers as they are sent.
+pub struct ActorPool<A: Actor, D: Delegate> {
+    /// The pool's workers
+    pool: Pool<A, D>,
+    /// How many messages each worker handles at once
+    capacity: usize,
+    /// One permit for each message the workers can handle at once, which queued messages wait for
+    slots: Arc<Semaphore>,
+    /// Where the search for an idle worker starts, which spreads messages across workers that are all idle
+    next: AtomicUsize,
+}
+
+impl<A: Actor, D: Delegate> ActorPool<A, D> {
+    /// # [`ActorPool::new`]
+    /// Creates a pool with `size` workers created by `factory`, adding each to the system.
+    ///
+    /// # Errors
+    /// Returns an error if any worker fails to initialize. Workers that were already added are killed.
+    pub async fn new(system: &Fluxion<D>, size: usize, factory: impl Fn() -> A + Send + Sync + 'static) -> Result<Self, A::Error> {
+        let capacity = A::MAX_CONCURRENCY.map_or(1, |limit| limit.max(1));
+        let slots = Semaphore::new(size.saturating_mul(capacity).min(Semaphore::MAX_PERMITS));
+
+        // Messages sent to an empty pool would otherwise wait forever
+        if size == 0 {
+            slots.close();
+        }
+
+        Ok(Self {
+            pool: Pool::new(system, size, factory).await?,
+            capacity,
+            slots: Arc::new(slots),
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// # [`ActorPool::members`]
+    /// Returns the ids of the pool's workers, in pool order.
+    #[must_use]
+    pub fn members(&self) -> Vec<u64> {
+        self.pool.members()
+    }
+
+    /// # [`ActorPool::size`]
+    /// Returns the number of workers in the pool.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.pool.size()
+    }
+
+    /// # [`ActorPool::shutdown`]
+    /// Removes every worker from the pool and kills it.
+    /// Queued messages, and messages sent after the pool is shut down, return an error.
+    pub async fn shutdown(&self) {
+        self.slots.close();
+        self.pool.shutdown().await;
+    }
+
+    /// Waits for a worker to become idle, and sends it a message using the given send.
+    async fn route<R>(&self, send: impl AsyncFnOnce(&LocalRef<A, D>) -> Result<R, MessageSendError>) -> Result<R, MessageSendError> {
+        let Ok(_slot) = self.slots.acquire(1).await else {
+            return Err(MessageSendError::UnknownError(Box::new(EmptyPool)));
+        };
+
+        let member = self.claim().ok_or(MessageSendError::UnknownError(Box::new(EmptyPool)))?;
+        let _in_flight = InFlight(&member.in_flight);
+
+        send(&member.reference).await
+    }
+
+    /// Waits for a worker to become idle, and tells it a message. The worker's slot is held until the handler finishes,
+    /// even if the message is handed to the system's executor.
+    async fn tell<M: Message>(&self, message: M) -> Result<(), MessageSendError>
+        where A: Handler<M> {
+        let Ok(slot) = self.slots.acquire(1).await else {
+            return Err(MessageSendError::UnknownError(Box::new(EmptyPool)));
+        };
+
+        let member = self.claim().ok_or(MessageSendError::UnknownError(Box::new(EmptyPool)))?;
+
+        // The permit is returned by the handler's guard instead, which may outlive this call
+        slot.forget();
+        member.reference.tell_holding(message, (Slot(self.slots.clone()), Claimed(member.clone()))).await
+    }
+
+    /// Claims a slot on an idle worker, or returns [`None`] if the pool is empty.
+    /// The caller holds a permit, so a worker always has a free slot, although other senders may take the one it
+    /// finds first, in which case it keeps looking.
+    fn claim(&self) -> Option<Arc<Member<A, D>>> {
+        loop {
+            let members = self.pool.members.read();
+            if members.is_empty() {
+                return None;
+            }
+
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            let idle = (0..members.len())
+                .map(|offset| &members[(start + offset) % members.len()])
+                .find(|member| member.in_flight
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |in_flight| (in_flight < self.capacity).then_some(in_flight + 1))
+                    .is_ok());
+
+            if let Some(member) = idle {
+                return Some(member.clone());
+            }
+            drop(members);
+
+            core::hint::spin_loop();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: Message, D: Delegate> MessageSender<M> for ActorPool<A, D> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.route(async |member| member.send(message).await).await
+    }
+
+    async fn send_timeout(&self, message: M, timeout: Duration) -> Result<M::Result, MessageSendError> {
+        self.route(async |member| member.send_timeout(message, timeout).await).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        ActorPool::tell(self, message).await
+    }
+}
+
+/// The error returned when a message is sent to a [`Router`] with no actors.
+#[derive(Debug)]
+struct EmptyPool;
+
+impl core::fmt::Display for EmptyPool {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "the router has no actors")
+    }
+}
+
+impl core::error::Error for EmptyPool {}
@@ -0,0 +1,265 @@
+//! # Runtimes
+//! Ready made [`Executor`] and [`Timer`] implementations for common async runtimes, each behind a feature of the same
+//! name: [`Tokio`] with `tokio`, [`Smol`] with `smol`, [`AsyncStd`] with `async-std`, [`Embassy`] with `embassy`
+//! for microcontrollers, and [`Wasm`] with `wasm` for browsers. Neither of the last two requires `std`. Each implements
+//! both traits, so it can be passed to [`crate::FluxionBuilder::runtime`] to run the system's background tasks, scheduled
+//! messages, and timeouts on that runtime. Blocking work passed to [`Executor::spawn_blocking`] runs on the runtime's
+//! blocking pool, where it has one.
+//!
+//! Other runtimes only need to implement [`Executor::spawn`] and [`Timer::sleep`], as every timeout is built on the latter.
+
+use core::{future::Future, pin::Pin, time::Duration};
+
+use alloc::boxed::Box;
+#[cfg(any(feature = "tokio", feature = "smol", feature = "async-std"))]
+use std::time::Instant;
+
+use crate::{Executor, Timer};
+
+/// # [`Tokio`]
+/// Runs the system's tasks on the current Tokio runtime, which must be entered whenever a task is spawned.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy)]
+pub struct Tokio {
+    /// When the runtime handle was created, which [`Timer::now`] counts from
+    start: Instant,
+}
+
+#[cfg(feature = "tokio")]
+impl Tokio {
+    /// # [`Tokio::new`]
+    /// Creates a handle to the current Tokio runtime.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Default for Tokio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Executor for Tokio {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        tokio::spawn(future);
+    }
+
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+        tokio::task::spawn_blocking(task);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Timer for Tokio {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    fn now(&self) -> Option<Duration> {
+        Some(self.start.elapsed())
+    }
+}
+
+/// # [`Smol`]
+/// Runs the system's tasks on smol's global executor.
+#[cfg(feature = "smol")]
+#[derive(Debug, Clone, Copy)]
+pub struct Smol {
+    /// When the runtime handle was created, which [`Timer::now`] counts from
+    start: Instant,
+}
+
+#[cfg(feature = "smol")]
+impl Smol {
+    /// # [`Smol::new`]
+    /// Creates a handle to smol's global executor.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Default for Smol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Executor for Smol {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        smol::spawn(future).detach();
+    }
+
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+        smol::unblock(task).detach();
+    }
+}
+
+#[cfg(feature = "smol")]
+impl Timer for Smol {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            smol::Timer::after(duration).await;
+        })
+    }
+
+    fn now(&self) -> Option<Duration> {
+        Some(self.start.elapsed())
+    }
+}
+
+/// # [`AsyncStd`]
+/// Runs the system's tasks on async-std's global executor.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy)]
+pub struct AsyncStd {
+    /// When the runtime handle was created, which [`Timer::now`] counts from
+    start: Instant,
+}
+
+#[cfg(feature = "async-std")]
+impl AsyncStd {
+    /// # [`AsyncStd::new`]
+    /// Creates a handle to async-std's global executor.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl Default for AsyncStd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl Executor for AsyncStd {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        // Dropping the handle detaches the task
+        async_std::task::spawn(future);
+    }
+
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send>) {
+        async_std::task::spawn_blocking(task);
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl Timer for AsyncStd {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async_std::task::sleep(duration))
+    }
+
+    fn now(&self) -> Option<Duration> {
+        Some(self.start.elapsed())
+    }
+}
+
+/// Runs a future spawned through [`Embassy`]. Embassy tasks are statically allocated, so at most this many can run at once.
+#[cfg(feature = "embassy")]
+#[embassy_executor::task(pool_size = 32)]
+async fn embassy_task(future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+    future.await;
+}
+
+/// # [`Embassy`]
+/// Runs the system's tasks on an embassy executor, and keeps time with `embassy-time`, which needs a time driver.
+///
+/// Embassy tasks are statically allocated, and at most 32 of the system's tasks can run at once. Tasks spawned while
+/// the pool is full are dropped without running, so scheduled messages and timers beyond that limit are lost.
+/// Embassy has no blocking pool, so [`Executor::spawn_blocking`] runs closures on the calling thread.
+#[cfg(feature = "embassy")]
+#[derive(Clone, Copy)]
+pub struct Embassy(embassy_executor::SendSpawner);
+
+#[cfg(feature = "embassy")]
+impl Embassy {
+    /// # [`Embassy::new`]
+    /// Spawns the system's tasks with the given spawner.
+    #[must_use]
+    pub fn new(spawner: embassy_executor::Spawner) -> Self {
+        Self(spawner.make_send())
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl Executor for Embassy {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        // The future is dropped if every task in the pool is busy
+        let _ = self.0.spawn(embassy_task(future));
+    }
+}
+
+#[cfg(feature = "embassy")]
+impl Timer for Embassy {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let duration = embassy_time::Duration::from_micros(u64::try_from(duration.as_micros()).unwrap_or(u64::MAX));
+
+        Box::pin(async move {
+            embassy_time::Timer::after(duration).await;
+        })
+    }
+
+    fn now(&self) -> Option<Duration> {
+        Some(Duration::from_micros(embassy_time::Instant::now().as_micros()))
+    }
+}
+
+/// # [`Wasm`]
+/// Runs the system's tasks on the browser's event loop with `wasm-bindgen-futures`, and keeps time with `setTimeout`.
+///
+/// The browser's event loop is single threaded, and has no blocking pool, so [`Executor::spawn_blocking`] runs closures
+/// on the calling thread, blocking the page until they return.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy)]
+pub struct Wasm {
+    /// When the runtime handle was created in milliseconds since the epoch, which [`Timer::now`] counts from
+    start: f64,
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Wasm {
+    /// # [`Wasm::new`]
+    /// Creates a handle to the browser's event loop.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { start: js_sys::Date::now() }
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Default for Wasm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Executor for Wasm {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        wasm_bindgen_futures::spawn_local(future);
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Timer for Wasm {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+
+        // Timeouts are JavaScript values, which can't leave the thread, but there is only ever one thread
+        Box::pin(send_wrapper::SendWrapper::new(gloo_timers::future::TimeoutFuture::new(millis)))
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn now(&self) -> Option<Duration> {
+        Some(Duration::from_millis((js_sys::Date::now() - self.start).max(0.0) as u64))
+    }
+}
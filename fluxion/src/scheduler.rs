@@ -0,0 +1,131 @@
+//! # Scheduler
+//! Messages can be scheduled for delivery after a delay with [`crate::Fluxion::send_after`],
+//! or repeatedly with [`crate::Fluxion::send_interval`]. Scheduling requires both a [`crate::Timer`]
+//! and an [`crate::Executor`] to be provided to the system's [`crate::FluxionBuilder`].
+
+use core::{future::Future, pin::Pin, time::Duration};
+
+use alloc::{boxed::Box, sync::Arc};
+use maitake_sync::WaitQueue;
+
+use crate::{timer::timeout, Executor, Timer};
+
+/// # [`ScheduleError`]
+/// An error that might be returned when scheduling a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScheduleError {
+    /// The system was not built with a [`Timer`].
+    NoTimer,
+    /// The system was not built with an [`Executor`].
+    NoExecutor,
+    /// The executor dropped the task before it finished, for example because it panicked.
+    Abandoned,
+}
+
+impl core::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ScheduleError::NoTimer => write!(f, "ScheduleError: the system has no timer"),
+            ScheduleError::NoExecutor => write!(f, "ScheduleError: the system has no executor"),
+            ScheduleError::Abandoned => write!(f, "ScheduleError: the task was dropped before it finished"),
+        }
+    }
+}
+
+impl core::error::Error for ScheduleError {}
+
+/// # [`ScheduleHandle`]
+/// Cancels a scheduled message. Dropping the handle does not cancel the message.
+#[derive(Clone)]
+pub struct ScheduleHandle(Arc<WaitQueue>);
+
+impl ScheduleHandle {
+    /// # [`ScheduleHandle::cancel`]
+    /// Cancels the scheduled message. Messages that are already being delivered will still arrive.
+    pub fn cancel(&self) {
+        self.0.close();
+    }
+
+    /// # [`ScheduleHandle::is_cancelled`]
+    /// Returns true if the scheduled message has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    /// Returns true if both handles cancel the same scheduled message.
+    pub(crate) fn is(&self, other: &ScheduleHandle) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A delivery of a scheduled message.
+pub(crate) type Delivery = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Spawns a task that runs the delivery produced by `deliver` once `delay` has elapsed, unless the returned handle is
+/// cancelled first. `deliver` is given the handle, and the task ends as soon as the delivery has run.
+pub(crate) fn schedule_once(
+    timer: Option<&Arc<dyn Timer>>,
+    executor: Option<&Arc<dyn Executor>>,
+    delay: Duration,
+    deliver: impl FnOnce(&ScheduleHandle) -> Option<Delivery> + Send + 'static,
+) -> Result<ScheduleHandle, ScheduleError> {
+    let timer = timer.ok_or(ScheduleError::NoTimer)?.clone();
+    let executor = executor.ok_or(ScheduleError::NoExecutor)?;
+
+    let cancelled = Arc::new(WaitQueue::new());
+    let handle = ScheduleHandle(cancelled.clone());
+
+    executor.spawn(Box::pin({
+        let handle = handle.clone();
+        async move {
+            // The delay elapsing first means the message should be delivered.
+            if timeout(timer.as_ref(), delay, cancelled.wait()).await.is_some() {
+                return;
+            }
+
+            if let Some(delivery) = deliver(&handle) {
+                delivery.await;
+            }
+        }
+    }));
+
+    Ok(handle)
+}
+
+/// Spawns a task that runs the delivery produced by `next` after each `period`,
+/// until `next` returns [`None`] or the returned handle is cancelled.
+pub(crate) fn schedule(
+    timer: Option<&Arc<dyn Timer>>,
+    executor: Option<&Arc<dyn Executor>>,
+    period: Duration,
+    mut next: impl FnMut() -> Option<Delivery> + Send + 'static,
+) -> Result<ScheduleHandle, ScheduleError> {
+    let timer = timer.ok_or(ScheduleError::NoTimer)?.clone();
+    let executor = executor.ok_or(ScheduleError::NoExecutor)?;
+
+    let cancelled = Arc::new(WaitQueue::new());
+    let handle = ScheduleHandle(cancelled.clone());
+
+    executor.spawn(Box::pin(async move {
+        // Waiting on the queue only completes once it is closed by a cancellation, so the same wait is raced against
+        // every period rather than joining the queue again each time.
+        let mut cancel = core::pin::pin!(cancelled.wait());
+
+        loop {
+            // The delay elapsing first means the message should be delivered.
+            if timeout(timer.as_ref(), period, cancel.as_mut()).await.is_some() {
+                return;
+            }
+
+            let Some(delivery) = next() else {
+                return;
+            };
+
+            delivery.await;
+        }
+    }));
+
+    Ok(handle)
+}
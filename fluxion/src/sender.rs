@@ -0,0 +1,158 @@
+//! # Senders
+//! Handlers can find out which actor sent the message they are handling with [`crate::ActorContext::sender`].
+//! Because local messages are handled by the sending task, the sender is whichever actor's handler was running on the
+//! task when the message was sent. Messages sent from outside of any handler, such as from `main` or a scheduled
+//! delivery, have no sender. The bundled transport also carries the sender of messages sent to foreign systems.
+//!
+//! Senders are tracked per thread while a future is being polled, so they require the `std` feature.
+
+use core::{cell::RefCell, future::Future, pin::Pin, task::{Context, Poll}};
+
+use alloc::{boxed::Box, string::String, sync::Arc};
+
+std::thread_local! {
+    /// The actor whose handler is currently being polled on this thread
+    static ACTING: RefCell<Option<Caller>> = const { RefCell::new(None) };
+    /// The sender of the message whose handler is currently being polled on this thread
+    static SENDER: RefCell<Option<Caller>> = const { RefCell::new(None) };
+}
+
+/// # [`SenderId`]
+/// Identifies the actor that sent a message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SenderId {
+    /// An actor on the same system as the receiver, with the given id
+    Local(u64),
+    /// An actor on a foreign system
+    Foreign {
+        /// The id of the foreign system
+        system: String,
+        /// The id of the actor on the foreign system
+        actor: u64,
+    },
+}
+
+impl SenderId {
+    /// # [`SenderId::actor`]
+    /// Returns the sender's id on its own system.
+    #[must_use]
+    pub fn actor(&self) -> u64 {
+        match self {
+            SenderId::Local(actor) | SenderId::Foreign { actor, .. } => *actor,
+        }
+    }
+
+    /// # [`SenderId::is_local`]
+    /// Returns true if the sender is on the same system as the receiver.
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        matches!(self, SenderId::Local(_))
+    }
+}
+
+/// An actor, along with the id of the system it runs on.
+#[derive(Debug, Clone)]
+pub(crate) struct Caller {
+    /// The id of the actor's system
+    pub(crate) system: Arc<str>,
+    /// The actor's id
+    pub(crate) actor: u64,
+}
+
+impl Caller {
+    /// Returns the actor whose handler is currently running, if any.
+    pub(crate) fn acting() -> Option<Caller> {
+        ACTING.with(|acting| acting.borrow().clone())
+    }
+
+    /// Returns the sender of the message currently being handled, if it had one.
+    pub(crate) fn sender() -> Option<Caller> {
+        SENDER.with(|sender| sender.borrow().clone())
+    }
+
+    /// Converts the caller to a [`SenderId`], as seen by the given system.
+    pub(crate) fn to_sender_id(&self, system: &str) -> SenderId {
+        if &*self.system == system {
+            SenderId::Local(self.actor)
+        } else {
+            SenderId::Foreign { system: String::from(&*self.system), actor: self.actor }
+        }
+    }
+
+    /// Runs a handler as this actor, recording whichever actor is acting when it is first polled as the sender.
+    pub(crate) fn handle<F: Future>(self, future: F) -> Handling<F> {
+        Handling { acting: Some(self), sender: None, future: Box::pin(future) }
+    }
+
+    /// Converts the caller to the form carried by transport frames.
+    #[cfg(feature = "transport")]
+    pub(crate) fn into_frame(self) -> (String, u64) {
+        (String::from(&*self.system), self.actor)
+    }
+
+    /// Converts the form carried by transport frames back to a caller.
+    #[cfg(feature = "transport")]
+    pub(crate) fn from_frame((system, actor): (String, u64)) -> Caller {
+        Caller { system: Arc::from(system), actor }
+    }
+
+    /// Runs the future as the given actor if there is one, so that every message it sends has that actor as its sender.
+    /// This is used to dispatch messages from actors on foreign systems, and messages told on the system's executor.
+    pub(crate) fn act_if<F: Future>(caller: Option<Caller>, future: F) -> Acting<F> {
+        Acting { acting: caller, future: Box::pin(future) }
+    }
+}
+
+/// Runs a handler with the identity of the actor handling the message and of the message's sender installed.
+pub(crate) struct Handling<F> {
+    /// The actor handling the message, which is only taken while the future is being polled
+    acting: Option<Caller>,
+    /// The sender, once it has been captured by the first poll
+    sender: Option<Option<Caller>>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Handling<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        // The message was sent by whichever actor was acting when the handler first ran
+        let sender = this.sender.get_or_insert_with(Caller::acting).clone();
+
+        // Install both for the duration of the poll, and put back whatever was current before
+        let previous_acting = ACTING.with(|acting| acting.replace(this.acting.take()));
+        let previous_sender = SENDER.with(|current| current.replace(sender));
+        let result = this.future.as_mut().poll(cx);
+        this.acting = ACTING.with(|acting| acting.replace(previous_acting));
+        SENDER.with(|current| current.replace(previous_sender));
+
+        result
+    }
+}
+
+/// Runs a future as the given actor, if there is one.
+pub(crate) struct Acting<F> {
+    /// The actor, which is only taken while the future is being polled
+    acting: Option<Caller>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Acting<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        let Some(acting) = this.acting.take() else {
+            return this.future.as_mut().poll(cx);
+        };
+
+        let previous = ACTING.with(|current| current.replace(Some(acting)));
+        let result = this.future.as_mut().poll(cx);
+        this.acting = ACTING.with(|current| current.replace(previous));
+
+        result
+    }
+}
@@ -0,0 +1,595 @@
+//! # Sharding
+//! Sharding spreads a large number of entities, such as one actor per user or per device, across several Fluxion systems.
+//! Each entity is addressed by its id, which is hashed to a shard, and each shard is owned by exactly one system.
+//!
+//! Every participating system creates a [`ShardRegion`] with the same name and number of shards, and is told about the other
+//! systems with [`ShardRegion::add_node`]. Messages sent through any region are forwarded through the system's [`Delegate`]
+//! to the region that owns the entity's shard, which creates the entity on its first message.
+//! Shards are assigned to systems with rendezvous hashing, so when a system joins or leaves only the shards it gains or loses move.
+//!
+//! Every region checks that it owns a message's shard before delivering it, including messages forwarded by another
+//! region, whose view of the region's systems may be out of date. Those messages are forwarded again to the owner the
+//! region knows of. With the `std` feature, a message is forwarded at most twice, so that regions that briefly disagree
+//! about who owns a shard don't pass it back and forth. Messages for an entity that is still live in a region are
+//! delivered to it there, even if its shard has just moved.
+//!
+//! Entities that a region no longer owns are killed, and are recreated by their new owner when they are next messaged.
+//! Regions created with [`ShardRegion::with_handoff`] first export each moving entity's state with
+//! [`crate::Handoff::export_state`] and send it to the new owner in an [`ImportEntity`], which recreates the entity with it.
+
+use core::{convert::Infallible, future::Future, pin::Pin, sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use alloc::{borrow::ToOwned, boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use maitake_sync::{spin, RwLock};
+
+use crate::{hash::stable_hash, Actor, ActorContext, AddActorError, Delegate, Fluxion, Handler, Identifier, IndeterminateMessage, MessageSendError, MessageSender, ScheduleError, ScheduleHandle};
+#[cfg(feature = "transport")]
+use crate::{actor::ExportState, Handoff, Message, MessageID};
+
+/// The metadata entry set on a message forwarded by a region that received it from another region, holding the
+/// region's name and the entity's id.
+#[cfg(feature = "std")]
+const FORWARDED: &str = "fluxion::sharding::forwarded";
+
+/// # [`EntityMessage`]
+/// A message addressed to a sharded entity.
+pub trait EntityMessage: IndeterminateMessage
+    where Self::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+    /// # [`EntityMessage::entity_id`]
+    /// Returns the id of the entity the message is addressed to.
+    fn entity_id(&self) -> &str;
+
+    /// # [`EntityMessage::shard`]
+    /// Returns the shard the entity belongs to, out of `shards` shards.
+    /// By default, this is a hash of the entity's id. It can be overridden to keep related entities in the same shard.
+    fn shard(&self, shards: u32) -> u32 {
+        shard_of(self.entity_id(), shards)
+    }
+
+    /// # [`EntityMessage::undeliverable`]
+    /// Returns the result a region answers the message with when another region sent it the message, but the region
+    /// that owns the entity's shard could not be reached when it forwarded it on.
+    fn undeliverable(error: MessageSendError) -> Self::Result;
+}
+
+/// # [`ImportEntity`]
+/// Sent by a region to the new owner of an entity's shard, with the state the entity exported, so that it can be recreated
+/// there with its state. The [`ShardRegionActor`] of regions created with [`ShardRegion::with_handoff`] must be exported
+/// for it: `exports.export::<ShardRegionActor<A, D>, ImportEntity<A::State>>()`.
+#[cfg(feature = "transport")]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ImportEntity<S> {
+    /// The id of the entity
+    pub entity: String,
+    /// The shard the entity belongs to
+    pub shard: u32,
+    /// The state the entity exported, if it exported any
+    pub state: Option<S>,
+}
+
+#[cfg(feature = "transport")]
+impl<S: Send + 'static> Message for ImportEntity<S> {
+    type Result = ();
+}
+
+#[cfg(feature = "transport")]
+impl<S> MessageID for ImportEntity<S> {
+    const ID: &'static str = "fluxion::sharding::ImportEntity";
+}
+
+/// A live entity in a region.
+struct Entity {
+    /// The entity actor's id
+    id: u64,
+    /// The shard the entity belongs to
+    shard: u32,
+    /// Whether the entity has received a message since the last passivation sweep
+    active: AtomicBool,
+}
+
+/// Hands an entity that is moving to another system over to the region that now owns it, given its id, actor id, and shard.
+type HandOff<A, D> = for<'a> fn(&'a Region<A, D>, &'a str, u64, u32) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// The state shared by a [`ShardRegion`] and its region actor.
+struct Region<A, D> {
+    /// The system the region's entities are added to
+    system: Fluxion<D>,
+    /// The name of the region, which is also the name of its region actor on every system
+    name: String,
+    /// The number of shards entities are divided into
+    shards: u32,
+    /// The ids of the systems participating in the region, including this one
+    nodes: spin::RwLock<Vec<String>>,
+    /// Creates an entity given its id
+    factory: Box<dyn Fn(&str) -> A + Send + Sync>,
+    /// The live entities owned by this region, keyed by entity id
+    entities: RwLock<BTreeMap<String, Entity>>,
+    /// Hands moving entities over to their new owner, if the region was created with [`ShardRegion::with_handoff`]
+    hand_off: Option<HandOff<A, D>>,
+}
+
+impl<A: Actor<Error = Infallible>, D: Delegate> Region<A, D> {
+    /// Returns the id of the system that owns the given shard.
+    fn owner(&self, shard: u32) -> String {
+        let nodes = self.nodes.read();
+
+        // Rendezvous hashing: the node with the highest score for the shard owns it.
+        nodes.iter()
+            .max_by_key(|node| stable_hash(&(node.as_str(), shard)))
+            .cloned()
+            .unwrap_or_else(|| self.system.get_id().to_owned())
+    }
+
+    /// Delivers a message to its entity, forwarding it to the region that owns the entity's shard unless the entity is live
+    /// in this region. `forwarded` is true if another region sent the message to this one, and `key` is the idempotency key
+    /// the message is forwarded with, if it was sent with one.
+    async fn route<M: EntityMessage>(&self, message: M, key: Option<&str>, forwarded: bool) -> Result<M::Result, MessageSendError>
+        where A: Handler<M>, M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        let entity = message.entity_id();
+        let shard = message.shard(self.shards);
+
+        // A message that a region has already forwarded once is delivered wherever it arrives
+        #[cfg(feature = "std")]
+        let owned = forwarded && crate::Metadata::current()
+            .is_some_and(|metadata| metadata.get(FORWARDED) == Some(alloc::format!("{}/{entity}", self.name).as_str()));
+        #[cfg(not(feature = "std"))]
+        let owned = false;
+
+        loop {
+            let existing = self.entities.read().await.get(entity).map(|entity| {
+                entity.active.store(true, Ordering::Relaxed);
+                entity.id
+            });
+
+            let id = match existing {
+                Some(id) => id,
+                None => match self.create(entity, shard, owned).await {
+                    Some(id) => id,
+                    None => return self.forward(message, key, forwarded).await,
+                },
+            };
+
+            if let Some(actor) = self.system.get_local::<A>(id).await {
+                return actor.send(message).await;
+            }
+
+            // The entity was killed between being looked up and being messaged, so it is forgotten and recreated.
+            let mut entities = self.entities.write().await;
+            if entities.get(entity).is_some_and(|entity| entity.id == id) {
+                entities.remove(entity);
+            }
+        }
+    }
+
+    /// Forwards a message to the region that owns its entity's shard. `forwarded` is true if another region sent the
+    /// message to this one, in which case it is marked so that it isn't forwarded again.
+    async fn forward<M: EntityMessage>(&self, message: M, key: Option<&str>, forwarded: bool) -> Result<M::Result, MessageSendError>
+        where A: Handler<M>, M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        let owner = self.owner(message.shard(self.shards));
+
+        let Some(region) = self.system.get::<ShardRegionActor<A, D>, M>(Identifier::ForeignNamed(&self.name, &owner)).await else {
+            return Err(MessageSendError::UnknownError(Box::new(RegionUnreachable(owner))));
+        };
+
+        #[cfg(feature = "std")]
+        let metadata = forwarded.then(|| {
+            let mut metadata = crate::Metadata::current().unwrap_or_default();
+            metadata.insert(FORWARDED, &alloc::format!("{}/{}", self.name, message.entity_id()));
+            metadata
+        });
+        #[cfg(not(feature = "std"))]
+        let _ = forwarded;
+
+        let send = async move {
+            match key {
+                Some(key) => region.send_idempotent(key, message).await,
+                None => region.send(message).await,
+            }
+        };
+
+        #[cfg(feature = "std")]
+        let send = crate::Metadata::scope_if(metadata, send);
+
+        send.await
+    }
+
+    /// Creates an entity, unless another message created it first, and returns its actor's id.
+    /// Returns [`None`] if the entity's shard is owned by another system, unless `owned` is true.
+    async fn create(&self, entity: &str, shard: u32, owned: bool) -> Option<u64> {
+        let mut entities = self.entities.write().await;
+
+        if let Some(existing) = entities.get(entity) {
+            return Some(existing.id);
+        }
+
+        // Ownership is checked while the entities are locked, so an entity can't be created after a rebalance missed it
+        if !owned && self.owner(shard) != self.system.get_id() {
+            return None;
+        }
+
+        let Ok(id) = self.system.add((self.factory)(entity)).await;
+        entities.insert(entity.to_owned(), Entity { id, shard, active: AtomicBool::new(true) });
+
+        Some(id)
+    }
+
+    /// Moves every entity whose shard is now owned by another system, handing it over to its new owner if the region
+    /// hands off entities, and killing it.
+    async fn rebalance(&self) {
+        let local = self.system.get_id();
+
+        let moved = self.entities.read().await.iter()
+            .filter(|(_, entity)| self.owner(entity.shard) != local)
+            .map(|(entity, moved)| (entity.clone(), moved.id, moved.shard))
+            .collect::<Vec<_>>();
+
+        for (entity, id, shard) in moved {
+            // The entity keeps handling messages until its state has been handed over
+            if let Some(hand_off) = self.hand_off {
+                hand_off(self, &entity, id, shard).await;
+            }
+
+            {
+                let mut entities = self.entities.write().await;
+                if entities.get(&entity).is_some_and(|entity| entity.id == id) {
+                    entities.remove(&entity);
+                }
+            }
+
+            self.system.kill::<A>(id).await;
+        }
+    }
+
+    /// Recreates an entity that another region handed over with the state it exported. An entity that was already
+    /// created here, because a message reached it first, is replaced, as the exported state reflects every message the
+    /// entity handled before it moved.
+    #[cfg(feature = "transport")]
+    async fn import(&self, message: ImportEntity<A::State>)
+        where A: Handoff {
+        let mut actor = (self.factory)(&message.entity);
+        if let Some(state) = message.state {
+            actor.import_state(state);
+        }
+
+        let Ok(id) = self.system.add(actor).await;
+        let entity = Entity { id, shard: message.shard, active: AtomicBool::new(true) };
+        let replaced = self.entities.write().await.insert(message.entity, entity);
+
+        if let Some(replaced) = replaced {
+            self.system.kill::<A>(replaced.id).await;
+        }
+    }
+
+    /// Kills every entity that has not received a message since the previous sweep.
+    async fn passivate_idle(&self) {
+        let idle = {
+            let mut entities = self.entities.write().await;
+            let mut idle = Vec::new();
+
+            entities.retain(|_, entity| {
+                let active = entity.active.swap(false, Ordering::Relaxed);
+                if !active {
+                    idle.push(entity.id);
+                }
+                active
+            });
+
+            idle
+        };
+
+        for id in idle {
+            self.system.kill::<A>(id).await;
+        }
+    }
+}
+
+/// # [`ShardRegionActor`]
+/// The actor that receives messages for a [`ShardRegion`]'s entities from other systems.
+/// It is added to the system under the region's name when the region is created, and must be exported by
+/// whatever transport the system's [`Delegate`] uses for each [`EntityMessage`] the entities handle.
+pub struct ShardRegionActor<A, D>(Arc<Region<A, D>>);
+
+impl<A: Actor<Error = Infallible>, D: Delegate> Actor for ShardRegionActor<A, D> {
+    type Error = ();
+}
+
+impl<A: Handler<M, Error = Infallible>, M: EntityMessage, D: Delegate> Handler<M> for ShardRegionActor<A, D>
+    where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+    async fn handle_message<D2: Delegate>(&self, message: M, _context: &ActorContext<D2>) -> M::Result {
+        self.0.route(message, None, true).await.unwrap_or_else(M::undeliverable)
+    }
+}
+
+#[cfg(feature = "transport")]
+impl<A: Handoff<Error = Infallible>, D: Delegate> Handler<ImportEntity<A::State>> for ShardRegionActor<A, D>
+    where A::State: serde::Serialize + for<'a> serde::Deserialize<'a> {
+    async fn handle_message<D2: Delegate>(&self, message: ImportEntity<A::State>, _context: &ActorContext<D2>) {
+        self.0.import(message).await;
+    }
+}
+
+/// Exports the state of an entity that is moving to another system, and sends it to the region that now owns its shard.
+/// A failed export or import leaves the new owner to recreate the entity from scratch.
+#[cfg(feature = "transport")]
+fn hand_off<'a, A, D>(region: &'a Region<A, D>, entity: &'a str, id: u64, shard: u32) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>
+    where A: Handoff<Error = Infallible>, A::State: serde::Serialize + for<'b> serde::Deserialize<'b>, D: Delegate {
+    Box::pin(async move {
+        let Some(actor) = region.system.get_local::<A>(id).await else {
+            return;
+        };
+
+        let state = actor.send(ExportState::<A::State>::new()).await.ok().flatten();
+        let owner = region.owner(shard);
+
+        if let Some(target) = region.system.get::<ShardRegionActor<A, D>, ImportEntity<A::State>>(Identifier::ForeignNamed(&region.name, &owner)).await {
+            let _ = target.send(ImportEntity { entity: entity.to_owned(), shard, state }).await;
+        }
+    })
+}
+
+/// # [`ShardRegion`]
+/// Routes [`EntityMessage`]s to entities of type `A`, which are spread across every system participating in the region.
+///
+/// Entities are created on demand by a factory, and so their [`Actor::initialize`] may not fail.
+/// Use `#[actor(core::convert::Infallible)]` to declare such an actor.
+pub struct ShardRegion<A, D>(Arc<Region<A, D>>);
+
+impl<A, D> Clone for ShardRegion<A, D> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<A: Actor<Error = Infallible>, D: Delegate> ShardRegion<A, D> {
+    /// # [`ShardRegion::new`]
+    /// Creates a region with the given name and number of shards, adding its [`ShardRegionActor`] to the system under the region's name.
+    /// Initially, the local system is the only node in the region.
+    ///
+    /// # Errors
+    /// Returns an error if the region's name is already taken by another actor.
+    pub async fn new(system: &Fluxion<D>, name: &str, shards: u32, factory: impl Fn(&str) -> A + Send + Sync + 'static) -> Result<Self, AddActorError<()>> {
+        Self::create(system, name, shards, Box::new(factory), None).await
+    }
+
+    /// # [`ShardRegion::with_handoff`]
+    /// Creates a region like [`ShardRegion::new`], whose entities hand their state over to their new owner with
+    /// [`Handoff`] when their shard moves to another system, rather than starting from scratch there.
+    /// Messages an entity handles after its state is exported are not reflected in the state it is recreated with.
+    ///
+    /// # Errors
+    /// Returns an error if the region's name is already taken by another actor.
+    #[cfg(feature = "transport")]
+    pub async fn with_handoff(system: &Fluxion<D>, name: &str, shards: u32, factory: impl Fn(&str) -> A + Send + Sync + 'static) -> Result<Self, AddActorError<()>>
+        where A: Handoff, A::State: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        Self::create(system, name, shards, Box::new(factory), Some(hand_off::<A, D>)).await
+    }
+
+    /// Creates a region and adds its [`ShardRegionActor`] to the system.
+    async fn create(system: &Fluxion<D>, name: &str, shards: u32, factory: Box<dyn Fn(&str) -> A + Send + Sync>, hand_off: Option<HandOff<A, D>>) -> Result<Self, AddActorError<()>> {
+        let region = Arc::new(Region {
+            system: system.clone(),
+            name: name.to_owned(),
+            shards: shards.max(1),
+            nodes: spin::RwLock::new(alloc::vec![system.get_id().to_owned()]),
+            factory,
+            entities: RwLock::new(BTreeMap::new()),
+            hand_off,
+        });
+
+        system.add_named(name, ShardRegionActor(region.clone())).await?;
+
+        Ok(Self(region))
+    }
+
+    /// # [`ShardRegion::add_node`]
+    /// Adds a system to the region, moving the shards it now owns to it.
+    pub async fn add_node(&self, system: &str) {
+        {
+            let mut nodes = self.0.nodes.write();
+            if nodes.iter().any(|node| node == system) {
+                return;
+            }
+            nodes.push(system.to_owned());
+        }
+
+        self.0.rebalance().await;
+    }
+
+    /// # [`ShardRegion::remove_node`]
+    /// Removes a system from the region, so that the shards it owned are moved to the remaining systems.
+    /// The local system can not be removed.
+    pub async fn remove_node(&self, system: &str) {
+        if system == self.0.system.get_id() {
+            return;
+        }
+
+        self.0.nodes.write().retain(|node| node != system);
+        self.0.rebalance().await;
+    }
+
+    /// # [`ShardRegion::apply_membership`]
+    /// Updates the region's systems from a change in the cluster's membership, adding systems that joined or recovered
+    /// and removing systems that failed. Suspected systems keep their shards until they are declared failed.
+    #[cfg(feature = "transport")]
+    pub async fn apply_membership(&self, event: &crate::MembershipEvent) {
+        match event {
+            crate::MembershipEvent::NodeJoined(system) | crate::MembershipEvent::NodeRecovered(system) => self.add_node(system).await,
+            crate::MembershipEvent::NodeFailed(system) => self.remove_node(system).await,
+            _ => {},
+        }
+    }
+
+    /// # [`ShardRegion::nodes`]
+    /// Returns the ids of the systems participating in the region.
+    #[must_use]
+    pub fn nodes(&self) -> Vec<String> {
+        self.0.nodes.read().clone()
+    }
+
+    /// # [`ShardRegion::owner`]
+    /// Returns the id of the system that owns the given shard.
+    #[must_use]
+    pub fn owner(&self, shard: u32) -> String {
+        self.0.owner(shard)
+    }
+
+    /// # [`ShardRegion::shard_for`]
+    /// Returns the shard an entity id belongs to when [`EntityMessage::shard`] is not overridden.
+    #[must_use]
+    pub fn shard_for(&self, entity: &str) -> u32 {
+        shard_of(entity, self.0.shards)
+    }
+
+    /// # [`ShardRegion::entities`]
+    /// Returns the ids of the entities currently live in this system.
+    pub async fn entities(&self) -> Vec<String> {
+        self.0.entities.read().await.keys().cloned().collect()
+    }
+
+    /// # [`ShardRegion::passivate_after`]
+    /// Kills entities that receive no messages for roughly the given duration. An idle entity is killed after between one
+    /// and two periods, and is recreated when it is next messaged.
+    ///
+    /// # Errors
+    /// Returns an error if the system was built without a [`crate::Timer`] or an [`crate::Executor`].
+    pub fn passivate_after(&self, idle: Duration) -> Result<ScheduleHandle, ScheduleError> {
+        let region = self.0.clone();
+
+        crate::scheduler::schedule(self.0.system.timer.as_ref(), self.0.system.executor.as_ref(), idle, move || {
+            let region = region.clone();
+            Some(Box::pin(async move { region.passivate_idle().await }))
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M, Error = Infallible>, M: EntityMessage, D: Delegate> MessageSender<M> for ShardRegion<A, D>
+    where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.0.route(message, None, false).await
+    }
+
+    async fn send_idempotent(&self, key: &str, message: M) -> Result<M::Result, MessageSendError> {
+        self.0.route(message, Some(key), false).await
+    }
+}
+
+/// The error returned when the system that owns an entity's shard can not be reached.
+#[derive(Debug)]
+struct RegionUnreachable(String);
+
+impl core::fmt::Display for RegionUnreachable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "the shard region on system {} could not be reached", self.0)
+    }
+}
+
+impl core::error::Error for RegionUnreachable {}
+
+/// Returns the shard an entity id belongs to.
+#[allow(clippy::cast_possible_truncation)]
+fn shard_of(entity: &str, shards: u32) -> u32 {
+    (stable_hash(entity) % u64::from(shards.max(1))) as u32
+}
+
+#[cfg(all(test, feature = "transport"))]
+mod tests {
+    use core::sync::atomic::AtomicU64;
+
+    use super::*;
+    use crate::LocalNetwork;
+
+    /// Adds one to an entity's count, returning the new count.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Add(String);
+
+    impl Message for Add {
+        type Result = u64;
+    }
+
+    impl MessageID for Add {
+        const ID: &'static str = "fluxion::sharding::tests::Add";
+    }
+
+    impl EntityMessage for Add {
+        fn entity_id(&self) -> &str {
+            &self.0
+        }
+
+        fn undeliverable(_error: MessageSendError) -> u64 {
+            0
+        }
+    }
+
+    /// Counts the messages it handled, handing the count over when it moves.
+    #[derive(Default)]
+    struct Counter(AtomicU64);
+
+    impl Actor for Counter {
+        type Error = Infallible;
+    }
+
+    impl Handler<Add> for Counter {
+        async fn handle_message<D: Delegate>(&self, _message: Add, _context: &ActorContext<D>) -> u64 {
+            self.0.fetch_add(1, Ordering::Relaxed) + 1
+        }
+    }
+
+    impl Handoff for Counter {
+        type State = u64;
+
+        async fn export_state(&self) -> Option<u64> {
+            Some(self.0.load(Ordering::Relaxed))
+        }
+
+        fn import_state(&mut self, state: u64) {
+            *self.0.get_mut() = state;
+        }
+    }
+
+    #[tokio::test]
+    async fn hands_moving_entities_over_to_their_new_owner() {
+        let (_network, systems) = LocalNetwork::with_systems(["a", "b"]);
+        let first = ShardRegion::with_handoff(&systems[0], "counters", 64, |_: &str| Counter::default()).await.unwrap();
+        let second = ShardRegion::with_handoff(&systems[1], "counters", 64, |_: &str| Counter::default()).await.unwrap();
+        second.add_node("a").await;
+
+        let mut entities = (0..16).map(|i| alloc::format!("entity-{i}")).collect::<Vec<_>>();
+        entities.sort();
+
+        for entity in &entities {
+            assert_eq!(first.send(Add(entity.clone())).await.unwrap(), 1);
+        }
+
+        first.add_node("b").await;
+
+        let (moved, stayed): (Vec<_>, Vec<_>) = entities.into_iter().partition(|entity| first.owner(first.shard_for(entity)) == "b");
+        assert!(!moved.is_empty() && !stayed.is_empty());
+
+        assert_eq!(first.entities().await, stayed);
+        assert_eq!(second.entities().await, moved);
+
+        // Moved entities carry on from the count they exported
+        for entity in &moved {
+            assert_eq!(second.send(Add(entity.clone())).await.unwrap(), 2);
+        }
+
+        for entity in &stayed {
+            assert_eq!(first.send(Add(entity.clone())).await.unwrap(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn replaces_entities_created_before_their_state_arrives() {
+        let (_network, systems) = LocalNetwork::with_systems(["a"]);
+        let region = ShardRegion::with_handoff(&systems[0], "counters", 64, |_: &str| Counter::default()).await.unwrap();
+
+        assert_eq!(region.send(Add("entity".into())).await.unwrap(), 1);
+
+        let import = ImportEntity { entity: "entity".into(), shard: region.shard_for("entity"), state: Some(10) };
+        region.0.import(import).await;
+
+        assert_eq!(region.entities().await, ["entity"]);
+        assert_eq!(region.send(Add("entity".into())).await.unwrap(), 11);
+    }
+}
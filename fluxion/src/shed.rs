@@ -0,0 +1,116 @@
+//! # Load Shedding
+//! A system built with [`LoadShedding`] counts the messages being handled by all of its local actors at once. Fluxion has
+//! no mailboxes, as messages are handled by the sending task, so this count takes the place of the number of queued messages.
+//! Once it reaches the watermark, low priority sends are rejected with [`MessageSendError::Overloaded`] until it drops
+//! below the watermark again, protecting the process from running out of memory under a storm of messages.
+//! [`crate::Fluxion::overload_events`] reports when the system becomes overloaded and when it recovers.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use alloc::sync::Arc;
+
+use crate::{channel::Publisher, Dispatch, MessageSendError};
+
+/// # [`LoadShedding`]
+/// When a system rejects messages because it is handling too many at once, set with [`crate::FluxionBuilder::load_shedding`].
+#[derive(Clone)]
+pub struct LoadShedding {
+    /// The number of messages being handled at once at which low priority sends are rejected
+    watermark: usize,
+    /// Decides whether a send is low priority
+    low_priority: Arc<dyn Fn(&Dispatch) -> bool + Send + Sync>,
+}
+
+impl LoadShedding {
+    /// # [`LoadShedding::new`]
+    /// Rejects sends once `watermark` messages are being handled at once. By default, every send is low priority.
+    #[must_use]
+    pub fn new(watermark: usize) -> Self {
+        Self { watermark, low_priority: Arc::new(|_| true) }
+    }
+
+    /// # [`LoadShedding::low_priority`]
+    /// Only rejects the sends for which the predicate returns true, for example based on the message's type or metadata.
+    #[must_use]
+    pub fn low_priority(mut self, predicate: impl Fn(&Dispatch) -> bool + Send + Sync + 'static) -> Self {
+        self.low_priority = Arc::new(predicate);
+        self
+    }
+}
+
+/// # [`OverloadEvent`]
+/// Published when a system with [`LoadShedding`] starts or stops rejecting sends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverloadEvent {
+    /// The number of messages being handled reached the watermark, and low priority sends are being rejected.
+    Overloaded {
+        /// The number of messages being handled
+        in_flight: usize,
+    },
+    /// The number of messages being handled dropped below the watermark, and sends are accepted again.
+    Recovered {
+        /// The number of messages being handled
+        in_flight: usize,
+    },
+}
+
+/// Tracks the number of messages being handled by a system's local actors.
+#[derive(Default)]
+pub(crate) struct Load {
+    /// When sends are rejected, if they ever are
+    pub(crate) shedding: Option<LoadShedding>,
+    /// The number of messages being handled
+    in_flight: AtomicUsize,
+    /// Whether the watermark has been reached
+    overloaded: AtomicBool,
+    /// Publishes when the system becomes overloaded or recovers
+    pub(crate) events: Publisher<OverloadEvent>,
+}
+
+impl Load {
+    /// Creates the load of a system, which sheds sends according to the configuration if there is one.
+    pub(crate) fn new(shedding: Option<LoadShedding>) -> Self {
+        Self { shedding, ..Self::default() }
+    }
+
+    /// Admits a message, which is counted until the returned guard is dropped, or rejects it if the system is overloaded.
+    pub(crate) fn admit<M>(&self, actor: u64) -> Result<Admitted<'_>, MessageSendError> {
+        // Systems without load shedding don't need to count messages
+        let Some(shedding) = &self.shedding else {
+            return Ok(Admitted(None));
+        };
+
+        let in_flight = self.in_flight.load(Ordering::Relaxed);
+        if in_flight >= shedding.watermark {
+            if !self.overloaded.swap(true, Ordering::Relaxed) {
+                self.events.publish(&OverloadEvent::Overloaded { in_flight });
+            }
+
+            if (shedding.low_priority)(&Dispatch::new::<M>(actor)) {
+                return Err(MessageSendError::Overloaded);
+            }
+        }
+
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Ok(Admitted(Some(self)))
+    }
+}
+
+/// A message counted by a system's [`Load`] until it is dropped.
+pub(crate) struct Admitted<'a>(Option<&'a Load>);
+
+impl Drop for Admitted<'_> {
+    fn drop(&mut self) {
+        let Some(load) = self.0 else {
+            return;
+        };
+
+        let in_flight = load.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        let watermark = load.shedding.as_ref().map_or(usize::MAX, |shedding| shedding.watermark);
+
+        if in_flight < watermark && load.overloaded.swap(false, Ordering::Relaxed) {
+            load.events.publish(&OverloadEvent::Recovered { in_flight });
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! # Shutdown
+//! [`crate::Fluxion::shutdown`] stops actors in phases, so that for example actors receiving work from outside the system
+//! can be stopped before the workers they feed, which are in turn stopped before the storage the workers write to.
+//!
+//! Phases are declared in order with [`crate::FluxionBuilder::shutdown_phase`], each with a deadline, and actors are
+//! assigned to them with [`crate::Fluxion::set_shutdown_phase`]. The actors of a phase are stopped one at a time, and any
+//! that have not stopped when the phase's deadline elapses are reported and force-killed once every phase has run.
+//! Actors without a phase are stopped last, all at once. Deadlines are only enforced if the system has a [`crate::Timer`].
+//!
+//! Messages sent to an actor once it has started stopping fail with [`crate::MessageSendError::SystemShuttingDown`],
+//! and each actor finishes handling the messages it was already sent before it is killed.
+//!
+//! Anything can ask the system to shut down with [`Fluxion::request_shutdown`], which wakes every task waiting on
+//! [`Fluxion::on_shutdown_requested`], such as a binary's main task, which then calls [`Fluxion::shutdown`]. With the
+//! `signals` feature, [`Fluxion::shutdown_on_signal`] does this for SIGINT and SIGTERM, so binaries don't each need
+//! their own signal handling.
+
+use core::{sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use alloc::{string::String, vec::Vec};
+use maitake_sync::WaitQueue;
+
+use crate::{Delegate, Fluxion};
+
+/// # [`ShutdownPhase`]
+/// A named group of actors that are stopped together during shutdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownPhase {
+    /// The phase's name
+    pub name: String,
+    /// How long the phase's actors have to stop before they are force-killed
+    pub deadline: Duration,
+}
+
+/// # [`ShutdownReport`]
+/// Describes how a shutdown went.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ShutdownReport {
+    /// Every actor that did not stop before its phase's deadline, in the order they were due to stop
+    pub timed_out: Vec<StopTimeout>,
+}
+
+impl ShutdownReport {
+    /// # [`ShutdownReport::is_clean`]
+    /// Returns true if every actor stopped before its phase's deadline.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.timed_out.is_empty()
+    }
+}
+
+/// # [`StopTimeout`]
+/// An actor that did not stop before its shutdown phase's deadline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StopTimeout {
+    /// The actor's id
+    pub id: u64,
+    /// The type name of the actor
+    pub actor: &'static str,
+    /// The name of the phase the actor was stopped in
+    pub phase: String,
+}
+
+/// Whether a system has been asked to shut down, and the tasks waiting for it to be.
+#[derive(Default)]
+pub(crate) struct ShutdownRequest {
+    /// Set once a shutdown is requested
+    requested: AtomicBool,
+    /// Closed once a shutdown is requested, which wakes every waiting task
+    waiters: WaitQueue,
+}
+
+impl ShutdownRequest {
+    /// Requests a shutdown, waking every waiting task.
+    pub(crate) fn request(&self) {
+        if !self.requested.swap(true, Ordering::AcqRel) {
+            self.waiters.close();
+        }
+    }
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::request_shutdown`]
+    /// Asks the system to shut down, waking every task waiting on [`Fluxion::on_shutdown_requested`].
+    /// This does not stop any actors by itself. [`Fluxion::shutdown`] requests a shutdown as it starts.
+    pub fn request_shutdown(&self) {
+        self.shutdown_request.request();
+    }
+
+    /// # [`Fluxion::is_shutdown_requested`]
+    /// Returns true if a shutdown has been requested.
+    #[must_use]
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.shutdown_request.requested.load(Ordering::Acquire)
+    }
+
+    /// # [`Fluxion::on_shutdown_requested`]
+    /// Completes once a shutdown has been requested with [`Fluxion::request_shutdown`], or immediately if one already has.
+    pub async fn on_shutdown_requested(&self) {
+        // Waiting only ends once the queue is closed by the request
+        let _ = self.shutdown_request.waiters.wait().await;
+    }
+
+    /// # [`Fluxion::shutdown_on_signal`]
+    /// Waits for SIGINT (ctrl-c), SIGTERM on unix, or a call to [`Fluxion::request_shutdown`], and then shuts the system
+    /// down gracefully with [`Fluxion::shutdown`], returning its report. Must be called within a Tokio runtime.
+    #[cfg(feature = "signals")]
+    pub async fn shutdown_on_signal(&self) -> ShutdownReport {
+        use core::{future::Future, task::Poll};
+
+        let mut requested = core::pin::pin!(self.on_shutdown_requested());
+
+        // Signals that can't be listened for are never received
+        let mut interrupt = core::pin::pin!(async {
+            if tokio::signal::ctrl_c().await.is_err() {
+                core::future::pending::<()>().await;
+            }
+        });
+
+        #[cfg(unix)]
+        let mut terminate = core::pin::pin!(async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => { signal.recv().await; },
+                Err(_) => core::future::pending::<()>().await,
+            }
+        });
+        #[cfg(not(unix))]
+        let mut terminate = core::pin::pin!(core::future::pending::<()>());
+
+        core::future::poll_fn(|cx| {
+            if requested.as_mut().poll(cx).is_ready() || interrupt.as_mut().poll(cx).is_ready() || terminate.as_mut().poll(cx).is_ready() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }).await;
+
+        self.shutdown().await
+    }
+}
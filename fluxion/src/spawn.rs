@@ -0,0 +1,261 @@
+//! # Spawning
+//! [`Fluxion::spawn`] returns a [`Spawn`], which configures an actor before adding it to the system. Every option can
+//! be combined with every other, instead of each combination needing its own `add_*` method.
+//!
+//! ```ignore
+//! let id = system.spawn(Worker::new())
+//!     .named("worker")
+//!     .rate_limit(RateLimit::new(100, 10))
+//!     .passivate_after(Duration::from_secs(60))
+//!     .shutdown_phase("workers")
+//!     .group("ingest")
+//!     .start().await?;
+//! ```
+
+use core::time::Duration;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{trace::instrument, Actor, AddActorError, Delegate, Executor, Fluxion, Handler, LocalRef, Message, Middleware, RateLimit, ScheduleError};
+
+/// The options an actor is added to the system with.
+#[derive(Default)]
+pub(crate) struct SpawnOptions {
+    /// The middleware that runs around the actor's messages, after the system's
+    pub(crate) middleware: crate::middleware::Layers,
+    /// Limits how fast the actor accepts messages
+    pub(crate) rate_limit: Option<Arc<crate::rate::TokenBucket>>,
+    /// The index of the shutdown phase the actor is stopped in
+    pub(crate) phase: Option<usize>,
+    /// What happens to the actor if one of its handlers panics, if not the system's policy
+    #[cfg(feature = "std")]
+    pub(crate) panic_policy: Option<crate::PanicPolicy>,
+    /// Whether the actor is owned by its references, and killed once they are all dropped
+    pub(crate) owned: bool,
+    /// The executor of the core the actor is pinned to
+    pub(crate) core: Option<Arc<dyn Executor>>,
+    /// The labels of the groups the actor is in
+    pub(crate) groups: Vec<String>,
+    /// The message types the actor accepts broadcasts of
+    pub(crate) handles: Vec<crate::broadcast::Handles>,
+}
+
+/// # [`Spawn`]
+/// Configures an actor before it is added to a system, as described in the [module documentation](self).
+/// Created by [`Fluxion::spawn`], and does nothing until [`Spawn::start`] is awaited.
+#[must_use = "the actor is only added once `start` is awaited"]
+pub struct Spawn<'a, A: Actor, D: Delegate> {
+    /// The system the actor is added to
+    system: &'a Fluxion<D>,
+    /// The actor being added
+    actor: A,
+    /// The name the actor is assigned
+    name: Option<String>,
+    /// The actor's middleware
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// The actor's rate limit
+    rate_limit: Option<RateLimit>,
+    /// How long the actor may go without receiving a message before it is passivated
+    passivation: Option<Duration>,
+    /// The name of the shutdown phase the actor is stopped in
+    shutdown_phase: Option<String>,
+    /// The index of the core the actor is pinned to
+    core: Option<usize>,
+    /// The labels of the groups the actor is in
+    groups: Vec<String>,
+    /// The message types the actor accepts broadcasts of
+    handles: Vec<crate::broadcast::Handles>,
+    /// The actor's panic policy
+    #[cfg(feature = "std")]
+    panic_policy: Option<crate::PanicPolicy>,
+}
+
+impl<D: Delegate> Fluxion<D> {
+    /// # [`Fluxion::spawn`]
+    /// Starts configuring an actor to add to the system, which is added once [`Spawn::start`] is awaited.
+    pub fn spawn<A: Actor>(&self, actor: A) -> Spawn<'_, A, D> {
+        Spawn {
+            system: self,
+            actor,
+            name: None,
+            middleware: Vec::new(),
+            rate_limit: None,
+            passivation: None,
+            shutdown_phase: None,
+            core: None,
+            groups: Vec::new(),
+            handles: Vec::new(),
+            #[cfg(feature = "std")]
+            panic_policy: None,
+        }
+    }
+}
+
+impl<A: Actor, D: Delegate> Spawn<'_, A, D> {
+    /// # [`Spawn::named`]
+    /// Assigns the actor a name, as with [`Fluxion::add_named`].
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(String::from(name));
+        self
+    }
+
+    /// # [`Spawn::middleware`]
+    /// Adds [`Middleware`] that runs around the actor's messages, after the system's middleware and any added before.
+    pub fn middleware(mut self, middleware: impl IntoIterator<Item = Arc<dyn Middleware>>) -> Self {
+        self.middleware.extend(middleware);
+        self
+    }
+
+    /// # [`Spawn::rate_limit`]
+    /// Accepts messages no faster than the given [`RateLimit`], which requires the system to have a [`crate::Timer`].
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// # [`Spawn::passivate_after`]
+    /// Passivates the actor once it has received no messages for roughly the given duration, as with
+    /// [`Fluxion::add_named_with_passivation`]. Requires the system to have a [`crate::Timer`] and an [`crate::Executor`].
+    pub fn passivate_after(mut self, idle: Duration) -> Self {
+        self.passivation = Some(idle);
+        self
+    }
+
+    /// # [`Spawn::shutdown_phase`]
+    /// Stops the actor in the given phase, added with [`crate::FluxionBuilder::shutdown_phase`], when the system shuts down.
+    pub fn shutdown_phase(mut self, phase: &str) -> Self {
+        self.shutdown_phase = Some(String::from(phase));
+        self
+    }
+
+    /// # [`Spawn::on_core`]
+    /// Pins the actor to a core added with [`crate::FluxionBuilder::core`], so that every message it receives is handled
+    /// on that core's executor, whichever task sent it. Messages are still handled if their send is cancelled once they have been handed over.
+    pub fn on_core(mut self, core: usize) -> Self {
+        self.core = Some(core);
+        self
+    }
+
+    /// # [`Spawn::group`]
+    /// Tags the actor with a group label, so that it can be operated on alongside the group's other actors through
+    /// [`Fluxion::group`]. May be called more than once to add the actor to several groups.
+    pub fn group(mut self, label: &str) -> Self {
+        self.groups.push(String::from(label));
+        self
+    }
+
+    /// # [`Spawn::handles`]
+    /// Indexes the actor as a handler of `M`, so that it receives every message passed to [`Fluxion::broadcast`] for that type.
+    /// May be called once for each message type the actor should receive broadcasts of.
+    pub fn handles<M: Message>(mut self) -> Self
+        where A: Handler<M> {
+        self.handles.push(crate::broadcast::Handles::new::<A, M, D>());
+        self
+    }
+
+    /// # [`Spawn::health_check`]
+    /// Probes the actor's [`crate::HealthCheck`] whenever the system checks the health of its actors, as described in
+    /// [`Fluxion::check_health`].
+    pub fn health_check(mut self) -> Self
+        where A: crate::HealthCheck {
+        self.handles.push(crate::broadcast::Handles::new::<A, crate::health::CheckHealth, D>());
+        self
+    }
+
+    /// # [`Spawn::panic_policy`]
+    /// Decides what happens to the actor if one of its handlers panics, instead of the system's [`crate::PanicPolicy`].
+    #[cfg(feature = "std")]
+    pub fn panic_policy(mut self, policy: crate::PanicPolicy) -> Self {
+        self.panic_policy = Some(policy);
+        self
+    }
+
+    /// # [`Spawn::start`]
+    /// Initializes the actor and adds it to the system with the configured options, returning its id.
+    ///
+    /// # Errors
+    /// Returns [`AddActorError::Initialize`] if the actor failed to initialize, [`AddActorError::NameTaken`] if it was
+    /// named and the name is taken as described in [`Fluxion::add_named`], [`AddActorError::UnknownShutdownPhase`]
+    /// or [`AddActorError::UnknownCore`] if the system has no such shutdown phase or core, or [`AddActorError::Schedule`] if the system lacks the [`crate::Timer`]
+    /// or [`crate::Executor`] an option requires. Options are checked before the actor is initialized, and on an error,
+    /// the actor will not be spawned.
+    pub async fn start(self) -> Result<u64, AddActorError<A::Error>> {
+        self.launch(false).await
+    }
+
+    /// # [`Spawn::start_owned`]
+    /// Initializes the actor and adds it to the system like [`Spawn::start`], but makes it owned by its references,
+    /// returning the first of them. Once every [`LocalRef`] to the actor has been dropped, and it no longer has its name,
+    /// it is killed on the system's [`crate::Executor`] and [`Actor::deinitialize`] is called. This suits short-lived
+    /// actors, such as one per request or per connection, that would otherwise have to be killed by hand.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`Spawn::start`], [`AddActorError::Schedule`] if the system has no [`crate::Executor`]
+    /// to kill the actor on, or [`AddActorError::Killed`] if the actor was killed before its reference could be returned.
+    pub async fn start_owned(self) -> Result<LocalRef<A, D>, AddActorError<A::Error>> {
+        let system = self.system;
+        if system.executor.is_none() {
+            return Err(AddActorError::Schedule(ScheduleError::NoExecutor));
+        }
+        let named = self.name.is_some();
+
+        let id = self.launch(true).await?;
+        let reference = system.try_get_local::<A>(id).await;
+
+        // Unnamed actors are only owned by their references from here on
+        if !named {
+            system.release(id);
+        }
+
+        reference.map_err(|_| AddActorError::Killed(id))
+    }
+
+    /// Adds the actor to the system with the configured options, returning its id.
+    async fn launch(self, owned: bool) -> Result<u64, AddActorError<A::Error>> {
+        let system = self.system;
+        let mut options = SpawnOptions { middleware: self.middleware.into(), owned, groups: self.groups, handles: self.handles, ..SpawnOptions::default() };
+
+        if let Some(limit) = self.rate_limit {
+            let timer = system.timer.clone().ok_or(AddActorError::Schedule(ScheduleError::NoTimer))?;
+            options.rate_limit = Some(Arc::new(crate::rate::TokenBucket::new(limit, timer)));
+        }
+
+        if let Some(phase) = self.shutdown_phase {
+            let index = system.shutdown_phases.iter().position(|existing| existing.name == phase)
+                .ok_or(AddActorError::UnknownShutdownPhase(phase))?;
+            options.phase = Some(index);
+        }
+
+        if let Some(core) = self.core {
+            options.core = Some(system.cores.get(core).cloned().ok_or(AddActorError::UnknownCore(core))?);
+        }
+
+        // Fail before spawning if the passivation could never be scheduled
+        if self.passivation.is_some() {
+            if system.timer.is_none() {
+                return Err(AddActorError::Schedule(ScheduleError::NoTimer));
+            }
+            if system.executor.is_none() {
+                return Err(AddActorError::Schedule(ScheduleError::NoExecutor));
+            }
+        }
+
+        #[cfg(feature = "std")]
+        {
+            options.panic_policy = self.panic_policy;
+        }
+
+        let (id, context) = match self.name.as_deref() {
+            Some(name) => instrument!(system.spawn_named(name, self.actor, options), "fluxion::add", system = %system.system_id, actor = core::any::type_name::<A>(), name)
+                .await?,
+            None => system.add_with(self.actor, options).await
+                .map_err(AddActorError::Initialize)?,
+        };
+
+        if let Some(idle) = self.passivation {
+            context.passivate_after::<A>(idle).map_err(AddActorError::Schedule)?;
+        }
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,228 @@
+//! # Streaming
+//! A [`StreamMessage`] is answered with a stream of items instead of a single result. Actors handle it by implementing
+//! [`StreamHandler`], and callers receive a [`MessageStream`] from [`StreamSender::send_stream`].
+//!
+//! Items are pumped from the handler's stream into a bounded channel, so a handler that produces items faster than
+//! they are consumed waits for space. The handler runs as the stream is polled, and is cancelled if the stream is dropped.
+//! Delegates can stream items from foreign actors by implementing [`crate::Delegate::get_stream_actor`].
+
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicBool, Ordering}, task::{Context, Poll}};
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
+use futures_core::Stream;
+use maitake_sync::{spin::Mutex, WaitCell, WaitQueue};
+
+use crate::{Actor, ActorContext, Delegate, Handler, LocalRef, Message, MessageSendError, MessageSender};
+
+/// The number of items buffered between a local handler and its [`MessageStream`].
+const LOCAL_CAPACITY: usize = 16;
+
+/// # [`StreamMessage`]
+/// A message that is answered with a stream of items.
+pub trait StreamMessage: Send + 'static {
+    /// The type of the items the message is answered with
+    type Item: Send + 'static;
+}
+
+/// # [`IndeterminateStreamMessage`]
+/// A [`StreamMessage`] that may be sent to a foreign actor, which requires it and its items to be serializable
+/// when the `serde` feature is enabled.
+#[cfg(feature = "serde")]
+pub trait IndeterminateStreamMessage:
+    StreamMessage<Item: serde::Serialize + serde::de::DeserializeOwned> + crate::MessageID + serde::Serialize + serde::de::DeserializeOwned {}
+
+#[cfg(feature = "serde")]
+impl<T> IndeterminateStreamMessage for T
+    where T: StreamMessage<Item: serde::Serialize + serde::de::DeserializeOwned> + crate::MessageID + serde::Serialize + serde::de::DeserializeOwned {}
+
+/// # [`IndeterminateStreamMessage`]
+/// A [`StreamMessage`] that may be sent to a foreign actor.
+#[cfg(not(feature = "serde"))]
+pub trait IndeterminateStreamMessage: StreamMessage {}
+
+#[cfg(not(feature = "serde"))]
+impl<T: StreamMessage> IndeterminateStreamMessage for T {}
+
+/// # [`StreamHandler`]
+/// Answers a [`StreamMessage`] with a stream of items.
+pub trait StreamHandler<M: StreamMessage>: Actor {
+    /// # [`StreamHandler::handle_stream`]
+    /// Returns the stream of items answering the message. The stream is polled as the caller consumes it.
+    fn handle_stream<'a, D: Delegate>(&'a self, message: M, context: &'a ActorContext<D>) -> impl Stream<Item = M::Item> + Send + 'a;
+}
+
+/// # [`StreamSender`]
+/// Sends a specific [`StreamMessage`] to a specific actor, like [`MessageSender`] does for other messages.
+#[async_trait::async_trait]
+pub trait StreamSender<M: StreamMessage>: Send + Sync + 'static {
+    /// # [`StreamSender::send_stream`]
+    /// Sends the message, returning the stream of items it is answered with.
+    ///
+    /// # Errors
+    /// Returns an error if the message could not be sent.
+    async fn send_stream(&self, message: M) -> Result<MessageStream<M::Item>, MessageSendError>;
+}
+
+/// Delivers a [`StreamMessage`] to its handler, along with the channel its items are pumped into.
+pub(crate) struct Streamed<M: StreamMessage>(M, ItemSender<M::Item>);
+
+impl<M: StreamMessage> Message for Streamed<M> {
+    type Result = ();
+}
+
+impl<A: StreamHandler<M>, M: StreamMessage> Handler<Streamed<M>> for A {
+    async fn handle_message<D: Delegate>(&self, message: Streamed<M>, context: &ActorContext<D>) {
+        let Streamed(message, items) = message;
+        let mut stream = core::pin::pin!(self.handle_stream(message, context));
+
+        while let Some(item) = core::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            // Stop producing items once nobody is listening
+            if items.send(item).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: StreamHandler<M>, M: StreamMessage, D: Delegate> StreamSender<M> for LocalRef<A, D> {
+    async fn send_stream(&self, message: M) -> Result<MessageStream<M::Item>, MessageSendError> {
+        // The message is admitted now, so that an actor which is draining or stopped refuses it rather than ending the stream
+        let pass = self.gate.enter_owned()?;
+        let (items, stream) = MessageStream::channel(LOCAL_CAPACITY);
+        let actor = self.clone();
+
+        // The handler runs as the stream is polled rather than on the executor, so that it is paced by the reader
+        Ok(stream.driven_by(async move {
+            let _pass = pass;
+            let _ = actor.dispatch(Streamed(message, items), false).await;
+        }))
+    }
+}
+
+/// Items buffered between an [`ItemSender`] and a [`MessageStream`].
+struct Channel<T> {
+    /// The buffered items
+    items: Mutex<VecDeque<T>>,
+    /// The most items buffered at once
+    capacity: usize,
+    /// Woken when an item is taken, and closed when the stream is dropped
+    space: WaitQueue,
+    /// Woken when an item is added, or when the sender is dropped
+    ready: WaitCell,
+    /// Whether the sender was dropped
+    closed: AtomicBool,
+}
+
+/// # [`ItemSender`]
+/// Sends items to a [`MessageStream`], waiting while its buffer is full. The stream ends once the sender is dropped.
+/// Delegates use this to feed items received from a foreign system into a stream.
+pub struct ItemSender<T>(Arc<Channel<T>>);
+
+impl<T> ItemSender<T> {
+    /// # [`ItemSender::send`]
+    /// Adds an item to the stream, waiting until there is space for it.
+    ///
+    /// # Errors
+    /// Returns the item if the stream was dropped.
+    pub async fn send(&self, item: T) -> Result<(), T> {
+        if self.0.space.wait_for(|| self.0.items.lock().len() < self.0.capacity).await.is_err() {
+            return Err(item);
+        }
+
+        self.0.items.lock().push_back(item);
+        self.0.ready.wake();
+        Ok(())
+    }
+}
+
+impl<T> Drop for ItemSender<T> {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::Release);
+        self.0.ready.wake();
+    }
+}
+
+/// # [`MessageStream`]
+/// The stream of items answering a [`StreamMessage`].
+pub struct MessageStream<T> {
+    /// The items sent so far
+    channel: Arc<Channel<T>>,
+    /// Produces the items, if they are produced by polling the stream
+    driver: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<T> MessageStream<T> {
+    /// # [`MessageStream::channel`]
+    /// Creates a stream that buffers up to `capacity` items sent through the returned [`ItemSender`].
+    /// A capacity of zero is treated as one.
+    #[must_use]
+    pub fn channel(capacity: usize) -> (ItemSender<T>, Self) {
+        let channel = Arc::new(Channel {
+            items: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            space: WaitQueue::new(),
+            ready: WaitCell::new(),
+            closed: AtomicBool::new(false),
+        });
+
+        (ItemSender(channel.clone()), Self { channel, driver: None })
+    }
+
+    /// # [`MessageStream::driven_by`]
+    /// Runs the future whenever the stream is polled, for example to produce the items without spawning a task.
+    /// The stream ends once the future has finished and the sender has been dropped.
+    #[must_use]
+    pub fn driven_by(mut self, driver: impl Future<Output = ()> + Send + 'static) -> Self {
+        self.driver = Some(Box::pin(driver));
+        self
+    }
+}
+
+impl<T> Stream for MessageStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            if let Some(item) = self.channel.items.lock().pop_front() {
+                self.channel.space.wake_all();
+                return Poll::Ready(Some(item));
+            }
+
+            // Let the driver produce more items
+            if let Some(driver) = &mut self.driver {
+                if driver.as_mut().poll(cx).is_ready() {
+                    self.driver = None;
+                }
+
+                if !self.channel.items.lock().is_empty() {
+                    continue;
+                }
+            }
+
+            if self.finished() {
+                return Poll::Ready(None);
+            }
+
+            // Wait for an item or for the sender to be dropped, checking again after registering so no wakeup is missed
+            if self.channel.ready.poll_wait(cx).is_ready() || !self.channel.items.lock().is_empty() || self.finished() {
+                continue;
+            }
+
+            return Poll::Pending;
+        }
+    }
+}
+
+impl<T> MessageStream<T> {
+    /// Returns true if no more items can be sent.
+    fn finished(&self) -> bool {
+        self.driver.is_none() && self.channel.closed.load(Ordering::Acquire) && self.channel.items.lock().is_empty()
+    }
+}
+
+impl<T> Drop for MessageStream<T> {
+    fn drop(&mut self) {
+        self.channel.space.close();
+    }
+}
@@ -0,0 +1,372 @@
+//! # Testkit
+//! Testing actors normally needs a real runtime, and waiting on real time for timers and timeouts.
+//! A [`TestSystem`] instead runs a system on a deterministic single-threaded executor with virtual time:
+//! background tasks run in the order they are woken, and time only passes when the test advances it with
+//! [`TestSystem::advance`], or when every task is waiting on a timer while [`TestSystem::block_on`] runs.
+//! A [`TestProbe`] is an actor that records the messages it receives, so tests can assert on them.
+//! With the `foreign` feature, a [`MockDelegate`] stands in for foreign systems, and a [`Simulation`] runs several
+//! systems that talk to each other over a simulated network.
+//!
+//! The testkit is enabled by the `testkit` feature.
+
+use core::{future::Future, pin::Pin, sync::atomic::{AtomicBool, AtomicU64, Ordering}, task::{Context, Poll, Waker}, time::Duration};
+
+use alloc::{boxed::Box, collections::{BTreeMap, VecDeque}, sync::Arc, task::Wake};
+use maitake_sync::spin::Mutex;
+
+use crate::{Delegate, Executor, Fluxion, FluxionBuilder, Timer};
+
+mod probe;
+pub use probe::*;
+
+#[cfg(feature = "foreign")]
+mod mock;
+#[cfg(feature = "foreign")]
+pub use mock::*;
+
+#[cfg(feature = "foreign")]
+mod simulation;
+#[cfg(feature = "foreign")]
+pub use simulation::*;
+
+/// A task spawned on the test executor, which is queued to run again whenever it is woken.
+struct Task {
+    /// The task's future, which is dropped once it completes
+    future: Mutex<Option<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+    /// The runtime the task is queued on
+    runtime: alloc::sync::Weak<Runtime>,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if let Some(runtime) = self.runtime.upgrade() {
+            runtime.ready.lock().push_back(self);
+        }
+    }
+}
+
+/// Wakes the future passed to [`TestSystem::block_on`].
+#[derive(Default)]
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// A small deterministic random number generator using splitmix64, so that seeded runs are reproducible on every platform.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from a seed.
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Returns the next random number.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        crate::hash::mix(self.0)
+    }
+
+    /// Returns a random number below `bound`, which must not be zero.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        // The result is below a usize, so it fits in one, and the modulo bias is irrelevant for testing
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// The executor and virtual clock shared by a [`TestSystem`] and its probes.
+#[derive(Default)]
+pub(crate) struct Runtime {
+    /// Tasks that were woken and have not run since, in the order they were woken
+    ready: Mutex<VecDeque<Arc<Task>>>,
+    /// The current virtual time
+    now: Mutex<Duration>,
+    /// Wakers of sleeping futures, keyed by their deadline and then by the order they went to sleep
+    sleepers: Mutex<BTreeMap<(Duration, u64), Waker>>,
+    /// The key given to the next sleeping future
+    next_sleeper: AtomicU64,
+    /// Picks the next task to run in seeded runtimes, which otherwise run tasks in the order they were woken
+    scheduler: Mutex<Option<Rng>>,
+}
+
+impl Runtime {
+    /// Creates a runtime that runs woken tasks in an order picked by the given seed.
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self { scheduler: Mutex::new(Some(Rng::new(seed))), ..Self::default() }
+    }
+
+    /// Runs the next woken task, returning false if there was none.
+    fn run_once(&self) -> bool {
+        let next = {
+            let mut ready = self.ready.lock();
+            match self.scheduler.lock().as_mut() {
+                // Seeded runtimes run a random woken task, so that tests see many interleavings
+                Some(rng) if !ready.is_empty() => ready.swap_remove_back(rng.below(ready.len())),
+                _ => ready.pop_front(),
+            }
+        };
+
+        let Some(task) = next else {
+            return false;
+        };
+
+        let waker = Waker::from(task.clone());
+        let mut future = task.future.lock();
+        if let Some(running) = future.as_mut()
+            && running.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+            *future = None;
+        }
+
+        true
+    }
+
+    /// Runs woken tasks until none are left.
+    pub(crate) fn run_until_stalled(&self) {
+        while self.run_once() {}
+    }
+
+    /// Returns the current virtual time.
+    pub(crate) fn now(&self) -> Duration {
+        *self.now.lock()
+    }
+
+    /// Returns the earliest deadline of any sleeping future.
+    fn next_deadline(&self) -> Option<Duration> {
+        self.sleepers.lock().keys().next().map(|(deadline, _)| *deadline)
+    }
+
+    /// Moves the clock to the given time, waking every future that was sleeping until then.
+    fn set_time(&self, time: Duration) {
+        {
+            let mut now = self.now.lock();
+            *now = (*now).max(time);
+        }
+
+        // Wake the sleepers outside of the lock, as waking may need it
+        let mut sleepers = self.sleepers.lock();
+        let later = sleepers.split_off(&(time, u64::MAX));
+        let due = core::mem::replace(&mut *sleepers, later);
+        drop(sleepers);
+
+        for waker in due.into_values() {
+            waker.wake();
+        }
+    }
+
+    /// Runs tasks and advances the clock until it reaches the given time.
+    pub(crate) fn advance_to(&self, time: Duration) {
+        loop {
+            self.run_until_stalled();
+
+            match self.next_deadline() {
+                Some(deadline) if deadline <= time => self.set_time(deadline),
+                _ => break,
+            }
+        }
+
+        self.set_time(time);
+        self.run_until_stalled();
+    }
+
+    /// Runs tasks until the condition holds, advancing the clock to each timer's deadline in turn but never past `until`.
+    /// Returns whether the condition held.
+    pub(crate) fn run_until(&self, until: Duration, mut condition: impl FnMut() -> bool) -> bool {
+        loop {
+            self.run_until_stalled();
+            if condition() {
+                return true;
+            }
+
+            match self.next_deadline() {
+                Some(deadline) if deadline <= until => self.set_time(deadline),
+                _ => {
+                    self.set_time(until);
+                    self.run_until_stalled();
+                    return condition();
+                },
+            }
+        }
+    }
+}
+
+/// # [`VirtualTimer`]
+/// A [`Timer`] whose time only passes when a [`TestSystem`] advances it.
+#[derive(Clone)]
+pub struct VirtualTimer(Arc<Runtime>);
+
+impl Timer for VirtualTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(Sleep { runtime: self.0.clone(), deadline: self.0.now().saturating_add(duration), key: None })
+    }
+
+    fn now(&self) -> Option<Duration> {
+        Some(self.0.now())
+    }
+}
+
+/// Waits until the virtual clock reaches the deadline.
+struct Sleep {
+    /// The runtime whose clock is waited on
+    runtime: Arc<Runtime>,
+    /// When the sleep completes
+    deadline: Duration,
+    /// The key of the sleep's waker, once it has been registered
+    key: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+
+        if this.runtime.now() >= this.deadline {
+            return Poll::Ready(());
+        }
+
+        let key = *this.key.get_or_insert_with(|| this.runtime.next_sleeper.fetch_add(1, Ordering::Relaxed));
+        this.runtime.sleepers.lock().insert((this.deadline, key), cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.runtime.sleepers.lock().remove(&(self.deadline, key));
+        }
+    }
+}
+
+/// # [`TestExecutor`]
+/// An [`Executor`] that runs tasks when a [`TestSystem`] runs them, one at a time in the order they were woken.
+#[derive(Clone)]
+pub struct TestExecutor(Arc<Runtime>);
+
+impl Executor for TestExecutor {
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        let task = Arc::new(Task { future: Mutex::new(Some(future)), runtime: Arc::downgrade(&self.0) });
+        self.0.ready.lock().push_back(task);
+    }
+}
+
+/// # [`TestSystem`]
+/// A [`Fluxion`] system running on a deterministic executor with virtual time, as described in the [module documentation](self).
+pub struct TestSystem<D = ()> {
+    /// The system under test
+    system: Fluxion<D>,
+    /// Runs the system's tasks and keeps its time
+    runtime: Arc<Runtime>,
+}
+
+impl TestSystem<()> {
+    /// # [`TestSystem::new`]
+    /// Creates a test system with the given id and no delegate.
+    #[must_use]
+    pub fn new(id: &str) -> Self {
+        Self::build(Fluxion::builder(id, ()))
+    }
+}
+
+#[cfg(feature = "foreign")]
+impl TestSystem<MockDelegate> {
+    /// # [`TestSystem::mocked`]
+    /// Creates a test system with the given id, whose delegate is a [`MockDelegate`] on the system's virtual time.
+    /// The delegate is scripted through [`Fluxion::get_delegate`].
+    #[must_use]
+    pub fn mocked(id: &str) -> Self {
+        let runtime = Arc::new(Runtime::default());
+        let delegate = MockDelegate::new(VirtualTimer(runtime.clone()));
+
+        Self::with_runtime(runtime, Fluxion::builder(id, delegate))
+    }
+}
+
+impl<D: Delegate> TestSystem<D> {
+    /// # [`TestSystem::build`]
+    /// Creates a test system from a builder, replacing its timer and executor with virtual ones.
+    #[must_use]
+    pub fn build(builder: FluxionBuilder<D>) -> Self {
+        Self::with_runtime(Arc::new(Runtime::default()), builder)
+    }
+
+    /// Creates a test system from a builder, running on the given runtime.
+    fn with_runtime(runtime: Arc<Runtime>, builder: FluxionBuilder<D>) -> Self {
+        let system = builder
+            .timer(VirtualTimer(runtime.clone()))
+            .executor(TestExecutor(runtime.clone()))
+            .build();
+
+        Self { system, runtime }
+    }
+
+    /// # [`TestSystem::system`]
+    /// Returns the system under test.
+    #[must_use]
+    pub fn system(&self) -> &Fluxion<D> {
+        &self.system
+    }
+
+    /// # [`TestSystem::now`]
+    /// Returns the current virtual time, which starts at zero.
+    #[must_use]
+    pub fn now(&self) -> Duration {
+        self.runtime.now()
+    }
+
+    /// # [`TestSystem::spawn`]
+    /// Spawns a task on the system's executor, which runs the next time the system runs its tasks.
+    pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        TestExecutor(self.runtime.clone()).spawn(Box::pin(future));
+    }
+
+    /// # [`TestSystem::block_on`]
+    /// Runs the future to completion along with the system's tasks. Whenever the future and every task are waiting
+    /// on timers, the clock jumps to the earliest deadline, so timeouts elapse without any real waiting.
+    ///
+    /// # Panics
+    /// Panics if the future and every task are waiting on something other than a timer, as they would wait forever.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let flag = Arc::new(Flag(AtomicBool::new(true)));
+        let waker = Waker::from(flag.clone());
+
+        loop {
+            if flag.0.swap(false, Ordering::Acquire)
+                && let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                return output;
+            }
+
+            if self.runtime.run_once() || flag.0.load(Ordering::Acquire) {
+                continue;
+            }
+
+            let Some(deadline) = self.runtime.next_deadline() else {
+                panic!("TestSystem::block_on: the future can never complete, as nothing is waiting on a timer");
+            };
+            self.runtime.set_time(deadline);
+        }
+    }
+
+    /// # [`TestSystem::run_until_stalled`]
+    /// Runs the system's tasks until every one is waiting, without advancing the clock.
+    pub fn run_until_stalled(&self) {
+        self.runtime.run_until_stalled();
+    }
+
+    /// # [`TestSystem::advance`]
+    /// Advances the clock by the given duration, firing every timer that elapses in order, and runs the system's tasks.
+    pub fn advance(&self, duration: Duration) {
+        self.runtime.advance_to(self.runtime.now().saturating_add(duration));
+    }
+
+    /// # [`TestSystem::probe`]
+    /// Adds a [`TestProbe`] to the system.
+    pub fn probe(&self) -> TestProbe {
+        TestProbe::spawn(self)
+    }
+}
@@ -0,0 +1,242 @@
+//! # Mock Delegates
+//! A [`MockDelegate`] stands in for the foreign systems a system talks to. Tests script how each foreign actor responds
+//! to each message type, make sends to an actor fail or take time, and inspect every message sent to a foreign actor.
+//! Foreign actors that have nothing scripted can't be found, just as if they didn't exist.
+
+use core::{any::{Any, TypeId}, marker::PhantomData, time::Duration};
+
+use alloc::{boxed::Box, collections::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec};
+use maitake_sync::spin::Mutex;
+
+use crate::{Delegate, Handler, Identifier, IndeterminateMessage, Message, MessageSendError, MessageSender, Timer};
+
+/// Produces the response to a message, which is passed in and returned as its concrete types.
+type Respond = Arc<dyn Fn(&dyn Any) -> Box<dyn Any + Send> + Send + Sync>;
+
+/// Produces the error a failing foreign actor returns.
+type Fail = Arc<dyn Fn() -> MessageSendError + Send + Sync>;
+
+/// # [`ForeignActor`]
+/// An actor on a foreign system, as it was identified by the sender.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForeignActor {
+    /// An actor identified by its id
+    Id(u64),
+    /// An actor identified by its name
+    Named(String),
+}
+
+/// Returns the system and actor named by a foreign identifier, or [`None`] if it is local.
+fn address(id: Identifier<'_>) -> Option<(String, ForeignActor)> {
+    match id {
+        Identifier::Foreign(id, system) => Some((system.to_string(), ForeignActor::Id(id))),
+        Identifier::ForeignNamed(name, system) => Some((system.to_string(), ForeignActor::Named(name.to_string()))),
+        Identifier::Local(_) | Identifier::LocalNamed(_) => None,
+    }
+}
+
+/// # [`ForeignSend`]
+/// A message sent through a [`MockDelegate`], recorded whether or not the send succeeded.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ForeignSend {
+    /// The id of the foreign system the message was sent to
+    pub system: String,
+    /// The actor the message was sent to
+    pub actor: ForeignActor,
+    /// The type name of the message
+    pub message_type: &'static str,
+    /// The message, which can be downcast to its type with [`ForeignSend::message`]
+    pub message: Box<dyn Any + Send>,
+    /// The time at which the message was sent, if the delegate's timer can read a clock
+    pub at: Option<Duration>,
+}
+
+impl ForeignSend {
+    /// # [`ForeignSend::message`]
+    /// Returns the message if it is of type `M`.
+    #[must_use]
+    pub fn message<M: Message>(&self) -> Option<&M> {
+        self.message.downcast_ref()
+    }
+}
+
+/// How a single foreign actor behaves.
+#[derive(Default)]
+struct Script {
+    /// The actor's responses, keyed by the type of message they respond to
+    responses: BTreeMap<TypeId, Respond>,
+    /// The error every send to the actor fails with, if it fails
+    failure: Option<Fail>,
+    /// How long every send to the actor takes
+    latency: Duration,
+}
+
+/// The state shared by a [`MockDelegate`] and the senders it returns.
+struct MockState {
+    /// Used to read the time and to inject latency
+    timer: Arc<dyn Timer>,
+    /// The behaviour of each scripted foreign actor
+    scripts: Mutex<BTreeMap<(String, ForeignActor), Script>>,
+    /// Every message sent to a foreign actor, in the order they were sent
+    traffic: Mutex<Vec<ForeignSend>>,
+}
+
+/// # [`MockDelegate`]
+/// A [`Delegate`] whose foreign actors are scripted by the test, as described in the [module documentation](self).
+/// Clones share the same script and recorded traffic, and [`crate::testkit::TestSystem::mocked`] creates a test system that uses one.
+#[derive(Clone)]
+pub struct MockDelegate(Arc<MockState>);
+
+impl MockDelegate {
+    /// # [`MockDelegate::new`]
+    /// Creates a delegate with no foreign actors, which uses the given timer to record when messages are sent and to inject latency.
+    #[must_use]
+    pub fn new<T: Timer>(timer: T) -> Self {
+        Self(Arc::new(MockState {
+            timer: Arc::new(timer),
+            scripts: Mutex::new(BTreeMap::new()),
+            traffic: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// # [`MockDelegate::respond`]
+    /// Makes the foreign actor respond to messages of type `M` with the result of the function.
+    /// Local identifiers are ignored.
+    pub fn respond<M: Message>(&self, actor: Identifier<'_>, respond: impl Fn(&M) -> M::Result + Send + Sync + 'static) {
+        let Some(address) = address(actor) else {
+            return;
+        };
+
+        let respond: Respond = Arc::new(move |message| {
+            let message = message.downcast_ref::<M>().expect("responses are keyed by the message's type");
+            Box::new(respond(message))
+        });
+
+        self.0.scripts.lock().entry(address).or_default().responses.insert(TypeId::of::<M>(), respond);
+    }
+
+    /// # [`MockDelegate::fail`]
+    /// Makes every send to the foreign actor fail with the error returned by the function, until [`MockDelegate::recover`] is called.
+    /// Local identifiers are ignored.
+    pub fn fail(&self, actor: Identifier<'_>, error: impl Fn() -> MessageSendError + Send + Sync + 'static) {
+        let Some(address) = address(actor) else {
+            return;
+        };
+
+        self.0.scripts.lock().entry(address).or_default().failure = Some(Arc::new(error));
+    }
+
+    /// # [`MockDelegate::recover`]
+    /// Stops sends to the foreign actor from failing.
+    pub fn recover(&self, actor: Identifier<'_>) {
+        let Some(address) = address(actor) else {
+            return;
+        };
+
+        if let Some(script) = self.0.scripts.lock().get_mut(&address) {
+            script.failure = None;
+        }
+    }
+
+    /// # [`MockDelegate::latency`]
+    /// Makes every send to the foreign actor take the given duration before it responds or fails.
+    /// Local identifiers are ignored.
+    pub fn latency(&self, actor: Identifier<'_>, latency: Duration) {
+        let Some(address) = address(actor) else {
+            return;
+        };
+
+        self.0.scripts.lock().entry(address).or_default().latency = latency;
+    }
+
+    /// # [`MockDelegate::traffic`]
+    /// Returns the messages sent to foreign actors since the last call, in the order they were sent.
+    #[must_use]
+    pub fn traffic(&self) -> Vec<ForeignSend> {
+        core::mem::take(&mut *self.0.traffic.lock())
+    }
+
+    /// Returns whether the foreign actor can be sent messages of type `M`.
+    fn handles<M: Message>(&self, address: &(String, ForeignActor)) -> bool {
+        self.0.scripts.lock().get(address)
+            .is_some_and(|script| script.failure.is_some() || script.responses.contains_key(&TypeId::of::<M>()))
+    }
+
+    /// Returns a sender for the foreign actor, if it can be sent messages of type `M`.
+    fn sender<M: Message>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> {
+        let address = address(id)?;
+
+        if !self.handles::<M>(&address) {
+            return None;
+        }
+
+        Some(Arc::new(MockSender { delegate: self.clone(), address, _message: PhantomData }))
+    }
+}
+
+impl Delegate for MockDelegate {
+    #[cfg(not(feature = "serde"))]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> {
+        self.sender(id)
+    }
+
+    #[cfg(feature = "serde")]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        self.sender(id)
+    }
+}
+
+/// Sends messages to a scripted foreign actor.
+struct MockSender<M> {
+    /// The delegate the actor is scripted on
+    delegate: MockDelegate,
+    /// The foreign actor
+    address: (String, ForeignActor),
+    /// The type of message sent
+    _message: PhantomData<fn(M)>,
+}
+
+#[async_trait::async_trait]
+impl<M: Message> MessageSender<M> for MockSender<M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        let state = &self.delegate.0;
+        let at = state.timer.now();
+
+        // The script is read when the message is sent, so changes apply to senders that were already retrieved
+        let (latency, failure, respond) = {
+            let scripts = state.scripts.lock();
+            let script = scripts.get(&self.address);
+            (
+                script.map_or(Duration::ZERO, |script| script.latency),
+                script.and_then(|script| script.failure.clone()),
+                script.and_then(|script| script.responses.get(&TypeId::of::<M>()).cloned()),
+            )
+        };
+
+        if !latency.is_zero() {
+            state.timer.sleep(latency).await;
+        }
+
+        let result = match (failure, respond) {
+            (Some(failure), _) => Err(failure()),
+            (None, Some(respond)) => Ok(respond(&message)),
+            // The actor only had a failure scripted, and has since recovered
+            (None, None) => Err(MessageSendError::PeerUnreachable),
+        };
+
+        state.traffic.lock().push(ForeignSend {
+            system: self.address.0.clone(),
+            actor: self.address.1.clone(),
+            message_type: core::any::type_name::<M>(),
+            message: Box::new(message),
+            at,
+        });
+
+        result.map(|response| match response.downcast::<M::Result>() {
+            Ok(response) => *response,
+            Err(_) => unreachable!("responses are keyed by the message's type"),
+        })
+    }
+}
@@ -0,0 +1,200 @@
+//! # Probes
+//! A [`TestProbe`] is an actor that accepts every message, and records each one along with when it arrived for tests
+//! to assert on. Messages with a result other than `()` are answered with a reply set by the test, either ahead of
+//! time with [`TestProbe::reply_with`], or to a message that is already waiting with [`TestProbe::reply`].
+
+use core::{any::{Any, TypeId}, time::Duration};
+
+use alloc::{boxed::Box, collections::{BTreeMap, VecDeque}, sync::Arc, vec::Vec};
+use maitake_sync::{spin::Mutex, WaitQueue};
+
+use crate::{Actor, ActorContext, Delegate, Handler, Message};
+
+use super::{Runtime, TestSystem};
+
+/// Produces the reply to a message, which is passed in and returned as its concrete types.
+type ReplyFn = Box<dyn Fn(&dyn Any) -> Box<dyn Any + Send> + Send + Sync>;
+
+/// # [`Received`]
+/// A message received by a [`TestProbe`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Received<M> {
+    /// The message
+    pub message: M,
+    /// The virtual time at which the message arrived
+    pub at: Duration,
+}
+
+/// The state shared by a [`TestProbe`] and its actor.
+struct ProbeState {
+    /// The runtime the probe's system runs on
+    runtime: Arc<Runtime>,
+    /// The messages received and not yet expected, along with when they arrived
+    received: Mutex<VecDeque<(Duration, Box<dyn Any + Send>)>>,
+    /// The type name and arrival time of every message received
+    history: Mutex<Vec<(Duration, &'static str)>>,
+    /// Replies produced for every message of a type, keyed by the message's type
+    reply_with: Mutex<BTreeMap<TypeId, ReplyFn>>,
+    /// Replies for the next messages of a type, keyed by the message's type
+    replies: Mutex<BTreeMap<TypeId, VecDeque<Box<dyn Any + Send>>>>,
+    /// Woken when a reply is queued
+    replied: WaitQueue,
+}
+
+/// # [`ProbeActor`]
+/// The actor behind a [`TestProbe`].
+pub struct ProbeActor(Arc<ProbeState>);
+
+impl Actor for ProbeActor {
+    type Error = ();
+}
+
+impl<M: Message> Handler<M> for ProbeActor {
+    async fn handle_message<D: Delegate>(&self, message: M, _context: &ActorContext<D>) -> M::Result {
+        let state = &self.0;
+        let at = state.runtime.now();
+        let key = TypeId::of::<M>();
+
+        state.history.lock().push((at, core::any::type_name::<M>()));
+
+        // Work out the reply before giving the message up to the test
+        let reply = state.reply_with.lock().get(&key).map(|reply| reply(&message));
+        state.received.lock().push_back((at, Box::new(message)));
+
+        let reply = match reply {
+            Some(reply) => reply,
+            // Messages without a result need no reply
+            None if TypeId::of::<M::Result>() == TypeId::of::<()>() => Box::new(()),
+            None => {
+                let queued = state.replied.wait_for_value(|| state.replies.lock().get_mut(&key).and_then(VecDeque::pop_front)).await;
+                queued.expect("the probe's reply queue is never closed")
+            },
+        };
+
+        match reply.downcast::<M::Result>() {
+            Ok(reply) => *reply,
+            Err(_) => panic!("TestProbe: the reply to a {} was not a {}", core::any::type_name::<M>(), core::any::type_name::<M::Result>()),
+        }
+    }
+}
+
+/// # [`TestProbe`]
+/// An actor in a [`TestSystem`] that records the messages it receives, created with [`TestSystem::probe`].
+/// Messages are sent to the probe like any other local actor, using [`TestProbe::id`].
+#[derive(Clone)]
+pub struct TestProbe {
+    /// The probe's actor id
+    id: u64,
+    /// The state shared with the probe's actor
+    state: Arc<ProbeState>,
+}
+
+impl TestProbe {
+    /// Adds a probe to the test system.
+    pub(crate) fn spawn<D: Delegate>(system: &TestSystem<D>) -> Self {
+        let state = Arc::new(ProbeState {
+            runtime: system.runtime.clone(),
+            received: Mutex::new(VecDeque::new()),
+            history: Mutex::new(Vec::new()),
+            reply_with: Mutex::new(BTreeMap::new()),
+            replies: Mutex::new(BTreeMap::new()),
+            replied: WaitQueue::new(),
+        });
+
+        // Probes never fail to initialize
+        let id = system.block_on(system.system().add(ProbeActor(state.clone())))
+            .unwrap_or_else(|()| unreachable!());
+
+        Self { id, state }
+    }
+
+    /// # [`TestProbe::id`]
+    /// Returns the probe's actor id.
+    #[must_use]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// # [`TestProbe::history`]
+    /// Returns the arrival time and type name of every message the probe has received, in the order they arrived.
+    #[must_use]
+    pub fn history(&self) -> Vec<(Duration, &'static str)> {
+        self.state.history.lock().clone()
+    }
+
+    /// # [`TestProbe::reply_with`]
+    /// Answers every message of type `M` that arrives from now on with the result of the function.
+    pub fn reply_with<M: Message>(&self, reply: impl Fn(&M) -> M::Result + Send + Sync + 'static) {
+        let reply: ReplyFn = Box::new(move |message| {
+            let message = message.downcast_ref::<M>().expect("replies are keyed by the message's type");
+            Box::new(reply(message))
+        });
+
+        self.state.reply_with.lock().insert(TypeId::of::<M>(), reply);
+    }
+
+    /// # [`TestProbe::reply`]
+    /// Answers the next message of type `M` that is waiting for a reply, or that arrives without one set by [`TestProbe::reply_with`].
+    pub fn reply<M: Message>(&self, reply: M::Result) {
+        self.state.replies.lock().entry(TypeId::of::<M>()).or_default().push_back(Box::new(reply));
+        self.state.replied.wake_all();
+        self.state.runtime.run_until_stalled();
+    }
+
+    /// # [`TestProbe::expect_message`]
+    /// Runs the system until the probe receives a message, for at most `within` of virtual time, and returns it.
+    ///
+    /// # Panics
+    /// Panics if no message arrives in time, or if the next message is not of type `M`.
+    #[must_use]
+    pub fn expect_message<M: Message>(&self, within: Duration) -> M {
+        let until = self.state.runtime.now().saturating_add(within);
+        if !self.state.runtime.run_until(until, || !self.state.received.lock().is_empty()) {
+            panic!("TestProbe::expect_message: no message was received within {within:?}");
+        }
+
+        let (_, next) = self.state.received.lock().pop_front().expect("the probe received a message");
+        match next.downcast::<M>() {
+            Ok(message) => *message,
+            Err(_) => panic!("TestProbe::expect_message: the next message was not a {}", core::any::type_name::<M>()),
+        }
+    }
+
+    /// # [`TestProbe::await_message`]
+    /// Runs the system until the probe receives a message of type `M`, for at most `timeout` of virtual time, and returns it
+    /// along with when it arrived. Messages of other types that arrive first are left for later expectations.
+    ///
+    /// # Panics
+    /// Panics if no message of type `M` arrives in time.
+    #[must_use]
+    pub fn await_message<M: Message>(&self, timeout: Duration) -> Received<M> {
+        let position = || self.state.received.lock().iter().position(|(_, message)| message.is::<M>());
+
+        let until = self.state.runtime.now().saturating_add(timeout);
+        if !self.state.runtime.run_until(until, || position().is_some()) {
+            panic!("TestProbe::await_message: no {} was received within {timeout:?}", core::any::type_name::<M>());
+        }
+
+        let (at, message) = position()
+            .and_then(|position| self.state.received.lock().remove(position))
+            .expect("the probe received the message");
+
+        match message.downcast::<M>() {
+            Ok(message) => Received { message: *message, at },
+            Err(_) => unreachable!("the message was checked to be a {}", core::any::type_name::<M>()),
+        }
+    }
+
+    /// # [`TestProbe::expect_no_message`]
+    /// Runs the system for `within` of virtual time, and checks that the probe received no messages.
+    ///
+    /// # Panics
+    /// Panics if the probe received a message.
+    pub fn expect_no_message(&self, within: Duration) {
+        self.state.runtime.advance_to(self.state.runtime.now().saturating_add(within));
+
+        let received = self.state.received.lock().len();
+        assert!(received == 0, "TestProbe::expect_no_message: {received} messages were received within {within:?}");
+    }
+}
@@ -0,0 +1,243 @@
+//! # Simulation
+//! A [`Simulation`] runs several systems on one deterministic runtime, connected by a simulated network. Every system
+//! uses a [`SimDelegate`], which delivers foreign messages straight to the actor on the target system, subject to the
+//! faults injected into the network: links can be partitioned, messages and responses can be dropped, and each one can
+//! be delayed by a random amount, which reorders them.
+//!
+//! Woken tasks run in a random order and every fault is decided by a random number generator, both seeded by
+//! [`Simulation::new`], so a failing run can be reproduced exactly by running it again with the same seed.
+
+use core::{marker::PhantomData, time::Duration};
+
+use alloc::{collections::{BTreeMap, BTreeSet}, string::{String, ToString}, sync::{Arc, Weak}};
+use maitake_sync::spin::Mutex;
+
+use crate::{Actor, Delegate, Fluxion, FluxionBuilder, Handler, Identifier, IndeterminateMessage, LocalRef, Message, MessageSendError, MessageSender, Timer};
+
+use super::{Rng, Runtime, TestSystem, VirtualTimer};
+
+/// The faults injected into a simulated network.
+#[derive(Default)]
+struct Faults {
+    /// The pairs of systems that can't reach each other, with the lower id first
+    partitions: BTreeSet<(String, String)>,
+    /// The percentage of messages and responses that are lost
+    drop_percent: u8,
+    /// The shortest and longest time a message or response takes to arrive
+    delay: (Duration, Duration),
+}
+
+/// What happens to a message sent over the network.
+enum Transmission {
+    /// The message arrives after the delay
+    Delivered(Duration),
+    /// The message is lost
+    Dropped,
+}
+
+/// The state shared by a [`Simulation`] and its systems' delegates.
+struct SimState {
+    /// The runtime every system runs on
+    runtime: Arc<Runtime>,
+    /// The systems in the simulation, keyed by their id
+    systems: Mutex<BTreeMap<String, Fluxion<SimDelegate>>>,
+    /// The faults injected into the network
+    faults: Mutex<Faults>,
+    /// Decides which messages are dropped and how long they are delayed
+    rng: Mutex<Rng>,
+}
+
+impl SimState {
+    /// Sends a message or response from one system to another, returning what happened to it.
+    ///
+    /// # Errors
+    /// Returns [`MessageSendError::PeerUnreachable`] if the systems are partitioned.
+    fn transmit(&self, from: &str, to: &str) -> Result<Transmission, MessageSendError> {
+        let faults = self.faults.lock();
+
+        if faults.partitions.contains(&link(from, to)) {
+            return Err(MessageSendError::PeerUnreachable);
+        }
+
+        let mut rng = self.rng.lock();
+        if rng.below(100) < usize::from(faults.drop_percent) {
+            return Ok(Transmission::Dropped);
+        }
+
+        let (shortest, longest) = faults.delay;
+        let spread = u64::try_from(longest.saturating_sub(shortest).as_nanos()).unwrap_or(u64::MAX);
+        let extra = match spread {
+            0 => 0,
+            spread => rng.next_u64() % spread.saturating_add(1),
+        };
+
+        Ok(Transmission::Delivered(shortest + Duration::from_nanos(extra)))
+    }
+
+    /// Carries a message or response over the network, suspending forever if it is dropped.
+    async fn carry(&self, from: &str, to: &str) -> Result<(), MessageSendError> {
+        match self.transmit(from, to)? {
+            Transmission::Delivered(Duration::ZERO) => Ok(()),
+            Transmission::Delivered(delay) => {
+                VirtualTimer(self.runtime.clone()).sleep(delay).await;
+                Ok(())
+            },
+            // The sender never hears back, just as it wouldn't on a real network
+            Transmission::Dropped => core::future::pending().await,
+        }
+    }
+}
+
+/// Returns the key of the link between two systems, which is the same in both directions.
+fn link(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+/// # [`Simulation`]
+/// Several systems connected by a simulated network, as described in the [module documentation](self).
+pub struct Simulation(Arc<SimState>);
+
+impl Simulation {
+    /// # [`Simulation::new`]
+    /// Creates a simulation with no systems and a perfect network, whose scheduling and faults are decided by the seed.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self(Arc::new(SimState {
+            runtime: Arc::new(Runtime::seeded(seed)),
+            systems: Mutex::new(BTreeMap::new()),
+            faults: Mutex::new(Faults::default()),
+            // Use a different stream of numbers than the scheduler
+            rng: Mutex::new(Rng::new(!seed)),
+        }))
+    }
+
+    /// # [`Simulation::system`]
+    /// Adds a system with the given id to the simulation. Every system shares the simulation's runtime and clock,
+    /// so any of them can be used to run the simulation.
+    #[must_use]
+    pub fn system(&self, id: &str) -> TestSystem<SimDelegate> {
+        self.system_with(id, |builder| builder)
+    }
+
+    /// # [`Simulation::system_with`]
+    /// Adds a system with the given id to the simulation, configured by the function.
+    #[must_use]
+    pub fn system_with(&self, id: &str, configure: impl FnOnce(FluxionBuilder<SimDelegate>) -> FluxionBuilder<SimDelegate>) -> TestSystem<SimDelegate> {
+        let delegate = SimDelegate { system: id.to_string(), simulation: Arc::downgrade(&self.0) };
+        let system = TestSystem::with_runtime(self.0.runtime.clone(), configure(Fluxion::builder(id, delegate)));
+
+        self.0.systems.lock().insert(id.to_string(), system.system().clone());
+        system
+    }
+
+    /// # [`Simulation::partition`]
+    /// Stops the two systems from reaching each other, in both directions.
+    pub fn partition(&self, a: &str, b: &str) {
+        self.0.faults.lock().partitions.insert(link(a, b));
+    }
+
+    /// # [`Simulation::heal`]
+    /// Reconnects the two systems.
+    pub fn heal(&self, a: &str, b: &str) {
+        self.0.faults.lock().partitions.remove(&link(a, b));
+    }
+
+    /// # [`Simulation::heal_all`]
+    /// Reconnects every partitioned system.
+    pub fn heal_all(&self) {
+        self.0.faults.lock().partitions.clear();
+    }
+
+    /// # [`Simulation::drop_percent`]
+    /// Loses the given percentage of messages and responses. Senders of a lost message never hear back, so they should
+    /// send with a timeout. Percentages over 100 are treated as 100.
+    pub fn drop_percent(&self, percent: u8) {
+        self.0.faults.lock().drop_percent = percent.min(100);
+    }
+
+    /// # [`Simulation::delay`]
+    /// Delays every message and response by a random duration between `shortest` and `longest`, which reorders them.
+    pub fn delay(&self, shortest: Duration, longest: Duration) {
+        self.0.faults.lock().delay = (shortest, longest.max(shortest));
+    }
+}
+
+/// # [`SimDelegate`]
+/// The [`Delegate`] of each system in a [`Simulation`], which delivers foreign messages over the simulated network.
+pub struct SimDelegate {
+    /// The id of the system this delegate belongs to
+    system: String,
+    /// The simulation the system is part of
+    simulation: Weak<SimState>,
+}
+
+impl SimDelegate {
+    /// Returns a sender for the actor on another system in the simulation, if it exists.
+    async fn sender<A: Handler<M>, M: Message>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> {
+        let simulation = self.simulation.upgrade()?;
+        let remote = |system: &str| simulation.systems.lock().get(system).cloned();
+
+        let (remote, system, id) = match id {
+            Identifier::Foreign(id, system) => (remote(system)?, system, id),
+            Identifier::ForeignNamed(name, system) => {
+                let remote = remote(system)?;
+                let id = remote.get_actor_id(name).await?;
+                (remote, system, id)
+            },
+            Identifier::Local(_) | Identifier::LocalNamed(_) => return None,
+        };
+        let actor = remote.get_local::<A>(id).await?;
+
+        Some(Arc::new(SimSender {
+            simulation: self.simulation.clone(),
+            from: self.system.clone(),
+            to: system.to_string(),
+            actor,
+            _message: PhantomData,
+        }))
+    }
+}
+
+impl Delegate for SimDelegate {
+    #[cfg(not(feature = "serde"))]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>> {
+        self.sender::<A, M>(id).await
+    }
+
+    #[cfg(feature = "serde")]
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: serde::Serialize + for<'a> serde::Deserialize<'a> {
+        self.sender::<A, M>(id).await
+    }
+}
+
+/// Sends messages to an actor on another system in a [`Simulation`].
+struct SimSender<A: Actor, M> {
+    /// The simulation carrying the messages
+    simulation: Weak<SimState>,
+    /// The id of the sending system
+    from: String,
+    /// The id of the receiving system
+    to: String,
+    /// The actor on the receiving system
+    actor: LocalRef<A, SimDelegate>,
+    /// The type of message sent
+    _message: PhantomData<fn(M)>,
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: Message> MessageSender<M> for SimSender<A, M> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        let simulation = self.simulation.upgrade().ok_or(MessageSendError::PeerUnreachable)?;
+
+        simulation.carry(&self.from, &self.to).await?;
+        let response = self.actor.send(message).await?;
+        simulation.carry(&self.to, &self.from).await?;
+
+        Ok(response)
+    }
+}
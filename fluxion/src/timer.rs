@@ -0,0 +1,43 @@
+//! # Timers
+//! Fluxion never reads a clock on its own, which is what keeps it executor agnostic.
+//! Anything time based, such as request timeouts, goes through a user provided [`Timer`].
+
+use core::{future::Future, pin::Pin, task::Poll, time::Duration};
+
+use alloc::boxed::Box;
+
+/// # [`Timer`]
+/// Provides Fluxion with the ability to wait for a period of time on whatever executor the user is running.
+/// For example, with Tokio this is just a wrapper around `tokio::time::sleep`.
+pub trait Timer: Send + Sync + 'static {
+    /// # [`Timer::sleep`]
+    /// Returns a future that completes once the given duration has elapsed.
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    /// # [`Timer::now`]
+    /// Returns the time elapsed since some fixed point, such as when the timer was created, which is used to measure
+    /// how long handlers take for [`crate::metrics`]. Timers that can't read a clock return [`None`], which is the default.
+    fn now(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Races the given future against a sleep of the given duration on `timer`.
+/// Returns [`None`] if the sleep completed first.
+pub(crate) async fn timeout<F: Future>(timer: &dyn Timer, duration: Duration, future: F) -> Option<F::Output> {
+    let mut future = core::pin::pin!(future);
+    let mut sleep = timer.sleep(duration);
+
+    core::future::poll_fn(|cx| {
+        // The future is polled first so that a result which is ready at the deadline still wins.
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+
+        if sleep.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+
+        Poll::Pending
+    }).await
+}
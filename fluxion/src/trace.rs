@@ -0,0 +1,21 @@
+//! # Tracing
+//! With the `tracing` feature, adding actors, sending messages, handling them, and calls to the [`crate::Delegate`]
+//! are wrapped in `tracing` spans carrying the actor's id and the message's type. Without it, these are no-ops.
+
+/// Wraps a future in a debug span with the given name and fields.
+#[cfg(feature = "tracing")]
+macro_rules! instrument {
+    ($future:expr, $($span:tt)+) => {
+        tracing::Instrument::instrument($future, tracing::debug_span!($($span)+))
+    };
+}
+
+/// Wraps a future in a debug span with the given name and fields.
+#[cfg(not(feature = "tracing"))]
+macro_rules! instrument {
+    ($future:expr, $($span:tt)+) => {
+        $future
+    };
+}
+
+pub(crate) use instrument;
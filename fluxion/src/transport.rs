@@ -0,0 +1,1737 @@
+//! # Transport
+//! Building blocks shared by Fluxion's bundled [`Delegate`] implementations.
+//!
+//! Every transport speaks the same protocol: [`Frame`]s serialized with bincode, multiplexed over a
+//! single connection per peer system using request ids. Each frame is prefixed with the [`PROTOCOL_VERSION`],
+//! and messages carry the stable type identifier of their [`crate::MessageID`], so that a receiver never has to guess
+//! what a payload contains. The receiving system only dispatches messages to
+//! actor/message pairs that have been explicitly exported via [`Exports::export`].
+//! Messages themselves are encoded by a [`MessageSerializer`], which defaults to [`BincodeSerializer`].
+//! Frames larger than the configured [`Chunking::chunk_size`] are split into [`Frame::Chunk`]s and reassembled on arrival.
+//! Systems configured with a credit window use [`Frame::Credit`]s to stop a fast sender from overwhelming a slow receiver.
+//! Actor names are resolved with [`Frame::Resolve`] and cached per connection in a [`NameCache`], and a serving system
+//! sends a [`Frame::Invalidate`] whenever one of its actors stops, so that stale names are never used.
+//! Connections can be probed with [`Frame::Ping`]s, so that an unresponsive system is detected as described in [`Liveness`].
+//! Notifications passed to [`Fluxion::notify`] are sent to every peer as a [`Frame::Notify`], and broadcast by the
+//! receiving system if it exported them with [`Exports::notification`].
+//! With the `signing` feature, messages can be signed by the system that sent them, as described in [`signing`].
+//! Messages sent between a pair of actors are handled in the order they were sent, as described in [`sequence`].
+
+#[cfg(feature = "tcp")]
+pub mod tcp;
+
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+#[cfg(feature = "quic")]
+pub mod quic;
+
+#[cfg(feature = "tls")]
+pub mod tls;
+
+#[cfg(feature = "signing")]
+pub mod signing;
+
+pub mod serialize;
+
+mod chunk;
+pub use chunk::Chunking;
+
+mod liveness;
+pub use liveness::Liveness;
+
+mod report;
+pub use report::{ErrorSink, ServeError};
+
+pub mod sequence;
+use sequence::{Lane, Lanes, Ready, Resequencer, Sequences};
+
+#[cfg(feature = "rkyv")]
+pub mod zero_copy;
+
+use core::{future::Future, marker::PhantomData, pin::Pin, sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}};
+use std::collections::HashMap;
+
+use alloc::{boxed::Box, collections::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec};
+use maitake_sync::spin::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::{sender::Caller, Credits, DeadLetterReason, Delegate, Fluxion, LifecycleEvent, NameCache, Handler, Identifier, IndeterminateMessage, Message, MessageSendError, MessageSender, MessageUpgrade, Metadata, Timer};
+use serialize::{BincodeSerializer, MessageSerializer};
+use chunk::Reassembly;
+use crate::trace::instrument;
+
+/// # [`TransportError`]
+/// An error that occurred while communicating with a foreign system.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TransportError {
+    /// The underlying connection failed.
+    Io(std::io::Error),
+    /// A frame could not be encoded or decoded.
+    Codec(bincode::Error),
+    /// The connection was closed before a response was received.
+    Closed,
+    /// A frame exceeded the maximum frame size.
+    FrameTooLarge(usize),
+    /// The foreign system speaks a different version of the protocol.
+    UnsupportedVersion(u16),
+    /// A message exceeded the maximum message size, even when split into chunks.
+    MessageTooLarge(usize),
+    /// The foreign system stopped answering liveness probes.
+    Unreachable,
+    /// The foreign system left more chunked messages partially sent than a connection buffers at once.
+    ReassemblyLimit,
+    /// The foreign system failed to handle the request.
+    Remote(RemoteError),
+}
+
+impl core::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "transport io error: {e}"),
+            Self::Codec(e) => write!(f, "failed to encode or decode frame: {e}"),
+            Self::Closed => write!(f, "connection closed"),
+            Self::FrameTooLarge(size) => write!(f, "frame of {size} bytes exceeds the maximum frame size"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported protocol version {version}, expected {PROTOCOL_VERSION}"),
+            Self::MessageTooLarge(size) => write!(f, "message of {size} bytes exceeds the maximum message size"),
+            Self::Unreachable => write!(f, "foreign system stopped responding"),
+            Self::ReassemblyLimit => write!(f, "too many partially received chunked messages on the connection"),
+            Self::Remote(e) => write!(f, "foreign system returned an error: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Codec(e) => Some(e.as_ref()),
+            Self::Remote(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<bincode::Error> for TransportError {
+    fn from(value: bincode::Error) -> Self {
+        Self::Codec(value)
+    }
+}
+
+impl From<TransportError> for MessageSendError {
+    fn from(value: TransportError) -> Self {
+        if let TransportError::Unreachable = value {
+            return MessageSendError::PeerUnreachable;
+        }
+
+        MessageSendError::DelegateError {
+            message: value.to_string(),
+            source: Box::new(value),
+        }
+    }
+}
+
+/// # [`RemoteError`]
+/// The reason a foreign system could not handle a [`Frame::Request`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum RemoteError {
+    /// No actor on the foreign system accepts the message, because it was never exported.
+    NotExported {
+        /// The type identifier of the message
+        message: String,
+    },
+    /// The actor exists, but is not of a type the message was exported for.
+    ForeignTypeMismatch {
+        /// The id of the actor on the foreign system
+        actor: u64,
+        /// The type identifier of the message
+        message: String,
+    },
+    /// The actor does not exist on the foreign system.
+    ActorNotFound(u64),
+    /// The message was encoded with a version of its schema that can't be decoded or upgraded.
+    UnsupportedMessageVersion {
+        /// The type identifier of the message
+        message: String,
+        /// The version the message was encoded with
+        version: u32,
+        /// The current version of the message on the foreign system
+        supported: u32,
+    },
+    /// The payload could not be decoded as the message it was tagged with.
+    Malformed {
+        /// The type identifier of the message
+        message: String,
+        /// Why decoding failed
+        reason: String,
+    },
+    /// The message was handled, but sending it to the actor or encoding its result failed.
+    Failed(String),
+    /// The message's signature was missing, was made by an untrusted system, or did not match the message.
+    InvalidSignature {
+        /// The type identifier of the message
+        message: String,
+        /// Why the signature was rejected
+        reason: String,
+    },
+}
+
+impl core::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NotExported { message } => write!(f, "message {message} is not exported"),
+            Self::ForeignTypeMismatch { actor, message } => write!(f, "actor {actor} is not of a type that accepts {message}"),
+            Self::ActorNotFound(actor) => write!(f, "actor {actor} does not exist"),
+            Self::UnsupportedMessageVersion { message, version, supported } =>
+                write!(f, "version {version} of {message} is not supported, the current version is {supported}"),
+            Self::Malformed { message, reason } => write!(f, "payload is not a valid {message}: {reason}"),
+            Self::Failed(e) => write!(f, "{e}"),
+            Self::InvalidSignature { message, reason } => write!(f, "signature of {message} is invalid: {reason}"),
+        }
+    }
+}
+
+impl core::error::Error for RemoteError {}
+
+/// The maximum size of a single frame, in bytes.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// # [`PROTOCOL_VERSION`]
+/// The version of the transport protocol, which prefixes every encoded frame.
+/// Frames with a different version are rejected, and the connection they arrived on is closed.
+pub const PROTOCOL_VERSION: u16 = 10;
+
+/// # [`Address`]
+/// Identifies an actor on a foreign system, either by id or by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Address {
+    Id(u64),
+    Name(String),
+}
+
+/// # [`Frame`]
+/// A single unit of the transport protocol.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Frame {
+    /// Asks the foreign system whether the given actor exists and accepts the given message.
+    Lookup { request: u64, actor: Address, message: String },
+    /// Answers a [`Frame::Lookup`] with the actor's id, if it was found.
+    Found { request: u64, actor: Option<u64> },
+    /// Sends a message, encoded with the given version of its schema, to an actor and expects a [`Frame::Response`].
+    /// A message with an idempotency key is only handled once, and duplicates are answered with the original response.
+    /// The message is handled with the sender's [`Metadata`], if it had any, and with the system and actor id of the
+    /// actor that sent it, if it was sent by one. Messages sent by an actor are numbered, so that they are handled in order
+    /// as described in [`sequence`]. A signed message carries the id of the system that signed it and its signature.
+    Request { request: u64, actor: u64, message: String, version: u32, key: Option<String>, metadata: Option<Metadata>, sender: Option<(String, u64)>, sequence: Option<u64>, signature: Option<(String, Vec<u8>)>, payload: Vec<u8> },
+    /// Sends a message to an actor without expecting a response.
+    Tell { actor: u64, message: String, version: u32, key: Option<String>, metadata: Option<Metadata>, sender: Option<(String, u64)>, sequence: Option<u64>, signature: Option<(String, Vec<u8>)>, payload: Vec<u8> },
+    /// The serialized result of a [`Frame::Request`], or why it failed.
+    Response { request: u64, result: Result<Vec<u8>, RemoteError> },
+    /// A piece of an encoded frame that was too large to send whole. The pieces of a transfer are sent in order,
+    /// and the frame is decoded and handled once the last one arrives.
+    Chunk { transfer: u64, last: bool, data: Vec<u8> },
+    /// Grants the receiver of the frame credits to send that many more [`Frame::Request`]s and [`Frame::Tell`]s.
+    Credit { credits: u64 },
+    /// Asks the receiver of the frame to grant more credits, as the sender has exhausted its own.
+    CreditRequest,
+    /// Asks the foreign system for the id of the actor with the given name, regardless of the messages it accepts.
+    Resolve { request: u64, name: String },
+    /// Answers a [`Frame::Resolve`] with the actor's id, if an actor with the name exists.
+    Resolved { request: u64, actor: Option<u64> },
+    /// Tells a connected system that the actor with the given id has stopped, so any names resolved to it are stale.
+    Invalidate { actor: u64 },
+    /// Checks that the foreign system is still responding, and expects a [`Frame::Pong`].
+    Ping { request: u64 },
+    /// Answers a [`Frame::Ping`].
+    Pong { request: u64 },
+    /// Broadcasts a notification, encoded with the given version of its schema, to every actor on the foreign system
+    /// that handles it, without expecting a response. A signed notification carries the id of the system that signed it and its signature.
+    Notify { message: String, version: u32, signature: Option<(String, Vec<u8>)>, payload: Vec<u8> },
+}
+
+impl Frame {
+    /// # [`Frame::encode`]
+    /// Encodes the frame, prefixed with the [`PROTOCOL_VERSION`].
+    ///
+    /// # Errors
+    /// Returns an error if the frame could not be encoded.
+    pub fn encode(&self) -> Result<Vec<u8>, TransportError> {
+        Ok(bincode::serialize(&(PROTOCOL_VERSION, self))?)
+    }
+
+    /// # [`Frame::decode`]
+    /// Decodes a frame encoded by [`Frame::encode`].
+    ///
+    /// # Errors
+    /// Returns [`TransportError::UnsupportedVersion`] if the frame was encoded with a different version of the protocol,
+    /// or [`TransportError::Codec`] if it is otherwise invalid.
+    pub fn decode(bytes: &[u8]) -> Result<Self, TransportError> {
+        // The version is read on its own first, as the rest of the frame may not be decodable if it differs
+        let version: u16 = bincode::deserialize(bytes)?;
+        if version != PROTOCOL_VERSION {
+            return Err(TransportError::UnsupportedVersion(version));
+        }
+
+        let (_, frame): (u16, Frame) = bincode::deserialize(bytes)?;
+        Ok(frame)
+    }
+
+    /// Returns the request id of frames that answer a request
+    fn response_to(&self) -> Option<u64> {
+        match self {
+            Frame::Found { request, .. } | Frame::Response { request, .. } | Frame::Resolved { request, .. } | Frame::Pong { request } => Some(*request),
+            _ => None,
+        }
+    }
+
+    /// Returns true for frames that deliver a message, which use up a credit
+    fn uses_credit(&self) -> bool {
+        matches!(self, Frame::Request { .. } | Frame::Tell { .. })
+    }
+
+    /// Returns the pair of actors a numbered message was sent between, along with its number
+    fn sequenced(&self) -> Option<(Lane, u64)> {
+        match self {
+            Frame::Request { actor, sender: Some(sender), sequence: Some(sequence), .. }
+                | Frame::Tell { actor, sender: Some(sender), sequence: Some(sequence), .. } => Some(((sender.clone(), *actor), *sequence)),
+            _ => None,
+        }
+    }
+}
+
+/// # [`FrameReader`]
+/// The receiving half of a connection, yielding whole encoded frames.
+#[async_trait::async_trait]
+pub trait FrameReader: Send + 'static {
+    /// Reads the next frame, returning [`None`] when the connection was closed cleanly.
+    async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError>;
+}
+
+/// # [`FrameWriter`]
+/// The sending half of a connection, accepting whole encoded frames.
+#[async_trait::async_trait]
+pub trait FrameWriter: Send + 'static {
+    /// Writes a single frame.
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError>;
+}
+
+/// # [`Connection`]
+/// The client side of a connection to a foreign system.
+/// Requests are multiplexed over the connection, with responses matched up by request id
+/// by a task that is spawned to read from the connection.
+pub struct Connection {
+    writer: tokio::sync::Mutex<Box<dyn FrameWriter>>,
+    pending: Mutex<HashMap<u64, oneshot::Sender<Frame>>>,
+    next_request: AtomicU64,
+    next_transfer: AtomicU64,
+    chunking: RwLock<Chunking>,
+    credits: RwLock<Option<Arc<Credits>>>,
+    names: NameCache,
+    sequences: Sequences,
+    closed: AtomicBool,
+    unreachable: AtomicBool,
+}
+
+impl Connection {
+    /// # [`Connection::new`]
+    /// Creates a new connection from the two halves of a transport, spawning a Tokio task that reads responses.
+    pub fn new(reader: impl FrameReader, writer: impl FrameWriter) -> Arc<Self> {
+        let (connection, driver) = Self::with_driver(reader, writer);
+        tokio::spawn(driver);
+        connection
+    }
+
+    /// # [`Connection::with_driver`]
+    /// Creates a new connection from the two halves of a transport, returning it alongside the future that reads responses.
+    /// The future must be driven to completion for any requests to complete, which allows runtimes other than Tokio to be used.
+    pub fn with_driver(reader: impl FrameReader, writer: impl FrameWriter) -> (Arc<Self>, impl Future<Output = ()> + Send + 'static) {
+        let connection = Arc::new(Self {
+            writer: tokio::sync::Mutex::new(Box::new(writer)),
+            pending: Mutex::default(),
+            next_request: AtomicU64::new(0),
+            next_transfer: AtomicU64::new(0),
+            chunking: RwLock::default(),
+            credits: RwLock::new(None),
+            names: NameCache::new(),
+            sequences: Sequences::default(),
+            closed: AtomicBool::new(false),
+            unreachable: AtomicBool::new(false),
+        });
+
+        (connection.clone(), connection.read_responses(reader))
+    }
+
+    /// Reads frames until the connection closes, completing pending requests as responses arrive.
+    async fn read_responses(self: Arc<Self>, mut reader: impl FrameReader) {
+        let mut reassembly = Reassembly::default();
+
+        while let Ok(Some(frame)) = reader.read_frame().await {
+            // Frames that fail to decode, or that don't answer a request, are ignored.
+            // A peer speaking another version of the protocol can't be understood at all, and a peer sending
+            // oversized messages, or too many chunked messages at once, can't be trusted not to exhaust memory, so the
+            // connection is closed.
+            let chunking = *self.chunking.read();
+            let frame = match reassembly.accept(&frame, &chunking) {
+                Ok(Some(frame)) => frame,
+                Err(TransportError::UnsupportedVersion(_) | TransportError::MessageTooLarge(_) | TransportError::ReassemblyLimit) => break,
+                Ok(None) | Err(_) => continue,
+            };
+            if let Frame::Credit { credits } = frame {
+                self.grant_credits(usize::try_from(credits).unwrap_or(usize::MAX));
+                continue;
+            }
+            if let Frame::Invalidate { actor } = frame {
+                self.names.invalidate(actor);
+                self.sequences.forget_actor(actor);
+                continue;
+            }
+            let Some(request) = frame.response_to() else {
+                continue;
+            };
+
+            let responder = self.pending.lock().remove(&request);
+            if let Some(responder) = responder {
+                let _ = responder.send(frame);
+            }
+        }
+
+        self.close();
+    }
+
+    /// Closes the connection, failing every pending request and any senders waiting for credits.
+    fn close(&self) {
+        // Dropping every pending responder fails their requests with `TransportError::Closed`.
+        self.closed.store(true, Ordering::Release);
+        self.pending.lock().clear();
+
+        // Senders waiting for credits would otherwise wait forever
+        if let Some(credits) = self.credits.read().as_ref() {
+            credits.close();
+        }
+    }
+
+    /// Marks the foreign system as unreachable and closes the connection, so that pending requests fail with
+    /// [`TransportError::Unreachable`] and a new connection is dialed for the next send.
+    pub(crate) fn mark_unreachable(&self) {
+        self.unreachable.store(true, Ordering::Release);
+        self.close();
+    }
+
+    /// # [`Connection::is_closed`]
+    /// Returns true if the connection has been closed, in which case a new one needs to be established.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// # [`Connection::is_unreachable`]
+    /// Returns true if the connection was closed because the foreign system stopped answering liveness probes.
+    pub fn is_unreachable(&self) -> bool {
+        self.unreachable.load(Ordering::Acquire)
+    }
+
+    /// # [`Connection::set_liveness`]
+    /// Starts probing the foreign system on a new Tokio task, as described in [`Liveness`].
+    /// The task stops once the connection closes or is dropped.
+    pub fn set_liveness(self: &Arc<Self>, liveness: Liveness) {
+        tokio::spawn(self.liveness_probe(liveness, Arc::new(liveness::TokioTimer)));
+    }
+
+    /// # [`Connection::liveness_probe`]
+    /// Returns the future that probes the foreign system as described in [`Liveness`], keeping time with the given [`Timer`].
+    /// Like [`Connection::with_driver`], this allows runtimes other than Tokio to be used, by spawning the future on them.
+    /// The future completes once the connection closes or is dropped.
+    pub fn liveness_probe(self: &Arc<Self>, liveness: Liveness, timer: Arc<dyn Timer>) -> impl Future<Output = ()> + Send + 'static {
+        liveness::probe(Arc::downgrade(self), liveness, timer)
+    }
+
+    /// # [`Connection::set_chunking`]
+    /// Changes how frames sent on the connection are split into chunks, and the largest message that may be sent.
+    pub fn set_chunking(&self, chunking: Chunking) {
+        *self.chunking.write() = chunking;
+    }
+
+    /// # [`Connection::set_credit_window`]
+    /// Enables credit based flow control, with the given number of credits initially granted by the foreign system.
+    /// The window must match the one passed to the foreign system's [`Exports::with_credit_window`].
+    pub fn set_credit_window(&self, window: usize) {
+        *self.credits.write() = Some(Arc::new(Credits::new(window)));
+    }
+
+    /// # [`Connection::grant_credits`]
+    /// Adds credits granted by the foreign system, waking any senders waiting for them.
+    /// Does nothing if flow control is not enabled.
+    pub fn grant_credits(&self, credits: usize) {
+        if let Some(window) = self.credits.read().as_ref() {
+            window.grant(credits);
+        }
+    }
+
+    /// # [`Connection::resolve`]
+    /// Resolves the name of an actor on the foreign system to its id, using the cached id if the name was already resolved.
+    ///
+    /// # Errors
+    /// Returns an error if the request could not be sent, or if the connection closed before it was answered.
+    pub async fn resolve(&self, name: &str) -> Result<Option<u64>, TransportError> {
+        if let Some(id) = self.names.get(name) {
+            return Ok(Some(id));
+        }
+
+        let resolved = self.request(|request| Frame::Resolve { request, name: String::from(name) }).await?;
+        let Frame::Resolved { actor, .. } = resolved else {
+            return Ok(None);
+        };
+
+        if let Some(id) = actor {
+            self.names.insert(name, id);
+        }
+
+        Ok(actor)
+    }
+
+    /// # [`Connection::names`]
+    /// Returns the cache of names resolved on this connection.
+    pub fn names(&self) -> &NameCache {
+        &self.names
+    }
+
+    /// # [`Connection::request_credits`]
+    /// Asks the foreign system to grant more credits.
+    ///
+    /// # Errors
+    /// Returns an error if the request could not be written.
+    pub async fn request_credits(&self) -> Result<(), TransportError> {
+        self.write(&Frame::CreditRequest).await
+    }
+
+    /// Takes a credit before a message is sent, asking for more and waiting for them if there are none left.
+    /// The credit is given back unless it is spent once the message has been written.
+    async fn acquire_credit(&self) -> Result<Credit, TransportError> {
+        let Some(credits) = self.credits.read().clone() else {
+            return Ok(Credit(None));
+        };
+
+        if credits.try_acquire() {
+            return Ok(Credit(Some(credits)));
+        }
+
+        self.request_credits().await?;
+        if credits.acquire().await {
+            Ok(Credit(Some(credits)))
+        } else {
+            Err(TransportError::Closed)
+        }
+    }
+
+    /// # [`Connection::send`]
+    /// Sends a frame without waiting for a response, split into chunks if it is larger than the chunk size.
+    /// Messages wait for a credit first if flow control is enabled.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::MessageTooLarge`] if the frame exceeds the maximum message size,
+    /// or another error if the frame could not be encoded or written.
+    pub async fn send(&self, frame: &Frame) -> Result<(), TransportError> {
+        // The frame is encoded before a credit is taken, so a frame that can't be encoded never holds one
+        let chunks = self.encode(frame)?;
+
+        let credit = if frame.uses_credit() {
+            Some(self.acquire_credit().await?)
+        } else {
+            None
+        };
+
+        self.write_chunks(chunks).await?;
+        if let Some(credit) = credit {
+            credit.spend();
+        }
+
+        Ok(())
+    }
+
+    /// Writes a frame, split into chunks if it is larger than the chunk size.
+    async fn write(&self, frame: &Frame) -> Result<(), TransportError> {
+        let chunks = self.encode(frame)?;
+        self.write_chunks(chunks).await
+    }
+
+    /// Encodes a frame with a fresh transfer id, split into chunks if it is larger than the chunk size.
+    fn encode(&self, frame: &Frame) -> Result<Vec<Vec<u8>>, TransportError> {
+        let transfer = self.next_transfer.fetch_add(1, Ordering::Relaxed);
+        self.chunking.read().encode(frame, transfer)
+    }
+
+    /// Writes the chunks of a single frame.
+    async fn write_chunks(&self, chunks: Vec<Vec<u8>>) -> Result<(), TransportError> {
+        // The writer is held for every chunk, so that chunks of different transfers are never interleaved
+        let mut writer = self.writer.lock().await;
+        for chunk in chunks {
+            writer.write_frame(&chunk).await?;
+        }
+
+        Ok(())
+    }
+
+    /// # [`Connection::request`]
+    /// Sends the frame built by `frame` with a fresh request id, and waits for the matching response.
+    ///
+    /// # Errors
+    /// Returns an error if the frame could not be sent, or if the connection closed before a response arrived.
+    pub async fn request(&self, frame: impl FnOnce(u64) -> Frame) -> Result<Frame, TransportError> {
+        let (request, response) = self.register()?;
+        let sent = self.send(&frame(request)).await;
+
+        self.await_response(request, response, sent).await
+    }
+
+    /// Sends a message frame built by `frame` like [`Connection::send`], numbering it if it was sent by an actor, as
+    /// described in [`sequence`]. The number is only taken once a credit is held and the frame has been encoded, under
+    /// the writer's lock, so a message that fails to send never leaves a gap in the numbering. A numbered frame that
+    /// fails part way through being written closes the connection, as the foreign system can't tell whether it arrived.
+    pub(crate) async fn send_numbered(&self, sender: Option<&(String, u64)>, actor: u64, frame: impl FnOnce(Option<u64>) -> Frame) -> Result<(), TransportError> {
+        // The credit is given back if the frame fails to encode or write
+        let credit = self.acquire_credit().await?;
+
+        let mut writer = self.writer.lock().await;
+        let sequence = self.sequences.peek(sender, actor);
+        let chunks = self.encode(&frame(sequence))?;
+
+        if let (Some(sender), Some(sequence)) = (sender, sequence) {
+            self.sequences.advance(sender, actor, sequence);
+        }
+
+        for chunk in chunks {
+            if let Err(e) = writer.write_frame(&chunk).await {
+                if sequence.is_some() {
+                    self.close();
+                }
+                return Err(e);
+            }
+        }
+
+        credit.spend();
+        Ok(())
+    }
+
+    /// Sends a message frame built by `frame` with a fresh request id, numbered like [`Connection::send_numbered`],
+    /// and waits for the matching response.
+    pub(crate) async fn request_numbered(&self, sender: Option<&(String, u64)>, actor: u64, frame: impl FnOnce(u64, Option<u64>) -> Frame) -> Result<Frame, TransportError> {
+        let (request, response) = self.register()?;
+        let sent = self.send_numbered(sender, actor, |sequence| frame(request, sequence)).await;
+
+        self.await_response(request, response, sent).await
+    }
+
+    /// Registers the responder for a fresh request id, before the request is sent so that the response can't arrive first.
+    fn register(&self) -> Result<(u64, oneshot::Receiver<Frame>), TransportError> {
+        let request = self.next_request.fetch_add(1, Ordering::Relaxed);
+        let (responder, response) = oneshot::channel();
+        self.pending.lock().insert(request, responder);
+
+        // A connection that closed before the responder was registered would never complete it
+        if self.is_closed() {
+            self.pending.lock().remove(&request);
+            return Err(self.closed_error());
+        }
+
+        Ok((request, response))
+    }
+
+    /// Waits for the response to a request, unless the request could not be sent.
+    async fn await_response(&self, request: u64, response: oneshot::Receiver<Frame>, sent: Result<(), TransportError>) -> Result<Frame, TransportError> {
+        if let Err(e) = sent {
+            self.pending.lock().remove(&request);
+            return Err(e);
+        }
+
+        response.await.map_err(|_| self.closed_error())
+    }
+
+    /// Returns the error requests fail with once the connection has closed.
+    fn closed_error(&self) -> TransportError {
+        if self.is_unreachable() {
+            TransportError::Unreachable
+        } else {
+            TransportError::Closed
+        }
+    }
+}
+
+/// A credit taken to send a message on a [`Connection`], which is given back when dropped unless it was spent.
+/// Credits are only re-granted by the foreign system for messages that reach it, so one taken by a message that
+/// failed to encode or write would otherwise be lost for good.
+struct Credit(Option<Arc<Credits>>);
+
+impl Credit {
+    /// Keeps the credit used up, once its message has been written.
+    fn spend(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Credit {
+    fn drop(&mut self) {
+        if let Some(credits) = self.0.take() {
+            credits.grant(1);
+        }
+    }
+}
+
+/// # [`Exports`]
+/// The set of actor/message pairs on a system that foreign systems are allowed to reach,
+/// and the [`MessageSerializer`] their messages are decoded with.
+pub struct Exports<D, S = BincodeSerializer> {
+    system: Fluxion<D>,
+    handlers: BTreeMap<&'static str, Vec<Box<dyn ExportedHandler<D>>>>,
+    notifications: BTreeMap<&'static str, NotifyFn<D>>,
+    chunking: Chunking,
+    credit_window: Option<usize>,
+    error_sink: Option<Arc<dyn ErrorSink>>,
+    _serializer: PhantomData<fn() -> S>,
+}
+
+impl<D: Delegate> Exports<D> {
+    /// # [`Exports::new`]
+    /// Creates an empty set of exports for the given system, whose messages are encoded with bincode.
+    #[must_use]
+    pub fn new(system: Fluxion<D>) -> Self {
+        Self::with_serializer(system, BincodeSerializer)
+    }
+}
+
+impl<D: Delegate, S: MessageSerializer> Exports<D, S> {
+    /// # [`Exports::with_serializer`]
+    /// Creates an empty set of exports for the given system, whose messages are encoded with the given serializer.
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn with_serializer(system: Fluxion<D>, serializer: S) -> Self {
+        let _ = serializer;
+        Self { system, handlers: BTreeMap::new(), notifications: BTreeMap::new(), chunking: Chunking::default(), credit_window: None, error_sink: None, _serializer: PhantomData }
+    }
+
+    /// # [`Exports::with_chunking`]
+    /// Sets how responses are split into chunks, and the largest message that may be received or sent.
+    #[must_use]
+    pub fn with_chunking(mut self, chunking: Chunking) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// # [`Exports::with_credit_window`]
+    /// Enables credit based flow control, allowing each connected system to send up to `window` messages
+    /// that have not yet been handled. Credits are granted back as messages finish being handled.
+    /// Peers must be configured with the same window, using [`PeerDelegate::with_credit_window`].
+    #[must_use]
+    pub fn with_credit_window(mut self, window: usize) -> Self {
+        self.credit_window = Some(window.max(1));
+        self
+    }
+
+    /// # [`Exports::with_error_sink`]
+    /// Reports the errors of served connections that can't be returned to the peer, such as frames that fail to decode
+    /// and messages sent without expecting a response that fail, to the given [`ErrorSink`] rather than logging them.
+    #[must_use]
+    pub fn with_error_sink<E: ErrorSink>(mut self, sink: E) -> Self {
+        self.error_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// # [`Exports::export`]
+    /// Allows foreign systems to send the message `M` to any actor of type `A` on this system.
+    /// Only the current version of `M` is accepted.
+    #[must_use]
+    pub fn export<A: Handler<M>, M: IndeterminateMessage>(mut self) -> Self
+        where M::Result: Serialize + for<'de> Deserialize<'de> {
+        self.handlers.entry(M::ID).or_default().push(Box::new(Export::<A, M, S>::new(decode_current::<M, S>)));
+        self
+    }
+
+    /// # [`Exports::export_upgradable`]
+    /// Allows foreign systems to send the message `M` to any actor of type `A` on this system, like [`Exports::export`].
+    /// Messages encoded with an older version of `M` are upgraded with [`MessageUpgrade`] before they are handled.
+    #[must_use]
+    pub fn export_upgradable<A: Handler<M>, M: IndeterminateMessage + MessageUpgrade>(mut self) -> Self
+        where M::Result: Serialize + for<'de> Deserialize<'de> {
+        self.handlers.entry(M::ID).or_default().push(Box::new(Export::<A, M, S>::new(decode_upgraded::<M, S>)));
+        self
+    }
+
+    /// # [`Exports::notification`]
+    /// Allows foreign systems to send the notification `N` with [`Fluxion::notify`], which is broadcast to every local
+    /// actor spawned with [`crate::Spawn::handles`] for `N`. Only the current version of `N` is accepted.
+    #[must_use]
+    pub fn notification<N: IndeterminateMessage + Clone>(mut self) -> Self
+        where N::Result: Serialize + for<'de> Deserialize<'de> {
+        self.notifications.insert(N::ID, notify_exported::<N, S, D>);
+        self
+    }
+
+    /// Resolves an address to a local actor id, if the actor exists, accepts the given message,
+    /// and may be reached by the peer.
+    async fn lookup(&self, actor: Address, message: &str, peer: Option<&str>) -> Option<u64> {
+        let id = match actor {
+            Address::Id(id) => id,
+            Address::Name(name) => self.system.get_actor_id(&name).await?,
+        };
+
+        if !self.system.allows_foreign(peer, id, Some(message)).await {
+            return None;
+        }
+
+        for handler in self.handlers.get(message)? {
+            if handler.accepts(&self.system, id).await {
+                return Some(id);
+            }
+        }
+
+        None
+    }
+
+    /// Returns true if the actor exists and has not started stopping.
+    fn is_running(&self, actor: u64) -> bool {
+        self.system.registry.get(actor, |registered| !registered.gate.is_stopped()) == Some(true)
+    }
+
+    /// Resolves a name to a local actor id, if the actor exists and may be reached by the peer.
+    async fn resolve(&self, name: &str, peer: Option<&str>) -> Option<u64> {
+        let id = self.system.get_actor_id(name).await?;
+        self.system.allows_foreign(peer, id, None).await.then_some(id)
+    }
+
+    /// Dispatches a serialized message to a local actor, returning its serialized response.
+    /// The message's type identifier decides how the payload is decoded, and it is only decoded
+    /// if the actor is of a type the message was exported for.
+    async fn dispatch(&self, actor: u64, message: &str, version: u32, payload: Vec<u8>) -> Result<Vec<u8>, RemoteError> {
+        let handlers = self.handlers.get(message)
+            .ok_or_else(|| RemoteError::NotExported { message: String::from(message) })?;
+
+        let mut exported = None;
+        for handler in handlers {
+            if handler.accepts(&self.system, actor).await {
+                exported = Some(handler);
+                break;
+            }
+        }
+
+        // No exported handler matched the actor, so either it doesn't exist or it is of another type
+        let Some(handler) = exported else {
+            return Err(if self.system.registry.contains(actor) {
+                RemoteError::ForeignTypeMismatch { actor, message: String::from(message) }
+            } else {
+                RemoteError::ActorNotFound(actor)
+            });
+        };
+
+        // The actor may have been removed since it was accepted
+        let result = handler.dispatch(&self.system, actor, version, payload).await
+            .ok_or(RemoteError::ActorNotFound(actor))?;
+
+        result.map_err(|e| e.into_remote(message, version))
+    }
+
+    /// Decodes a notification and broadcasts it to the local actors that handle it.
+    async fn notify(&self, message: &str, version: u32, payload: Vec<u8>) -> Result<(), RemoteError> {
+        let notify = self.notifications.get(message)
+            .ok_or_else(|| RemoteError::NotExported { message: String::from(message) })?;
+
+        notify(&self.system, version, payload).await
+            .map_err(|e| e.into_remote(message, version))
+    }
+
+    /// Dispatches a serialized message like [`Exports::dispatch`], unless a message with the same idempotency key
+    /// was already dispatched to the same actor, in which case the original response is returned.
+    /// Actors the peer may not reach are reported as not existing.
+    async fn dispatch_once(&self, actor: u64, message: &str, version: u32, key: Option<&str>, peer: Option<&str>, payload: Vec<u8>) -> Result<Vec<u8>, RemoteError> {
+        if !self.system.allows_foreign(peer, actor, Some(message)).await {
+            return Err(RemoteError::ActorNotFound(actor));
+        }
+
+        let Some(key) = key else {
+            return self.dispatch(actor, message, version, payload).await;
+        };
+
+        self.system.deduplicate(&alloc::format!("{actor}/{message}/{key}"), self.dispatch(actor, message, version, payload)).await
+    }
+
+    /// Reports an error that could not be returned to the peer to the [`ErrorSink`], or logs it if there is none.
+    fn report(&self, peer: Option<&str>, error: &ServeError) {
+        if let Some(sink) = &self.error_sink {
+            sink.report(peer, error);
+            return;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::warn!(peer, %error, "error while serving a foreign system");
+    }
+
+    /// Verifies the signature of a message frame with the system's signing keys, returning the id of the system that signed it.
+    #[cfg_attr(not(feature = "signing"), allow(clippy::unused_self, clippy::unnecessary_wraps))]
+    fn verify(&self, frame: &Frame) -> Result<Option<String>, RemoteError> {
+        #[cfg(feature = "signing")]
+        if let Some(signing) = &self.system.signing {
+            return signing.verify(frame);
+        }
+
+        let _ = frame;
+        Ok(None)
+    }
+
+    /// Reports a message that was refused before it was handled, such as one whose signature didn't verify, as it has no
+    /// response to carry the error, and records it as a dead letter. Notifications are recorded against this system.
+    async fn refuse(&self, frame: Frame, peer: Option<&str>, error: RemoteError) {
+        let reason = DeadLetterReason::SendFailed(alloc::format!("{error}"));
+
+        match frame {
+            Frame::Tell { actor, message, sender, .. } => {
+                // Dead letters are recorded with the static identifier the message was exported with
+                let name = self.handlers.get_key_value(message.as_str()).map_or("unknown", |(name, _)| *name);
+                Caller::act_if(sender.map(Caller::from_frame), self.system.dead_letters.record_named(actor, name, reason)).await;
+                self.report(peer, &ServeError::Tell { actor, message, error });
+            },
+            Frame::Notify { message, .. } => {
+                let name = self.notifications.get_key_value(message.as_str()).map_or("unknown", |(name, _)| *name);
+                self.system.dead_letters.record_named(self.system.get_id(), name, reason).await;
+                self.report(peer, &ServeError::Notify { message, error });
+            },
+            _ => {},
+        }
+    }
+
+    /// Handles a single frame received from a foreign system, returning the response frame if there is one.
+    /// Messages are handled with the peer's identity, if the transport established it.
+    /// Signed messages are handled with the identity of the system that signed them if the transport established none.
+    async fn handle(&self, frame: Frame, peer: Option<&str>) -> Option<Frame> {
+        let signer = match self.verify(&frame) {
+            Ok(signer) => signer,
+            Err(e) => {
+                if let Frame::Request { request, .. } = frame {
+                    return Some(Frame::Response { request, result: Err(e) });
+                }
+
+                self.refuse(frame, peer, e).await;
+                return None;
+            },
+        };
+        let peer = peer.or(signer.as_deref());
+
+        match frame {
+            Frame::Lookup { request, actor, message } => Some(Frame::Found {
+                request,
+                actor: self.lookup(actor, &message, peer).await,
+            }),
+            Frame::Request { request, actor, message, version, key, metadata, sender, payload, .. } => Some(Frame::Response {
+                request,
+                result: Metadata::scope_if(Metadata::with_peer(metadata, peer), Caller::act_if(sender.map(Caller::from_frame),
+                    instrument!(self.dispatch_once(actor, &message, version, key.as_deref(), peer, payload),
+                    "fluxion::transport::dispatch", actor, message, version, request))).await,
+            }),
+            Frame::Ping { request } => Some(Frame::Pong { request }),
+            Frame::Resolve { request, name } => Some(Frame::Resolved {
+                request,
+                actor: self.resolve(&name, peer).await,
+            }),
+            Frame::Tell { actor, message, version, key, metadata, sender, payload, .. } => {
+                let result = Metadata::scope_if(Metadata::with_peer(metadata, peer), Caller::act_if(sender.map(Caller::from_frame),
+                    instrument!(self.dispatch_once(actor, &message, version, key.as_deref(), peer, payload),
+                    "fluxion::transport::dispatch", actor, message, version))).await;
+
+                // There is no response to carry the error, so it is reported here instead
+                if let Err(error) = result {
+                    self.report(peer, &ServeError::Tell { actor, message, error });
+                }
+                None
+            },
+            Frame::Notify { message, version, payload, .. } => {
+                let result = Metadata::scope_if(Metadata::with_peer(None, peer),
+                    instrument!(self.notify(&message, version, payload), "fluxion::transport::notify", message, version)).await;
+
+                // Notifications have no response to carry the error either
+                if let Err(error) = result {
+                    self.report(peer, &ServeError::Notify { message, error });
+                }
+                None
+            },
+            Frame::Found { .. } | Frame::Response { .. } | Frame::Resolved { .. } | Frame::Invalidate { .. } | Frame::Pong { .. }
+                | Frame::Chunk { .. } | Frame::Credit { .. } | Frame::CreditRequest => None,
+        }
+    }
+}
+
+/// # [`serve_connection`]
+/// Handles frames arriving on a connection from a foreign system until it closes.
+/// Each frame is handled on its own task, so a slow handler does not block the rest of the connection,
+/// except that numbered messages between the same pair of actors are handled one at a time, in order.
+pub async fn serve_connection<D: Delegate, S: MessageSerializer>(exports: Arc<Exports<D, S>>, reader: impl FrameReader, writer: impl FrameWriter) {
+    serve_identified(exports, None, reader, writer).await;
+}
+
+/// Serves a connection like [`serve_connection`], from a peer whose identity was established by the transport,
+/// which is given to handlers through [`Metadata::peer`].
+pub(crate) async fn serve_identified<D: Delegate, S: MessageSerializer>(exports: Arc<Exports<D, S>>, peer: Option<Arc<str>>, mut reader: impl FrameReader, writer: impl FrameWriter) {
+    let session = Arc::new(Session {
+        credits: CreditGrants { window: exports.credit_window, released: AtomicUsize::new(0) },
+        exports,
+        peer,
+        writer: tokio::sync::Mutex::new(Box::new(writer)),
+        next_transfer: AtomicU64::new(0),
+        resequencer: Mutex::new(Resequencer::default()),
+        lanes: Lanes::default(),
+    });
+    let mut reassembly = Reassembly::default();
+
+    // Tell the peer whenever an actor stops, so that it never sends to a name that was resolved to a stopped actor,
+    // and forget the order of the messages sent to the actor, which the peer forgets once it is told
+    let events = session.exports.system.lifecycle_events();
+    let invalidations = tokio::spawn({
+        let session = session.clone();
+        async move {
+            while let Some(event) = events.recv().await {
+                if let LifecycleEvent::ActorStopped { id } = event {
+                    let waiting = session.resequencer.lock().forget_actor(id);
+                    for (lane, ready) in waiting {
+                        session.enqueue(lane, ready);
+                    }
+
+                    session.respond(&Frame::Invalidate { actor: id }).await;
+                }
+            }
+        }
+    });
+
+    while let Ok(Some(frame)) = reader.read_frame().await {
+        // A peer speaking another version of the protocol is disconnected, as its frames can't be answered,
+        // as is a peer sending a message larger than the maximum message size, or too many chunked messages at once
+        let frame = match reassembly.accept(&frame, &session.exports.chunking) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => continue,
+            Err(e @ (TransportError::UnsupportedVersion(_) | TransportError::MessageTooLarge(_) | TransportError::ReassemblyLimit)) => {
+                session.exports.report(session.peer.as_deref(), &ServeError::Decode(e));
+                break;
+            },
+            Err(e) => {
+                session.exports.report(session.peer.as_deref(), &ServeError::Decode(e));
+                continue;
+            },
+        };
+
+        // Credits that were released but not yet granted are granted straight away when the peer runs out
+        if matches!(frame, Frame::CreditRequest) {
+            if let Some(grant) = session.credits.take() {
+                session.respond(&grant).await;
+            }
+            continue;
+        }
+
+        let Some((lane, sequence)) = frame.sequenced() else {
+            let session = session.clone();
+            tokio::spawn(async move {
+                let uses_credit = frame.uses_credit();
+                session.serve(frame, uses_credit).await;
+            });
+            continue;
+        };
+
+        // Messages to an actor that is stopping are handled straight away, as their order no longer matters, and their
+        // pair of actors would otherwise be remembered after the actor's stop forgot it
+        let ready = {
+            let mut resequencer = session.resequencer.lock();
+            if session.exports.is_running(lane.1) {
+                resequencer.accept(lane.clone(), sequence, frame)
+            } else {
+                alloc::vec![(frame, true)]
+            }
+        };
+
+        // A buffered message gives its credit back straight away, as the message it waits for may need it
+        if !ready.iter().any(|(_, holds_credit)| *holds_credit) {
+            session.release_credit().await;
+        }
+        if !ready.is_empty() {
+            session.enqueue(lane, ready);
+        }
+    }
+
+    invalidations.abort();
+}
+
+/// The state shared by the tasks serving a single connection.
+struct Session<D, S> {
+    /// The actors and messages the peer may reach
+    exports: Arc<Exports<D, S>>,
+    /// The identity of the peer, if the transport established one
+    peer: Option<Arc<str>>,
+    /// The sending half of the connection
+    writer: tokio::sync::Mutex<Box<dyn FrameWriter>>,
+    /// The id of the next chunked transfer
+    next_transfer: AtomicU64,
+    /// The credits released by handling messages
+    credits: CreditGrants,
+    /// Puts numbered messages back in the order they were sent
+    resequencer: Mutex<Resequencer>,
+    /// The numbered messages waiting to be handled in order, for each pair of actors
+    lanes: Lanes,
+}
+
+impl<D: Delegate, S: MessageSerializer> Session<D, S> {
+    /// Handles a frame and writes its response, if it has one, then releases the credit it used if it still holds one.
+    async fn serve(&self, frame: Frame, holds_credit: bool) {
+        if let Some(response) = self.exports.handle(frame, self.peer.as_deref()).await {
+            self.respond(&response).await;
+        }
+
+        if holds_credit {
+            self.release_credit().await;
+        }
+    }
+
+    /// Releases the credit used by a message, granting the released credits back to the peer once there are enough.
+    async fn release_credit(&self) {
+        if let Some(grant) = self.credits.release() {
+            self.respond(&grant).await;
+        }
+    }
+
+    /// Writes a frame to the peer, reporting it if it could not be written.
+    async fn respond(&self, frame: &Frame) {
+        if let Err(e) = write_chunked(&self.exports.chunking, &self.writer, &self.next_transfer, frame).await {
+            self.exports.report(self.peer.as_deref(), &ServeError::Respond(e));
+        }
+    }
+
+    /// Queues numbered messages to be handled in order, starting a task for their pair of actors if it has none.
+    /// The task stops once every message queued for the pair has been handled, so idle pairs don't keep a task.
+    fn enqueue(self: &Arc<Self>, lane: Lane, ready: Vec<Ready>) {
+        if !self.lanes.push(lane.clone(), ready) {
+            return;
+        }
+
+        let session = self.clone();
+        tokio::spawn(async move {
+            while let Some((frame, holds_credit)) = session.lanes.next(&lane) {
+                session.serve(frame, holds_credit).await;
+            }
+        });
+    }
+}
+
+/// Encodes a frame served to a foreign system, and writes each of its chunks.
+async fn write_chunked(chunking: &Chunking, writer: &tokio::sync::Mutex<Box<dyn FrameWriter>>, next_transfer: &AtomicU64, frame: &Frame) -> Result<(), TransportError> {
+    let chunks = chunking.encode(frame, next_transfer.fetch_add(1, Ordering::Relaxed))?;
+
+    let mut writer = writer.lock().await;
+    for chunk in chunks {
+        writer.write_frame(&chunk).await?;
+    }
+
+    Ok(())
+}
+
+/// Tracks the credits a served connection has released by handling messages, and grants them back to the peer.
+struct CreditGrants {
+    /// The peer's credit window, if flow control is enabled
+    window: Option<usize>,
+    /// Credits released since the last grant
+    released: AtomicUsize,
+}
+
+impl CreditGrants {
+    /// Releases the credit used by a handled message, returning a grant once half of the window has been released.
+    fn release(&self) -> Option<Frame> {
+        let window = self.window?;
+        let released = self.released.fetch_add(1, Ordering::AcqRel) + 1;
+
+        if released < window.div_ceil(2) {
+            return None;
+        }
+
+        self.take()
+    }
+
+    /// Takes every released credit, returning a grant for them if there were any.
+    fn take(&self) -> Option<Frame> {
+        self.window?;
+
+        match self.released.swap(0, Ordering::AcqRel) {
+            0 => None,
+            credits => Some(Frame::Credit { credits: u64::try_from(credits).unwrap_or(u64::MAX) }),
+        }
+    }
+}
+
+/// A type erased handler for a single exported actor/message pair.
+#[async_trait::async_trait]
+trait ExportedHandler<D>: Send + Sync + 'static {
+    /// Returns true if the given actor exists and is of the exported type.
+    async fn accepts(&self, system: &Fluxion<D>, actor: u64) -> bool;
+
+    /// Deserializes and handles the message, returning [`None`] if the actor is not of the exported type.
+    async fn dispatch(&self, system: &Fluxion<D>, actor: u64, version: u32, payload: Vec<u8>) -> Option<Result<Vec<u8>, DispatchError>>;
+}
+
+/// Why an exported handler failed to handle a message.
+enum DispatchError {
+    /// The payload's version can't be decoded, and the current version is given
+    UnsupportedVersion(u32),
+    /// The payload could not be decoded
+    Malformed(String),
+    /// The message could not be sent, or its result could not be encoded
+    Failed(String),
+}
+
+impl DispatchError {
+    /// Converts the error into the [`RemoteError`] returned to the foreign system for the given message.
+    fn into_remote(self, message: &str, version: u32) -> RemoteError {
+        match self {
+            DispatchError::UnsupportedVersion(supported) =>
+                RemoteError::UnsupportedMessageVersion { message: String::from(message), version, supported },
+            DispatchError::Malformed(reason) => RemoteError::Malformed { message: String::from(message), reason },
+            DispatchError::Failed(e) => RemoteError::Failed(e),
+        }
+    }
+}
+
+/// Decodes a payload encoded with the given version of a message.
+type Decode<M> = fn(u32, Vec<u8>) -> Result<M, DispatchError>;
+
+/// Decodes a notification encoded with the given version, and broadcasts it to the local actors that handle it.
+type NotifyFn<D> = for<'a> fn(&'a Fluxion<D>, u32, Vec<u8>) -> Pin<Box<dyn Future<Output = Result<(), DispatchError>> + Send + 'a>>;
+
+/// Creates a [`NotifyFn`] for notifications of type `N`.
+fn notify_exported<N: IndeterminateMessage + Clone, S: MessageSerializer, D: Delegate>(system: &Fluxion<D>, version: u32, payload: Vec<u8>) -> Pin<Box<dyn Future<Output = Result<(), DispatchError>> + Send + '_>>
+    where N::Result: Serialize + for<'de> Deserialize<'de> {
+    Box::pin(async move {
+        let notification = decode_current::<N, S>(version, payload)?;
+        system.broadcast(notification).await;
+        Ok(())
+    })
+}
+
+/// Decodes a message, only accepting its current version.
+#[allow(clippy::needless_pass_by_value)]
+fn decode_current<M: crate::MessageID + serde::de::DeserializeOwned, S: MessageSerializer>(version: u32, payload: Vec<u8>) -> Result<M, DispatchError> {
+    if version != M::VERSION {
+        return Err(DispatchError::UnsupportedVersion(M::VERSION));
+    }
+
+    S::deserialize(&payload).map_err(|e| DispatchError::Malformed(e.to_string()))
+}
+
+/// Decodes a message, upgrading older versions to the current one.
+#[allow(clippy::needless_pass_by_value)]
+fn decode_upgraded<M: MessageUpgrade, S: MessageSerializer>(version: u32, payload: Vec<u8>) -> Result<M, DispatchError> {
+    decode_version::<M, S>(version, &payload)?.ok_or(DispatchError::UnsupportedVersion(M::VERSION))
+}
+
+/// Decodes the given version of a message as `M` or one of its previous versions, returning [`None`] if no version matches.
+fn decode_version<M: MessageUpgrade, S: MessageSerializer>(version: u32, payload: &[u8]) -> Result<Option<M>, DispatchError> {
+    if version == M::VERSION {
+        return S::deserialize(payload).map(Some).map_err(|e| DispatchError::Malformed(e.to_string()));
+    }
+
+    // Versions are only walked downwards, ending at `NoPreviousVersion`, whose version is zero
+    if version > M::VERSION || M::VERSION == 0 {
+        return Ok(None);
+    }
+
+    Ok(decode_version::<M::Previous, S>(version, payload)?.map(M::upgrade))
+}
+
+struct Export<A, M, S> {
+    /// Decodes the exported message
+    decode: Decode<M>,
+    _actor: PhantomData<fn() -> A>,
+    _serializer: PhantomData<fn() -> S>,
+}
+
+impl<A, M, S> Export<A, M, S> {
+    fn new(decode: Decode<M>) -> Self {
+        Self { decode, _actor: PhantomData, _serializer: PhantomData }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: Handler<M>, M: Message, D: Delegate, S: MessageSerializer> ExportedHandler<D> for Export<A, M, S>
+    where M::Result: Serialize + for<'de> Deserialize<'de> {
+    async fn accepts(&self, system: &Fluxion<D>, actor: u64) -> bool {
+        system.get_local::<A>(actor).await.is_some()
+    }
+
+    async fn dispatch(&self, system: &Fluxion<D>, actor: u64, version: u32, payload: Vec<u8>) -> Option<Result<Vec<u8>, DispatchError>> {
+        let actor = system.get_local::<A>(actor).await?;
+
+        let message = match (self.decode)(version, payload) {
+            Ok(message) => message,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let result = match MessageSender::<M>::send(&actor, message).await {
+            Ok(result) => result,
+            Err(e) => return Some(Err(DispatchError::Failed(e.to_string()))),
+        };
+
+        Some(S::serialize(&result).map_err(|e| DispatchError::Failed(e.to_string())))
+    }
+}
+
+/// Returns the host of a `host:port` address, without the brackets around an IPv6 address.
+/// This is the name a peer's certificate must be valid for.
+#[cfg(any(feature = "quic", feature = "tls"))]
+pub(crate) fn host(address: &str) -> &str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// # [`Dialer`]
+/// Establishes connections to foreign systems for a specific transport.
+#[async_trait::async_trait]
+pub trait Dialer: Send + Sync + 'static {
+    /// Connects to the system at the given address.
+    async fn dial(&self, address: &str) -> Result<Arc<Connection>, TransportError>;
+
+    /// Starts probing a connection this dialer established, as described in [`Liveness`].
+    /// Defaults to [`Connection::set_liveness`], which needs a Tokio runtime. Dialers for other runtimes spawn
+    /// [`Connection::liveness_probe`] on their own.
+    fn probe(&self, connection: &Arc<Connection>, liveness: Liveness) {
+        connection.set_liveness(liveness);
+    }
+}
+
+/// # [`Peer`]
+/// A single foreign system, and the connection to it if one is open.
+pub struct Peer<T> {
+    dialer: Arc<T>,
+    address: String,
+    chunking: Chunking,
+    credit_window: Option<usize>,
+    liveness: Option<Liveness>,
+    #[cfg(feature = "signing")]
+    signing: Option<signing::Signing>,
+    connection: tokio::sync::Mutex<Option<Arc<Connection>>>,
+}
+
+impl<T: Dialer> Peer<T> {
+    /// Signs a message frame with the delegate's keys, if it was given any.
+    #[cfg_attr(not(feature = "signing"), allow(clippy::unused_self))]
+    fn sign(&self, frame: Frame) -> Frame {
+        #[cfg(feature = "signing")]
+        if let Some(signing) = &self.signing {
+            return signing.sign(frame);
+        }
+
+        frame
+    }
+
+    /// # [`Peer::connection`]
+    /// Returns the open connection to the foreign system, dialing a new one if the last was closed.
+    ///
+    /// # Errors
+    /// Returns an error if a new connection could not be established.
+    pub async fn connection(&self) -> Result<Arc<Connection>, TransportError> {
+        let mut connection = self.connection.lock().await;
+
+        // Reuse the existing connection unless it has been closed.
+        if let Some(connection) = connection.as_ref().filter(|c| !c.is_closed()) {
+            return Ok(connection.clone());
+        }
+
+        let new = self.dialer.dial(&self.address).await?;
+        new.set_chunking(self.chunking);
+        if let Some(window) = self.credit_window {
+            new.set_credit_window(window);
+        }
+        if let Some(liveness) = self.liveness {
+            self.dialer.probe(&new, liveness);
+        }
+        *connection = Some(new.clone());
+
+        Ok(new)
+    }
+}
+
+/// # [`PeerDelegate`]
+/// A [`Delegate`] that resolves foreign actors on a known set of peer systems.
+/// Each peer shares one multiplexed connection, which is established lazily when an actor on the peer is
+/// first retrieved, and is re-established by the next send if it is lost.
+/// Messages are encoded with the [`MessageSerializer`] `S`, which must match the one used by the peers' [`Exports`].
+pub struct PeerDelegate<T, S = BincodeSerializer> {
+    dialer: Arc<T>,
+    peers: RwLock<HashMap<String, Arc<Peer<T>>>>,
+    chunking: Chunking,
+    credit_window: Option<usize>,
+    liveness: Option<Liveness>,
+    #[cfg(feature = "signing")]
+    signing: Option<signing::Signing>,
+    _serializer: PhantomData<fn() -> S>,
+}
+
+impl<T: Dialer> PeerDelegate<T> {
+    /// # [`PeerDelegate::with_dialer`]
+    /// Creates a delegate with no peers that connects using the given dialer, and encodes messages with bincode.
+    #[must_use]
+    pub fn with_dialer(dialer: T) -> Self {
+        Self::with_serializer(dialer, BincodeSerializer)
+    }
+}
+
+impl<T: Dialer, S: MessageSerializer> PeerDelegate<T, S> {
+    /// # [`PeerDelegate::with_serializer`]
+    /// Creates a delegate with no peers that connects using the given dialer, and encodes messages with the given serializer.
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn with_serializer(dialer: T, serializer: S) -> Self {
+        let _ = serializer;
+        Self {
+            dialer: Arc::new(dialer),
+            peers: RwLock::default(),
+            chunking: Chunking::default(),
+            credit_window: None,
+            liveness: None,
+            #[cfg(feature = "signing")]
+            signing: None,
+            _serializer: PhantomData,
+        }
+    }
+
+    /// # [`PeerDelegate::with_chunking`]
+    /// Sets how messages sent to peers are split into chunks, and the largest message that may be sent or received.
+    /// Only applies to connections established after it is set.
+    #[must_use]
+    pub fn with_chunking(mut self, chunking: Chunking) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// # [`PeerDelegate::with_credit_window`]
+    /// Enables credit based flow control, so that at most `window` messages are sent to a peer before it grants more credits.
+    /// Senders suspend while their peer's credits are exhausted. The window must match the peers' [`Exports::with_credit_window`],
+    /// and only applies to connections established after it is set.
+    #[must_use]
+    pub fn with_credit_window(mut self, window: usize) -> Self {
+        self.credit_window = Some(window.max(1));
+        self
+    }
+
+    /// # [`PeerDelegate::with_liveness`]
+    /// Probes every connection to a peer, so that sends to a peer that stopped responding fail with
+    /// [`MessageSendError::PeerUnreachable`] instead of waiting forever. Only applies to connections established after it is set.
+    #[must_use]
+    pub fn with_liveness(mut self, liveness: Liveness) -> Self {
+        self.liveness = Some(liveness);
+        self
+    }
+
+    /// # [`PeerDelegate::with_signing`]
+    /// Signs every message sent to peers with the given keys, so that peers whose system verifies signatures accept them.
+    /// This is usually the same [`signing::Signing`] given to the local system with [`crate::FluxionBuilder::signing`].
+    /// Only applies to peers registered after it is set.
+    #[cfg(feature = "signing")]
+    #[must_use]
+    pub fn with_signing(mut self, signing: signing::Signing) -> Self {
+        self.signing = Some(signing);
+        self
+    }
+
+    /// # [`PeerDelegate::add_peer`]
+    /// Registers the address of the system with the given id.
+    /// Registering a peer that already exists replaces its address and drops the existing connection.
+    pub fn add_peer(&self, system: &str, address: &str) {
+        self.peers.write().insert(String::from(system), Arc::new(Peer {
+            dialer: self.dialer.clone(),
+            address: String::from(address),
+            chunking: self.chunking,
+            credit_window: self.credit_window,
+            liveness: self.liveness,
+            #[cfg(feature = "signing")]
+            signing: self.signing.clone(),
+            connection: tokio::sync::Mutex::default(),
+        }));
+    }
+
+    /// # [`PeerDelegate::remove_peer`]
+    /// Forgets the peer with the given id. Senders that were already retrieved will keep working
+    /// for as long as the current connection stays open.
+    pub fn remove_peer(&self, system: &str) {
+        self.peers.write().remove(system);
+    }
+
+    /// # [`PeerDelegate::peer`]
+    /// Retrieves the peer with the given id
+    pub fn peer(&self, system: &str) -> Option<Arc<Peer<T>>> {
+        self.peers.read().get(system).cloned()
+    }
+}
+
+impl<T: Dialer, S: MessageSerializer> Delegate for PeerDelegate<T, S> {
+    async fn get_actor<A: Handler<M>, M: IndeterminateMessage>(&self, id: Identifier<'_>) -> Option<Arc<dyn MessageSender<M>>>
+        where M::Result: Serialize + for<'de> Deserialize<'de> {
+        let (address, system) = match id {
+            Identifier::Foreign(id, system) => (Address::Id(id), system),
+            Identifier::ForeignNamed(name, system) => (Address::Name(String::from(name)), system),
+            _ => return None,
+        };
+
+        let sender = resolve::<M, T, S>(self.peer(system)?, address).await?;
+
+        Some(Arc::new(sender))
+    }
+
+    async fn notify<N: IndeterminateMessage + Clone>(&self, notification: N)
+        where N::Result: Serialize + for<'de> Deserialize<'de> {
+        let Ok(payload) = S::serialize(&notification) else {
+            return;
+        };
+
+        // Notifications are best effort, so peers that can't be reached are skipped
+        let peers = self.peers.read().values().cloned().collect::<Vec<_>>();
+        for peer in peers {
+            let Ok(connection) = peer.connection().await else {
+                continue;
+            };
+
+            let _ = connection.send(&peer.sign(Frame::Notify {
+                message: String::from(N::ID),
+                version: N::VERSION,
+                signature: None,
+                payload: payload.clone(),
+            })).await;
+        }
+    }
+
+    async fn grant_credits(&self, system: &str, credits: usize) {
+        let Some(peer) = self.peer(system) else {
+            return;
+        };
+
+        // Credits belong to the current connection, so there is nothing to grant if it isn't open
+        if let Some(connection) = peer.connection.lock().await.as_ref() {
+            connection.grant_credits(credits);
+        }
+    }
+
+    async fn request_credits(&self, system: &str) {
+        let Some(peer) = self.peer(system) else {
+            return;
+        };
+
+        if let Ok(connection) = peer.connection().await {
+            let _ = connection.request_credits().await;
+        }
+    }
+}
+
+/// # [`RemoteSender`]
+/// A [`MessageSender`] for an actor on a foreign system, reached through a [`Peer`].
+pub struct RemoteSender<M, T, S = BincodeSerializer> {
+    /// The foreign system the actor lives on
+    peer: Arc<Peer<T>>,
+    /// The actor's id on the foreign system
+    actor: u64,
+    _message: PhantomData<fn() -> (M, S)>,
+}
+
+/// Resolves an actor on a foreign system to its id, if it exists and accepts the message with the given type identifier.
+/// Names are resolved to ids first, which are cached by the connection.
+async fn lookup<T: Dialer>(peer: &Peer<T>, actor: Address, message: &str) -> Option<u64> {
+    let connection = peer.connection().await.ok()?;
+
+    let (actor, name) = match actor {
+        Address::Name(name) => (Address::Id(connection.resolve(&name).await.ok()??), Some(name)),
+        actor @ Address::Id(_) => (actor, None),
+    };
+
+    let found = connection.request(|request| Frame::Lookup {
+        request,
+        actor,
+        message: String::from(message),
+    }).await.ok()?;
+
+    let Frame::Found { actor, .. } = found else {
+        return None;
+    };
+
+    // The name may have resolved to an actor that has since stopped
+    if let (None, Some(name)) = (actor, name) {
+        connection.names().remove(&name);
+    }
+
+    actor
+}
+
+/// Resolves an actor on a foreign system, returning a sender for it if it exists and accepts `M`.
+async fn resolve<M: IndeterminateMessage, T: Dialer, S: MessageSerializer>(peer: Arc<Peer<T>>, actor: Address) -> Option<RemoteSender<M, T, S>>
+    where M::Result: Serialize + for<'de> Deserialize<'de> {
+    let actor = lookup(&peer, actor, M::ID).await?;
+
+    Some(RemoteSender { peer, actor, _message: PhantomData })
+}
+impl<M: IndeterminateMessage, T: Dialer, S: MessageSerializer> RemoteSender<M, T, S>
+    where M::Result: Serialize + for<'de> Deserialize<'de> {
+    /// Sends a message with an optional idempotency key, and waits for the response.
+    async fn request(&self, message: M, key: Option<&str>) -> Result<M::Result, MessageSendError> {
+        let payload = S::serialize(&message).map_err(|e| MessageSendError::SerializationError {
+            message: e.to_string(),
+            source: Box::new(e),
+        })?;
+
+        let connection = self.peer.connection().await?;
+        let sender = Caller::acting().map(Caller::into_frame);
+        let response = connection
+            .request_numbered(sender.as_ref(), self.actor, |request, sequence| self.peer.sign(Frame::Request {
+                request,
+                actor: self.actor,
+                message: String::from(M::ID),
+                version: M::VERSION,
+                key: key.map(String::from),
+                metadata: Metadata::current(),
+                sender: sender.clone(),
+                sequence,
+                signature: None,
+                payload,
+            })).await?;
+
+        let Frame::Response { result, .. } = response else {
+            return Err(TransportError::Closed.into());
+        };
+
+        // Names that resolved to the actor are stale, even if the invalidation hasn't arrived yet
+        if let Err(RemoteError::ActorNotFound(actor)) = &result {
+            connection.names().invalidate(*actor);
+        }
+        let result = result.map_err(TransportError::Remote)?;
+
+        S::deserialize(&result).map_err(|e| MessageSendError::DeserializationError {
+            message: e.to_string(),
+            source: Box::new(e),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<M: IndeterminateMessage, T: Dialer, S: MessageSerializer> MessageSender<M> for RemoteSender<M, T, S>
+    where M::Result: Serialize + for<'de> Deserialize<'de> {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        self.request(message, None).await
+    }
+
+    async fn send_idempotent(&self, key: &str, message: M) -> Result<M::Result, MessageSendError> {
+        self.request(message, Some(key)).await
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        let payload = S::serialize(&message).map_err(|e| MessageSendError::SerializationError {
+            message: e.to_string(),
+            source: Box::new(e),
+        })?;
+
+        let connection = self.peer.connection().await?;
+        let sender = Caller::acting().map(Caller::into_frame);
+        connection
+            .send_numbered(sender.as_ref(), self.actor, |sequence| self.peer.sign(Frame::Tell {
+                actor: self.actor,
+                message: String::from(M::ID),
+                version: M::VERSION,
+                key: None,
+                metadata: Metadata::current(),
+                sender: sender.clone(),
+                sequence,
+                signature: None,
+                payload,
+            })).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+
+    /// Never yields a frame until the sending half is dropped.
+    struct Incoming(tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>);
+
+    #[async_trait::async_trait]
+    impl FrameReader for Incoming {
+        async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+            Ok(self.0.recv().await)
+        }
+    }
+
+    /// Records every frame written.
+    struct Written(Arc<std::sync::Mutex<Vec<Vec<u8>>>>);
+
+    #[async_trait::async_trait]
+    impl FrameWriter for Written {
+        async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+            self.0.lock().unwrap().push(frame.to_vec());
+            Ok(())
+        }
+    }
+
+    /// Creates a connection whose peer never answers, returning the frames it writes and the sender of frames it reads.
+    fn connect() -> (Arc<Connection>, Arc<std::sync::Mutex<Vec<Vec<u8>>>>, tokio::sync::mpsc::UnboundedSender<Vec<u8>>) {
+        let (incoming, reader) = tokio::sync::mpsc::unbounded_channel();
+        let written = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        (Connection::new(Incoming(reader), Written(written.clone())), written, incoming)
+    }
+
+    fn tell(sequence: Option<u64>, payload: Vec<u8>) -> Frame {
+        Frame::Tell {
+            actor: 1,
+            message: String::from("m"),
+            version: 1,
+            key: None,
+            metadata: None,
+            sender: sequence.map(|_| (String::from("sender"), 2)),
+            sequence,
+            signature: None,
+            payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_sends_give_their_credits_back() {
+        const WINDOW: usize = 2;
+
+        let (connection, written, _incoming) = connect();
+        connection.set_credit_window(WINDOW);
+        connection.set_chunking(Chunking { chunk_size: 1024, max_message_size: 128, ..Chunking::default() });
+
+        for _ in 0..=WINDOW {
+            let sent = connection.send(&tell(None, alloc::vec![0; 1024])).await;
+            assert!(matches!(sent, Err(TransportError::MessageTooLarge(_))));
+        }
+
+        let sender = (String::from("sender"), 2);
+        for _ in 0..=WINDOW {
+            let sent = connection.send_numbered(Some(&sender), 1, |sequence| tell(sequence, alloc::vec![0; 1024])).await;
+            assert!(matches!(sent, Err(TransportError::MessageTooLarge(_))));
+        }
+
+        // The peer never grants credits, so these would wait forever if the failed sends had kept theirs
+        tokio::time::timeout(Duration::from_secs(5), connection.send(&tell(None, Vec::new()))).await
+            .expect("the send waited for a credit")
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), connection.send_numbered(Some(&sender), 1, |sequence| tell(sequence, Vec::new()))).await
+            .expect("the send waited for a credit")
+            .unwrap();
+
+        let written = written.lock().unwrap();
+        assert_eq!(written.len(), 2);
+
+        // The failed numbered sends used up no numbers either
+        let Ok(Frame::Tell { sequence, .. }) = Frame::decode(&written[1]) else {
+            panic!("expected a tell");
+        };
+        assert_eq!(sequence, Some(0));
+    }
+}
@@ -0,0 +1,183 @@
+//! # Chunking
+//! Frames larger than a connection's chunk size are split into [`Frame::Chunk`]s when they are written,
+//! and reassembled by the receiving system before they are handled, so that a large message never exceeds
+//! the [`super::MAX_FRAME_SIZE`]. Reassembled frames are still bounded by [`Chunking::max_message_size`], and the frames
+//! being reassembled on a connection at once by [`Chunking::max_transfers`] and [`Chunking::max_buffered`].
+
+use std::collections::HashMap;
+
+use alloc::vec::Vec;
+
+use super::{Frame, TransportError, MAX_FRAME_SIZE};
+
+/// # [`Chunking`]
+/// Configures how large frames are split into chunks, and how large a reassembled frame may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunking {
+    /// The most bytes of a frame sent in a single chunk. Frames no larger than this are sent whole.
+    /// Defaults to 1 MiB, and is capped well below [`MAX_FRAME_SIZE`] to leave room for the chunk's header.
+    pub chunk_size: usize,
+    /// The largest encoded frame that may be sent or reassembled. Sending a larger frame fails with
+    /// [`TransportError::MessageTooLarge`], and receiving one closes the connection. Defaults to 64 MiB.
+    pub max_message_size: usize,
+    /// The most chunked frames that may be partially received on a connection at once. Starting another closes the
+    /// connection with [`TransportError::ReassemblyLimit`]. Defaults to 16.
+    pub max_transfers: usize,
+    /// The most bytes of partially received chunked frames buffered on a connection at once. Receiving more closes the
+    /// connection with [`TransportError::ReassemblyLimit`]. Defaults to 128 MiB.
+    pub max_buffered: usize,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self { chunk_size: 1024 * 1024, max_message_size: 64 * 1024 * 1024, max_transfers: 16, max_buffered: 128 * 1024 * 1024 }
+    }
+}
+
+impl Chunking {
+    /// Encodes a frame, splitting it into encoded chunks if it is larger than the chunk size.
+    /// The chunks are tagged with the given transfer id, which must be unique on the connection.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::MessageTooLarge`] if the encoded frame exceeds the maximum message size,
+    /// or an error if it could not be encoded.
+    pub(crate) fn encode(&self, frame: &Frame, transfer: u64) -> Result<Vec<Vec<u8>>, TransportError> {
+        let encoded = frame.encode()?;
+        let chunk_size = self.chunk_size.clamp(1, MAX_FRAME_SIZE / 2);
+
+        if encoded.len() > self.max_message_size {
+            return Err(TransportError::MessageTooLarge(encoded.len()));
+        }
+
+        if encoded.len() <= chunk_size {
+            return Ok(alloc::vec![encoded]);
+        }
+
+        let count = encoded.len().div_ceil(chunk_size);
+        encoded.chunks(chunk_size).enumerate()
+            .map(|(index, data)| Frame::Chunk { transfer, last: index + 1 == count, data: data.to_vec() }.encode())
+            .collect()
+    }
+}
+
+/// Returns whether an encoded frame is a [`Frame::Chunk`] without decoding it, so that transports which don't
+/// preserve the order of frames can send a transfer's chunks in order.
+#[cfg(feature = "quic")]
+pub(crate) fn is_chunk(encoded: &[u8]) -> bool {
+    // Frames start with the protocol version followed by the index of their variant
+    const HEADER: usize = size_of::<u16>() + size_of::<u32>();
+
+    let chunk = Frame::Chunk { transfer: 0, last: false, data: Vec::new() }.encode().unwrap_or_default();
+    encoded.get(..HEADER).is_some_and(|header| chunk.get(..HEADER) == Some(header))
+}
+
+/// Reassembles the chunked frames arriving on a single connection.
+#[derive(Default)]
+pub(crate) struct Reassembly {
+    /// The chunks received so far for each transfer
+    transfers: HashMap<u64, Vec<u8>>,
+    /// The bytes buffered across every transfer
+    buffered: usize,
+}
+
+impl Reassembly {
+    /// Decodes a frame that was read from the connection, returning [`None`] if it is a chunk of a frame that is not yet complete.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::MessageTooLarge`] if a chunked frame grows beyond [`Chunking::max_message_size`],
+    /// [`TransportError::ReassemblyLimit`] if the connection exceeds [`Chunking::max_transfers`] or [`Chunking::max_buffered`],
+    /// or any error returned by [`Frame::decode`].
+    pub(crate) fn accept(&mut self, bytes: &[u8], chunking: &Chunking) -> Result<Option<Frame>, TransportError> {
+        let (transfer, last, data) = match Frame::decode(bytes)? {
+            Frame::Chunk { transfer, last, data } => (transfer, last, data),
+            frame => return Ok(Some(frame)),
+        };
+
+        if !self.transfers.contains_key(&transfer) && self.transfers.len() >= chunking.max_transfers {
+            return Err(TransportError::ReassemblyLimit);
+        }
+        if self.buffered + data.len() > chunking.max_buffered {
+            return Err(TransportError::ReassemblyLimit);
+        }
+
+        let assembled = self.transfers.entry(transfer).or_default();
+        assembled.extend_from_slice(&data);
+        self.buffered += data.len();
+
+        if assembled.len() > chunking.max_message_size {
+            let size = assembled.len();
+            self.finish(transfer);
+            return Err(TransportError::MessageTooLarge(size));
+        }
+
+        if !last {
+            return Ok(None);
+        }
+
+        let assembled = self.finish(transfer);
+        Frame::decode(&assembled).map(Some)
+    }
+
+    /// Removes a transfer, returning the chunks received for it.
+    fn finish(&mut self, transfer: u64) -> Vec<u8> {
+        let assembled = self.transfers.remove(&transfer).unwrap_or_default();
+        self.buffered -= assembled.len();
+        assembled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(transfer: u64, last: bool, data: &[u8]) -> Vec<u8> {
+        Frame::Chunk { transfer, last, data: data.to_vec() }.encode().unwrap()
+    }
+
+    #[test]
+    fn reassembles_chunked_frames() {
+        let chunking = Chunking { chunk_size: 4, ..Chunking::default() };
+        let mut reassembly = Reassembly::default();
+
+        let chunks = chunking.encode(&Frame::Ping { request: 7 }, 0).unwrap();
+        assert!(chunks.len() > 1);
+
+        let (last, rest) = chunks.split_last().unwrap();
+        for chunk in rest {
+            assert!(reassembly.accept(chunk, &chunking).unwrap().is_none());
+        }
+        assert!(matches!(reassembly.accept(last, &chunking), Ok(Some(Frame::Ping { request: 7 }))));
+        assert_eq!(reassembly.buffered, 0);
+    }
+
+    #[test]
+    fn limits_transfers_in_flight() {
+        let chunking = Chunking { max_transfers: 2, ..Chunking::default() };
+        let mut reassembly = Reassembly::default();
+
+        assert!(reassembly.accept(&chunk(0, false, b"a"), &chunking).unwrap().is_none());
+        assert!(reassembly.accept(&chunk(1, false, b"b"), &chunking).unwrap().is_none());
+
+        // Existing transfers can still grow, but a third can't start
+        assert!(reassembly.accept(&chunk(1, false, b"c"), &chunking).unwrap().is_none());
+        assert!(matches!(reassembly.accept(&chunk(2, false, b"d"), &chunking), Err(TransportError::ReassemblyLimit)));
+    }
+
+    #[test]
+    fn limits_buffered_bytes() {
+        let chunking = Chunking { max_buffered: 10, ..Chunking::default() };
+        let mut reassembly = Reassembly::default();
+
+        assert!(reassembly.accept(&chunk(0, false, &[0; 6]), &chunking).unwrap().is_none());
+        assert!(matches!(reassembly.accept(&chunk(1, false, &[0; 6]), &chunking), Err(TransportError::ReassemblyLimit)));
+    }
+
+    #[test]
+    fn rejects_oversized_messages() {
+        let chunking = Chunking { max_message_size: 4, ..Chunking::default() };
+        let mut reassembly = Reassembly::default();
+
+        assert!(matches!(reassembly.accept(&chunk(0, false, &[0; 5]), &chunking), Err(TransportError::MessageTooLarge(5))));
+        assert_eq!(reassembly.buffered, 0);
+    }
+}
@@ -0,0 +1,62 @@
+//! # Liveness
+//! A foreign system can stop responding without its connection being closed, for example if its host loses power
+//! or the network between the systems is partitioned. Connections with liveness probing enabled send a [`Frame::Ping`]
+//! every interval, and if one is not answered in time the connection is marked unreachable and closed. Requests waiting
+//! on it then fail with [`TransportError::Unreachable`] instead of waiting forever, and the next send dials a new connection.
+
+use core::{future::Future, pin::Pin, time::Duration};
+
+use alloc::{boxed::Box, sync::{Arc, Weak}};
+
+use super::{Connection, Frame, TransportError};
+use crate::Timer;
+
+/// # [`Liveness`]
+/// Configures how often a connection is probed, and how long a probe may go unanswered.
+/// Probes keep time with a [`Timer`], which is Tokio's unless the probe is started with [`Connection::liveness_probe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Liveness {
+    /// How long to wait between probes. Defaults to 5 seconds.
+    pub interval: Duration,
+    /// How long a probe may go unanswered before the foreign system is considered unreachable. Defaults to 10 seconds.
+    pub timeout: Duration,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self { interval: Duration::from_secs(5), timeout: Duration::from_secs(10) }
+    }
+}
+
+/// Keeps time for [`Connection::set_liveness`], which requires a Tokio runtime with time enabled.
+pub(crate) struct TokioTimer;
+
+impl Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Probes the connection until it closes or is dropped, marking it unreachable if a probe goes unanswered.
+pub(crate) async fn probe(connection: Weak<Connection>, liveness: Liveness, timer: Arc<dyn Timer>) {
+    loop {
+        timer.sleep(liveness.interval).await;
+
+        let Some(connection) = connection.upgrade() else {
+            return;
+        };
+        if connection.is_closed() {
+            return;
+        }
+
+        match crate::timer::timeout(&*timer, liveness.timeout, connection.request(|request| Frame::Ping { request })).await {
+            Some(Ok(_)) => {},
+            // The connection closed by itself, so there is nothing left to probe
+            Some(Err(TransportError::Closed | TransportError::Unreachable)) => return,
+            Some(Err(_)) | None => {
+                connection.mark_unreachable();
+                return;
+            },
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! # QUIC Transport
+//! A [`Delegate`] that reaches foreign systems over QUIC, using `quinn`. QUIC encrypts every connection with TLS,
+//! which is configured on the [`quinn::Endpoint`]s given to [`QuicDelegate::new`] and [`QuicServer::serve`].
+//!
+//! Each [`super::Frame`] is sent on its own QUIC stream, so a request or response that is slow to arrive, such as one
+//! whose packets were lost, never holds up the frames sent after it. The only exception is [`super::Frame::Chunk`]s,
+//! which must arrive in order, so they share a single stream per connection.
+//!
+//! Connections to peers that were reached before are resumed with 0-RTT where the endpoint's TLS configuration allows
+//! it, so that requests are sent without waiting for a handshake. Frames sent in 0-RTT may be replayed by an attacker,
+//! so messages that must not be handled twice should be sent with an idempotency key.
+
+use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+use quinn::{Endpoint, RecvStream, SendStream};
+use tokio::sync::mpsc;
+
+use super::{chunk::is_chunk, serialize::{BincodeSerializer, MessageSerializer}, Connection, Dialer, Exports, FrameReader, FrameWriter, PeerDelegate, TransportError, MAX_FRAME_SIZE};
+use crate::Delegate;
+
+/// Marks a stream that carries a single frame.
+const SINGLE: u8 = 0;
+
+/// Marks the stream that carries a connection's chunks in order, each prefixed with its length as a big endian u32.
+const ORDERED: u8 = 1;
+
+/// Wraps a QUIC error as an io error, as it can only ever occur due to the underlying connection failing.
+#[allow(clippy::needless_pass_by_value)]
+fn quic_error(error: impl ToString) -> TransportError {
+    TransportError::Io(std::io::Error::other(error.to_string()))
+}
+
+/// Yields the frames arriving on every stream the peer opens, in the order they are completed.
+struct QuicReader(mpsc::Receiver<Result<Vec<u8>, TransportError>>);
+
+impl QuicReader {
+    /// Starts accepting streams on the connection.
+    fn new(connection: quinn::Connection) -> Self {
+        let (frames, receiver) = mpsc::channel(64);
+        tokio::spawn(accept_streams(connection, frames));
+        Self(receiver)
+    }
+}
+
+#[async_trait::async_trait]
+impl FrameReader for QuicReader {
+    async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        // The channel closes once the connection does and every stream has been read
+        self.0.recv().await.transpose()
+    }
+}
+
+/// Accepts streams until the connection closes, reading each on its own task.
+async fn accept_streams(connection: quinn::Connection, frames: mpsc::Sender<Result<Vec<u8>, TransportError>>) {
+    loop {
+        let stream = match connection.accept_uni().await {
+            Ok(stream) => stream,
+            Err(quinn::ConnectionError::ApplicationClosed(_) | quinn::ConnectionError::LocallyClosed) => return,
+            Err(e) => {
+                let _ = frames.send(Err(quic_error(e))).await;
+                return;
+            },
+        };
+
+        tokio::spawn(read_stream(stream, frames.clone()));
+    }
+}
+
+/// Reads the frames on a single stream.
+async fn read_stream(mut stream: RecvStream, frames: mpsc::Sender<Result<Vec<u8>, TransportError>>) {
+    let mut kind = [0];
+    if let Err(e) = stream.read_exact(&mut kind).await {
+        let _ = frames.send(Err(quic_error(e))).await;
+        return;
+    }
+
+    if kind[0] == SINGLE {
+        let frame = stream.read_to_end(MAX_FRAME_SIZE).await.map_err(quic_error);
+        let _ = frames.send(frame).await;
+        return;
+    }
+
+    loop {
+        let frame = match read_delimited(&mut stream).await {
+            Ok(Some(frame)) => Ok(frame),
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+
+        let failed = frame.is_err();
+        if frames.send(frame).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Reads a frame prefixed with its length, returning [`None`] if the stream finished cleanly.
+async fn read_delimited(stream: &mut RecvStream) -> Result<Option<Vec<u8>>, TransportError> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length).await {
+        Ok(()) => {},
+        Err(quinn::ReadExactError::FinishedEarly(0)) => return Ok(None),
+        Err(e) => return Err(quic_error(e)),
+    }
+
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(TransportError::FrameTooLarge(length));
+    }
+
+    let mut frame = alloc::vec![0; length];
+    stream.read_exact(&mut frame).await.map_err(quic_error)?;
+
+    Ok(Some(frame))
+}
+
+/// Writes each frame on a new stream, and chunks on the connection's ordered stream.
+struct QuicWriter {
+    /// The connection streams are opened on
+    connection: quinn::Connection,
+    /// The stream chunks are written to, once one has been sent
+    ordered: Option<SendStream>,
+}
+
+#[async_trait::async_trait]
+impl FrameWriter for QuicWriter {
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(TransportError::FrameTooLarge(frame.len()));
+        }
+
+        if !is_chunk(frame) {
+            let mut stream = self.connection.open_uni().await.map_err(quic_error)?;
+            stream.write_all(&[SINGLE]).await.map_err(quic_error)?;
+            stream.write_all(frame).await.map_err(quic_error)?;
+            stream.finish().map_err(quic_error)?;
+
+            return Ok(());
+        }
+
+        let stream = match &mut self.ordered {
+            Some(stream) => stream,
+            ordered => {
+                let mut stream = self.connection.open_uni().await.map_err(quic_error)?;
+                stream.write_all(&[ORDERED]).await.map_err(quic_error)?;
+                ordered.insert(stream)
+            },
+        };
+
+        // The length check above guarantees that this fits in a u32.
+        #[allow(clippy::cast_possible_truncation)]
+        stream.write_all(&(frame.len() as u32).to_be_bytes()).await.map_err(quic_error)?;
+        stream.write_all(frame).await.map_err(quic_error)?;
+
+        Ok(())
+    }
+}
+
+/// Splits a QUIC connection into the two halves of a transport connection.
+fn split(connection: quinn::Connection) -> (QuicReader, QuicWriter) {
+    (QuicReader::new(connection.clone()), QuicWriter { connection, ordered: None })
+}
+
+/// # [`QuicDialer`]
+/// Connects to foreign systems over QUIC from a client endpoint.
+pub struct QuicDialer {
+    endpoint: Endpoint,
+}
+
+impl QuicDialer {
+    /// # [`QuicDialer::new`]
+    /// Creates a dialer that connects from the given endpoint, which must have a default client configuration.
+    #[must_use]
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint }
+    }
+}
+
+#[async_trait::async_trait]
+impl Dialer for QuicDialer {
+    async fn dial(&self, address: &str) -> Result<Arc<Connection>, TransportError> {
+        let socket = tokio::net::lookup_host(address).await?
+            .next()
+            .ok_or_else(|| quic_error(alloc::format!("{address} did not resolve to any address")))?;
+
+        // The host is the name the peer's certificate must be valid for
+        let connecting = self.endpoint.connect(socket, super::host(address)).map_err(quic_error)?;
+        let connection = match connecting.into_0rtt() {
+            Ok((connection, _)) => connection,
+            Err(connecting) => connecting.await.map_err(quic_error)?,
+        };
+
+        let (reader, writer) = split(connection);
+        Ok(Connection::new(reader, writer))
+    }
+}
+
+/// # [`QuicDelegate`]
+/// A [`crate::Delegate`] that resolves foreign actors on peer systems over QUIC.
+/// Peers are registered with [`PeerDelegate::add_peer`], using a `host:port` address whose host the peer's certificate is valid for.
+/// Messages are encoded with bincode unless another serializer is given with [`PeerDelegate::with_serializer`].
+pub type QuicDelegate<S = BincodeSerializer> = PeerDelegate<QuicDialer, S>;
+
+impl QuicDelegate {
+    /// # [`QuicDelegate::new`]
+    /// Creates a delegate with no peers, which connects to them from the given client endpoint.
+    #[must_use]
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self::with_dialer(QuicDialer::new(endpoint))
+    }
+}
+
+/// # [`QuicServer`]
+/// Accepts connections from peer systems and dispatches their messages to the local system's exported actors.
+pub struct QuicServer<D, S = BincodeSerializer> {
+    exports: Arc<Exports<D, S>>,
+}
+
+impl<D: Delegate, S: MessageSerializer> QuicServer<D, S> {
+    /// # [`QuicServer::new`]
+    /// Creates a server that exposes the given exports.
+    #[must_use]
+    pub fn new(exports: Exports<D, S>) -> Self {
+        Self { exports: Arc::new(exports) }
+    }
+
+    /// # [`QuicServer::serve`]
+    /// Serves peers on a server endpoint until it is closed. Each connection is handled on its own task,
+    /// and connections that fail their handshake are dropped.
+    pub async fn serve(&self, endpoint: Endpoint) {
+        while let Some(incoming) = endpoint.accept().await {
+            let exports = self.exports.clone();
+
+            tokio::spawn(async move {
+                let Ok(connecting) = incoming.accept() else {
+                    return;
+                };
+
+                let connection = match connecting.into_0rtt() {
+                    Ok((connection, _)) => connection,
+                    Err(connecting) => match connecting.await {
+                        Ok(connection) => connection,
+                        Err(_) => return,
+                    },
+                };
+
+                let (reader, writer) = split(connection);
+                super::serve_connection(exports, reader, writer).await;
+            });
+        }
+    }
+}
+
+impl<D, S> Clone for QuicServer<D, S> {
+    fn clone(&self) -> Self {
+        Self { exports: self.exports.clone() }
+    }
+}
+
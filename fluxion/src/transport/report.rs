@@ -0,0 +1,73 @@
+//! # Error Reporting
+//! Errors that happen while serving a connection can't always be returned to the peer: a frame that doesn't decode
+//! can't be answered, a [`super::Frame::Tell`] or [`super::Frame::Notify`] has no response to carry its error, and a response that fails to write
+//! has nowhere else to go. A [`super::Frame::Tell`] or [`super::Frame::Notify`] whose signature doesn't verify is refused
+//! for the same reason, and is also recorded as a [`crate::DeadLetter`]. These are reported to the [`ErrorSink`] set with [`super::Exports::with_error_sink`] instead,
+//! or logged with the `tracing` feature if no sink was set.
+
+use alloc::string::String;
+
+use super::{RemoteError, TransportError};
+
+/// # [`ServeError`]
+/// An error that happened while serving a connection from a foreign system, which could not be returned to the peer.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServeError {
+    /// A frame received from the peer could not be decoded, and was dropped.
+    /// Frames with an unsupported protocol version or an oversized message also close the connection.
+    Decode(TransportError),
+    /// A message sent without expecting a response could not be handled, so its sender was never told.
+    Tell {
+        /// The id of the local actor the message was sent to
+        actor: u64,
+        /// The type identifier of the message
+        message: String,
+        /// Why the message could not be handled
+        error: RemoteError,
+    },
+    /// A notification could not be broadcast, so its sender was never told.
+    Notify {
+        /// The type identifier of the notification
+        message: String,
+        /// Why the notification could not be broadcast
+        error: RemoteError,
+    },
+    /// A response, or another frame sent to the peer, could not be written.
+    Respond(TransportError),
+}
+
+impl core::fmt::Display for ServeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Decode(e) => write!(f, "ServeError: a frame could not be decoded: {e}"),
+            Self::Tell { actor, message, error } => write!(f, "ServeError: {message} sent to actor {actor} failed: {error}"),
+            Self::Notify { message, error } => write!(f, "ServeError: notification {message} failed: {error}"),
+            Self::Respond(e) => write!(f, "ServeError: a frame could not be written: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for ServeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Decode(e) | Self::Respond(e) => Some(e),
+            Self::Tell { error, .. } | Self::Notify { error, .. } => Some(error),
+        }
+    }
+}
+
+/// # [`ErrorSink`]
+/// Receives the [`ServeError`]s of every connection served with an [`super::Exports`], along with the identity of the peer,
+/// if its transport established one. Implemented for functions with the same signature as [`ErrorSink::report`].
+pub trait ErrorSink: Send + Sync + 'static {
+    /// # [`ErrorSink::report`]
+    /// Reports an error that could not be returned to the peer.
+    fn report(&self, peer: Option<&str>, error: &ServeError);
+}
+
+impl<F: Fn(Option<&str>, &ServeError) + Send + Sync + 'static> ErrorSink for F {
+    fn report(&self, peer: Option<&str>, error: &ServeError) {
+        self(peer, error);
+    }
+}
@@ -0,0 +1,288 @@
+//! # Sequencing
+//! Messages sent by an actor are numbered per connection and per receiving actor as they are sent, and carried in
+//! [`Frame::Request`]s and [`Frame::Tell`]s. The serving system buffers messages that arrive ahead of their turn, and
+//! handles those sent between the same pair of actors one at a time, in the order they were sent, even over transports
+//! that don't preserve the order of frames. Messages sent outside of a handler have no sender, so they aren't numbered.
+//!
+//! A message that is numbered but never arrives, for example because it was too large to send, would hold back every
+//! later message between the pair. Once [`MAX_OUT_OF_ORDER`] messages are waiting behind it, it is given up on, and a
+//! message that arrives after its turn was skipped is handled straight away rather than dropped.
+//!
+//! A number is only used up once the message holds a credit and has been encoded, so a message that fails before it is
+//! written leaves no gap. Each pair of actors is handled by its own task only while it has messages queued. When an actor
+//! stops, its system forgets the order of the messages sent to it, handling those still waiting, and tells the peer with a
+//! [`Frame::Invalidate`], after which the peer numbers the messages it sends to that actor id from the start again.
+//! Messages to an actor that has started stopping are handled straight away, in whatever order they arrive.
+
+use std::collections::{HashMap, hash_map::Entry};
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    vec::Vec,
+};
+use maitake_sync::spin::Mutex;
+
+use super::Frame;
+
+/// # [`MAX_OUT_OF_ORDER`]
+/// The most messages between a pair of actors that are buffered while waiting for an earlier one.
+pub const MAX_OUT_OF_ORDER: usize = 1024;
+
+/// The actor that sent a numbered message, as its system and actor id, and the id of the actor it was sent to.
+pub(crate) type Lane = ((String, u64), u64);
+
+/// Numbers the messages sent on a single connection.
+#[derive(Default)]
+pub(crate) struct Sequences(Mutex<HashMap<Lane, u64>>);
+
+impl Sequences {
+    /// Returns the number the next message sent by `sender` to `actor` will be given, or [`None`] if there is no sender.
+    /// The number isn't used up until it is passed to [`Sequences::advance`].
+    pub(crate) fn peek(&self, sender: Option<&(String, u64)>, actor: u64) -> Option<u64> {
+        let sender = sender?;
+        Some(self.0.lock().get(&(sender.clone(), actor)).copied().unwrap_or(0))
+    }
+
+    /// Uses up a number returned by [`Sequences::peek`] once its message has been encoded. Nothing changes if the
+    /// numbering for the pair was forgotten in the meantime.
+    pub(crate) fn advance(&self, sender: &(String, u64), actor: u64, sequence: u64) {
+        let mut sequences = self.0.lock();
+        let lane = (sender.clone(), actor);
+        if sequences.get(&lane).copied().unwrap_or(0) == sequence {
+            sequences.insert(lane, sequence + 1);
+        }
+    }
+
+    /// Forgets the numbering of the messages sent to an actor that the foreign system said has stopped.
+    pub(crate) fn forget_actor(&self, actor: u64) {
+        self.0.lock().retain(|(_, target), _| *target != actor);
+    }
+}
+
+/// The messages between a pair of actors that have arrived ahead of their turn.
+#[derive(Default)]
+struct Waiting {
+    /// The number of the next message to deliver
+    next: u64,
+    /// The messages that arrived early, by number
+    pending: BTreeMap<u64, Frame>,
+}
+
+/// A numbered message that is ready to be handled, along with whether it still holds the credit it used.
+/// Buffered messages release their credit as soon as they are buffered, as the sender may need it to send the message
+/// they are waiting for.
+pub(crate) type Ready = (Frame, bool);
+
+/// Puts the numbered messages arriving on a single connection back in the order they were sent.
+#[derive(Default)]
+pub(crate) struct Resequencer {
+    /// The messages waiting for each pair of actors
+    lanes: HashMap<Lane, Waiting>,
+}
+
+impl Resequencer {
+    /// Accepts a numbered message, returning the messages between the pair of actors that can now be handled, in order.
+    /// An empty result means the message was buffered.
+    pub(crate) fn accept(&mut self, lane: Lane, sequence: u64, frame: Frame) -> Vec<Ready> {
+        let waiting = self.lanes.entry(lane).or_default();
+
+        // Late and repeated messages are handled rather than dropped, as a request would otherwise never be answered
+        if sequence < waiting.next || waiting.pending.contains_key(&sequence) {
+            return alloc::vec![(frame, true)];
+        }
+
+        waiting.pending.insert(sequence, frame);
+        if sequence > waiting.next {
+            // Give up on the missing message once too many are waiting behind it
+            if waiting.pending.len() <= MAX_OUT_OF_ORDER {
+                return Vec::new();
+            }
+            waiting.next = waiting.pending.keys().next().copied().unwrap_or(sequence);
+        }
+
+        let mut ready = Vec::new();
+        while let Some(frame) = waiting.pending.remove(&waiting.next) {
+            ready.push((frame, waiting.next == sequence));
+            waiting.next += 1;
+        }
+
+        ready
+    }
+
+    /// Forgets the order of the messages sent to an actor that has stopped, returning the messages that were still
+    /// waiting for an earlier one, in order for each pair, so they can still be handled. They released their credits
+    /// when they were buffered.
+    pub(crate) fn forget_actor(&mut self, actor: u64) -> Vec<(Lane, Vec<Ready>)> {
+        let mut waiting = Vec::new();
+        self.lanes.retain(|lane, lane_waiting| {
+            if lane.1 != actor {
+                return true;
+            }
+
+            let pending = core::mem::take(&mut lane_waiting.pending);
+            if !pending.is_empty() {
+                waiting.push((lane.clone(), pending.into_values().map(|frame| (frame, false)).collect()));
+            }
+            false
+        });
+
+        waiting
+    }
+}
+
+/// The numbered messages ready to be handled on a single connection, queued for each pair of actors. A pair only has a
+/// queue, and a task handling it, while it has messages waiting.
+#[derive(Default)]
+pub(crate) struct Lanes(Mutex<HashMap<Lane, VecDeque<Ready>>>);
+
+impl Lanes {
+    /// Queues messages for a pair of actors, returning true if the pair had no queue, in which case the caller must start
+    /// a task that takes them with [`Lanes::next`].
+    pub(crate) fn push(&self, lane: Lane, ready: Vec<Ready>) -> bool {
+        match self.0.lock().entry(lane) {
+            Entry::Occupied(mut queue) => {
+                queue.get_mut().extend(ready);
+                false
+            },
+            Entry::Vacant(queue) => {
+                queue.insert(ready.into());
+                true
+            },
+        }
+    }
+
+    /// Takes the next message queued for a pair of actors, removing the pair's queue once it is empty so that the task
+    /// taking them can stop.
+    pub(crate) fn next(&self, lane: &Lane) -> Option<Ready> {
+        let mut lanes = self.0.lock();
+        let ready = lanes.get_mut(lane)?.pop_front();
+        if ready.is_none() {
+            lanes.remove(lane);
+        }
+
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lane(actor: u64) -> Lane {
+        ((String::from("sender"), 1), actor)
+    }
+
+    fn ping(request: u64) -> Frame {
+        Frame::Ping { request }
+    }
+
+    /// Returns the number of each ready message, and whether it still holds its credit.
+    fn numbers(ready: &[Ready]) -> Vec<(u64, bool)> {
+        ready.iter().map(|(frame, credit)| match frame {
+            Frame::Ping { request } => (*request, *credit),
+            _ => unreachable!("only pings are resequenced in these tests"),
+        }).collect()
+    }
+
+    #[test]
+    fn delivers_in_order() {
+        let mut resequencer = Resequencer::default();
+
+        assert!(resequencer.accept(lane(1), 2, ping(2)).is_empty());
+        assert!(resequencer.accept(lane(1), 1, ping(1)).is_empty());
+        assert_eq!(numbers(&resequencer.accept(lane(1), 0, ping(0))), [(0, true), (1, false), (2, false)]);
+        assert_eq!(numbers(&resequencer.accept(lane(1), 3, ping(3))), [(3, true)]);
+    }
+
+    #[test]
+    fn keeps_lanes_apart() {
+        let mut resequencer = Resequencer::default();
+
+        assert!(resequencer.accept(lane(1), 1, ping(1)).is_empty());
+        assert_eq!(numbers(&resequencer.accept(lane(2), 0, ping(0))), [(0, true)]);
+    }
+
+    #[test]
+    fn handles_late_and_repeated_messages() {
+        let mut resequencer = Resequencer::default();
+
+        assert_eq!(numbers(&resequencer.accept(lane(1), 0, ping(0))), [(0, true)]);
+        assert_eq!(numbers(&resequencer.accept(lane(1), 0, ping(0))), [(0, true)]);
+
+        assert!(resequencer.accept(lane(1), 2, ping(2)).is_empty());
+        assert_eq!(numbers(&resequencer.accept(lane(1), 2, ping(2))), [(2, true)]);
+    }
+
+    #[test]
+    fn skips_missing_messages() {
+        let mut resequencer = Resequencer::default();
+        let max = MAX_OUT_OF_ORDER as u64;
+
+        // Message 0 never arrives, so everything after it waits until too many are waiting
+        for sequence in 1..=max {
+            assert!(resequencer.accept(lane(1), sequence, ping(sequence)).is_empty());
+        }
+
+        let ready = numbers(&resequencer.accept(lane(1), max + 1, ping(max + 1)));
+        assert_eq!(ready.len(), MAX_OUT_OF_ORDER + 1);
+        assert_eq!(ready.first(), Some(&(1, false)));
+        assert_eq!(ready.last(), Some(&(max + 1, true)));
+
+        // The skipped message is handled as soon as it turns up
+        assert_eq!(numbers(&resequencer.accept(lane(1), 0, ping(0))), [(0, true)]);
+        assert_eq!(numbers(&resequencer.accept(lane(1), max + 2, ping(max + 2))), [(max + 2, true)]);
+    }
+
+    #[test]
+    fn forgets_stopped_actors() {
+        let mut resequencer = Resequencer::default();
+
+        assert_eq!(numbers(&resequencer.accept(lane(1), 0, ping(0))), [(0, true)]);
+        assert!(resequencer.accept(lane(1), 2, ping(2)).is_empty());
+        assert!(resequencer.accept(lane(2), 1, ping(1)).is_empty());
+
+        let waiting = resequencer.forget_actor(1);
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].0, lane(1));
+        assert_eq!(numbers(&waiting[0].1), [(2, false)]);
+
+        // The peer numbers its messages to the actor from the start again, and other actors are unaffected
+        assert_eq!(numbers(&resequencer.accept(lane(1), 0, ping(0))), [(0, true)]);
+        assert!(resequencer.accept(lane(2), 2, ping(2)).is_empty());
+    }
+
+    #[test]
+    fn numbers_messages_per_pair() {
+        let sequences = Sequences::default();
+        let sender = (String::from("sender"), 1);
+
+        assert_eq!(sequences.peek(None, 1), None);
+        assert_eq!(sequences.peek(Some(&sender), 1), Some(0));
+
+        // A number is only used up once it is advanced past, and stale numbers change nothing
+        assert_eq!(sequences.peek(Some(&sender), 1), Some(0));
+        sequences.advance(&sender, 1, 0);
+        sequences.advance(&sender, 1, 0);
+        assert_eq!(sequences.peek(Some(&sender), 1), Some(1));
+        assert_eq!(sequences.peek(Some(&sender), 2), Some(0));
+
+        sequences.forget_actor(1);
+        assert_eq!(sequences.peek(Some(&sender), 1), Some(0));
+    }
+
+    #[test]
+    fn queues_lanes_while_they_have_messages() {
+        let lanes = Lanes::default();
+
+        assert!(lanes.push(lane(1), alloc::vec![(ping(0), true)]));
+        assert!(!lanes.push(lane(1), alloc::vec![(ping(1), true)]));
+
+        assert_eq!(numbers(&[lanes.next(&lane(1)).unwrap()]), [(0, true)]);
+        assert_eq!(numbers(&[lanes.next(&lane(1)).unwrap()]), [(1, true)]);
+        assert!(lanes.next(&lane(1)).is_none());
+
+        // The lane's queue was removed, so the next push needs a new task
+        assert!(lanes.push(lane(1), alloc::vec![(ping(2), true)]));
+    }
+}
@@ -0,0 +1,164 @@
+//! # Serialization
+//! Foreign messages and their results are encoded by a [`MessageSerializer`] before they are sent. Bincode is always available,
+//! and Postcard, CBOR, and `MessagePack` are available behind the `postcard`, `cbor`, and `messagepack` features.
+//!
+//! The serializer is chosen with a type parameter on [`super::PeerDelegate`] and [`super::Exports`], and both ends of a connection
+//! must use the same one. The transport's own frames are always encoded with bincode.
+
+use alloc::vec::Vec;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// # [`MessageSerializer`]
+/// Encodes foreign messages and their results.
+pub trait MessageSerializer: Send + Sync + 'static {
+    /// # [`MessageSerializer::Error`]
+    /// The error returned when a value can not be encoded or decoded.
+    type Error: core::error::Error + Send + Sync + 'static;
+
+    /// # [`MessageSerializer::serialize`]
+    /// Encodes a value.
+    ///
+    /// # Errors
+    /// Returns an error if the value can not be encoded.
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    /// # [`MessageSerializer::deserialize`]
+    /// Decodes a value.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a valid encoding of `T`.
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// # [`BincodeSerializer`]
+/// Encodes messages with bincode. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeSerializer;
+
+impl MessageSerializer for BincodeSerializer {
+    type Error = bincode::Error;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        bincode::serialize(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// # [`PostcardSerializer`]
+/// Encodes messages with postcard, a compact format designed for embedded systems.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostcardSerializer;
+
+#[cfg(feature = "postcard")]
+impl MessageSerializer for PostcardSerializer {
+    type Error = postcard::Error;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        postcard::to_allocvec(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        postcard::from_bytes(bytes)
+    }
+}
+
+/// # [`CborSerializer`]
+/// Encodes messages as CBOR, a self-describing format that many languages can read.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborSerializer;
+
+/// # [`CborError`]
+/// An error returned by [`CborSerializer`].
+#[cfg(feature = "cbor")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CborError {
+    /// A value could not be encoded.
+    Serialize(ciborium::ser::Error<std::io::Error>),
+    /// Bytes could not be decoded.
+    Deserialize(ciborium::de::Error<std::io::Error>),
+}
+
+#[cfg(feature = "cbor")]
+impl core::fmt::Display for CborError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Serialize(e) => write!(f, "failed to encode cbor: {e}"),
+            Self::Deserialize(e) => write!(f, "failed to decode cbor: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl core::error::Error for CborError {}
+
+#[cfg(feature = "cbor")]
+impl MessageSerializer for CborSerializer {
+    type Error = CborError;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(CborError::Serialize)?;
+        Ok(bytes)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        ciborium::from_reader(bytes).map_err(CborError::Deserialize)
+    }
+}
+
+/// # [`MessagePackSerializer`]
+/// Encodes messages as `MessagePack`. Structs are encoded as maps, so fields can be added to messages compatibly.
+#[cfg(feature = "messagepack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackSerializer;
+
+/// # [`MessagePackError`]
+/// An error returned by [`MessagePackSerializer`].
+#[cfg(feature = "messagepack")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MessagePackError {
+    /// A value could not be encoded.
+    Serialize(rmp_serde::encode::Error),
+    /// Bytes could not be decoded.
+    Deserialize(rmp_serde::decode::Error),
+}
+
+#[cfg(feature = "messagepack")]
+impl core::fmt::Display for MessagePackError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Serialize(e) => write!(f, "failed to encode messagepack: {e}"),
+            Self::Deserialize(e) => write!(f, "failed to decode messagepack: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "messagepack")]
+impl core::error::Error for MessagePackError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Serialize(e) => Some(e),
+            Self::Deserialize(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "messagepack")]
+impl MessageSerializer for MessagePackSerializer {
+    type Error = MessagePackError;
+
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        rmp_serde::to_vec_named(value).map_err(MessagePackError::Serialize)
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        rmp_serde::from_slice(bytes).map_err(MessagePackError::Deserialize)
+    }
+}
@@ -0,0 +1,130 @@
+//! # Signing
+//! Signs the messages a system sends with its ed25519 key, so that the systems receiving them can verify which system
+//! sent each message and that it wasn't changed on the way, even if the transport itself can't be trusted.
+//!
+//! A [`Signing`] holds the local system's key and the public keys of the systems it trusts. It is given to the local
+//! system with [`crate::FluxionBuilder::signing`], which verifies the signatures of the messages its [`super::Exports`]
+//! receive, and to its delegate with [`super::PeerDelegate::with_signing`], which signs the messages it sends.
+//!
+//! A message whose signature is invalid, or that was signed by a system that isn't trusted, is rejected with
+//! [`super::RemoteError::InvalidSignature`]. Unsigned messages are accepted unless [`Signing::require_signatures`] is set.
+//! A verified message is handled with the id of the system that signed it as its [`crate::Metadata::peer`], unless the
+//! transport already authenticated the peer.
+//!
+//! Signatures cover every part of a message besides its request id, so a message that is captured can be sent again.
+//! Messages that must not be handled twice should be sent with an idempotency key.
+
+use alloc::{collections::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use super::{Frame, RemoteError, PROTOCOL_VERSION};
+
+/// The keys of a [`Signing`].
+#[derive(Clone)]
+struct Keys {
+    /// The id of the local system, which messages are signed as
+    system: String,
+    /// The local system's key, if it signs the messages it sends
+    key: Option<SigningKey>,
+    /// The public keys of the systems whose signatures are accepted, keyed by their id
+    trusted: BTreeMap<String, VerifyingKey>,
+    /// Whether unsigned messages are rejected
+    required: bool,
+}
+
+/// # [`Signing`]
+/// The keys a system signs and verifies messages with, as described in the [module documentation](self).
+/// Clones share the same keys.
+#[derive(Clone)]
+pub struct Signing(Arc<Keys>);
+
+impl Signing {
+    /// # [`Signing::new`]
+    /// Signs messages as the system with the given id, using its key. No other system is trusted until added with [`Signing::trust`].
+    #[must_use]
+    pub fn new(system: &str, key: SigningKey) -> Self {
+        Self(Arc::new(Keys { system: system.to_string(), key: Some(key), trusted: BTreeMap::new(), required: false }))
+    }
+
+    /// # [`Signing::verify_only`]
+    /// Verifies the signatures of messages from trusted systems without signing any messages.
+    #[must_use]
+    pub fn verify_only() -> Self {
+        Self(Arc::new(Keys { system: String::new(), key: None, trusted: BTreeMap::new(), required: false }))
+    }
+
+    /// # [`Signing::trust`]
+    /// Accepts messages signed by the system with the given id, using its public key.
+    /// Trusting a system that was already trusted replaces its key.
+    #[must_use]
+    pub fn trust(mut self, system: &str, key: VerifyingKey) -> Self {
+        Arc::make_mut(&mut self.0).trusted.insert(system.to_string(), key);
+        self
+    }
+
+    /// # [`Signing::require_signatures`]
+    /// Rejects unsigned messages, instead of handling them without a verified origin.
+    #[must_use]
+    pub fn require_signatures(mut self) -> Self {
+        Arc::make_mut(&mut self.0).required = true;
+        self
+    }
+
+    /// Signs a message frame, if there is a key to sign it with. Other frames are returned as they are.
+    pub(crate) fn sign(&self, mut frame: Frame) -> Frame {
+        let Some(key) = &self.0.key else {
+            return frame;
+        };
+
+        let Some(bytes) = signed_bytes(&frame, &self.0.system) else {
+            return frame;
+        };
+
+        if let Frame::Request { signature, .. } | Frame::Tell { signature, .. } | Frame::Notify { signature, .. } = &mut frame {
+            *signature = Some((self.0.system.clone(), key.sign(&bytes).to_vec()));
+        }
+
+        frame
+    }
+
+    /// Verifies the signature of a message frame, returning the id of the system that signed it.
+    /// Other frames, and unsigned messages if signatures aren't required, are verified by no one.
+    ///
+    /// # Errors
+    /// Returns [`RemoteError::InvalidSignature`] if the signature is missing but required, was made by an untrusted
+    /// system, or does not match the message.
+    pub(crate) fn verify(&self, frame: &Frame) -> Result<Option<String>, RemoteError> {
+        let (Frame::Request { message, signature, .. } | Frame::Tell { message, signature, .. } | Frame::Notify { message, signature, .. }) = frame else {
+            return Ok(None);
+        };
+        let invalid = |reason: &str| RemoteError::InvalidSignature { message: message.clone(), reason: reason.to_string() };
+
+        let Some((signer, signature)) = signature else {
+            return if self.0.required { Err(invalid("message is not signed")) } else { Ok(None) };
+        };
+
+        let key = self.0.trusted.get(signer)
+            .ok_or_else(|| invalid(&alloc::format!("system {signer} is not trusted")))?;
+        let signature = Signature::from_slice(signature)
+            .map_err(|_| invalid("signature is malformed"))?;
+        let bytes = signed_bytes(frame, signer)
+            .ok_or_else(|| invalid("message could not be encoded"))?;
+
+        key.verify_strict(&bytes, &signature)
+            .map_err(|_| invalid("signature does not match the message"))?;
+
+        Ok(Some(signer.clone()))
+    }
+}
+
+/// Returns the bytes of a message frame that its signature covers, which are every part of the message besides
+/// its request id and signature, along with the protocol version and the id of the system that signed it.
+fn signed_bytes(frame: &Frame, signer: &str) -> Option<Vec<u8>> {
+    match frame {
+        Frame::Request { actor, message, version, key, metadata, sender, sequence, payload, .. }
+            | Frame::Tell { actor, message, version, key, metadata, sender, sequence, payload, .. } =>
+            bincode::serialize(&(PROTOCOL_VERSION, signer, actor, message, version, key, metadata, sender, sequence, payload)).ok(),
+        Frame::Notify { message, version, payload, .. } => bincode::serialize(&(PROTOCOL_VERSION, signer, message, version, payload)).ok(),
+        _ => None,
+    }
+}
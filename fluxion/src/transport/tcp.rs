@@ -0,0 +1,146 @@
+//! # TCP Transport
+//! A [`Delegate`] that reaches foreign systems over TCP, using length prefixed [`super::Frame`]s.
+//!
+//! [`TcpDelegate`] is the client half: it is given the address of each peer system, and keeps one
+//! multiplexed connection per peer, reconnecting whenever the previous connection was lost.
+//! [`TcpServer`] is the server half: it accepts connections from peers and dispatches their messages
+//! to the actors exported from the local system.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{tcp::{OwnedReadHalf, OwnedWriteHalf}, TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use super::{serialize::{BincodeSerializer, MessageSerializer}, Connection, Dialer, Exports, FrameReader, FrameWriter, PeerDelegate, TransportError, MAX_FRAME_SIZE};
+use crate::Delegate;
+
+/// Reads frames prefixed with their length as a big endian u32.
+pub(super) struct LengthDelimitedReader<R = OwnedReadHalf>(pub(super) R);
+
+#[async_trait::async_trait]
+impl<R: AsyncRead + Unpin + Send + 'static> FrameReader for LengthDelimitedReader<R> {
+    async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        let length = match self.0.read_u32().await {
+            Ok(length) => length as usize,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if length > MAX_FRAME_SIZE {
+            return Err(TransportError::FrameTooLarge(length));
+        }
+
+        let mut frame = alloc::vec![0; length];
+        self.0.read_exact(&mut frame).await?;
+
+        Ok(Some(frame))
+    }
+}
+
+/// Writes frames prefixed with their length as a big endian u32.
+pub(super) struct LengthDelimitedWriter<W = OwnedWriteHalf>(pub(super) W);
+
+#[async_trait::async_trait]
+impl<W: AsyncWrite + Unpin + Send + 'static> FrameWriter for LengthDelimitedWriter<W> {
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(TransportError::FrameTooLarge(frame.len()));
+        }
+
+        // The length check above guarantees that this fits in a u32.
+        #[allow(clippy::cast_possible_truncation)]
+        self.0.write_u32(frame.len() as u32).await?;
+        self.0.write_all(frame).await?;
+
+        Ok(())
+    }
+}
+
+/// Splits a TCP stream into the two halves of a connection
+fn split(stream: TcpStream) -> (LengthDelimitedReader, LengthDelimitedWriter) {
+    // Frames are small and latency sensitive, so disable Nagle's algorithm.
+    let _ = stream.set_nodelay(true);
+    let (reader, writer) = stream.into_split();
+    (LengthDelimitedReader(reader), LengthDelimitedWriter(writer))
+}
+
+/// # [`TcpDialer`]
+/// Connects to foreign systems over TCP.
+#[derive(Default)]
+pub struct TcpDialer;
+
+#[async_trait::async_trait]
+impl Dialer for TcpDialer {
+    async fn dial(&self, address: &str) -> Result<Arc<Connection>, TransportError> {
+        let (reader, writer) = split(TcpStream::connect(address).await?);
+        Ok(Connection::new(reader, writer))
+    }
+}
+
+/// # [`TcpDelegate`]
+/// A [`crate::Delegate`] that resolves foreign actors on peer systems over TCP.
+/// Peers must be registered with [`PeerDelegate::add_peer`] before their actors can be retrieved.
+/// Messages are encoded with bincode unless another serializer is given with [`PeerDelegate::with_serializer`].
+pub type TcpDelegate<S = BincodeSerializer> = PeerDelegate<TcpDialer, S>;
+
+impl TcpDelegate {
+    /// # [`TcpDelegate::new`]
+    /// Creates a delegate with no peers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_dialer(TcpDialer)
+    }
+}
+
+impl Default for TcpDelegate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # [`TcpServer`]
+/// Accepts connections from peer systems and dispatches their messages to the local system's exported actors.
+pub struct TcpServer<D, S = BincodeSerializer> {
+    exports: Arc<Exports<D, S>>,
+}
+
+impl<D: Delegate, S: MessageSerializer> TcpServer<D, S> {
+    /// # [`TcpServer::new`]
+    /// Creates a server that exposes the given exports.
+    #[must_use]
+    pub fn new(exports: Exports<D, S>) -> Self {
+        Self { exports: Arc::new(exports) }
+    }
+
+    /// # [`TcpServer::listen`]
+    /// Binds to the given address and serves peers until an error occurs while accepting connections.
+    ///
+    /// # Errors
+    /// Returns an error if binding or accepting fails.
+    pub async fn listen(&self, address: impl ToSocketAddrs) -> Result<(), TransportError> {
+        self.serve(TcpListener::bind(address).await?).await
+    }
+
+    /// # [`TcpServer::serve`]
+    /// Serves peers on an existing listener until an error occurs while accepting connections.
+    /// Each connection is handled on its own task.
+    ///
+    /// # Errors
+    /// Returns an error if accepting a connection fails.
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), TransportError> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let (reader, writer) = split(stream);
+
+            tokio::spawn(super::serve_connection(self.exports.clone(), reader, writer));
+        }
+    }
+}
+
+impl<D, S> Clone for TcpServer<D, S> {
+    fn clone(&self) -> Self {
+        Self { exports: self.exports.clone() }
+    }
+}
+
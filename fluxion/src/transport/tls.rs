@@ -0,0 +1,133 @@
+//! # TLS Transport
+//! The TCP transport, encrypted with TLS using `tokio-rustls`. [`TlsDelegate`] connects to peers, and [`TlsServer`]
+//! accepts connections from them, each configured with a `rustls` configuration.
+//!
+//! For mutual authentication, the server's configuration should require client certificates, for example with
+//! `rustls::server::WebPkiClientVerifier`, and each client's configuration should present one. Peers without a
+//! trusted certificate then fail the handshake, and can neither resolve actors nor send messages. The identity of an
+//! authenticated peer is decided from its certificate by the function given to [`TlsServer::with_identity`], and handlers
+//! see it through [`crate::Metadata::peer`].
+
+use alloc::{boxed::Box, string::{String, ToString}, sync::Arc};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio_rustls::{rustls::{self, pki_types::{CertificateDer, ServerName}}, TlsAcceptor, TlsConnector};
+
+use super::{serialize::{BincodeSerializer, MessageSerializer}, serve_identified, tcp::{LengthDelimitedReader, LengthDelimitedWriter}, Connection, Dialer, Exports, PeerDelegate, TransportError};
+use crate::Delegate;
+
+/// Decides a peer's identity from its certificate chain, which starts with the peer's own certificate.
+type Identify = Arc<dyn Fn(&[CertificateDer<'_>]) -> Option<String> + Send + Sync>;
+
+/// # [`TlsDialer`]
+/// Connects to foreign systems over TCP, encrypted with TLS.
+pub struct TlsDialer {
+    connector: TlsConnector,
+}
+
+impl TlsDialer {
+    /// # [`TlsDialer::new`]
+    /// Creates a dialer that connects with the given client configuration.
+    #[must_use]
+    pub fn new(config: Arc<rustls::ClientConfig>) -> Self {
+        Self { connector: TlsConnector::from(config) }
+    }
+}
+
+#[async_trait::async_trait]
+impl Dialer for TlsDialer {
+    async fn dial(&self, address: &str) -> Result<Arc<Connection>, TransportError> {
+        let name = ServerName::try_from(super::host(address).to_string())
+            .map_err(|e| TransportError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
+
+        let stream = TcpStream::connect(address).await?;
+        // Frames are small and latency sensitive, so disable Nagle's algorithm.
+        let _ = stream.set_nodelay(true);
+
+        let (reader, writer) = tokio::io::split(self.connector.connect(name, stream).await?);
+        Ok(Connection::new(LengthDelimitedReader(reader), LengthDelimitedWriter(writer)))
+    }
+}
+
+/// # [`TlsDelegate`]
+/// A [`crate::Delegate`] that resolves foreign actors on peer systems over TCP, encrypted with TLS.
+/// Peers are registered with [`PeerDelegate::add_peer`], using a `host:port` address whose host the peer's certificate is valid for.
+/// Messages are encoded with bincode unless another serializer is given with [`PeerDelegate::with_serializer`].
+pub type TlsDelegate<S = BincodeSerializer> = PeerDelegate<TlsDialer, S>;
+
+impl TlsDelegate {
+    /// # [`TlsDelegate::new`]
+    /// Creates a delegate with no peers, which connects to them with the given client configuration.
+    #[must_use]
+    pub fn new(config: Arc<rustls::ClientConfig>) -> Self {
+        Self::with_dialer(TlsDialer::new(config))
+    }
+}
+
+/// # [`TlsServer`]
+/// Accepts TLS connections from peer systems and dispatches their messages to the local system's exported actors.
+pub struct TlsServer<D, S = BincodeSerializer> {
+    exports: Arc<Exports<D, S>>,
+    acceptor: TlsAcceptor,
+    identify: Option<Identify>,
+}
+
+impl<D: Delegate, S: MessageSerializer> TlsServer<D, S> {
+    /// # [`TlsServer::new`]
+    /// Creates a server that exposes the given exports, and accepts connections with the given server configuration.
+    #[must_use]
+    pub fn new(exports: Exports<D, S>, config: Arc<rustls::ServerConfig>) -> Self {
+        Self { exports: Arc::new(exports), acceptor: TlsAcceptor::from(config), identify: None }
+    }
+
+    /// # [`TlsServer::with_identity`]
+    /// Decides the identity of each peer from the certificate chain it presented, starting with its own certificate,
+    /// for example by reading the certificate's subject. Peers that presented no certificate have no identity.
+    #[must_use]
+    pub fn with_identity(mut self, identify: impl Fn(&[CertificateDer<'_>]) -> Option<String> + Send + Sync + 'static) -> Self {
+        self.identify = Some(Arc::new(identify));
+        self
+    }
+
+    /// # [`TlsServer::listen`]
+    /// Binds to the given address and serves peers until an error occurs while accepting connections.
+    ///
+    /// # Errors
+    /// Returns an error if binding or accepting fails.
+    pub async fn listen(&self, address: impl ToSocketAddrs) -> Result<(), TransportError> {
+        self.serve(TcpListener::bind(address).await?).await
+    }
+
+    /// # [`TlsServer::serve`]
+    /// Serves peers on an existing listener until an error occurs while accepting connections.
+    /// Each connection is handled on its own task, and connections that fail their handshake are dropped.
+    ///
+    /// # Errors
+    /// Returns an error if accepting a connection fails.
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), TransportError> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let _ = stream.set_nodelay(true);
+
+            let server = self.clone();
+            tokio::spawn(async move {
+                let Ok(stream) = server.acceptor.accept(stream).await else {
+                    return;
+                };
+
+                let peer = stream.get_ref().1.peer_certificates()
+                    .zip(server.identify.as_ref())
+                    .and_then(|(certificates, identify)| identify(certificates))
+                    .map(Arc::from);
+
+                let (reader, writer) = tokio::io::split(stream);
+                serve_identified(server.exports, peer, LengthDelimitedReader(reader), LengthDelimitedWriter(writer)).await;
+            });
+        }
+    }
+}
+
+impl<D, S> Clone for TlsServer<D, S> {
+    fn clone(&self) -> Self {
+        Self { exports: self.exports.clone(), acceptor: self.acceptor.clone(), identify: self.identify.clone() }
+    }
+}
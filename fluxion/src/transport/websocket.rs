@@ -0,0 +1,216 @@
+//! # WebSocket Transport
+//! A [`crate::Delegate`] that reaches foreign systems over websockets, sending each [`super::Frame`] as a binary message.
+//!
+//! On native targets, both halves are provided using `tokio-tungstenite`: [`WebSocketDelegate`] connects to peers,
+//! and [`WebSocketServer`] accepts connections from them. When compiled for `wasm32`, only the client half is available,
+//! and connections are made through the browser's WebSocket API. This allows a Fluxion system running in a browser
+//! to message actors on a native server, and vice versa over the same connection.
+//!
+//! The interop tests in `tests/websocket.rs` and `tests/websocket_browser.rs` exercise native and browser clients against
+//! the same server. The browser tests run with `wasm-pack test`, against the server started by the `interop_server` example.
+
+use alloc::string::ToString;
+
+use super::{serialize::BincodeSerializer, PeerDelegate, TransportError};
+
+/// Wraps a websocket error as an io error, as it can only ever occur due to the underlying connection failing.
+#[allow(clippy::needless_pass_by_value)]
+fn websocket_error(error: impl ToString) -> TransportError {
+    TransportError::Io(std::io::Error::other(error.to_string()))
+}
+
+/// # [`WebSocketDelegate`]
+/// A [`crate::Delegate`] that resolves foreign actors on peer systems over websockets.
+/// Peers are registered with [`PeerDelegate::add_peer`], using a `ws://` or `wss://` url as their address.
+/// Messages are encoded with bincode unless another serializer is given with [`PeerDelegate::with_serializer`].
+pub type WebSocketDelegate<S = BincodeSerializer> = PeerDelegate<WebSocketDialer, S>;
+
+impl WebSocketDelegate {
+    /// # [`WebSocketDelegate::new`]
+    /// Creates a delegate with no peers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_dialer(WebSocketDialer)
+    }
+}
+
+impl Default for WebSocketDelegate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # [`WebSocketDialer`]
+/// Connects to foreign systems over websockets.
+#[derive(Default)]
+pub struct WebSocketDialer;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use alloc::{boxed::Box, sync::Arc, vec::Vec};
+    use futures_util::{stream::{SplitSink, SplitStream}, SinkExt, StreamExt};
+    use tokio::{io::{AsyncRead, AsyncWrite}, net::{TcpListener, ToSocketAddrs}};
+    use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+    use super::{websocket_error, WebSocketDialer};
+    use crate::{transport::{serialize::{BincodeSerializer, MessageSerializer}, serve_connection, Connection, Dialer, Exports, FrameReader, FrameWriter, TransportError}, Delegate};
+
+    struct Reader<S>(SplitStream<WebSocketStream<S>>);
+
+    #[async_trait::async_trait]
+    impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> FrameReader for Reader<S> {
+        async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+            loop {
+                match self.0.next().await {
+                    Some(Ok(Message::Binary(frame))) => return Ok(Some(frame)),
+                    Some(Ok(Message::Close(_))) | None => return Ok(None),
+                    // Pings are answered by tungstenite, and text messages aren't part of the protocol.
+                    Some(Ok(_)) => {},
+                    Some(Err(e)) => return Err(websocket_error(e)),
+                }
+            }
+        }
+    }
+
+    struct Writer<S>(SplitSink<WebSocketStream<S>, Message>);
+
+    #[async_trait::async_trait]
+    impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> FrameWriter for Writer<S> {
+        async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+            self.0.send(Message::Binary(frame.to_vec())).await.map_err(websocket_error)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Dialer for WebSocketDialer {
+        async fn dial(&self, address: &str) -> Result<Arc<Connection>, TransportError> {
+            let (socket, _) = tokio_tungstenite::connect_async(address).await.map_err(websocket_error)?;
+            let (writer, reader) = socket.split();
+
+            Ok(Connection::new(Reader(reader), Writer(writer)))
+        }
+    }
+
+    /// # [`WebSocketServer`]
+    /// Accepts WebSocket connections from peer systems and dispatches their messages to the local system's exported actors.
+    pub struct WebSocketServer<D, S = BincodeSerializer> {
+        exports: Arc<Exports<D, S>>,
+    }
+
+    impl<D: Delegate, S: MessageSerializer> WebSocketServer<D, S> {
+        /// # [`WebSocketServer::new`]
+        /// Creates a server that exposes the given exports.
+        #[must_use]
+        pub fn new(exports: Exports<D, S>) -> Self {
+            Self { exports: Arc::new(exports) }
+        }
+
+        /// # [`WebSocketServer::listen`]
+        /// Binds to the given address and serves peers until an error occurs while accepting connections.
+        ///
+        /// # Errors
+        /// Returns an error if binding or accepting fails.
+        pub async fn listen(&self, address: impl ToSocketAddrs) -> Result<(), TransportError> {
+            self.serve(TcpListener::bind(address).await?).await
+        }
+
+        /// # [`WebSocketServer::serve`]
+        /// Serves peers on an existing listener until an error occurs while accepting connections.
+        /// Each connection is upgraded and handled on its own task.
+        ///
+        /// # Errors
+        /// Returns an error if accepting a connection fails.
+        pub async fn serve(&self, listener: TcpListener) -> Result<(), TransportError> {
+            loop {
+                let (stream, _) = listener.accept().await?;
+
+                let server = self.clone();
+                tokio::spawn(async move {
+                    let _ = server.accept(stream).await;
+                });
+            }
+        }
+
+        /// # [`WebSocketServer::accept`]
+        /// Performs the WebSocket handshake on a single stream, and serves the peer until it disconnects.
+        /// This allows the server to be embedded in an existing HTTP server that hands off upgraded connections.
+        ///
+        /// # Errors
+        /// Returns an error if the handshake fails.
+        pub async fn accept<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(&self, stream: T) -> Result<(), TransportError> {
+            let socket = tokio_tungstenite::accept_async(stream).await.map_err(websocket_error)?;
+            let (writer, reader) = socket.split();
+
+            serve_connection(self.exports.clone(), Reader(reader), Writer(writer)).await;
+
+            Ok(())
+        }
+    }
+
+    impl<D, S> Clone for WebSocketServer<D, S> {
+        fn clone(&self) -> Self {
+            Self { exports: self.exports.clone() }
+        }
+    }
+}
+
+/// The browser's WebSocket is not [`Send`], but wasm32 is single threaded,
+/// so it is wrapped in a [`send_wrapper::SendWrapper`] to satisfy the transport traits.
+#[cfg(target_arch = "wasm32")]
+mod browser {
+    use alloc::{boxed::Box, sync::Arc, vec::Vec};
+    use futures_util::{stream::{SplitSink, SplitStream}, SinkExt, StreamExt};
+    use gloo_net::websocket::{futures::WebSocket, Message};
+    use send_wrapper::SendWrapper;
+
+    use super::{websocket_error, WebSocketDialer};
+    use crate::transport::{Connection, Dialer, FrameReader, FrameWriter, Liveness, TransportError};
+
+    struct Reader(SendWrapper<SplitStream<WebSocket>>);
+
+    #[async_trait::async_trait]
+    impl FrameReader for Reader {
+        async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+            loop {
+                match SendWrapper::new(self.0.next()).await {
+                    Some(Ok(Message::Bytes(frame))) => return Ok(Some(frame)),
+                    // Text messages aren't part of the protocol.
+                    Some(Ok(Message::Text(_))) => {},
+                    Some(Err(e)) => return Err(websocket_error(e)),
+                    None => return Ok(None),
+                }
+            }
+        }
+    }
+
+    struct Writer(SendWrapper<SplitSink<WebSocket, Message>>);
+
+    #[async_trait::async_trait]
+    impl FrameWriter for Writer {
+        async fn write_frame(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+            SendWrapper::new(self.0.send(Message::Bytes(frame.to_vec()))).await.map_err(websocket_error)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Dialer for WebSocketDialer {
+        async fn dial(&self, address: &str) -> Result<Arc<Connection>, TransportError> {
+            let socket = WebSocket::open(address).map_err(websocket_error)?;
+            let (writer, reader) = socket.split();
+
+            // Tokio's runtime isn't available in the browser, so the connection is driven on the page's event loop.
+            let (connection, driver) = Connection::with_driver(Reader(SendWrapper::new(reader)), Writer(SendWrapper::new(writer)));
+            wasm_bindgen_futures::spawn_local(driver);
+
+            Ok(connection)
+        }
+
+        fn probe(&self, connection: &Arc<Connection>, liveness: Liveness) {
+            // Probes keep time with `setTimeout` and run on the page's event loop, just like the driver.
+            wasm_bindgen_futures::spawn_local(connection.liveness_probe(liveness, Arc::new(crate::runtime::Wasm::new())));
+        }
+    }
+}
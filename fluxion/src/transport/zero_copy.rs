@@ -0,0 +1,268 @@
+//! # Zero Copy
+//! Foreign messages can be encoded with rkyv instead of a [`MessageSerializer`], so that the receiving system validates the
+//! payload in place and hands it to the actor as an [`ArchivedMessage`] without deserializing or copying it.
+//! This is available behind the `rkyv` feature.
+//!
+//! Actors receive these messages by implementing [`Handler<ArchivedMessage<M>>`](crate::Handler), and are exported
+//! with [`Exports::export_archived`]. Senders are retrieved from the delegate with [`PeerDelegate::get_archived`],
+//! rather than through [`crate::Fluxion::get`], as the message does not need to implement serde's traits.
+//! Results are still encoded with the exports' [`MessageSerializer`].
+
+use core::{marker::PhantomData, mem::align_of};
+
+use alloc::{boxed::Box, string::{String, ToString}, sync::Arc, vec::Vec};
+use rkyv::{
+    api::high::{HighSerializer, HighValidator},
+    bytecheck::CheckBytes,
+    rancor,
+    ser::allocator::ArenaHandle,
+    util::AlignedVec,
+    Archive, Portable,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{sender::Caller, Delegate, Handler, Identifier, Message, MessageID, MessageSendError, MessageSender, Metadata};
+
+use super::{Address, DispatchError, Export, Exports, Frame, Dialer, Peer, PeerDelegate, TransportError, serialize::MessageSerializer};
+
+/// # [`ZeroCopySerializer`]
+/// Encodes foreign messages in a format that can be read in place, without deserializing them.
+///
+/// # Safety
+/// [`ArchivedMessage`] reads payloads accepted by [`ZeroCopySerializer::access`] without validating them again,
+/// so implementations must only return [`Ok`] if the bytes are a valid rkyv archive of `T`.
+pub unsafe trait ZeroCopySerializer: Send + Sync + 'static {
+    /// # [`ZeroCopySerializer::Error`]
+    /// The error returned when a value can not be encoded or validated.
+    type Error: core::error::Error + Send + Sync + 'static;
+
+    /// # [`ZeroCopySerializer::serialize`]
+    /// Encodes a value.
+    ///
+    /// # Errors
+    /// Returns an error if the value can not be encoded.
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::Error>
+        where T: for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>;
+
+    /// # [`ZeroCopySerializer::access`]
+    /// Validates the bytes, and returns the archived value they contain.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a valid archive of `T`.
+    fn access<T: Archive>(bytes: &[u8]) -> Result<&T::Archived, Self::Error>
+        where T::Archived: Portable + for<'a> CheckBytes<HighValidator<'a, rancor::Error>>;
+}
+
+/// # [`RkyvSerializer`]
+/// Encodes messages with rkyv.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RkyvSerializer;
+
+// SAFETY: `rkyv::access` fully validates the archive.
+unsafe impl ZeroCopySerializer for RkyvSerializer {
+    type Error = rancor::Error;
+
+    fn serialize<T>(value: &T) -> Result<Vec<u8>, Self::Error>
+        where T: for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>> {
+        rkyv::to_bytes(value).map(AlignedVec::into_vec)
+    }
+
+    fn access<T: Archive>(bytes: &[u8]) -> Result<&T::Archived, Self::Error>
+        where T::Archived: Portable + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> {
+        rkyv::access(bytes)
+    }
+}
+
+/// The bytes of an [`ArchivedMessage`], which are only copied if they were not aligned for the archive.
+enum Payload {
+    /// The payload as it was received
+    Received(Vec<u8>),
+    /// A copy of a payload that was not suitably aligned
+    Aligned(AlignedVec),
+}
+
+impl Payload {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Payload::Received(bytes) => bytes,
+            Payload::Aligned(bytes) => bytes.as_slice(),
+        }
+    }
+}
+
+/// # [`ArchivedMessage`]
+/// A foreign message that was validated on arrival, and is read in place.
+/// Actors handle it by implementing [`Handler<ArchivedMessage<M>>`](crate::Handler), which responds with `M`'s result.
+pub struct ArchivedMessage<M> {
+    /// The validated archive
+    payload: Payload,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M: Archive> ArchivedMessage<M>
+    where M::Archived: Portable + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> {
+    /// Validates a payload with the given serializer, copying it only if it is not aligned for `M`'s archive.
+    fn validate<Z: ZeroCopySerializer>(payload: Vec<u8>) -> Result<Self, Z::Error> {
+        let payload = if payload.as_ptr().align_offset(align_of::<M::Archived>()) == 0 {
+            Payload::Received(payload)
+        } else {
+            let mut aligned = AlignedVec::with_capacity(payload.len());
+            aligned.extend_from_slice(&payload);
+            Payload::Aligned(aligned)
+        };
+
+        Z::access::<M>(payload.as_slice())?;
+
+        Ok(Self { payload, _message: PhantomData })
+    }
+
+    /// # [`ArchivedMessage::get`]
+    /// Returns the archived message.
+    #[must_use]
+    pub fn get(&self) -> &M::Archived {
+        // SAFETY: The payload was validated as an archive of `M` by a `ZeroCopySerializer` when this was created,
+        // and is never modified.
+        unsafe { rkyv::access_unchecked::<M::Archived>(self.payload.as_slice()) }
+    }
+
+    /// # [`ArchivedMessage::bytes`]
+    /// Returns the encoded message.
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        self.payload.as_slice()
+    }
+}
+
+impl<M: Message> Message for ArchivedMessage<M> {
+    type Result = M::Result;
+}
+
+/// Validates a payload as an archive of `M`, only accepting its current version.
+fn decode_archived<M: MessageID + Archive, Z: ZeroCopySerializer>(version: u32, payload: Vec<u8>) -> Result<ArchivedMessage<M>, DispatchError>
+    where M::Archived: Portable + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> {
+    if version != M::VERSION {
+        return Err(DispatchError::UnsupportedVersion(M::VERSION));
+    }
+
+    ArchivedMessage::validate::<Z>(payload).map_err(|e| DispatchError::Malformed(e.to_string()))
+}
+
+impl<D: Delegate, S: MessageSerializer> Exports<D, S> {
+    /// # [`Exports::export_archived`]
+    /// Allows foreign systems to send the message `M`, encoded with the [`ZeroCopySerializer`] `Z`, to any actor of type `A`
+    /// on this system. The actor receives the message as an [`ArchivedMessage`], without it being deserialized.
+    #[must_use]
+    pub fn export_archived<A, M, Z>(mut self) -> Self
+        where A: Handler<ArchivedMessage<M>>,
+            M: Message + MessageID + Archive,
+            M::Archived: Portable + for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+            M::Result: Serialize + for<'de> Deserialize<'de>,
+            Z: ZeroCopySerializer {
+        self.handlers.entry(M::ID).or_default()
+            .push(Box::new(Export::<A, ArchivedMessage<M>, S>::new(decode_archived::<M, Z>)));
+        self
+    }
+}
+
+impl<T: Dialer, S: MessageSerializer> PeerDelegate<T, S> {
+    /// # [`PeerDelegate::get_archived`]
+    /// Retrieves a sender for the given foreign actor that encodes `M` with the [`ZeroCopySerializer`] `Z`.
+    /// The foreign system must export the actor with [`Exports::export_archived`].
+    pub async fn get_archived<M, Z>(&self, id: Identifier<'_>) -> Option<ArchivedSender<M, T, Z, S>>
+        where M: Message + MessageID + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+            M::Result: Serialize + for<'de> Deserialize<'de>,
+            Z: ZeroCopySerializer {
+        let (address, system) = match id {
+            Identifier::Foreign(id, system) => (Address::Id(id), system),
+            Identifier::ForeignNamed(name, system) => (Address::Name(String::from(name)), system),
+            _ => return None,
+        };
+
+        let peer = self.peer(system)?;
+        let actor = super::lookup(&peer, address, M::ID).await?;
+
+        Some(ArchivedSender { peer, actor, _message: PhantomData, _serializers: PhantomData })
+    }
+}
+
+/// # [`ArchivedSender`]
+/// A [`MessageSender`] for an actor on a foreign system that encodes messages with the [`ZeroCopySerializer`] `Z`,
+/// and decodes their results with the [`MessageSerializer`] `S`.
+pub struct ArchivedSender<M, T, Z = RkyvSerializer, S = super::serialize::BincodeSerializer> {
+    /// The foreign system the actor lives on
+    peer: Arc<Peer<T>>,
+    /// The actor's id on the foreign system
+    actor: u64,
+    _message: PhantomData<fn() -> M>,
+    _serializers: PhantomData<fn() -> (Z, S)>,
+}
+
+impl<M, T, Z, S> ArchivedSender<M, T, Z, S>
+    where M: Message + MessageID + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+        M::Result: Serialize + for<'de> Deserialize<'de>,
+        T: Dialer, Z: ZeroCopySerializer, S: MessageSerializer {
+    /// Encodes a message with `Z`.
+    fn encode(message: &M) -> Result<Vec<u8>, MessageSendError> {
+        Z::serialize(message).map_err(|e| MessageSendError::SerializationError {
+            message: e.to_string(),
+            source: Box::new(e),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl<M, T, Z, S> MessageSender<M> for ArchivedSender<M, T, Z, S>
+    where M: Message + MessageID + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+        M::Result: Serialize + for<'de> Deserialize<'de>,
+        T: Dialer, Z: ZeroCopySerializer, S: MessageSerializer {
+    async fn send(&self, message: M) -> Result<M::Result, MessageSendError> {
+        let payload = Self::encode(&message)?;
+
+        let connection = self.peer.connection().await?;
+        let sender = Caller::acting().map(Caller::into_frame);
+        let response = connection
+            .request_numbered(sender.as_ref(), self.actor, |request, sequence| self.peer.sign(Frame::Request {
+                request,
+                actor: self.actor,
+                message: String::from(M::ID),
+                version: M::VERSION,
+                key: None,
+                metadata: Metadata::current(),
+                sender: sender.clone(),
+                sequence,
+                signature: None,
+                payload,
+            })).await?;
+
+        let Frame::Response { result, .. } = response else {
+            return Err(TransportError::Closed.into());
+        };
+        let result = result.map_err(TransportError::Remote)?;
+
+        S::deserialize(&result).map_err(|e| MessageSendError::DeserializationError {
+            message: e.to_string(),
+            source: Box::new(e),
+        })
+    }
+
+    async fn tell(&self, message: M) -> Result<(), MessageSendError> {
+        let payload = Self::encode(&message)?;
+
+        let connection = self.peer.connection().await?;
+        let sender = Caller::acting().map(Caller::into_frame);
+        connection
+            .send_numbered(sender.as_ref(), self.actor, |sequence| self.peer.sign(Frame::Tell {
+                actor: self.actor,
+                message: String::from(M::ID),
+                version: M::VERSION,
+                key: None,
+                metadata: Metadata::current(),
+                sender: sender.clone(),
+                sequence,
+                signature: None,
+                payload,
+            })).await?;
+
+        Ok(())
+    }
+}
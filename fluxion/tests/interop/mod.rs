@@ -0,0 +1,54 @@
+//! The actors and messages shared by the websocket interop tests, and the native server they reach.
+//! Messages are given explicit ids, as their default ids include the module path, which differs between the test crates.
+#![allow(dead_code)]
+
+use fluxion::{actor, message, ActorContext, Delegate, Handler};
+use serde::{Deserialize, Serialize};
+
+/// The id of the server system
+pub const SERVER: &str = "server";
+
+/// The name of the server's greeter
+pub const GREETER: &str = "greeter";
+
+/// Returns the address the browser tests reach the server on, which can be changed with `FLUXION_INTEROP_SERVER`
+/// when the tests and the `interop_server` example are built.
+pub fn address() -> &'static str {
+    option_env!("FLUXION_INTEROP_SERVER").unwrap_or("ws://127.0.0.1:9001")
+}
+
+#[actor]
+pub struct Greeter;
+
+#[message(String, "fluxion::interop::Greet")]
+#[derive(Serialize, Deserialize)]
+pub struct Greet(pub String);
+
+/// Handled by the greeter, but not exported by the server.
+#[message(String, "fluxion::interop::Shout")]
+#[derive(Serialize, Deserialize)]
+pub struct Shout(pub String);
+
+impl Handler<Greet> for Greeter {
+    async fn handle_message<D: Delegate>(&self, message: Greet, context: &ActorContext<D>) -> String {
+        format!("Hello {}, from {}", message.0, context.system().get_id())
+    }
+}
+
+impl Handler<Shout> for Greeter {
+    async fn handle_message<D: Delegate>(&self, message: Shout, _context: &ActorContext<D>) -> String {
+        message.0.to_uppercase()
+    }
+}
+
+/// Starts the server system, which only exports [`Greet`], and serves it on the listener until accepting fails.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn serve(listener: tokio::net::TcpListener) -> Result<(), fluxion::transport::TransportError> {
+    use fluxion::{transport::{websocket::{WebSocketDelegate, WebSocketServer}, Exports}, Fluxion};
+
+    let server = Fluxion::new(SERVER, WebSocketDelegate::new());
+    server.add_named(GREETER, Greeter).await.unwrap();
+
+    let exports = Exports::new(server).export::<Greeter, Greet>();
+    WebSocketServer::new(exports).serve(listener).await
+}
@@ -0,0 +1,44 @@
+//! Native clients reaching a native server over the websocket transport.
+//! The browser client is tested against the same server in `websocket_browser.rs`.
+
+mod interop;
+
+use fluxion::{transport::websocket::WebSocketDelegate, Fluxion, Identifier};
+use interop::{Greet, Greeter, Shout, GREETER, SERVER};
+use tokio::net::TcpListener;
+
+/// Serves the interop server on a free port, returning a client system that knows where to find it.
+async fn connect() -> Fluxion<WebSocketDelegate> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(interop::serve(listener));
+
+    let delegate = WebSocketDelegate::new();
+    delegate.add_peer(SERVER, &address);
+    Fluxion::new("client", delegate)
+}
+
+#[tokio::test]
+async fn client_sends_to_exported_actor() {
+    let client = connect().await;
+
+    let greeter = client.get::<Greeter, Greet>(Identifier::ForeignNamed(GREETER, SERVER)).await.unwrap();
+    assert_eq!(greeter.send(Greet("client".to_string())).await.unwrap(), "Hello client, from server");
+}
+
+#[tokio::test]
+async fn client_sends_many_messages_over_one_connection() {
+    let client = connect().await;
+
+    let greeter = client.get::<Greeter, Greet>(Identifier::ForeignNamed(GREETER, SERVER)).await.unwrap();
+    for i in 0..100 {
+        assert_eq!(greeter.send(Greet(i.to_string())).await.unwrap(), format!("Hello {i}, from server"));
+    }
+}
+
+#[tokio::test]
+async fn client_cannot_reach_unexported_message() {
+    let client = connect().await;
+
+    assert!(client.get::<Greeter, Shout>(Identifier::ForeignNamed(GREETER, SERVER)).await.is_none());
+}
@@ -0,0 +1,48 @@
+//! The browser client reaching a native server over the websocket transport. These run in a headless browser against
+//! the server started by the `interop_server` example:
+//!
+//! ```sh
+//! cargo run --example interop_server --features websocket &
+//! wasm-pack test --headless --firefox -- --test websocket_browser --features websocket,wasm
+//! ```
+#![cfg(target_arch = "wasm32")]
+
+mod interop;
+
+use fluxion::{transport::websocket::WebSocketDelegate, Fluxion, Identifier};
+use interop::{Greet, Greeter, Shout, GREETER, SERVER};
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// Returns a browser system that knows where to find the interop server.
+fn connect() -> Fluxion<WebSocketDelegate> {
+    let delegate = WebSocketDelegate::new();
+    delegate.add_peer(SERVER, interop::address());
+    Fluxion::new("browser", delegate)
+}
+
+#[wasm_bindgen_test]
+async fn browser_sends_to_native_server() {
+    let client = connect();
+
+    let greeter = client.get::<Greeter, Greet>(Identifier::ForeignNamed(GREETER, SERVER)).await.unwrap();
+    assert_eq!(greeter.send(Greet("browser".to_string())).await.unwrap(), "Hello browser, from server");
+}
+
+#[wasm_bindgen_test]
+async fn browser_sends_many_messages_over_one_connection() {
+    let client = connect();
+
+    let greeter = client.get::<Greeter, Greet>(Identifier::ForeignNamed(GREETER, SERVER)).await.unwrap();
+    for i in 0..100 {
+        assert_eq!(greeter.send(Greet(i.to_string())).await.unwrap(), format!("Hello {i}, from server"));
+    }
+}
+
+#[wasm_bindgen_test]
+async fn browser_cannot_reach_unexported_message() {
+    let client = connect();
+
+    assert!(client.get::<Greeter, Shout>(Identifier::ForeignNamed(GREETER, SERVER)).await.is_none());
+}
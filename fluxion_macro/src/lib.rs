@@ -1,45 +1,72 @@
 use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{quote, ToTokens};
-use syn::{parse::Parse, punctuated::Punctuated, token::Comma, DeriveInput, LitStr, Token, Type};
+use syn::{parse::Parse, punctuated::Punctuated, token::Comma, Data, DeriveInput, Ident, LitInt, LitStr, Token, Type};
 
 struct MessageParams {
     pub result_type: Type,
     pub name: Option<LitStr>,
+    pub version: Option<LitInt>,
 }
 
 impl Parse for MessageParams {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        // Parse the result type
-        let result_type = input.parse()?;
+        // Default to the unit result type
+        let mut params = Self {
+            result_type: Type::Tuple(syn::TypeTuple {
+                paren_token: syn::token::Paren(Span::call_site()),
+                elems: Punctuated::new(),
+            }),
+            name: None,
+            version: None,
+        };
+
+        let mut first = true;
+        while !input.is_empty() {
+            // Every parameter after the first is preceded by a comma
+            if !first {
+                input.parse::<Comma>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
 
-        // If there is a comma, parse it
-        let name = if input.peek(Token![,]) {
-            input.parse::<Comma>()?;
+            if input.peek(LitStr) {
+                params.name = Some(input.parse()?);
+            } else if input.peek(Ident) && input.peek2(Token![=]) {
+                let key: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
 
-            Some(input.parse()?)
-        } else {
-            None
-        };
+                if key != "version" {
+                    return Err(syn::Error::new(key.span(), "unknown message parameter, expected `version`"));
+                }
+                params.version = Some(input.parse()?);
+            } else if first {
+                // The result type may only be given first
+                params.result_type = input.parse()?;
+            } else {
+                return Err(input.error("expected a message id or `version = N`"));
+            }
+
+            first = false;
+        }
 
-        Ok(Self { result_type, name })
+        Ok(params)
+    }
+}
+
+/// Generates the version constant of a message, if one was provided.
+fn message_version(version: Option<LitInt>) -> TokenStream2 {
+    match version {
+        Some(version) => quote! { const VERSION: u32 = #version; },
+        None => TokenStream2::new(),
     }
 }
 
 #[proc_macro_attribute]
 pub fn message(attr: TokenStream, item: TokenStream) -> TokenStream {
     // Get the parameters
-    let params = if attr.is_empty() {
-        MessageParams {
-            result_type: Type::Tuple(syn::TypeTuple {
-                paren_token: syn::token::Paren(Span::call_site()),
-                elems: Punctuated::new(),
-            }),
-            name: None,
-        }
-    } else {
-        syn::parse_macro_input!(attr as MessageParams)
-    };
+    let params = syn::parse_macro_input!(attr as MessageParams);
 
     // Get the item's name
     let item_name = item.clone();
@@ -66,12 +93,14 @@ pub fn message(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     // Extract the result type
     let result_type = params.result_type;
+    let version = message_version(params.version);
 
     quote! {
         #item
 
         impl fluxion::MessageID for #item_name {
             const ID: &'static str = #id;
+            #version
         }
 
         impl fluxion::Message for #item_name {
@@ -84,17 +113,7 @@ pub fn message(attr: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn generic_message(attr: TokenStream, item: TokenStream) -> TokenStream {
     // Get the parameters
-    let params = if attr.is_empty() {
-        MessageParams {
-            result_type: Type::Tuple(syn::TypeTuple {
-                paren_token: syn::token::Paren(Span::call_site()),
-                elems: Punctuated::new(),
-            }),
-            name: None,
-        }
-    } else {
-        syn::parse_macro_input!(attr as MessageParams)
-    };
+    let params = syn::parse_macro_input!(attr as MessageParams);
 
     // Parse the input struct
     let input = syn::parse_macro_input!(item as DeriveInput);
@@ -118,6 +137,7 @@ pub fn generic_message(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     // Extract the result type
     let result_type = params.result_type;
+    let version = message_version(params.version);
 
     // Convert item back to TokenStream2 for quote!
     let item = quote! { #input };
@@ -127,6 +147,7 @@ pub fn generic_message(attr: TokenStream, item: TokenStream) -> TokenStream {
 
         impl #impl_generics fluxion::MessageID for #item_name #ty_generics #where_clause {
             const ID: &'static str = #id;
+            #version
         }
 
         impl #impl_generics fluxion::Message for #item_name #ty_generics #where_clause {
@@ -138,20 +159,166 @@ pub fn generic_message(attr: TokenStream, item: TokenStream) -> TokenStream {
 
 
 
+/// The parameters of the `actor` macro: an optional error type, followed by the messages the actor handles.
+struct ActorParams {
+    pub error_type: Type,
+    pub handles: Punctuated<syn::Path, Comma>,
+}
+
+impl Parse for ActorParams {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        // Default to the unit error type
+        let mut params = Self {
+            error_type: Type::Tuple(syn::TypeTuple {
+                paren_token: syn::token::Paren(Span::call_site()),
+                elems: Punctuated::new(),
+            }),
+            handles: Punctuated::new(),
+        };
+
+        // The error type may only be given first
+        if !input.is_empty() && !is_handles(input) {
+            params.error_type = input.parse()?;
+
+            if !input.is_empty() {
+                input.parse::<Comma>()?;
+            }
+        }
+
+        if !input.is_empty() {
+            if !is_handles(input) {
+                return Err(input.error("expected `handles(...)`"));
+            }
+
+            input.parse::<Ident>()?;
+            let content;
+            syn::parenthesized!(content in input);
+            params.handles = Punctuated::parse_terminated(&content)?;
+
+            // Allow a trailing comma
+            if !input.is_empty() {
+                input.parse::<Comma>()?;
+            }
+        }
+
+        if !input.is_empty() {
+            return Err(input.error("unexpected actor parameter"));
+        }
+
+        Ok(params)
+    }
+}
+
+/// Returns true if the input continues with the list of handled messages.
+fn is_handles(input: syn::parse::ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Ident>().is_ok_and(|ident| ident == "handles") && fork.peek(syn::token::Paren)
+}
+
+/// Converts a type name to the name of a method, such as `DoThing` to `do_thing`.
+fn method_name(message: &Ident) -> Ident {
+    let name = message.to_string();
+    let chars: Vec<char> = name.chars().collect();
+
+    let mut snake = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            // Split before a new word, keeping acronyms such as `HTTP` together
+            let previous = chars[i - 1];
+            let next_lowercase = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase() || previous.is_ascii_digit() || (previous.is_uppercase() && next_lowercase) {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+
+    // Names that are keywords, such as `Move`, become raw identifiers
+    syn::parse_str::<Ident>(&snake).unwrap_or_else(|_| Ident::new_raw(&snake, message.span()))
+}
+
+/// Generates a typed reference to the actor, with a method for each message it handles.
+fn actor_ref(input: &DeriveInput, handles: &Punctuated<syn::Path, Comma>) -> syn::Result<TokenStream2> {
+    if handles.is_empty() {
+        return Ok(TokenStream2::new());
+    }
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "typed references can't be generated for generic actors"));
+    }
+
+    let vis = &input.vis;
+    let actor = &input.ident;
+    let ref_name = quote::format_ident!("{}Ref", actor);
+    let doc = format!(" # [`{ref_name}`]\n A typed reference to [`{actor}`], with a method that sends each message it handles.");
+
+    let mut fields = Vec::new();
+    let mut senders = Vec::new();
+    let mut methods = Vec::new();
+    for message in handles {
+        let Some(last) = message.segments.last() else {
+            return Err(syn::Error::new_spanned(message, "expected a message type"));
+        };
+
+        let method = method_name(&last.ident);
+        let method_doc = format!(" Sends a [`{}`] to the actor and waits for its response.", last.ident);
+
+        fields.push(quote! { #method: fluxion::__private::Arc<dyn fluxion::MessageSender<#message>> });
+        senders.push(quote! { #method: fluxion::__private::Arc::new(actor.clone()) });
+        methods.push(quote! {
+            #[doc = #method_doc]
+            ///
+            /// # Errors
+            /// Returns an error if the message could not be delivered.
+            #vis async fn #method(&self, message: #message) -> Result<<#message as fluxion::Message>::Result, fluxion::MessageSendError> {
+                self.#method.send(message).await
+            }
+        });
+    }
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        #vis struct #ref_name {
+            #(#fields,)*
+        }
+
+        impl #ref_name {
+            /// Gets a typed reference to the local actor with the given id.
+            #vis async fn local<D: fluxion::Delegate>(system: &fluxion::Fluxion<D>, id: u64) -> Option<Self> {
+                system.get_local::<#actor>(id).await.map(Self::from)
+            }
+
+            #(#methods)*
+        }
+
+        impl<D: fluxion::Delegate> From<fluxion::LocalRef<#actor, D>> for #ref_name {
+            fn from(actor: fluxion::LocalRef<#actor, D>) -> Self {
+                Self {
+                    #(#senders,)*
+                }
+            }
+        }
+    })
+}
+
 #[proc_macro_attribute]
 pub fn actor(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // Get the parameters
+    let params = syn::parse_macro_input!(attr as ActorParams);
+
     // Get the item's name
-    let item_name = item.clone();
-    let item_name = syn::parse_macro_input!(item_name as DeriveInput).ident;
+    let input = item.clone();
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    let item_name = &input.ident;
 
     // Get the optional error type, defaulting to ()
-    let error_type = if attr.is_empty() {
-        Type::Tuple(syn::TypeTuple {
-            paren_token: syn::token::Paren(Span::call_site()),
-            elems: Punctuated::new(),
-        })
-    } else {
-        syn::parse_macro_input!(attr as Type)
+    let error_type = params.error_type;
+
+    // Generate the typed reference, if the handled messages were listed
+    let actor_ref = match actor_ref(&input, &params.handles) {
+        Ok(actor_ref) => actor_ref,
+        Err(e) => return e.to_compile_error().into(),
     };
 
     let item: TokenStream2 = item.into();
@@ -162,6 +329,155 @@ pub fn actor(attr: TokenStream, item: TokenStream) -> TokenStream {
         impl fluxion::Actor for #item_name {
             type Error = #error_type;
         }
+
+        #actor_ref
+    }
+    .into()
+}
+
+#[proc_macro_attribute]
+pub fn message_enum(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // Get the actors that handle every variant
+    let actors = syn::parse_macro_input!(attr with Punctuated::<Type, Comma>::parse_terminated);
+
+    let input = syn::parse_macro_input!(item as DeriveInput);
+    let item_name = &input.ident;
+    let vis = &input.vis;
+
+    // The protocol is declared as an enum with a message wrapped in each variant
+    let Data::Enum(data) = &input.data else {
+        return syn::Error::new_spanned(item_name, "message_enum can only be used on enums")
+            .to_compile_error()
+            .into();
+    };
+
+    if !input.generics.params.is_empty() {
+        return syn::Error::new_spanned(&input.generics, "message_enum can't be used on generic enums")
+            .to_compile_error()
+            .into();
+    }
+
+    let mut variants = Vec::new();
+    for variant in &data.variants {
+        let syn::Fields::Unnamed(fields) = &variant.fields else {
+            return syn::Error::new_spanned(variant, "each variant must wrap a single message, such as `Variant(Message)`")
+                .to_compile_error()
+                .into();
+        };
+
+        let Some(field) = fields.unnamed.first().filter(|_| fields.unnamed.len() == 1) else {
+            return syn::Error::new_spanned(variant, "each variant must wrap a single message, such as `Variant(Message)`")
+                .to_compile_error()
+                .into();
+        };
+
+        variants.push((&variant.ident, &field.ty));
+    }
+
+    // Each variant's response is wrapped in the matching variant of the result enum
+    let result_name = quote::format_ident!("{}Result", item_name);
+    let result_doc = format!(" # [`{result_name}`]\n The response to a [`{item_name}`], in the variant matching the message that was sent.");
+    let result_variants = variants.iter().map(|(variant, message)| quote! {
+        #variant(<#message as fluxion::Message>::Result)
+    });
+
+    let handlers = actors.iter().map(|actor| {
+        let arms = variants.iter().map(|(variant, message)| quote! {
+            #item_name::#variant(message) => #result_name::#variant(
+                <Self as fluxion::Handler<#message>>::handle_message(self, message, context).await
+            ),
+        });
+
+        quote! {
+            impl fluxion::Handler<#item_name> for #actor {
+                async fn handle_message<D: fluxion::Delegate>(&self, message: #item_name, context: &fluxion::ActorContext<D>) -> #result_name {
+                    match message {
+                        #(#arms)*
+                    }
+                }
+            }
+        }
+    });
+
+    let name: TokenStream2 = format!("\"{}\"", item_name)
+        .parse()
+        .expect("this should always succeed parsing as a string");
+
+    quote! {
+        #input
+
+        #[doc = #result_doc]
+        #vis enum #result_name {
+            #(#result_variants,)*
+        }
+
+        impl fluxion::MessageID for #item_name {
+            const ID: &'static str = fluxion::concatcp!(module_path!(), "::", #name);
+        }
+
+        impl fluxion::Message for #item_name {
+            type Result = #result_name;
+        }
+
+        #(#handlers)*
+    }
+    .into()
+}
+
+#[proc_macro_derive(Transitions, attributes(transitions))]
+pub fn derive_transitions(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as DeriveInput);
+    let item_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // Transitions are declared on the variants of an enum
+    let Data::Enum(data) = &input.data else {
+        return syn::Error::new_spanned(item_name, "Transitions can only be derived for enums")
+            .to_compile_error()
+            .into();
+    };
+
+    // Collect an arm for every declared transition.
+    // Targets are matched as patterns, so a target that isn't a variant fails to compile.
+    let mut arms = Vec::new();
+    let mut names = Vec::new();
+    for variant in &data.variants {
+        let from = &variant.ident;
+        let name = from.to_string();
+        names.push(quote! {
+            Self::#from { .. } => #name,
+        });
+
+        for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("transitions")) {
+            let targets = match attr.parse_args_with(Punctuated::<Ident, Comma>::parse_terminated) {
+                Ok(targets) => targets,
+                Err(e) => return e.to_compile_error().into(),
+            };
+
+            for to in targets {
+                arms.push(quote! {
+                    (Self::#from { .. }, Self::#to { .. }) => true,
+                });
+            }
+        }
+    }
+
+    quote! {
+        impl #impl_generics fluxion::fsm::Transitions for #item_name #ty_generics #where_clause {
+            #[allow(unreachable_patterns)]
+            fn allows(&self, next: &Self) -> bool {
+                match (self, next) {
+                    #(#arms)*
+                    _ => false,
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                match *self {
+                    #(#names)*
+                }
+            }
+        }
     }
     .into()
 }